@@ -0,0 +1,42 @@
+//! An address's balance on chains of growing length, each block paying it
+//! its reward and taking a payment out of it. Needs the `testing` feature.
+
+use aeonia::testing::{self, TestChain};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn chain(blocks: usize) -> TestChain {
+    let mut holder = testing::wallet(1);
+    let other = *testing::wallet(2).address();
+    let mut chain = TestChain::new(0);
+    for _ in 0..blocks {
+        // The first payment has nothing confirmed to spend yet.
+        let _ = chain.pay(&mut holder, &other, 0.5, 0.01);
+        chain.mine(&holder);
+    }
+    chain
+}
+
+fn balance(c: &mut Criterion) {
+    let holder = *testing::wallet(1).address();
+    let mut group = c.benchmark_group("balance");
+    for blocks in [100, 1_000] {
+        let chain = chain(blocks);
+        for min_confirmations in [0, 6, 100] {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}_confirmations", min_confirmations), blocks),
+                &chain,
+                |b, chain| {
+                    b.iter(|| {
+                        chain
+                            .blockchain
+                            .balance(black_box(&holder), min_confirmations)
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, balance);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+//! Proof of work hashes against each other, on a block header as mining
+//! hashes it and on a larger buffer. Run with `--features blake3` to
+//! include BLAKE3.
+
+use aeonia::{
+    address::Address,
+    blockchain::{Block, DoubleSha256Hasher, Hasher, Sha256Hasher},
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+fn hashers() -> Vec<(&'static str, &'static dyn Hasher)> {
+    vec![
+        ("sha256", &Sha256Hasher),
+        ("sha256d", &DoubleSha256Hasher),
+        #[cfg(feature = "blake3")]
+        ("blake3", &aeonia::blockchain::Blake3Hasher),
+    ]
+}
+
+fn header(c: &mut Criterion) {
+    let block = Block::new(
+        0,
+        "0".repeat(64),
+        vec![],
+        1_700_000_000,
+        Address::from_public_key_bytes(&[2; 33], 0x00),
+    );
+    let header = block.header();
+    let mut group = c.benchmark_group("pow_hash/header");
+    group.throughput(Throughput::Elements(1));
+    for (name, hasher) in hashers() {
+        group.bench_function(name, |b| b.iter(|| black_box(header).pow_hash(hasher)));
+    }
+    group.finish();
+}
+
+fn buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pow_hash/buffer");
+    for size in [1024, 64 * 1024] {
+        let data = vec![0xa5; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        for (name, hasher) in hashers() {
+            group.bench_with_input(BenchmarkId::new(name, size), &data, |b, data| {
+                b.iter(|| hasher.hash(black_box(data)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, header, buffer);
+criterion_main!(benches);
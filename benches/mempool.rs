@@ -0,0 +1,79 @@
+//! Filling the pool, and admitting a transaction into a full one, which
+//! evicts the lowest-fee entry to make room.
+
+use aeonia::{
+    address::Address,
+    blockchain::{Mempool, MempoolConfig, Transaction},
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+/// Payments with distinct senders, so the per-sender limit never applies.
+fn payments(count: usize, fee: f64) -> Vec<Transaction> {
+    let recipient = Address::from_public_key_bytes(&[2; 33], 0x00);
+    (0..count as u32)
+        .map(|i| {
+            let mut key = [3; 33];
+            key[1..5].copy_from_slice(&i.to_be_bytes());
+            let sender = Address::from_public_key_bytes(&key, 0x00);
+            Transaction::new(sender, recipient, 1.0, fee, 0)
+        })
+        .collect()
+}
+
+fn pool(size: usize) -> Mempool {
+    Mempool::new(MempoolConfig {
+        max_size: size,
+        ..MempoolConfig::default()
+    })
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mempool/insert");
+    for size in [100, 1_000, 5_000] {
+        let transactions = payments(size, 0.01);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(size),
+            &transactions,
+            |b, transactions| {
+                b.iter_batched(
+                    || (pool(size), transactions.clone()),
+                    |(mut pool, transactions)| {
+                        for transaction in transactions {
+                            pool.insert(transaction).unwrap();
+                        }
+                        pool
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn evict(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mempool/evict");
+    for size in [100, 1_000, 5_000] {
+        let mut full = pool(size);
+        let mut transactions = payments(size + 1, 0.01).into_iter();
+        for transaction in transactions.by_ref().take(size) {
+            full.insert(transaction).unwrap();
+        }
+        let mut richer = transactions.next().unwrap();
+        richer.fee = 1.0;
+        group.bench_with_input(BenchmarkId::from_parameter(size), &full, |b, full| {
+            b.iter_batched(
+                || (full.clone(), richer.clone()),
+                |(mut pool, richer)| {
+                    assert!(pool.insert(richer).unwrap().is_some());
+                    pool
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, evict);
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+//! Sealing a block under proof of work, at targets needing one to four
+//! leading zero hex digits. Each step is about sixteen times the work of
+//! the one before.
+
+use aeonia::{
+    address::Address,
+    blockchain::{
+        Block, CancelToken, ConsensusEngine, EmissionSchedule, HashAlgorithm, ProofOfWork, Target,
+    },
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+fn seal(c: &mut Criterion) {
+    let miner = Address::from_public_key_bytes(&[2; 33], 0x00);
+    let mut group = c.benchmark_group("pow/seal");
+    group.sample_size(20);
+    for digits in 1..=4 {
+        let engine = ProofOfWork {
+            bits: Target::from_zero_digits(digits).to_compact(),
+            hash: HashAlgorithm::Sha256,
+            emission: EmissionSchedule::default(),
+        };
+        let mut timestamp = 0;
+        let cancel = CancelToken::new();
+        group.bench_with_input(BenchmarkId::from_parameter(digits), &engine, |b, engine| {
+            // A fresh timestamp each time, so no two blocks share a nonce
+            // search.
+            b.iter_batched(
+                || {
+                    timestamp += 1;
+                    Block::new(0, "0".repeat(64), vec![], timestamp, miner)
+                },
+                |mut block| engine.seal(&mut block, &cancel),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, seal);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! Encoding and decoding a block of signed payments, in the binary
+//! encoding blocks are stored and sent in and as JSON.
+
+use aeonia::{
+    blockchain::{Block, Transaction},
+    codec::{Decode, Encode},
+    network::Network,
+    wallet::Wallet,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+fn block(transactions: usize) -> Block {
+    let mut sender = Wallet::new(Network::Regtest).unwrap();
+    let recipient = *sender.address();
+    let transactions: Vec<Transaction> = (0..transactions as u64)
+        .map(|sequence| {
+            sender
+                .sign_transaction(&recipient, 1.0, 0.01, sequence, vec![], b"memo".to_vec())
+                .unwrap()
+        })
+        .collect();
+    Block::new(0, "0".repeat(64), transactions, 0, recipient)
+}
+
+fn serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialization");
+    for transactions in [1, 100, 1_000] {
+        let block = block(transactions);
+        let encoded = block.encode();
+        let json = serde_json::to_vec(&block).unwrap();
+        group.throughput(Throughput::Elements(transactions as u64));
+        group.bench_with_input(
+            BenchmarkId::new("encode", transactions),
+            &block,
+            |b, block| b.iter(|| black_box(block).encode()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("decode", transactions),
+            &encoded,
+            |b, encoded| b.iter(|| Block::decode(black_box(encoded)).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("to_json", transactions),
+            &block,
+            |b, block| b.iter(|| serde_json::to_vec(black_box(block)).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("from_json", transactions),
+            &json,
+            |b, json| b.iter(|| serde_json::from_slice::<Block>(black_box(json)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, serialization);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! Checking the signature of a payment, for each signature scheme.
+
+use aeonia::{crypto::SignatureScheme, wallet::Wallet};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signatures/verify");
+    for scheme in [
+        SignatureScheme::P256,
+        SignatureScheme::Secp256k1,
+        SignatureScheme::Ed25519,
+    ] {
+        let mut wallet = Wallet::with_scheme(scheme, 0).unwrap();
+        let recipient = *wallet.address();
+        let transaction = wallet
+            .sign_transaction(&recipient, 1.0, 0.1, 0, vec![], vec![])
+            .unwrap();
+        let sender = *transaction.sender().unwrap();
+        let message = transaction.signing_bytes();
+        group.bench_function(scheme.to_string(), |b| {
+            b.iter(|| {
+                transaction
+                    .witness
+                    .verify(black_box(&sender), black_box(&message))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        let protoc =
+            protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/aeonia.proto")
+            .expect("failed to compile proto/aeonia.proto");
+    }
+}
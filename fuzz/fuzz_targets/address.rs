@@ -0,0 +1,15 @@
+//! Addresses as typed by users or sent over RPC.
+
+#![no_main]
+
+use aeonia::address::Address;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(s) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(address) = s.parse::<Address>() {
+        assert_eq!(address.to_string().parse::<Address>().ok(), Some(address));
+    }
+});
@@ -0,0 +1,23 @@
+//! Blocks and headers as they arrive from peers or are read off disk. What
+//! decodes must encode back to something that decodes the same.
+
+#![no_main]
+
+use aeonia::{
+    blockchain::{Block, BlockHeader},
+    codec::{Decode, Encode},
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(block) = Block::decode(data) {
+        let encoded = block.encode();
+        let again = Block::decode(&encoded).expect("re-encoded block decodes");
+        assert_eq!(again.encode(), encoded);
+        let _ = block.header().hash();
+    }
+    if let Ok(header) = BlockHeader::decode(data) {
+        let _ = header.target();
+        let _ = header.work();
+    }
+});
@@ -0,0 +1,11 @@
+//! Whatever a peer may send down a connection, length prefix included.
+
+#![no_main]
+
+use aeonia::network::Message;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = data;
+    while let Ok(_message) = Message::read_from(&mut reader) {}
+});
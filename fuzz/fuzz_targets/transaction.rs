@@ -0,0 +1,27 @@
+//! Transactions as they arrive from peers, through the checks that need
+//! nothing but the transaction: its memo, its script and its signature.
+
+#![no_main]
+
+use aeonia::{
+    blockchain::Transaction,
+    codec::{Decode, Encode},
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(transaction) = Transaction::decode(data) else {
+        return;
+    };
+    let encoded = transaction.encode();
+    let again = Transaction::decode(&encoded).expect("re-encoded transaction decodes");
+    assert_eq!(again.encode(), encoded);
+    let _ = transaction.id();
+    let _ = transaction.check_memo();
+    let _ = transaction.check_script(0, 0);
+    if let Some(sender) = transaction.sender() {
+        let _ = transaction
+            .witness
+            .verify(sender, &transaction.signing_bytes());
+    }
+});
@@ -0,0 +1,320 @@
+use std::str::FromStr;
+
+use ripemd::{Digest, Ripemd160};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Sha256;
+
+use crate::{
+    codec::{self, Decode, Encode, Reader},
+    crypto::PublicKey,
+};
+
+const HASH_LEN: usize = 20;
+const CHECKSUM_LEN: usize = 4;
+
+/// Largest number of keys a multisig address may combine.
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[error("address is not valid base58")]
+    InvalidBase58,
+    #[error("address decodes to {0} bytes")]
+    InvalidLength(usize),
+    #[error("address checksum does not match")]
+    ChecksumMismatch,
+    #[error("invalid {threshold} of {keys} multisig, it needs 1 to {max} keys and a threshold of at most the number of keys", max = MAX_MULTISIG_KEYS)]
+    InvalidThreshold { threshold: u8, keys: usize },
+}
+
+impl Error {
+    /// Identifier of the kind of error, stable across releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidBase58 | Error::InvalidLength(_) | Error::ChecksumMismatch => {
+                "invalid_address"
+            }
+            Error::InvalidThreshold { .. } => "invalid_threshold",
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(value: Error) -> Self {
+        Self::new(std::io::ErrorKind::InvalidInput, value)
+    }
+}
+
+/// Account address: a version byte and the HASH160 (RIPEMD-160 of SHA-256) of
+/// a public key, written out in Base58Check so mistyped addresses are
+/// rejected.
+///
+/// Addresses are laid out exactly like Bitcoin P2PKH addresses:
+///
+/// ```
+/// use aeonia::address::Address;
+///
+/// let encoded = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa";
+/// assert!(Address::verify_checksum(encoded));
+/// let address: Address = encoded.parse().unwrap();
+/// assert_eq!(address.version(), 0x00);
+/// assert_eq!(address.to_string(), encoded);
+///
+/// assert!(!Address::verify_checksum("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb"));
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    version: u8,
+    hash: [u8; HASH_LEN],
+}
+
+impl Address {
+    pub fn new(version: u8, hash: [u8; HASH_LEN]) -> Self {
+        Address { version, hash }
+    }
+
+    /// Derives the address of a key from its bytes, see
+    /// [`PublicKey::to_bytes`]. A secp256k1 key gets the same address as on
+    /// Bitcoin.
+    pub fn from_public_key(public_key: &PublicKey, version: u8) -> Self {
+        Self::from_public_key_bytes(&public_key.to_bytes(), version)
+    }
+
+    /// Derives the address of an already serialized public key.
+    ///
+    /// ```
+    /// use aeonia::address::Address;
+    ///
+    /// // Compressed secp256k1 key from the Bitcoin wiki's address walkthrough.
+    /// let public_key = "0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352";
+    /// let bytes: Vec<u8> = (0..public_key.len())
+    ///     .step_by(2)
+    ///     .map(|i| u8::from_str_radix(&public_key[i..i + 2], 16).unwrap())
+    ///     .collect();
+    /// let address = Address::from_public_key_bytes(&bytes, 0x00);
+    /// assert_eq!(address.to_string(), "1PMycacnJaSqwwJqjawXBErnLsZ7RkXUAs");
+    /// ```
+    pub fn from_public_key_bytes(public_key: &[u8], version: u8) -> Self {
+        let mut hash = [0; HASH_LEN];
+        hash.copy_from_slice(&Ripemd160::digest(Sha256::digest(public_key)));
+        Address { version, hash }
+    }
+
+    /// Checks that a Base58 string carries a valid checksum for its payload,
+    /// without otherwise interpreting it.
+    pub fn verify_checksum(encoded: &str) -> bool {
+        match bs58::decode(encoded).into_vec() {
+            Ok(bytes) if bytes.len() > CHECKSUM_LEN => {
+                let (payload, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+                Self::checksum(payload) == checksum
+            }
+            _ => false,
+        }
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn hash(&self) -> &[u8; HASH_LEN] {
+        &self.hash
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        [&[self.version], self.hash.as_slice()].concat()
+    }
+
+    /// First four bytes of the double SHA-256 of the payload.
+    fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+        let digest = Sha256::digest(Sha256::digest(payload));
+        let mut checksum = [0; CHECKSUM_LEN];
+        checksum.copy_from_slice(&digest[..CHECKSUM_LEN]);
+        checksum
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let payload = self.payload();
+        let checksum = Self::checksum(&payload);
+        write!(
+            f,
+            "{}",
+            bs58::encode([&payload[..], &checksum].concat()).into_string()
+        )
+    }
+}
+
+impl std::fmt::Debug for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Address({})", self)
+    }
+}
+
+impl FromStr for Address {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| Error::InvalidBase58)?;
+        if bytes.len() != 1 + HASH_LEN + CHECKSUM_LEN {
+            return Err(Error::InvalidLength(bytes.len()));
+        }
+        let (payload, checksum) = bytes.split_at(1 + HASH_LEN);
+        if Self::checksum(payload) != checksum {
+            return Err(Error::ChecksumMismatch);
+        }
+        let mut hash = [0; HASH_LEN];
+        hash.copy_from_slice(&payload[1..]);
+        Ok(Address {
+            version: payload[0],
+            hash,
+        })
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Encode for Address {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.version.encode_to(out);
+        out.extend_from_slice(&self.hash);
+    }
+}
+
+impl Decode for Address {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let version = u8::decode_from(reader)?;
+        let mut hash = [0; HASH_LEN];
+        hash.copy_from_slice(reader.take(HASH_LEN)?);
+        Ok(Address { version, hash })
+    }
+}
+
+/// M-of-N spending policy: funds sent to its address can only be moved by a
+/// transaction carrying valid signatures from at least `threshold` of `keys`.
+///
+/// Keys of any scheme can be mixed. They are kept sorted by their encoding,
+/// so the same set of keys always yields the same address whatever order it
+/// was given in. The binary encoding has to list them in that order, each
+/// once, so every policy has exactly one encoding:
+///
+/// ```
+/// use aeonia::{
+///     address::MultisigAddress,
+///     codec::{Decode, Encode},
+///     network::Network,
+///     wallet::Wallet,
+/// };
+///
+/// let keys: Vec<_> = (0..2)
+///     .map(|_| Wallet::new(Network::Regtest).unwrap().public_key().clone())
+///     .collect();
+/// let policy = MultisigAddress::new(1, keys).unwrap();
+/// assert_eq!(MultisigAddress::decode(&policy.encode()).unwrap(), policy);
+///
+/// let [first, second] = policy.keys() else { unreachable!() };
+/// for keys in [vec![second, first], vec![first, first]] {
+///     let mut bytes = vec![1];
+///     keys.into_iter().cloned().collect::<Vec<_>>().encode_to(&mut bytes);
+///     assert!(MultisigAddress::decode(&bytes).is_err());
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "MultisigParts")]
+pub struct MultisigAddress {
+    threshold: u8,
+    #[serde(with = "codec::as_hex")]
+    keys: Vec<PublicKey>,
+}
+
+#[derive(Deserialize)]
+struct MultisigParts {
+    threshold: u8,
+    #[serde(with = "codec::as_hex")]
+    keys: Vec<PublicKey>,
+}
+
+impl TryFrom<MultisigParts> for MultisigAddress {
+    type Error = Error;
+
+    fn try_from(parts: MultisigParts) -> Result<Self, Self::Error> {
+        Self::new(parts.threshold, parts.keys)
+    }
+}
+
+impl MultisigAddress {
+    pub fn new(threshold: u8, mut keys: Vec<PublicKey>) -> Result<Self, Error> {
+        keys.sort_by_key(|key| key.encode());
+        keys.dedup();
+        if threshold == 0 || usize::from(threshold) > keys.len() || keys.len() > MAX_MULTISIG_KEYS {
+            return Err(Error::InvalidThreshold {
+                threshold,
+                keys: keys.len(),
+            });
+        }
+        Ok(MultisigAddress { threshold, keys })
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    /// Position of `key` in the policy, which is how signatures refer to it.
+    pub fn key_index(&self, key: &PublicKey) -> Option<u8> {
+        let index = self.keys.iter().position(|k| k == key)?;
+        u8::try_from(index).ok()
+    }
+
+    /// The HASH160 of the encoded policy, the same way single-key addresses
+    /// hash their public key.
+    pub fn address(&self, version: u8) -> Address {
+        Address::from_public_key_bytes(&self.encode(), version)
+    }
+}
+
+impl Encode for MultisigAddress {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.threshold.encode_to(out);
+        self.keys.encode_to(out);
+    }
+}
+
+impl Decode for MultisigAddress {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let threshold = u8::decode_from(reader)?;
+        let len = reader.take_len()?;
+        if len > MAX_MULTISIG_KEYS {
+            return Err(codec::Error::InvalidValue(format!(
+                "{} multisig keys exceed {}",
+                len, MAX_MULTISIG_KEYS
+            )));
+        }
+        let keys: Vec<PublicKey> = codec::decode_items(reader, len)?;
+        if !keys
+            .windows(2)
+            .all(|pair| pair[0].encode() < pair[1].encode())
+        {
+            return Err(codec::Error::InvalidValue(
+                "multisig keys aren't strictly sorted".into(),
+            ));
+        }
+        Self::new(threshold, keys).map_err(|e| codec::Error::InvalidValue(e.to_string()))
+    }
+}
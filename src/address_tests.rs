@@ -0,0 +1,75 @@
+//! Addresses against known Bitcoin vectors, and the ways a Base58 string
+//! fails to be one.
+
+use crate::{
+    address::{Address, Error},
+    crypto::{PublicKey, SignatureScheme},
+};
+
+/// Compressed secp256k1 key from the Bitcoin wiki's address walkthrough.
+const PUBLIC_KEY: &str = "0250863ad64a87ae8a2fe83c1af1a8403cb53f53e486d8511dad8a04887e5b2352";
+const ADDRESS: &str = "1PMycacnJaSqwwJqjawXBErnLsZ7RkXUAs";
+/// The genesis block's coinbase address.
+const GENESIS: &str = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa";
+
+fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[test]
+fn secp256k1_keys_get_their_bitcoin_address() {
+    let bytes = unhex(PUBLIC_KEY);
+    let key = PublicKey::from_bytes(SignatureScheme::Secp256k1, &bytes).unwrap();
+
+    let address = Address::from_public_key(&key, 0x00);
+    assert_eq!(address, Address::from_public_key_bytes(&bytes, 0x00));
+    assert_eq!(address.to_string(), ADDRESS);
+    assert_eq!(ADDRESS.parse::<Address>().unwrap(), address);
+}
+
+#[test]
+fn bitcoin_addresses_round_trip() {
+    let address: Address = GENESIS.parse().unwrap();
+    assert_eq!(address.version(), 0x00);
+    assert_eq!(
+        address.hash().as_slice(),
+        unhex("62e907b15cbf27d5425399ebf6f0fb50ebb88f18")
+    );
+    assert_eq!(address.to_string(), GENESIS);
+    assert!(Address::verify_checksum(GENESIS));
+}
+
+#[test]
+fn corrupted_checksum_is_refused() {
+    let mut bytes = bs58::decode(GENESIS).into_vec().unwrap();
+    *bytes.last_mut().unwrap() ^= 1;
+    let corrupted = bs58::encode(bytes).into_string();
+
+    assert_eq!(corrupted.parse::<Address>(), Err(Error::ChecksumMismatch));
+    assert!(!Address::verify_checksum(&corrupted));
+}
+
+#[test]
+fn wrong_length_is_refused() {
+    let bytes = bs58::decode(GENESIS).into_vec().unwrap();
+    for len in [0, 1, bytes.len() - 1] {
+        let short = bs58::encode(&bytes[..len]).into_string();
+        assert_eq!(short.parse::<Address>(), Err(Error::InvalidLength(len)));
+    }
+    let long = bs58::encode([bytes.as_slice(), &[0]].concat()).into_string();
+    assert_eq!(
+        long.parse::<Address>(),
+        Err(Error::InvalidLength(bytes.len() + 1))
+    );
+}
+
+#[test]
+fn invalid_base58_is_refused() {
+    // 0, O, I and l aren't in the Base58 alphabet.
+    for invalid in ["0A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", "1OIl", "1A1z P1eP"] {
+        assert_eq!(invalid.parse::<Address>(), Err(Error::InvalidBase58));
+    }
+}
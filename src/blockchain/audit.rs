@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    block::{Block, BlockHeader},
+    light::LightClient,
+    receipt::Receipt,
+    state::BALANCE_EPSILON,
+    transaction::Transaction,
+    Error, Result,
+};
+use crate::{address::Address, config::Config};
+
+/// A transaction to or from the audited address, with the receipt placing
+/// it in its block.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProvenTransaction {
+    pub transaction: Transaction,
+    pub receipt: Receipt,
+}
+
+/// Evidence of what moved through an address up to a block: confirmed
+/// transactions to or from it, each with a Merkle proof, and the headers
+/// from genesis to that block. An auditor who trusts only the genesis block
+/// checks it with [`BalanceProof::verify`], without the chain or a node. It
+/// serializes to JSON.
+///
+/// It doesn't prove a balance. Every transaction in it is proven, but the
+/// prover picks which to include: payments to the address can be left out,
+/// and while payments out of it have to carry every sequence number from 0
+/// on, no header commits to the address's sequence, so the latest ones can
+/// be left out too. `received` and `spent` are both lower bounds.
+///
+/// ```
+/// use aeonia::{blockchain::Blockchain, config::Config};
+///
+/// let config = Config::default();
+/// let blockchain = Blockchain::with_config(&config).unwrap();
+/// let genesis = blockchain.block_by_height(0).unwrap();
+/// let faucet = genesis.transactions()[0].recipient;
+/// let proof = blockchain.balance_proof(&faucet, 0).unwrap();
+///
+/// assert!(proof.verify(&genesis, &config).is_ok());
+/// assert_eq!(proof.received, genesis.transactions()[0].amount);
+/// assert_eq!(proof.spent, 0.0);
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BalanceProof {
+    pub address: Address,
+    pub height: u64,
+    /// What the proven transactions credit the address with. It may have
+    /// received more.
+    pub received: f64,
+    /// What the proven transactions take out of the address, fees
+    /// included. It may have paid out more.
+    pub spent: f64,
+    /// Number of payments out of the address the proof carries, which
+    /// have sequence numbers 0 up to this.
+    pub next_sequence: u64,
+    /// Main chain headers after genesis up to `height`, in chain order.
+    pub headers: Vec<BlockHeader>,
+    /// In chain order.
+    pub transactions: Vec<ProvenTransaction>,
+}
+
+impl BalanceProof {
+    /// Checks the headers against `genesis` and the consensus settings of
+    /// `config`, every transaction against the header of its block, that
+    /// no payment out is missing before the last one carried, and that the
+    /// transactions add up to what is claimed received and spent.
+    pub fn verify(&self, genesis: &Block, config: &Config) -> Result<()> {
+        let mut light = LightClient::new(genesis, config);
+        if light.add_headers(self.headers.iter().cloned())? != self.height {
+            return Err(self.invalid("headers don't reach the claimed height"));
+        }
+        let mut received = 0.0;
+        let mut spent = 0.0;
+        let mut sequence = 0;
+        let mut previous = None;
+        for ProvenTransaction {
+            transaction,
+            receipt,
+        } in &self.transactions
+        {
+            let location = (receipt.height, receipt.index);
+            if previous.is_some_and(|previous| previous >= location) {
+                return Err(self.invalid("transactions are out of chain order"));
+            }
+            previous = Some(location);
+            if receipt.txid != transaction.id() {
+                return Err(self.invalid("a receipt is for another transaction"));
+            }
+            light.verify_receipt(receipt)?;
+            if transaction.recipient == self.address {
+                received += transaction.credited();
+            }
+            match transaction.sender() {
+                Some(sender) if *sender == self.address => {
+                    if transaction.sequence != sequence {
+                        return Err(self.invalid("payments out of the address are missing"));
+                    }
+                    sequence += 1;
+                    spent += transaction.amount + transaction.fee;
+                }
+                _ if transaction.recipient != self.address => {
+                    return Err(self.invalid("a transaction doesn't involve the address"));
+                }
+                _ => {}
+            }
+        }
+        if sequence != self.next_sequence {
+            return Err(self.invalid("payments out of the address are missing"));
+        }
+        if (received - self.received).abs() > BALANCE_EPSILON
+            || (spent - self.spent).abs() > BALANCE_EPSILON
+        {
+            return Err(self.invalid("transactions don't add up to the amounts claimed"));
+        }
+        Ok(())
+    }
+
+    fn invalid(&self, reason: &'static str) -> Error {
+        Error::InvalidBalanceProof {
+            address: self.address,
+            reason,
+        }
+    }
+}
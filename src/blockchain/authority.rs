@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{transaction::Transaction, Error, Result};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode, Reader},
+};
+
+/// A change to the set of authorities a proof-of-authority chain takes
+/// block producers from, made by a payment from a current authority to
+/// itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorityOp {
+    Add { address: Address },
+    Remove { address: Address },
+}
+
+/// How the authorities changed on the main chain since genesis: for every
+/// address added or removed, how many more times it was added than
+/// removed. The genesis set itself comes from the config.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuthorityLedger {
+    changes: HashMap<Address, i64>,
+}
+
+impl AuthorityLedger {
+    pub fn from_parts<C: IntoIterator<Item = (Address, i64)>>(changes: C) -> Self {
+        AuthorityLedger {
+            changes: changes.into_iter().collect(),
+        }
+    }
+
+    /// Every non-zero change, sorted by address.
+    pub fn changes(&self) -> Vec<(Address, i64)> {
+        let mut changes: Vec<_> = self
+            .changes
+            .iter()
+            .map(|(address, change)| (*address, *change))
+            .collect();
+        changes.sort_by_key(|(address, _)| *address);
+        changes
+    }
+
+    /// The authorities now, starting from `genesis`, sorted by address.
+    pub fn authorities(&self, genesis: &[Address]) -> Vec<Address> {
+        let mut authorities: Vec<_> = genesis
+            .iter()
+            .chain(self.changes.keys())
+            .filter(|address| self.is_authority(genesis, address))
+            .copied()
+            .collect();
+        authorities.sort();
+        authorities.dedup();
+        authorities
+    }
+
+    pub fn is_authority(&self, genesis: &[Address], address: &Address) -> bool {
+        let base = i64::from(genesis.contains(address));
+        base + self.changes.get(address).copied().unwrap_or_default() > 0
+    }
+
+    /// Fails if `transaction` changes the authorities without its sender
+    /// being one, adds a current authority, removes an address that isn't
+    /// one, or removes the last one.
+    pub fn check(&self, genesis: &[Address], transaction: &Transaction) -> Result<()> {
+        let Some(op) = &transaction.authority else {
+            return Ok(());
+        };
+        let invalid = || Error::InvalidAuthority {
+            id: transaction.id(),
+        };
+        let Some(sender) = transaction.sender() else {
+            return Err(invalid());
+        };
+        if transaction.recipient != *sender || !self.is_authority(genesis, sender) {
+            return Err(invalid());
+        }
+        let valid = match op {
+            AuthorityOp::Add { address } => !self.is_authority(genesis, address),
+            AuthorityOp::Remove { address } => {
+                self.is_authority(genesis, address) && self.authorities(genesis).len() > 1
+            }
+        };
+        if !valid {
+            return Err(invalid());
+        }
+        Ok(())
+    }
+
+    /// Applies a transaction [`AuthorityLedger::check`] accepted.
+    pub fn apply(&mut self, transaction: &Transaction) {
+        match &transaction.authority {
+            Some(AuthorityOp::Add { address }) => self.add(*address, 1),
+            Some(AuthorityOp::Remove { address }) => self.add(*address, -1),
+            None => {}
+        }
+    }
+
+    pub fn revert(&mut self, transaction: &Transaction) {
+        match &transaction.authority {
+            Some(AuthorityOp::Add { address }) => self.add(*address, -1),
+            Some(AuthorityOp::Remove { address }) => self.add(*address, 1),
+            None => {}
+        }
+    }
+
+    /// The first address whose change differs between the two ledgers.
+    pub fn first_difference(&self, other: &AuthorityLedger) -> Option<Address> {
+        self.changes
+            .keys()
+            .chain(other.changes.keys())
+            .find(|address| self.changes.get(address) != other.changes.get(address))
+            .copied()
+    }
+
+    fn add(&mut self, address: Address, change: i64) {
+        let entry = self.changes.entry(address).or_default();
+        *entry += change;
+        if *entry == 0 {
+            self.changes.remove(&address);
+        }
+    }
+}
+
+impl Encode for AuthorityOp {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            AuthorityOp::Add { address } => {
+                0u8.encode_to(out);
+                address.encode_to(out);
+            }
+            AuthorityOp::Remove { address } => {
+                1u8.encode_to(out);
+                address.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for AuthorityOp {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(AuthorityOp::Add {
+                address: Address::decode_from(reader)?,
+            }),
+            1 => Ok(AuthorityOp::Remove {
+                address: Address::decode_from(reader)?,
+            }),
+            tag => Err(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
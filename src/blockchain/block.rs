@@ -1,86 +1,565 @@
-use super::transaction::Transaction;
-use chrono::Utc;
+use super::{
+    cancel::CancelToken,
+    hasher::Hasher,
+    merkle::{is_mutated, merkle_root},
+    target::Target,
+    template::{self, NonceRange},
+    transaction::Transaction,
+    witness::Witness,
+    Result,
+};
+use crate::{
+    address::Address,
+    clock::{Clock, SystemClock},
+    codec::{self, Decode, Encode, Reader},
+};
 use serde::{Deserialize, Serialize};
 
+/// Everything about a block but its transactions, which it commits to by
+/// their Merkle root. The block hash, the proof of work and the producer's
+/// signature all cover the header alone, so headers can be checked,
+/// stored and sent on their own.
 #[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct Block {
-    nonce: i32,
+pub struct BlockHeader {
+    nonce: u64,
     previous_hash: String,
     timestamp: i64,
+    /// Compact target the hash must meet under proof of work; 0 under
+    /// engines that take none.
+    #[serde(default)]
+    bits: u32,
+    merkle_root: String,
+    miner: Address,
+    /// The miner's signature over the rest of the header, made once the
+    /// proof is found.
+    #[serde(default)]
+    witness: Witness,
+}
+
+impl BlockHeader {
+    /// Hash of the nonce, previous hash, timestamp, target, Merkle root
+    /// and miner, with SHA-256. This is what identifies the block; the
+    /// proof of work is checked against [`BlockHeader::pow_hash`].
+    pub fn hash(&self) -> String {
+        sha256::digest(self.signing_bytes())
+    }
+
+    /// The header without its witness, which is what gets hashed and what
+    /// the miner signs. The low half of the nonce comes first and the high
+    /// half last, only when it isn't zero, so headers from when the nonce
+    /// was 32 bits keep their hash.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        (self.nonce as u32).encode_to(&mut out);
+        self.previous_hash.encode_to(&mut out);
+        self.timestamp.encode_to(&mut out);
+        self.bits.encode_to(&mut out);
+        self.merkle_root.encode_to(&mut out);
+        self.miner.encode_to(&mut out);
+        let high = (self.nonce >> 32) as u32;
+        if high != 0 {
+            high.encode_to(&mut out);
+        }
+        out
+    }
+
+    /// The target the header carries, or `None` if its bits don't encode
+    /// one.
+    pub fn target(&self) -> Option<Target> {
+        Target::from_compact(self.bits)
+    }
+
+    /// Hash of the same fields with `hasher`, which the proof of work is
+    /// checked against.
+    pub fn pow_hash(&self, hasher: &dyn Hasher) -> [u8; 32] {
+        hasher.hash(&self.signing_bytes())
+    }
+
+    /// Whether the proof of work hash is at or below the target in the
+    /// header.
+    pub fn meets_target(&self, hasher: &dyn Hasher) -> bool {
+        self.target()
+            .is_some_and(|target| target.is_met_by(&self.pow_hash(hasher)))
+    }
+
+    /// Expected number of hashes finding the block took, see
+    /// [`Target::work`]. Blocks of engines taking no proof of work count 1,
+    /// so forks between them go to the longest chain.
+    pub fn work(&self) -> u128 {
+        match self.bits {
+            0 => 1,
+            _ => self.target().map_or(1, |target| target.work()),
+        }
+    }
+
+    /// Searches for a nonce that makes the header meet its target under
+    /// `hasher`, leaving every other field as it is. Returns the number of
+    /// hashes computed, 0 if the bits encode no target.
+    pub fn mine(&mut self, hasher: &dyn Hasher) -> u64 {
+        self.mine_until(hasher, &CancelToken::new()).unwrap_or(0)
+    }
+
+    /// Like [`BlockHeader::mine`], but gives up as soon as `cancel` is
+    /// tripped, returning `None`.
+    pub fn mine_until(&mut self, hasher: &dyn Hasher, cancel: &CancelToken) -> Option<u64> {
+        let Some(target) = self.target() else {
+            return Some(0);
+        };
+        let mut hashes = 1;
+        while !target.is_met_by(&self.pow_hash(hasher)) {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            self.nonce = self.nonce.wrapping_add(1);
+            hashes += 1;
+        }
+        Some(hashes)
+    }
+
+    /// Searches `range` for a nonce that makes the header meet its target
+    /// under `hasher`, for miners splitting the nonce space between them.
+    /// Returns whether one was found, leaving it in the header, and the
+    /// number of hashes computed.
+    pub fn mine_range(&mut self, hasher: &dyn Hasher, range: NonceRange) -> (bool, u64) {
+        self.mine_range_until(hasher, range, &CancelToken::new())
+            .unwrap_or((false, 0))
+    }
+
+    /// Like [`BlockHeader::mine_range`], but gives up as soon as `cancel` is
+    /// tripped, returning `None`.
+    pub fn mine_range_until(
+        &mut self,
+        hasher: &dyn Hasher,
+        range: NonceRange,
+        cancel: &CancelToken,
+    ) -> Option<(bool, u64)> {
+        let Some(target) = self.target() else {
+            return Some((false, 0));
+        };
+        let mut hashes = 0;
+        for nonce in range.start..=range.end {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            self.nonce = nonce;
+            hashes += 1;
+            if target.is_met_by(&self.pow_hash(hasher)) {
+                return Some((true, hashes));
+            }
+        }
+        Some((false, hashes))
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    pub fn previous_hash(&self) -> &String {
+        &self.previous_hash
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn merkle_root(&self) -> &str {
+        &self.merkle_root
+    }
+
+    pub fn miner(&self) -> &Address {
+        &self.miner
+    }
+
+    pub fn witness(&self) -> &Witness {
+        &self.witness
+    }
+
+    pub(crate) fn set_witness(&mut self, witness: Witness) {
+        self.witness = witness;
+    }
+
+    pub(crate) fn set_bits(&mut self, bits: u32) {
+        self.bits = bits;
+    }
+
+    pub(crate) fn set_merkle_root(&mut self, merkle_root: String) {
+        self.merkle_root = merkle_root;
+    }
+
+    /// Sets the nonce, for miners searching outside
+    /// [`BlockHeader::mine`].
+    pub fn set_nonce(&mut self, nonce: u64) {
+        self.nonce = nonce;
+    }
+
+    /// The header as laid out when the nonce was 32 bits, `None` if it no
+    /// longer fits. Slashing evidence is still encoded this way, so the
+    /// transactions carrying it keep their ids.
+    pub(crate) fn encode_narrow(&self) -> Option<Vec<u8>> {
+        if self.nonce > u64::from(u32::MAX) {
+            return None;
+        }
+        let mut out = self.signing_bytes();
+        self.witness.encode_to(&mut out);
+        Some(out)
+    }
+
+    /// Reads a header written by [`BlockHeader::encode_narrow`].
+    pub(crate) fn decode_narrow(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(BlockHeader {
+            nonce: u32::decode_from(reader)?.into(),
+            previous_hash: String::decode_from(reader)?,
+            timestamp: i64::decode_from(reader)?,
+            bits: u32::decode_from(reader)?,
+            merkle_root: String::decode_from(reader)?,
+            miner: Address::decode_from(reader)?,
+            witness: Witness::decode_from(reader)?,
+        })
+    }
+
+    /// Checks that the header is signed by the key behind the `miner`
+    /// address, so nobody can claim another miner's work.
+    pub fn verify_signature(&self) -> Result<()> {
+        self.witness.verify(&self.miner, &self.signing_bytes())
+    }
+}
+
+/// The transactions of a block, kept apart from its header.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct BlockBody {
     transactions: Vec<Transaction>,
-    miner: String,
+}
+
+impl BlockBody {
+    pub fn new(transactions: Vec<Transaction>) -> Self {
+        BlockBody { transactions }
+    }
+
+    pub fn transactions(&self) -> &Vec<Transaction> {
+        &self.transactions
+    }
+
+    pub fn merkle_root(&self) -> String {
+        let ids: Vec<_> = self.transactions.iter().map(Transaction::id).collect();
+        merkle_root(&ids)
+    }
+
+    /// Whether the transactions repeat in a way that leaves the Merkle root
+    /// unchanged, see `merkle::is_mutated`.
+    fn is_mutated(&self) -> bool {
+        let ids: Vec<_> = self.transactions.iter().map(Transaction::id).collect();
+        is_mutated(&ids)
+    }
+}
+
+/// A header and, unless it has been pruned, the body it commits to.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(try_from = "BlockParts")]
+pub struct Block {
+    header: BlockHeader,
+    /// `None` once the transactions have been discarded by
+    /// [`Block::pruned`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<BlockBody>,
+}
+
+#[derive(Deserialize)]
+struct BlockParts {
+    header: BlockHeader,
+    #[serde(default)]
+    body: Option<BlockBody>,
+}
+
+impl TryFrom<BlockParts> for Block {
+    type Error = String;
+
+    fn try_from(parts: BlockParts) -> std::result::Result<Self, String> {
+        match parts.body {
+            Some(body) => Block::from_parts(parts.header, body)
+                .ok_or_else(|| "transactions don't match the merkle root".to_string()),
+            None => Ok(Block::from_header(parts.header)),
+        }
+    }
 }
 
 impl Block {
     pub fn new(
-        nonce: i32,
+        nonce: u64,
         previous_hash: String,
         transactions: Vec<Transaction>,
         timestamp: i64,
-        miner: String,
+        miner: Address,
     ) -> Self {
+        let body = BlockBody::new(transactions);
         Block {
-            nonce,
-            previous_hash,
-            timestamp,
-            transactions,
-            miner,
+            header: BlockHeader {
+                nonce,
+                previous_hash,
+                timestamp,
+                bits: 0,
+                merkle_root: body.merkle_root(),
+                miner,
+                witness: Witness::Unsigned,
+            },
+            body: Some(body),
         }
     }
 
+    /// Puts a header back together with its body. `None` if the body's
+    /// Merkle root isn't the one in the header, or if the body repeats
+    /// transactions so that a different body would have the same root.
+    pub fn from_parts(header: BlockHeader, body: BlockBody) -> Option<Self> {
+        (body.merkle_root() == header.merkle_root && !body.is_mutated()).then_some(Block {
+            header,
+            body: Some(body),
+        })
+    }
+
+    /// A pruned block holding only `header`.
+    pub fn from_header(header: BlockHeader) -> Self {
+        Block { header, body: None }
+    }
+
+    /// Empty block at `timestamp` whose previous hash is its own hash with
+    /// no previous hash. Genesis is built on top of it.
+    pub fn empty_at(timestamp: i64) -> Self {
+        let mut b = Block::new(0, String::new(), vec![], timestamp, Address::default());
+        b.header.previous_hash = b.hash();
+        b
+    }
+
+    /// Hash of the header, see [`BlockHeader::hash`].
     pub fn hash(&self) -> String {
-        sha256::digest(self.to_string())
+        self.header.hash()
     }
 
-    pub fn nonce(&self) -> i32 {
-        self.nonce
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// The body, or `None` if the block has been pruned.
+    pub fn body(&self) -> Option<&BlockBody> {
+        self.body.as_ref()
+    }
+
+    pub fn merkle_root(&self) -> &str {
+        self.header.merkle_root()
+    }
+
+    pub fn target(&self) -> Option<Target> {
+        self.header.target()
+    }
+
+    pub fn meets_target(&self, hasher: &dyn Hasher) -> bool {
+        self.header.meets_target(hasher)
+    }
+
+    pub fn work(&self) -> u128 {
+        self.header.work()
+    }
+
+    /// Searches for a nonce that makes the header meet its target under
+    /// `hasher`, rolling the coinbase's extra nonce whenever the nonce space
+    /// runs out, see [`Block::mine_range_until`]. Returns the number of
+    /// hashes computed, 0 if the bits encode no target.
+    pub fn mine(&mut self, hasher: &dyn Hasher) -> u64 {
+        self.mine_until(hasher, &CancelToken::new()).unwrap_or(0)
+    }
+
+    /// Like [`Block::mine`], but gives up as soon as `cancel` is tripped,
+    /// returning `None`.
+    pub fn mine_until(&mut self, hasher: &dyn Hasher, cancel: &CancelToken) -> Option<u64> {
+        self.mine_range_until(hasher, NonceRange::FULL, cancel)
+    }
+
+    /// Searches `range` for a nonce that makes the header meet its target
+    /// under `hasher`. Each time the range runs out, the extra nonce in the
+    /// coinbase is moved on, which changes the Merkle root and so gives the
+    /// header a fresh nonce space, and the range is searched again. Returns
+    /// the number of hashes computed, or `None` once `cancel` is tripped. A
+    /// block without a coinbase to roll searches the same range until then.
+    pub fn mine_range_until(
+        &mut self,
+        hasher: &dyn Hasher,
+        range: NonceRange,
+        cancel: &CancelToken,
+    ) -> Option<u64> {
+        let mut hashes = 0;
+        loop {
+            let (found, searched) = self.header.mine_range_until(hasher, range, cancel)?;
+            hashes += searched;
+            if found || self.header.target().is_none() {
+                return Some(hashes);
+            }
+            self.roll_extra_nonce();
+        }
+    }
+
+    /// The extra nonce the coinbase carries, 0 if it carries none.
+    pub fn extra_nonce(&self) -> u64 {
+        self.transactions().first().map_or(0, template::extra_nonce)
+    }
+
+    fn roll_extra_nonce(&mut self) {
+        if let Some(body) = &mut self.body {
+            let next = body
+                .transactions
+                .first()
+                .map_or(0, template::extra_nonce)
+                .wrapping_add(1);
+            template::set_extra_nonce(&mut body.transactions, next);
+            self.header.merkle_root = body.merkle_root();
+        }
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.header.nonce
     }
 
     pub fn previous_hash(&self) -> &String {
-        &self.previous_hash
+        &self.header.previous_hash
     }
 
     pub fn timestamp(&self) -> i64 {
-        self.timestamp
+        self.header.timestamp
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.header.bits
     }
 
+    /// The transactions, none if the block has been pruned.
     pub fn transactions(&self) -> &Vec<Transaction> {
-        &self.transactions
+        static PRUNED: Vec<Transaction> = Vec::new();
+        self.body.as_ref().map_or(&PRUNED, BlockBody::transactions)
     }
 
-    pub fn miner(&self) -> &String {
-        &self.miner
+    pub fn miner(&self) -> &Address {
+        &self.header.miner
+    }
+
+    pub fn witness(&self) -> &Witness {
+        &self.header.witness
+    }
+
+    /// Copy of the block without its transactions, keeping only what is
+    /// needed to check the header.
+    pub fn pruned(&self) -> Self {
+        Block::from_header(self.header.clone())
+    }
+
+    /// Whether the transactions have been discarded, see [`Block::pruned`].
+    pub fn is_pruned(&self) -> bool {
+        self.body.is_none()
+    }
+
+    pub(crate) fn set_witness(&mut self, witness: Witness) {
+        self.header.set_witness(witness);
+    }
+
+    pub(crate) fn set_bits(&mut self, bits: u32) {
+        self.header.set_bits(bits);
+    }
+
+    pub fn verify_signature(&self) -> Result<()> {
+        self.header.verify_signature()
     }
 }
 
 impl Default for Block {
     fn default() -> Self {
-        let timestamp = Utc::now().timestamp_nanos_opt().unwrap();
-        let mut b = Block::new(0, String::new(), vec![], timestamp, "none".into());
-        let json = serde_json::to_string(&b).unwrap();
-        b.previous_hash = sha256::digest(json);
-        b
+        Block::empty_at(SystemClock.now())
+    }
+}
+
+impl Encode for BlockHeader {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.nonce.encode_to(out);
+        self.previous_hash.encode_to(out);
+        self.timestamp.encode_to(out);
+        self.bits.encode_to(out);
+        self.merkle_root.encode_to(out);
+        self.miner.encode_to(out);
+        self.witness.encode_to(out);
+    }
+}
+
+impl Decode for BlockHeader {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(BlockHeader {
+            nonce: u64::decode_from(reader)?,
+            previous_hash: String::decode_from(reader)?,
+            timestamp: i64::decode_from(reader)?,
+            bits: u32::decode_from(reader)?,
+            merkle_root: String::decode_from(reader)?,
+            miner: Address::decode_from(reader)?,
+            witness: Witness::decode_from(reader)?,
+        })
+    }
+}
+
+impl Encode for BlockBody {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.transactions.encode_to(out);
+    }
+}
+
+impl Decode for BlockBody {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(BlockBody {
+            transactions: Vec::decode_from(reader)?,
+        })
+    }
+}
+
+impl Encode for Block {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.header.encode_to(out);
+        self.body.encode_to(out);
+    }
+}
+
+impl Decode for Block {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let header = BlockHeader::decode_from(reader)?;
+        match Option::decode_from(reader)? {
+            Some(body) => Block::from_parts(header, body).ok_or_else(|| {
+                codec::Error::InvalidValue("transactions don't match the merkle root".into())
+            }),
+            None => Ok(Block::from_header(header)),
+        }
     }
 }
 
-impl ToString for Block {
-    fn to_string(&self) -> String {
-        let transactions: Vec<String> = self.transactions.iter().map(|t| t.to_string()).collect();
-        format!(
-            r#"
-        {{
-            "nonce": {},
-            "previous_hash": "{}",
-            "timestamp": {},
-            "transactions": [{}],
-            "miner": "{}",
-        }}
-        "#,
+impl std::fmt::Display for BlockHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "block {}", self.hash())?;
+        writeln!(f, "  previous  {}", self.previous_hash)?;
+        writeln!(
+            f,
+            "  mined     {} by {} (nonce {}, bits {:08x}, work {})",
+            chrono::DateTime::from_timestamp_nanos(self.timestamp).to_rfc3339(),
+            self.miner,
             self.nonce,
-            self.previous_hash,
-            self.timestamp,
-            transactions.join(","),
-            self.miner
+            self.bits,
+            self.work()
         )
     }
 }
+
+impl std::fmt::Display for Block {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.header)?;
+        if self.is_pruned() {
+            writeln!(f, "  (transactions pruned)")?;
+        }
+        for transaction in self.transactions() {
+            writeln!(f, "  {}", transaction)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Asks a proof of work search running on another thread to give up, see
+/// [`ConsensusEngine::seal`](super::ConsensusEngine::seal). Clones share
+/// the same flag.
+///
+/// ```
+/// use aeonia::blockchain::CancelToken;
+///
+/// let token = CancelToken::new();
+/// let miner = token.clone();
+/// token.cancel();
+/// assert!(miner.is_cancelled());
+/// miner.reset();
+/// assert!(!token.is_cancelled());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Clears the flag before the next search.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    authority::AuthorityLedger,
+    block::Block,
+    stake::StakeLedger,
+    state::AccountState,
+    token::{Token, TokenId, TokenLedger},
+};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode, Reader},
+};
+
+/// Balances, sequence numbers, token holdings, stakes and authority changes
+/// of every account as of the block at `height`, taken every
+/// `checkpoint_interval` blocks so a restarting node can resume from it
+/// rather than replaying the chain from genesis. Peers can compare checkpoints by height and tip hash.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    height: u64,
+    tip_hash: String,
+    /// Both sorted by address so equal states encode identically.
+    balances: Vec<(Address, f64)>,
+    sequences: Vec<(Address, u64)>,
+    /// Sorted by id, and by id and holder.
+    #[serde(default)]
+    tokens: Vec<(TokenId, Token)>,
+    #[serde(default)]
+    token_balances: Vec<((TokenId, Address), u64)>,
+    /// Sorted by address.
+    #[serde(default)]
+    stakes: Vec<(Address, f64)>,
+    /// Sorted by address.
+    #[serde(default)]
+    authorities: Vec<(Address, i64)>,
+    /// Keys of the double-sign evidence already used, sorted.
+    #[serde(default)]
+    slashed: Vec<(String, String)>,
+}
+
+impl Checkpoint {
+    pub(super) fn new(height: u64, tip: &Block, state: &AccountState) -> Self {
+        let mut balances = state.balances();
+        balances.sort_by_key(|(address, _)| *address);
+        let mut sequences = state.sequences();
+        sequences.sort();
+        Checkpoint {
+            height,
+            tip_hash: tip.hash(),
+            balances,
+            sequences,
+            tokens: state.tokens().tokens(),
+            token_balances: state.tokens().balances(),
+            stakes: state.stakes().stakes(),
+            authorities: state.authorities().changes(),
+            slashed: state.stakes().slashed(),
+        }
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn tip_hash(&self) -> &str {
+        &self.tip_hash
+    }
+
+    /// Whether this checkpoint was taken on `chain`, i.e. the block at its
+    /// height is still the one it was taken at.
+    pub fn matches(&self, chain: &[Block]) -> bool {
+        usize::try_from(self.height)
+            .ok()
+            .and_then(|height| chain.get(height))
+            .is_some_and(|block| block.hash() == self.tip_hash)
+    }
+
+    pub(super) fn state(&self) -> AccountState {
+        AccountState::from_parts(
+            self.balances.iter().copied(),
+            self.sequences.iter().copied(),
+            TokenLedger::from_parts(self.tokens.clone(), self.token_balances.clone()),
+            StakeLedger::from_parts(self.stakes.iter().copied(), self.slashed.iter().cloned()),
+            AuthorityLedger::from_parts(self.authorities.iter().copied()),
+        )
+    }
+}
+
+impl Encode for Checkpoint {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.height.encode_to(out);
+        self.tip_hash.encode_to(out);
+        self.balances.encode_to(out);
+        self.sequences.encode_to(out);
+        self.tokens.encode_to(out);
+        self.token_balances.encode_to(out);
+        self.stakes.encode_to(out);
+        self.authorities.encode_to(out);
+        self.slashed.encode_to(out);
+    }
+}
+
+impl Decode for Checkpoint {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(Checkpoint {
+            height: u64::decode_from(reader)?,
+            tip_hash: String::decode_from(reader)?,
+            balances: Vec::decode_from(reader)?,
+            sequences: Vec::decode_from(reader)?,
+            tokens: Vec::decode_from(reader)?,
+            token_balances: Vec::decode_from(reader)?,
+            stakes: Vec::decode_from(reader)?,
+            authorities: Vec::decode_from(reader)?,
+            slashed: Vec::decode_from(reader)?,
+        })
+    }
+}
@@ -0,0 +1,125 @@
+use super::{
+    block::{Block, BlockBody, BlockHeader},
+    transaction::Transaction,
+    Error, Result, TxId,
+};
+use crate::codec::{self, Decode, Encode, Reader};
+
+/// A block announced by its header and the ids of its transactions. Peers
+/// already hold most of those transactions in their pool, so they rebuild
+/// the block from it and only fetch the ones they miss instead of
+/// downloading the whole body again. Coinbases can't be in any pool and are
+/// sent whole.
+///
+/// ```
+/// use aeonia::blockchain::{Block, CompactBlock, Reconstruction};
+///
+/// let block = Block::default();
+/// let compact = CompactBlock::new(&block);
+/// assert_eq!(compact.hash(), block.hash());
+/// match compact.reconstruct(|_| None).unwrap() {
+///     Reconstruction::Complete(rebuilt) => assert_eq!(rebuilt.hash(), block.hash()),
+///     Reconstruction::Missing(ids) => panic!("missing {:?}", ids),
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompactBlock {
+    header: BlockHeader,
+    ids: Vec<TxId>,
+    /// Transactions sent along with the ids, with their index in the block.
+    prefilled: Vec<(u32, Transaction)>,
+}
+
+/// Outcome of rebuilding a [`CompactBlock`].
+#[derive(Clone, Debug)]
+pub enum Reconstruction {
+    Complete(Box<Block>),
+    /// Ids of the transactions that have to be fetched first, in block
+    /// order.
+    Missing(Vec<TxId>),
+}
+
+impl CompactBlock {
+    pub fn new(block: &Block) -> Self {
+        CompactBlock {
+            header: block.header().clone(),
+            ids: block.transactions().iter().map(Transaction::id).collect(),
+            prefilled: block
+                .transactions()
+                .iter()
+                .enumerate()
+                .filter(|(_, transaction)| transaction.is_coinbase())
+                .map(|(index, transaction)| (index as u32, transaction.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        self.header.hash()
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn ids(&self) -> &[TxId] {
+        &self.ids
+    }
+
+    /// Rebuilds the block, taking the transactions that weren't sent along
+    /// from `lookup`. Fails if the transactions don't add up to the
+    /// announced header.
+    pub fn reconstruct(
+        &self,
+        lookup: impl Fn(&TxId) -> Option<Transaction>,
+    ) -> Result<Reconstruction> {
+        let mut transactions: Vec<Option<Transaction>> = vec![None; self.ids.len()];
+        for (index, transaction) in &self.prefilled {
+            let slot = transactions
+                .get_mut(*index as usize)
+                .ok_or_else(|| self.invalid("prefilled transaction out of range"))?;
+            *slot = Some(transaction.clone());
+        }
+        let mut missing = vec![];
+        for (slot, id) in transactions.iter_mut().zip(&self.ids) {
+            if slot.is_none() {
+                *slot = lookup(id);
+            }
+            if slot.is_none() {
+                missing.push(id.clone());
+            }
+        }
+        if !missing.is_empty() {
+            return Ok(Reconstruction::Missing(missing));
+        }
+        let body = BlockBody::new(transactions.into_iter().flatten().collect());
+        let block = Block::from_parts(self.header.clone(), body)
+            .ok_or_else(|| self.invalid("transactions don't match the header"))?;
+        Ok(Reconstruction::Complete(Box::new(block)))
+    }
+
+    fn invalid(&self, reason: &'static str) -> Error {
+        Error::InvalidCompactBlock {
+            block: self.hash(),
+            reason,
+        }
+    }
+}
+
+impl Encode for CompactBlock {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.header.encode_to(out);
+        self.ids.encode_to(out);
+        self.prefilled.encode_to(out);
+    }
+}
+
+impl Decode for CompactBlock {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(CompactBlock {
+            header: BlockHeader::decode_from(reader)?,
+            ids: Vec::decode_from(reader)?,
+            prefilled: Vec::decode_from(reader)?,
+        })
+    }
+}
@@ -0,0 +1,248 @@
+//! Stress tests in the spirit of loom for a chain shared between threads:
+//! miners, payers, readers and an event listener all go through the same
+//! [`SharedBlockchain`] at once, and a watchdog fails the test if they stop
+//! making progress.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::{
+    CancelToken, ChainEvent, ConsensusEngine, EmissionSchedule, Error, HashAlgorithm, Mined,
+    ProofOfWork, SharedBlockchain, Target,
+};
+use crate::{
+    miner::{Miner, MinerConfig},
+    testing::{self, TestChain},
+};
+
+const SEED: u64 = 7;
+const THREADS: u64 = 4;
+const ROUNDS: u64 = 20;
+/// Far longer than the tests take, short enough to report a deadlock.
+const WATCHDOG: Duration = Duration::from_secs(120);
+
+/// Runs `test` on its own thread, failing if it doesn't finish within
+/// [`WATCHDOG`].
+fn with_watchdog(test: impl FnOnce() + Send + 'static) {
+    let (done, finished) = mpsc::channel();
+    let runner = thread::spawn(move || {
+        test();
+        let _ = done.send(());
+    });
+    match finished.recv_timeout(WATCHDOG) {
+        Ok(()) => runner.join().unwrap(),
+        // The test panicked and dropped `done` on the way out.
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            if let Err(panic) = runner.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+        Err(mpsc::RecvTimeoutError::Timeout) => panic!("no progress in {:?}", WATCHDOG),
+    }
+}
+
+#[test]
+fn threads_sharing_the_chain_make_progress() {
+    with_watchdog(|| {
+        let blockchain = SharedBlockchain::new(TestChain::new(SEED).blockchain);
+        let events = blockchain.subscribe().unwrap();
+        let mined = AtomicU64::new(0);
+        let paid = Mutex::new(vec![]);
+        let (blockchain, mined, paid) = (&blockchain, &mined, &paid);
+        thread::scope(|scope| {
+            // Reads the chain back on every event, as the node's mempool log
+            // does.
+            let listener = scope.spawn(move || {
+                for event in events {
+                    if event == ChainEvent::Shutdown {
+                        break;
+                    }
+                    blockchain.pending_transactions();
+                    blockchain.chain_height().unwrap();
+                }
+            });
+            let mut workers = vec![];
+            for thread in 0..THREADS {
+                workers.push(scope.spawn(move || {
+                    let miner = testing::wallet(thread);
+                    for _ in 0..ROUNDS {
+                        if blockchain.mine(&miner) {
+                            mined.fetch_add(1, Ordering::AcqRel);
+                        }
+                    }
+                }));
+                workers.push(scope.spawn(move || {
+                    for round in 0..ROUNDS {
+                        // The faucet pays each recipient only once in a while.
+                        let recipient = *testing::wallet(100 + thread * ROUNDS + round).address();
+                        let payment = blockchain
+                            .write()
+                            .unwrap()
+                            .request_from_faucet(&recipient, 1.0);
+                        if let Ok(transaction) = payment {
+                            paid.lock().unwrap().push(transaction.id());
+                        }
+                    }
+                }));
+                workers.push(scope.spawn(move || {
+                    for _ in 0..ROUNDS {
+                        let chain = blockchain.read().unwrap();
+                        chain.verify_state_against_chain().unwrap();
+                        chain.snapshot().unwrap();
+                    }
+                }));
+            }
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            blockchain.shutdown().unwrap();
+            listener.join().unwrap();
+        });
+        let chain = blockchain.read().unwrap();
+        assert_eq!(chain.chain_height().unwrap(), mined.load(Ordering::Acquire));
+        chain.verify_state_against_chain().unwrap();
+        for id in paid.lock().unwrap().iter() {
+            let confirmed = chain.transaction_by_id(id).unwrap().is_some();
+            assert!(confirmed || chain.pending_transaction(id).is_some());
+        }
+    });
+}
+
+#[test]
+fn block_mined_on_an_old_tip_is_dropped() {
+    let mut chain = TestChain::new(SEED);
+    let miner = testing::wallet(1);
+    let candidate = chain.blockchain.block_candidate(miner.address()).unwrap();
+    // Another block lands while the candidate's proof of work is searched.
+    chain.mine(&miner);
+    let tip = chain.blockchain.last_block().unwrap();
+    let consensus = testing::config().consensus_engine();
+    let mined = Mined::new(candidate, consensus.as_ref(), &miner, &CancelToken::new()).unwrap();
+    assert!(matches!(
+        chain.blockchain.connect_mined(mined),
+        Err(Error::StaleBlock { .. })
+    ));
+    assert_eq!(chain.blockchain.last_block().unwrap().hash(), tip.hash());
+}
+
+/// Proof of work no search will finish.
+fn unreachable_engine() -> Arc<dyn ConsensusEngine> {
+    Arc::new(ProofOfWork {
+        bits: Target::from_zero_digits(64).to_compact(),
+        hash: HashAlgorithm::Sha256,
+        emission: EmissionSchedule::default(),
+    })
+}
+
+#[test]
+fn cancelled_search_gives_up() {
+    with_watchdog(|| {
+        let chain = TestChain::new(SEED);
+        let miner = testing::wallet(1);
+        let candidate = chain.blockchain.block_candidate(miner.address()).unwrap();
+        let cancel = CancelToken::new();
+        let search = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                Mined::new(candidate, unreachable_engine().as_ref(), &miner, &cancel)
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        cancel.cancel();
+        assert!(matches!(
+            search.join().unwrap(),
+            Err(Error::MiningCancelled)
+        ));
+    });
+}
+
+#[test]
+fn miner_starts_over_when_the_tip_changes() {
+    with_watchdog(|| {
+        let mut chain = TestChain::new(SEED);
+        chain.mine_blocks(&testing::wallet(2), 2);
+        let blockchain =
+            SharedBlockchain::new(chain.blockchain.with_consensus(unreachable_engine()));
+        let config = MinerConfig {
+            poll_interval: Duration::from_millis(10),
+            idle_interval: Some(Duration::ZERO),
+        };
+        let miner = Miner::start(blockchain.clone(), testing::wallet(1), config);
+        // Each change of tip abandons the search and starts another one.
+        for _ in 0..2 {
+            thread::sleep(Duration::from_millis(50));
+            blockchain.write().unwrap().disconnect_tip().unwrap();
+        }
+        assert!(miner.is_running());
+        // Stopping abandons the last search too, so this returns at once.
+        miner.stop();
+        assert_eq!(blockchain.chain_height().unwrap(), 0);
+    });
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_miner_starts_over_when_the_tip_changes() {
+    use crate::runtime::AsyncBlockchain;
+
+    with_watchdog(|| {
+        let mut chain = TestChain::new(SEED);
+        chain.mine_blocks(&testing::wallet(2), 2);
+        let blockchain =
+            AsyncBlockchain::new(chain.blockchain.with_consensus(unreachable_engine()));
+        let config = MinerConfig {
+            poll_interval: Duration::from_millis(10),
+            idle_interval: Some(Duration::ZERO),
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let miner = blockchain.spawn_miner(testing::wallet(1), config);
+            for _ in 0..2 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                blockchain.write().await.disconnect_tip().unwrap();
+            }
+            // Stopping abandons the last search too, so this returns at once.
+            miner.stop().await;
+            assert_eq!(blockchain.write().await.chain_height().unwrap(), 0);
+        });
+    });
+}
+
+#[test]
+fn racing_payments_never_overdraw_their_sender() {
+    with_watchdog(|| {
+        let mut chain = TestChain::new(SEED);
+        let mut sender = testing::wallet(3);
+        let recipient = *testing::wallet(4).address();
+        chain.fund(sender.address(), 1.0, &testing::wallet(1));
+        // Ten payments in order, of which only the first three fit.
+        let payments: Vec<_> = (0..10)
+            .map(|sequence| {
+                sender
+                    .sign_transaction(&recipient, 0.3, 0.0, sequence, vec![], vec![])
+                    .unwrap()
+            })
+            .collect();
+        let blockchain = SharedBlockchain::new(chain.blockchain);
+        let (blockchain, payments) = (&blockchain, &payments);
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(move || {
+                    for payment in payments {
+                        let _ = blockchain.add_transaction(payment.clone());
+                    }
+                });
+            }
+        });
+        let blockchain = blockchain.read().unwrap();
+        assert_eq!(blockchain.pending_transaction_count(), 3);
+        let outflow = blockchain.mempool().pending_outflow(sender.address());
+        assert!((outflow - 0.9).abs() < 1e-9);
+    });
+}
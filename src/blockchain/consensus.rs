@@ -0,0 +1,369 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    authority::AuthorityLedger,
+    block::{Block, BlockHeader},
+    cancel::CancelToken,
+    emission::EmissionSchedule,
+    hasher::HashAlgorithm,
+    stake::StakeLedger,
+    transaction::Transaction,
+    Error, Result,
+};
+use crate::address::Address;
+
+/// Which [`ConsensusEngine`] a chain runs. Every node on a network must agree on
+/// it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    /// [`ProofOfWork`].
+    #[default]
+    Pow,
+    /// [`ProofOfStake`].
+    Pos,
+    /// [`FixedInterval`], for development chains.
+    Dev,
+    /// [`ProofOfAuthority`].
+    Poa,
+}
+
+impl std::str::FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "pow" => Ok(Engine::Pow),
+            "pos" => Ok(Engine::Pos),
+            "dev" => Ok(Engine::Dev),
+            "poa" => Ok(Engine::Poa),
+            _ => Err(format!("unknown consensus engine {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Engine::Pow => write!(f, "pow"),
+            Engine::Pos => write!(f, "pos"),
+            Engine::Dev => write!(f, "dev"),
+            Engine::Poa => write!(f, "poa"),
+        }
+    }
+}
+
+/// What the chain records about who may produce blocks, as of some block.
+#[derive(Clone, Copy)]
+pub struct ProducerState<'a> {
+    pub stakes: &'a StakeLedger,
+    pub authorities: &'a AuthorityLedger,
+}
+
+/// Rules for who may produce a block, what proof it carries besides its
+/// producer's signature, which every engine checks, and what it may pay its
+/// producer. [`Blockchain`](super::Blockchain) only goes through this, so
+/// swapping engines takes no change to it.
+pub trait ConsensusEngine: Send + Sync {
+    /// Fills in the proof of a block before its producer signs it. Returns
+    /// the number of hashes computed, or `None` if `cancel` was tripped
+    /// before the proof was found. Needs no access to the chain, so a
+    /// shared chain stays usable meanwhile.
+    fn seal(&self, block: &mut Block, cancel: &CancelToken) -> Option<u64>;
+
+    /// Checks the proof a header carries on its own, which is all a light
+    /// client or headers-first sync can check.
+    fn check_header(&self, header: &BlockHeader) -> Result<()>;
+
+    /// Checks that the block's producer may produce it on `parent`, given
+    /// who could produce blocks as of the parent.
+    fn check_producer(
+        &self,
+        block: &BlockHeader,
+        parent: &BlockHeader,
+        producers: ProducerState,
+    ) -> Result<()>;
+
+    /// Checks what a transaction does that only some engines allow. None
+    /// but [`ProofOfAuthority`] lets anything change the authorities, and
+    /// none but [`ProofOfStake`] lets anything stake.
+    fn check_transaction(
+        &self,
+        transaction: &Transaction,
+        _producers: ProducerState,
+    ) -> Result<()> {
+        check_no_authority(transaction)?;
+        check_no_stake(transaction)
+    }
+
+    /// The authorities that take turns producing blocks, if the engine has
+    /// any, sorted by address.
+    fn authorities(&self, _producers: ProducerState) -> Vec<Address> {
+        vec![]
+    }
+
+    /// How much a block may pay its producer on top of its fees.
+    fn emission(&self) -> &EmissionSchedule;
+
+    /// Compact target and hash of the proof of work blocks must carry,
+    /// `None` for engines that take none. Miners outside the node search
+    /// against it.
+    fn proof_of_work(&self) -> Option<(u32, HashAlgorithm)> {
+        None
+    }
+}
+
+/// Blocks need a proof of work hash, with `hash`, at or below the target
+/// whose compact form is `bits`, and anyone may produce one.
+pub struct ProofOfWork {
+    pub bits: u32,
+    pub hash: HashAlgorithm,
+    pub emission: EmissionSchedule,
+}
+
+impl ConsensusEngine for ProofOfWork {
+    fn seal(&self, block: &mut Block, cancel: &CancelToken) -> Option<u64> {
+        block.set_bits(self.bits);
+        // Configurations asking for a hasher this build lacks are refused
+        // on load; one made in code just produces blocks that fail.
+        match self.hash.hasher() {
+            Some(hasher) => block.mine_until(hasher, cancel),
+            None => Some(0),
+        }
+    }
+
+    fn check_header(&self, header: &BlockHeader) -> Result<()> {
+        let meets_target = self
+            .hash
+            .hasher()
+            .is_some_and(|hasher| header.meets_target(hasher));
+        if header.bits() != self.bits || !meets_target {
+            return Err(Error::InvalidProof {
+                hash: header.hash(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_producer(
+        &self,
+        _block: &BlockHeader,
+        _parent: &BlockHeader,
+        _producers: ProducerState,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn emission(&self) -> &EmissionSchedule {
+        &self.emission
+    }
+
+    fn proof_of_work(&self) -> Option<(u32, HashAlgorithm)> {
+        Some((self.bits, self.hash))
+    }
+}
+
+/// Time is cut into slots of `slot` and each slot on each parent belongs to
+/// one validator, drawn with a chance proportional to its stake. Blocks
+/// take no hashing, only the signature of the slot's validator. Until
+/// anything is staked, anyone may produce a block, so the chain can start.
+pub struct ProofOfStake {
+    pub slot: Duration,
+    pub emission: EmissionSchedule,
+}
+
+impl ProofOfStake {
+    /// The slot a block with `timestamp` falls in.
+    pub fn slot_of(&self, timestamp: i64) -> i64 {
+        slot_of(self.slot, timestamp)
+    }
+
+    /// The validator whose turn it is to build on `parent` at `timestamp`,
+    /// or `None` while nothing is staked.
+    pub fn producer(&self, parent: &str, timestamp: i64, stakes: &StakeLedger) -> Option<Address> {
+        let mut seed = Sha256::new();
+        seed.update(parent.as_bytes());
+        seed.update(self.slot_of(timestamp).to_le_bytes());
+        stakes.select(&seed.finalize())
+    }
+}
+
+impl ConsensusEngine for ProofOfStake {
+    fn seal(&self, _block: &mut Block, _cancel: &CancelToken) -> Option<u64> {
+        Some(0)
+    }
+
+    fn check_transaction(
+        &self,
+        transaction: &Transaction,
+        _producers: ProducerState,
+    ) -> Result<()> {
+        check_no_authority(transaction)
+    }
+
+    fn check_header(&self, _header: &BlockHeader) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_producer(
+        &self,
+        block: &BlockHeader,
+        parent: &BlockHeader,
+        producers: ProducerState,
+    ) -> Result<()> {
+        match self.producer(&parent.hash(), block.timestamp(), producers.stakes) {
+            Some(expected) if expected != *block.miner() => Err(Error::WrongProducer {
+                hash: block.hash(),
+                expected,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn emission(&self) -> &EmissionSchedule {
+        &self.emission
+    }
+}
+
+/// Anyone may produce a block without any proof, but no sooner than
+/// `interval` after its parent, so a development chain grows at a steady
+/// pace without burning CPU.
+pub struct FixedInterval {
+    pub interval: Duration,
+    pub emission: EmissionSchedule,
+}
+
+impl ConsensusEngine for FixedInterval {
+    fn seal(&self, _block: &mut Block, _cancel: &CancelToken) -> Option<u64> {
+        Some(0)
+    }
+
+    fn check_header(&self, _header: &BlockHeader) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_producer(
+        &self,
+        block: &BlockHeader,
+        parent: &BlockHeader,
+        _producers: ProducerState,
+    ) -> Result<()> {
+        let interval = i64::try_from(self.interval.as_nanos()).unwrap_or(i64::MAX);
+        let earliest = parent.timestamp().saturating_add(interval);
+        if block.timestamp() < earliest {
+            return Err(Error::TooSoon {
+                hash: block.hash(),
+                earliest,
+            });
+        }
+        Ok(())
+    }
+
+    fn emission(&self) -> &EmissionSchedule {
+        &self.emission
+    }
+}
+
+/// Time is cut into slots of `slot` and the authorities take turns
+/// producing a block per slot, in address order. Blocks take no hashing,
+/// only the signature of the slot's authority, and one slot holds at most
+/// one block of a chain. The set starts as `authorities` and changes by
+/// payments from a current authority to itself, see [`AuthorityOp`].
+///
+/// [`AuthorityOp`]: super::AuthorityOp
+pub struct ProofOfAuthority {
+    pub slot: Duration,
+    /// The authorities at genesis.
+    pub authorities: Vec<Address>,
+    pub emission: EmissionSchedule,
+}
+
+impl ProofOfAuthority {
+    /// The authority whose turn it is at `timestamp`, or `None` if there
+    /// are none.
+    pub fn producer(&self, timestamp: i64, authorities: &AuthorityLedger) -> Option<Address> {
+        let authorities = authorities.authorities(&self.authorities);
+        let len = i64::try_from(authorities.len())
+            .ok()
+            .filter(|len| *len > 0)?;
+        let turn = slot_of(self.slot, timestamp).rem_euclid(len);
+        authorities.get(usize::try_from(turn).ok()?).copied()
+    }
+}
+
+impl ConsensusEngine for ProofOfAuthority {
+    fn seal(&self, _block: &mut Block, _cancel: &CancelToken) -> Option<u64> {
+        Some(0)
+    }
+
+    fn check_header(&self, _header: &BlockHeader) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_producer(
+        &self,
+        block: &BlockHeader,
+        parent: &BlockHeader,
+        producers: ProducerState,
+    ) -> Result<()> {
+        let slot = slot_of(self.slot, parent.timestamp());
+        if slot_of(self.slot, block.timestamp()) <= slot {
+            let nanos = i64::try_from(self.slot.as_nanos()).unwrap_or(i64::MAX);
+            return Err(Error::TooSoon {
+                hash: block.hash(),
+                earliest: (slot + 1).saturating_mul(nanos.max(1)),
+            });
+        }
+        match self.producer(block.timestamp(), producers.authorities) {
+            Some(expected) if expected != *block.miner() => Err(Error::WrongProducer {
+                hash: block.hash(),
+                expected,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_transaction(&self, transaction: &Transaction, producers: ProducerState) -> Result<()> {
+        check_no_stake(transaction)?;
+        producers.authorities.check(&self.authorities, transaction)
+    }
+
+    fn authorities(&self, producers: ProducerState) -> Vec<Address> {
+        producers.authorities.authorities(&self.authorities)
+    }
+
+    fn emission(&self) -> &EmissionSchedule {
+        &self.emission
+    }
+}
+
+/// Fails if `transaction` changes the authorities, on an engine without
+/// any.
+fn check_no_authority(transaction: &Transaction) -> Result<()> {
+    if transaction.authority.is_some() {
+        return Err(Error::InvalidAuthority {
+            id: transaction.id(),
+        });
+    }
+    Ok(())
+}
+
+/// Fails if `transaction` stakes, unstakes or slashes, on an engine that
+/// doesn't select producers by stake.
+fn check_no_stake(transaction: &Transaction) -> Result<()> {
+    if transaction.stake.is_some() {
+        return Err(Error::InvalidStake {
+            id: transaction.id(),
+        });
+    }
+    Ok(())
+}
+
+/// The slot of length `slot` a block with `timestamp` falls in.
+fn slot_of(slot: Duration, timestamp: i64) -> i64 {
+    let nanos = i64::try_from(slot.as_nanos()).unwrap_or(i64::MAX).max(1);
+    timestamp.div_euclid(nanos)
+}
@@ -0,0 +1,95 @@
+use super::{state::BALANCE_EPSILON, Error, Result};
+
+const DEFAULT_INITIAL_REWARD: f64 = 1.0;
+const DEFAULT_HALVING_INTERVAL: u64 = 210_000;
+/// After this many halvings the halved reward is below any amount worth
+/// paying, so only the tail emission is left.
+const MAX_HALVINGS: u64 = 64;
+
+/// How much a block may pay its miner on top of the fees it collects,
+/// depending on its height.
+#[derive(Clone, Debug)]
+pub struct EmissionSchedule {
+    /// Reward of the blocks before the first halving.
+    pub initial_reward: f64,
+    /// Blocks between halvings of the reward; 0 never halves it.
+    pub halving_interval: u64,
+    /// Reward the halvings never go below, so mining stays paid once they
+    /// have run their course; 0 lets the reward run out.
+    pub tail_emission: f64,
+    /// Most coins that may ever exist, genesis allocation included; 0 for
+    /// no limit. Rewards are cut short once it is reached.
+    pub max_supply: f64,
+}
+
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        EmissionSchedule {
+            initial_reward: DEFAULT_INITIAL_REWARD,
+            halving_interval: DEFAULT_HALVING_INTERVAL,
+            tail_emission: 0.0,
+            max_supply: 0.0,
+        }
+    }
+}
+
+impl EmissionSchedule {
+    /// Reward of the block at `height`. Genesis has none; its allocation is
+    /// fixed when the chain is created.
+    pub fn reward_at(&self, height: u64) -> f64 {
+        if height == 0 {
+            return 0.0;
+        }
+        let halvings = height
+            .checked_div(self.halving_interval)
+            .unwrap_or_default();
+        if halvings >= MAX_HALVINGS {
+            return self.tail_emission;
+        }
+        (self.initial_reward / (1u64 << halvings) as f64).max(self.tail_emission)
+    }
+
+    /// Largest reward the block at `height` may pay with `issued` coins
+    /// already in existence.
+    pub fn capped_reward_at(&self, height: u64, issued: f64) -> f64 {
+        let reward = self.reward_at(height);
+        if self.max_supply <= 0.0 {
+            return reward;
+        }
+        reward.min(self.max_supply - issued).max(0.0)
+    }
+
+    /// Fails if `issued` coins would be more than the cap allows.
+    pub fn check_supply(&self, issued: f64) -> Result<()> {
+        if self.max_supply > 0.0 && issued > self.max_supply + BALANCE_EPSILON {
+            return Err(Error::SupplyCapExceeded {
+                cap: self.max_supply,
+            });
+        }
+        Ok(())
+    }
+
+    /// Sum of the rewards of every block up to and including `height`.
+    pub fn issued_through(&self, height: u64) -> f64 {
+        if self.halving_interval == 0 {
+            return self.reward_at(1) * height as f64;
+        }
+        let mut total = 0.0;
+        let mut start = 1;
+        while start <= height {
+            let era = start / self.halving_interval;
+            let reward = self.reward_at(start);
+            if era >= MAX_HALVINGS {
+                total += reward * (height - start + 1) as f64;
+                break;
+            }
+            let end = (era + 1)
+                .saturating_mul(self.halving_interval)
+                .saturating_sub(1)
+                .min(height);
+            total += reward * (end - start + 1) as f64;
+            start = end + 1;
+        }
+        total
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use super::transaction::TxId;
+
+/// Notable changes to the chain or the transaction pool.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChainEvent {
+    /// This node mined a block onto its chain.
+    BlockMined {
+        height: u64,
+        hash: String,
+    },
+    /// A block received from elsewhere extended the tip.
+    BlockConnected {
+        height: u64,
+        hash: String,
+    },
+    /// The tip was rolled back with
+    /// [`Blockchain::disconnect_tip`](super::Blockchain::disconnect_tip).
+    BlockDisconnected {
+        height: u64,
+        hash: String,
+    },
+    TransactionAccepted {
+        id: TxId,
+    },
+    TransactionRejected {
+        id: TxId,
+        reason: String,
+    },
+    /// A pending transaction was swapped for one with the same sequence
+    /// number and a higher fee.
+    TransactionReplaced {
+        replaced: TxId,
+        by: TxId,
+    },
+    /// The main chain switched to another branch. Hashes are listed oldest
+    /// first.
+    Reorg {
+        fork_height: u64,
+        disconnected: Vec<String>,
+        connected: Vec<String>,
+    },
+    /// Initial sync connected blocks, bringing the chain to `height` of the
+    /// `target` its peers announced.
+    SyncProgress {
+        height: u64,
+        target: u64,
+    },
+    /// The node is shutting down; no further events follow.
+    Shutdown,
+}
+
+/// Fans events out to every live subscriber. Subscribers that dropped their
+/// receiver are forgotten on the next publish.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Sender<ChainEvent>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self) -> Receiver<ChainEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn publish(&mut self, event: ChainEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Drops every subscriber, ending their receive loops once they have
+    /// drained what was already published.
+    pub fn close(&mut self) {
+        self.subscribers.clear();
+    }
+}
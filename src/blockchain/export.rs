@@ -0,0 +1,138 @@
+use serde::Serialize;
+
+use super::{
+    block::Block,
+    transaction::{Expiry, Transaction},
+    TxId,
+};
+use crate::address::Address;
+
+/// Structured copy of the chain and the pool for consumption by scripts,
+/// produced by [`Blockchain::export`](super::Blockchain::export).
+#[derive(Serialize)]
+pub struct ChainExport {
+    pub height: u64,
+    pub blocks: Vec<BlockRecord>,
+    pub pending: Vec<TransactionRecord>,
+}
+
+#[derive(Serialize)]
+pub struct BlockRecord {
+    pub height: u64,
+    pub hash: String,
+    pub previous_hash: String,
+    pub nonce: u64,
+    pub timestamp: i64,
+    pub bits: u32,
+    /// Work of the block alone, see [`Block::work`].
+    pub work: u128,
+    pub miner: Address,
+    pub transactions: Vec<TransactionRecord>,
+}
+
+#[derive(Serialize)]
+pub struct TransactionRecord {
+    pub id: TxId,
+    /// `None` for coinbases.
+    pub sender: Option<Address>,
+    pub recipient: Address,
+    pub amount: f64,
+    pub fee: f64,
+    pub sequence: u64,
+    /// Left out of the CSV, where it could break the columns.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub memo: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<Expiry>,
+}
+
+impl BlockRecord {
+    pub(super) fn new(height: u64, block: &Block) -> Self {
+        BlockRecord {
+            height,
+            hash: block.hash(),
+            previous_hash: block.previous_hash().clone(),
+            nonce: block.nonce(),
+            timestamp: block.timestamp(),
+            bits: block.bits(),
+            work: block.work(),
+            miner: *block.miner(),
+            transactions: block
+                .transactions()
+                .iter()
+                .map(TransactionRecord::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&Transaction> for TransactionRecord {
+    fn from(transaction: &Transaction) -> Self {
+        TransactionRecord {
+            id: transaction.id(),
+            sender: transaction.sender().copied(),
+            recipient: transaction.recipient,
+            amount: transaction.amount,
+            fee: transaction.fee,
+            sequence: transaction.sequence,
+            memo: String::from_utf8_lossy(&transaction.memo).into_owned(),
+            expiry: transaction.expiry,
+        }
+    }
+}
+
+const CSV_HEADER: &str = "height,block_hash,index,id,sender,recipient,amount,fee,sequence";
+
+impl ChainExport {
+    /// One row per transaction, confirmed ones first. Pending transactions
+    /// have empty `height`, `block_hash` and `index` columns, and coinbases an
+    /// empty `sender`. No field ever contains a comma, so nothing is quoted.
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("{}\n", CSV_HEADER);
+        for block in &self.blocks {
+            for (index, transaction) in block.transactions.iter().enumerate() {
+                let location = format!("{},{},{}", block.height, block.hash, index);
+                out.push_str(&transaction.csv_row(&location));
+            }
+        }
+        for transaction in &self.pending {
+            out.push_str(&transaction.csv_row(",,"));
+        }
+        out
+    }
+
+    /// Fixed width summary of every block, one line each.
+    pub fn to_table(&self) -> String {
+        let mut out = format!(
+            "{:>6}  {:<64}  {:>4}  {:<34}  {}\n",
+            "HEIGHT", "HASH", "TXS", "MINER", "TIMESTAMP"
+        );
+        for block in &self.blocks {
+            out.push_str(&format!(
+                "{:>6}  {:<64}  {:>4}  {:<34}  {}\n",
+                block.height,
+                block.hash,
+                block.transactions.len(),
+                block.miner.to_string(),
+                chrono::DateTime::from_timestamp_nanos(block.timestamp).to_rfc3339(),
+            ));
+        }
+        out.push_str(&format!("{} pending transactions\n", self.pending.len()));
+        out
+    }
+}
+
+impl TransactionRecord {
+    fn csv_row(&self, location: &str) -> String {
+        format!(
+            "{},{},{},{},{},{},{}\n",
+            location,
+            self.id,
+            self.sender.map(|s| s.to_string()).unwrap_or_default(),
+            self.recipient,
+            self.amount,
+            self.fee,
+            self.sequence
+        )
+    }
+}
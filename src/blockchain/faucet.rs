@@ -0,0 +1,69 @@
+use std::{collections::HashMap, time::Duration};
+
+use super::{Error, Result};
+use crate::address::Address;
+
+const DEFAULT_GENESIS_FUNDS: f64 = 1_000_000.0;
+const DEFAULT_MAX_PER_REQUEST: f64 = 100.0;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub struct FaucetConfig {
+    /// Coins allocated to the faucet account in the genesis block. This is
+    /// all the faucet can ever pay out.
+    pub genesis_funds: f64,
+    /// Largest amount a single request may ask for.
+    pub max_per_request: f64,
+    /// Minimum time between two payouts to the same address.
+    pub cooldown: Duration,
+}
+
+impl Default for FaucetConfig {
+    fn default() -> Self {
+        FaucetConfig {
+            genesis_funds: DEFAULT_GENESIS_FUNDS,
+            max_per_request: DEFAULT_MAX_PER_REQUEST,
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+}
+
+/// Request limits for the faucet account. Its balance is an ordinary
+/// account balance, so payouts go through the usual balance checks.
+pub struct Faucet {
+    config: FaucetConfig,
+    /// Time of the last payout to each address, in nanoseconds since the
+    /// Unix epoch.
+    last_payout: HashMap<Address, i64>,
+}
+
+impl Faucet {
+    pub fn new(config: FaucetConfig) -> Self {
+        Faucet {
+            config,
+            last_payout: HashMap::new(),
+        }
+    }
+
+    /// Checks a request for `amount` made at `now`.
+    pub fn check(&self, recipient: &Address, amount: f64, now: i64) -> Result<()> {
+        if amount > self.config.max_per_request {
+            return Err(Error::FaucetLimitExceeded {
+                max: self.config.max_per_request,
+            });
+        }
+        if let Some(last) = self.last_payout.get(recipient) {
+            let cooldown = i64::try_from(self.config.cooldown.as_nanos()).unwrap_or(i64::MAX);
+            if now.saturating_sub(*last) < cooldown {
+                return Err(Error::FaucetRateLimited {
+                    recipient: *recipient,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record_payout(&mut self, recipient: Address, now: i64) {
+        self.last_payout.insert(recipient, now);
+    }
+}
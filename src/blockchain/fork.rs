@@ -0,0 +1,69 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::block::Block;
+
+/// Result of handing a block to [`super::Blockchain::receive_block`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlockStatus {
+    /// The block was already part of the main chain or a side branch.
+    AlreadyKnown,
+    /// The block extended the current tip.
+    Extended,
+    /// The block was stored on a side branch with no more work than the
+    /// main chain.
+    SideBranch,
+    /// The block gave a side branch more work than the main chain, which
+    /// became the new main chain.
+    Reorganized {
+        disconnected: usize,
+        connected: usize,
+    },
+}
+
+/// Blocks that are known but not part of the main chain, keyed by hash,
+/// with their height and the work of their chain up to them.
+#[derive(Default)]
+pub struct SideBranches {
+    blocks: HashMap<String, (Arc<Block>, u64, u128)>,
+}
+
+impl SideBranches {
+    pub fn contains(&self, hash: &str) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn height_of(&self, hash: &str) -> Option<u64> {
+        self.blocks.get(hash).map(|(_, height, _)| *height)
+    }
+
+    pub fn chain_work(&self, hash: &str) -> Option<u128> {
+        self.blocks.get(hash).map(|(_, _, work)| *work)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<Arc<Block>> {
+        self.blocks.get(hash).map(|(block, _, _)| block.clone())
+    }
+
+    pub fn insert(&mut self, block: Arc<Block>, height: u64, chain_work: u128) {
+        self.blocks
+            .insert(block.hash(), (block, height, chain_work));
+    }
+
+    pub fn remove(&mut self, hash: &str) -> Option<Arc<Block>> {
+        self.blocks.remove(hash).map(|(block, _, _)| block)
+    }
+
+    /// Walks back from `tip` through side-branch blocks, returning them in
+    /// chain order (oldest first). The first block's parent is the point where
+    /// the branch joins the main chain.
+    pub fn branch_to(&self, tip: &str) -> Vec<Arc<Block>> {
+        let mut branch = vec![];
+        let mut hash = tip.to_string();
+        while let Some((block, _, _)) = self.blocks.get(&hash) {
+            hash = block.previous_hash().clone();
+            branch.push(block.clone());
+        }
+        branch.reverse();
+        branch
+    }
+}
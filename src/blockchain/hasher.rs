@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{block::Block, Error, Result};
+
+/// Hash function a header's proof of work is computed with. Only the proof
+/// is pluggable: block hashes, which identify blocks and link the chain,
+/// transaction ids and Merkle roots stay SHA-256, so changing the proof of
+/// work hash leaves every other part of the protocol alone.
+pub trait Hasher: Send + Sync {
+    fn hash(&self, data: &[u8]) -> [u8; 32];
+}
+
+/// SHA-256, what chains were mined with before the algorithm could be
+/// chosen.
+pub struct Sha256Hasher;
+
+impl Hasher for Sha256Hasher {
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
+
+/// SHA-256 applied twice, as Bitcoin mines.
+pub struct DoubleSha256Hasher;
+
+impl Hasher for DoubleSha256Hasher {
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        Sha256::digest(Sha256::digest(data)).into()
+    }
+}
+
+/// BLAKE3, several times faster than SHA-256 on CPUs without SHA
+/// instructions and over large inputs, for private
+/// networks that would rather validate quickly than share hashing hardware
+/// with Bitcoin. Needs the `blake3` feature.
+#[cfg(feature = "blake3")]
+pub struct Blake3Hasher;
+
+#[cfg(feature = "blake3")]
+impl Hasher for Blake3Hasher {
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        blake3::hash(data).into()
+    }
+}
+
+/// Which [`Hasher`] a chain's proof of work takes. Every node on a network
+/// must agree on it, so chains not on the default record it in their
+/// genesis block, see [`HashAlgorithm::of_genesis`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// [`Sha256Hasher`].
+    #[default]
+    Sha256,
+    /// [`DoubleSha256Hasher`].
+    Sha256d,
+    /// [`Blake3Hasher`]. Builds without the `blake3` feature refuse to load
+    /// a configuration asking for it.
+    Blake3,
+}
+
+/// Prefix of the genesis allocation's memo naming the algorithm.
+const GENESIS_MEMO_PREFIX: &str = "pow_hash=";
+
+impl HashAlgorithm {
+    /// The hasher, `None` if this build lacks the feature it needs.
+    pub fn hasher(self) -> Option<&'static dyn Hasher> {
+        match self {
+            HashAlgorithm::Sha256 => Some(&Sha256Hasher),
+            HashAlgorithm::Sha256d => Some(&DoubleSha256Hasher),
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => Some(&Blake3Hasher),
+            #[cfg(not(feature = "blake3"))]
+            HashAlgorithm::Blake3 => None,
+        }
+    }
+
+    /// Memo of the genesis allocation recording the algorithm, empty for
+    /// the default so chains from before the choice keep their genesis.
+    pub(super) fn genesis_memo(self) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => vec![],
+            algorithm => format!("{}{}", GENESIS_MEMO_PREFIX, algorithm).into_bytes(),
+        }
+    }
+
+    /// The algorithm `genesis` records, SHA-256 if it records none. `None`
+    /// if its transactions were pruned, which leaves nothing to tell by.
+    pub fn of_genesis(genesis: &Block) -> Option<Result<Self>> {
+        if genesis.is_pruned() {
+            return None;
+        }
+        let memo = genesis
+            .transactions()
+            .first()
+            .map_or(&[][..], |allocation| &allocation.memo[..]);
+        if memo.is_empty() {
+            return Some(Ok(HashAlgorithm::Sha256));
+        }
+        Some(
+            std::str::from_utf8(memo)
+                .ok()
+                .and_then(|memo| memo.strip_prefix(GENESIS_MEMO_PREFIX))
+                .and_then(|name| name.parse().ok())
+                .ok_or_else(|| Error::UnknownHashAlgorithm {
+                    memo: String::from_utf8_lossy(memo).into_owned(),
+                }),
+        )
+    }
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha256d" => Ok(HashAlgorithm::Sha256d),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => Err(format!("unknown hash algorithm {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+            HashAlgorithm::Sha256d => write!(f, "sha256d"),
+            HashAlgorithm::Blake3 => write!(f, "blake3"),
+        }
+    }
+}
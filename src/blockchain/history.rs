@@ -0,0 +1,79 @@
+use super::transaction::{Transaction, TxId};
+use crate::address::Address;
+
+/// Which way a transaction moved coins, from the point of view of the
+/// address whose history it is in. Payments to oneself, such as
+/// cancellations, are outgoing since they still cost a fee.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+impl Direction {
+    pub(super) fn of(transaction: &Transaction, address: &Address) -> Self {
+        if transaction.sender() == Some(address) {
+            Direction::Outgoing
+        } else {
+            Direction::Incoming
+        }
+    }
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Incoming => write!(f, "in"),
+            Direction::Outgoing => write!(f, "out"),
+        }
+    }
+}
+
+/// A confirmed transaction sent or received by an address.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub txid: TxId,
+    pub direction: Direction,
+    pub height: u64,
+    /// 1 for a transaction in the tip.
+    pub confirmations: u64,
+    pub transaction: Transaction,
+}
+
+impl HistoryEntry {
+    /// Change to the address's balance: the amount received, or the amount
+    /// and fee paid out.
+    pub fn net(&self) -> f64 {
+        let transaction = &self.transaction;
+        match self.direction {
+            Direction::Incoming => transaction.amount,
+            Direction::Outgoing if transaction.sender() == Some(&transaction.recipient) => {
+                -transaction.fee
+            }
+            Direction::Outgoing => -(transaction.amount + transaction.fee),
+        }
+    }
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} {} {:+} ({} confirmations) {}",
+            self.height,
+            self.direction,
+            self.net(),
+            self.confirmations,
+            self.txid
+        )
+    }
+}
+
+/// One page of an address's history, newest first, see
+/// [`Blockchain::history`](super::Blockchain::history).
+#[derive(Clone, Debug)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    /// Transactions in the whole history, across every page.
+    pub total: usize,
+}
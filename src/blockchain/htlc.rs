@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{transaction::Transaction, witness::Witness};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode, Reader},
+    crypto::PublicKey,
+};
+
+/// Hash time-locked contract: coins sent to its address can be claimed by
+/// the holder of the `recipient` key with the preimage of `hash`, or taken
+/// back by the holder of the `refund` key once the chain reaches `timeout`.
+///
+/// Two of them locked to the same hash make an atomic swap between two
+/// chains: whoever knows the preimage claims on one chain, revealing it in
+/// the claim's witness, and the other side uses it to claim on the other
+/// chain. The contract on the chain claimed second needs the longer timeout,
+/// so its recipient can't be refunded before they had a chance to claim.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Htlc {
+    /// SHA-256 of the preimage.
+    pub hash: [u8; 32],
+    #[serde(with = "codec::as_hex")]
+    pub recipient: PublicKey,
+    #[serde(with = "codec::as_hex")]
+    pub refund: PublicKey,
+    /// First block height at which the refund key may spend.
+    pub timeout: u64,
+}
+
+impl Htlc {
+    pub fn new(hash: [u8; 32], recipient: PublicKey, refund: PublicKey, timeout: u64) -> Self {
+        Htlc {
+            hash,
+            recipient,
+            refund,
+            timeout,
+        }
+    }
+
+    /// The hash locking a contract whose preimage is `secret`.
+    pub fn lock(secret: &[u8]) -> [u8; 32] {
+        Sha256::digest(secret).into()
+    }
+
+    pub fn unlocks(&self, preimage: &[u8]) -> bool {
+        Self::lock(preimage) == self.hash
+    }
+
+    /// The HASH160 of the encoded contract, the same way multisig addresses
+    /// hash their policy.
+    pub fn address(&self, version: u8) -> Address {
+        Address::from_public_key_bytes(&self.encode(), version)
+    }
+
+    /// The preimage revealed by `transaction` if it claims this contract.
+    pub fn preimage_in(&self, transaction: &Transaction) -> Option<Vec<u8>> {
+        match &transaction.witness {
+            Witness::Htlc {
+                htlc,
+                preimage: Some(preimage),
+                ..
+            } if htlc == self => Some(preimage.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Encode for Htlc {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.hash);
+        self.recipient.encode_to(out);
+        self.refund.encode_to(out);
+        self.timeout.encode_to(out);
+    }
+}
+
+impl Decode for Htlc {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(reader.take(32)?);
+        Ok(Htlc {
+            hash,
+            recipient: PublicKey::decode_from(reader)?,
+            refund: PublicKey::decode_from(reader)?,
+            timeout: u64::decode_from(reader)?,
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use super::{block::Block, transaction::TxId};
+use crate::address::Address;
+
+/// Position of a transaction inside the chain: block height and index within
+/// that block's transaction list. Locations order the way the transactions
+/// appear in the chain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TxLocation {
+    pub height: u64,
+    pub index: usize,
+}
+
+/// Lookup tables over the confirmed chain, maintained incrementally as blocks
+/// are appended so queries don't need to scan every block.
+#[derive(Default)]
+pub struct ChainIndex {
+    blocks_by_hash: HashMap<String, u64>,
+    transactions_by_id: HashMap<TxId, TxLocation>,
+    transactions_by_address: HashMap<Address, Vec<TxLocation>>,
+    /// Work of every block up to and including each height.
+    chain_work: Vec<u128>,
+}
+
+impl ChainIndex {
+    pub fn build<'a, I: IntoIterator<Item = &'a Block>>(blocks: I) -> Self {
+        let mut index = ChainIndex::default();
+        for (height, block) in blocks.into_iter().enumerate() {
+            index.insert(height as u64, block);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, height: u64, block: &Block) {
+        self.blocks_by_hash.insert(block.hash(), height);
+        let parent_work = self.total_work();
+        self.chain_work
+            .push(parent_work.saturating_add(block.work()));
+        for (index, transaction) in block.transactions().iter().enumerate() {
+            let location = TxLocation { height, index };
+            self.transactions_by_id.insert(transaction.id(), location);
+            if let Some(sender) = transaction.sender() {
+                self.transactions_by_address
+                    .entry(*sender)
+                    .or_default()
+                    .push(location);
+            }
+            if transaction.sender() != Some(&transaction.recipient) {
+                self.transactions_by_address
+                    .entry(transaction.recipient)
+                    .or_default()
+                    .push(location);
+            }
+        }
+    }
+
+    /// Undoes [`ChainIndex::insert`] for the tip block at `height`.
+    pub fn remove(&mut self, height: u64, block: &Block) {
+        self.blocks_by_hash.remove(&block.hash());
+        self.chain_work.pop();
+        for transaction in block.transactions() {
+            self.transactions_by_id.remove(&transaction.id());
+            let addresses = transaction
+                .sender()
+                .into_iter()
+                .chain([&transaction.recipient]);
+            for address in addresses {
+                let Some(locations) = self.transactions_by_address.get_mut(address) else {
+                    continue;
+                };
+                locations.retain(|location| location.height != height);
+                if locations.is_empty() {
+                    self.transactions_by_address.remove(address);
+                }
+            }
+        }
+    }
+
+    pub fn height_of(&self, hash: &str) -> Option<u64> {
+        self.blocks_by_hash.get(hash).copied()
+    }
+
+    /// Work of the chain up to and including the block at `height`.
+    pub fn chain_work(&self, height: u64) -> Option<u128> {
+        self.chain_work.get(usize::try_from(height).ok()?).copied()
+    }
+
+    /// Work of the whole chain.
+    pub fn total_work(&self) -> u128 {
+        self.chain_work.last().copied().unwrap_or_default()
+    }
+
+    pub fn locate(&self, id: &TxId) -> Option<TxLocation> {
+        self.transactions_by_id.get(id).copied()
+    }
+
+    pub fn locations_for(&self, address: &Address) -> &[TxLocation] {
+        self.transactions_by_address
+            .get(address)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
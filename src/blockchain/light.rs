@@ -0,0 +1,176 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::{
+    block::{Block, BlockHeader},
+    consensus::ConsensusEngine,
+    merkle::MerkleProof,
+    receipt::Receipt,
+    timestamp::TimestampConfig,
+    transaction::TxId,
+    Error, Result,
+};
+use crate::{
+    clock::{Clock, SystemClock},
+    config::Config,
+};
+
+/// Follows the chain by block headers alone, for wallets that don't keep
+/// the full chain. Headers fetched from peers are checked for their link to
+/// the previous header, proof of work, miner signature and timestamp, and a
+/// payment is confirmed by a Merkle proof against the header of its block.
+///
+/// ```
+/// use aeonia::{
+///     blockchain::{Blockchain, LightClient},
+///     config::Config,
+/// };
+///
+/// let config = Config::default();
+/// let full = Blockchain::with_config(&config).unwrap();
+/// let genesis = full.block_by_height(0).unwrap();
+/// let mut light = LightClient::new(&genesis, &config);
+/// light.add_headers(full.headers(1, 100).unwrap()).unwrap();
+///
+/// let coinbase = genesis.transactions()[0].id();
+/// let (hash, proof) = full.merkle_proof(&coinbase).unwrap().unwrap();
+/// assert_eq!(light.verify_transaction(&coinbase, &hash, &proof).unwrap(), 0);
+/// ```
+pub struct LightClient {
+    consensus: Arc<dyn ConsensusEngine>,
+    timestamps: TimestampConfig,
+    clock: Arc<dyn Clock>,
+    /// Main chain headers from genesis on.
+    headers: Vec<BlockHeader>,
+    heights: HashMap<String, u64>,
+}
+
+impl LightClient {
+    /// Starts from a trusted `genesis` block, checking later headers against
+    /// the consensus settings of `config`.
+    pub fn new(genesis: &Block, config: &Config) -> Self {
+        let genesis = genesis.header().clone();
+        LightClient {
+            consensus: config.consensus_engine(),
+            timestamps: config.timestamp_config(),
+            clock: Arc::new(SystemClock),
+            heights: HashMap::from([(genesis.hash(), 0)]),
+            headers: vec![genesis],
+        }
+    }
+
+    /// Replaces the clock headers' timestamps are checked against.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn tip_height(&self) -> u64 {
+        self.headers.len() as u64 - 1
+    }
+
+    pub fn tip(&self) -> &BlockHeader {
+        &self.headers[self.headers.len() - 1]
+    }
+
+    pub fn header(&self, height: u64) -> Option<&BlockHeader> {
+        self.headers.get(usize::try_from(height).ok()?)
+    }
+
+    pub fn height_of(&self, hash: &str) -> Option<u64> {
+        self.heights.get(hash).copied()
+    }
+
+    /// Adds consecutive headers received from a peer, oldest first. The
+    /// first has to follow a known header. If they make a chain with more
+    /// work than the current one they replace the headers after the fork
+    /// point, otherwise they are dropped. Returns the height of the tip afterwards.
+    pub fn add_headers<I: IntoIterator<Item = BlockHeader>>(&mut self, headers: I) -> Result<u64> {
+        let mut headers = headers.into_iter().peekable();
+        let Some(first) = headers.peek() else {
+            return Ok(self.tip_height());
+        };
+        let fork_height =
+            self.height_of(first.previous_hash())
+                .ok_or_else(|| Error::UnknownParent {
+                    hash: first.previous_hash().clone(),
+                })?;
+        let mut branch: Vec<BlockHeader> = vec![];
+        for header in headers {
+            let parent = branch.last().unwrap_or(&self.headers[fork_height as usize]);
+            if *header.previous_hash() != parent.hash() {
+                return Err(Error::UnknownParent {
+                    hash: header.previous_hash().clone(),
+                });
+            }
+            let hash = header.hash();
+            self.consensus.check_header(&header)?;
+            header.verify_signature()?;
+            self.timestamps.validate(
+                &hash,
+                header.timestamp(),
+                &self.ancestor_timestamps(fork_height, &branch),
+                self.clock.now(),
+            )?;
+            branch.push(header);
+        }
+        let work = |headers: &[BlockHeader]| {
+            headers
+                .iter()
+                .fold(0u128, |work, header| work.saturating_add(header.work()))
+        };
+        if work(&branch) <= work(&self.headers[fork_height as usize + 1..]) {
+            return Ok(self.tip_height());
+        }
+        for header in self.headers.drain(fork_height as usize + 1..) {
+            self.heights.remove(&header.hash());
+        }
+        for header in branch {
+            self.heights
+                .insert(header.hash(), self.headers.len() as u64);
+            self.headers.push(header);
+        }
+        Ok(self.tip_height())
+    }
+
+    /// Checks that `proof` places transaction `id` in the main chain block
+    /// `block_hash`, returning the block's height.
+    pub fn verify_transaction(
+        &self,
+        id: &TxId,
+        block_hash: &str,
+        proof: &MerkleProof,
+    ) -> Result<u64> {
+        let height = self
+            .height_of(block_hash)
+            .ok_or_else(|| Error::UnknownBlock {
+                hash: block_hash.to_string(),
+            })?;
+        if proof.root(id) != self.headers[height as usize].merkle_root() {
+            return Err(Error::InvalidMerkleProof { id: id.clone() });
+        }
+        Ok(height)
+    }
+
+    /// Checks `receipt` against the main chain header at its height.
+    pub fn verify_receipt(&self, receipt: &Receipt) -> Result<()> {
+        let header = self
+            .headers
+            .get(usize::try_from(receipt.height).unwrap_or(usize::MAX))
+            .ok_or_else(|| Error::UnknownBlock {
+                hash: receipt.block_hash.clone(),
+            })?;
+        receipt.verify(header)
+    }
+
+    /// Timestamps of up to `median_window` headers ending with `branch`,
+    /// which forks off the main chain at `fork_height`.
+    fn ancestor_timestamps(&self, fork_height: u64, branch: &[BlockHeader]) -> Vec<i64> {
+        self.headers[..=fork_height as usize]
+            .iter()
+            .chain(branch)
+            .rev()
+            .take(self.timestamps.median_window)
+            .map(BlockHeader::timestamp)
+            .collect()
+    }
+}
@@ -0,0 +1,655 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::address::Address;
+
+use super::{
+    block::Block,
+    state::{AccountState, BALANCE_EPSILON},
+    transaction::{Transaction, TxId},
+    utxo::OutPoint,
+    Error, Result,
+};
+
+const DEFAULT_MAX_SIZE: usize = 5_000;
+const DEFAULT_MAX_PER_SENDER: usize = 25;
+const DEFAULT_MIN_CONFIRMATIONS: u64 = 1;
+const DEFAULT_MAX_TX_SIZE: usize = 100_000;
+/// Lowest fee per byte of each bucket of [`MempoolInfo::fee_histogram`].
+const FEE_BUCKETS: [f64; 8] = [0.0, 1e-8, 1e-7, 1e-6, 1e-5, 1e-4, 1e-3, 1e-2];
+
+/// Limits of the pool and the policy deciding which transactions it admits,
+/// on top of the consensus rules every block is held to.
+#[derive(Clone, Debug)]
+pub struct MempoolConfig {
+    /// Maximum number of transactions held at once.
+    pub max_size: usize,
+    /// Maximum number of pending transactions from a single sender, so how
+    /// many unconfirmed ancestors a new one may have.
+    pub max_per_sender: usize,
+    /// Confirmations a payment needs before its recipient can spend it. At
+    /// 0, payments still waiting in the pool can be spent too.
+    pub min_confirmations: u64,
+    /// Lowest fee per byte of encoded transaction admitted.
+    pub min_fee_rate: f64,
+    /// Largest encoded transaction admitted, in bytes.
+    pub max_tx_size: usize,
+    /// Whether transactions paying no fee are admitted at all.
+    pub accept_zero_fee: bool,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_size: DEFAULT_MAX_SIZE,
+            max_per_sender: DEFAULT_MAX_PER_SENDER,
+            min_confirmations: DEFAULT_MIN_CONFIRMATIONS,
+            min_fee_rate: 0.0,
+            max_tx_size: DEFAULT_MAX_TX_SIZE,
+            accept_zero_fee: true,
+        }
+    }
+}
+
+/// A pending transaction along with what the pool makes of it, see
+/// [`Mempool::entries`].
+#[derive(Clone, Debug)]
+pub struct MempoolEntry {
+    pub id: TxId,
+    /// Place in the order the pool would mine it, from 0.
+    pub position: usize,
+    /// See [`Transaction::size`].
+    pub size: usize,
+    pub fee_rate: f64,
+    /// Pending transactions from the same sender with a lower sequence,
+    /// which have to be mined first.
+    pub ancestors: usize,
+    pub transaction: Transaction,
+}
+
+/// A summary of the pool, see [`Mempool::info`].
+#[derive(Clone, Debug)]
+pub struct MempoolInfo {
+    pub size: usize,
+    /// Total encoded size of the pending transactions.
+    pub bytes: usize,
+    pub max_size: usize,
+    pub min_fee_rate: f64,
+    /// The pending transactions by fee rate, cheapest bucket first.
+    pub fee_histogram: Vec<FeeBucket>,
+}
+
+/// Pending transactions paying at least `min_fee_rate` per byte but less
+/// than the next bucket's.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeBucket {
+    pub min_fee_rate: f64,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Pool of validated transactions waiting to be included in a block.
+///
+/// Transactions are kept in arrival order so a sender's payments are mined in
+/// the order they were submitted. When the pool is full, a new transaction
+/// evicts the lowest-fee entry that is the last of another sender's pending
+/// payments, if it pays a strictly higher fee. Each sender
+/// has at most one pending transaction per sequence number; it can be
+/// swapped for another paying a higher fee with [`Mempool::replace`].
+///
+/// The pool never holds two transactions that can't both be confirmed: ones
+/// sharing a sequence number or an input, or ones that together spend more
+/// than their sender can spend. [`Mempool::conflicts_for`] lists what a
+/// transaction conflicts with.
+///
+/// What a sender can spend is its balance counting only payments to it with
+/// [`MempoolConfig::min_confirmations`], as set by the chain, less what its
+/// pending payments already take. The pool keeps those running totals as
+/// transactions come and go, so admitting one checks its sender in the same
+/// step that records it, without summing the pool.
+#[derive(Clone)]
+pub struct Mempool {
+    config: MempoolConfig,
+    entries: VecDeque<Transaction>,
+    ids: HashSet<TxId>,
+    per_sender: HashMap<Address, usize>,
+    sequences: HashMap<(Address, u64), TxId>,
+    /// Which pending transaction spends each output, on a UTXO chain.
+    spends: HashMap<OutPoint, TxId>,
+    /// Spendable confirmed balances of senders, as last set by the chain.
+    confirmed: HashMap<Address, f64>,
+    flows: PendingFlows,
+}
+
+/// Running totals of what pending transactions take from their senders and
+/// bring their recipients. An address's totals are dropped along with its
+/// last pending transaction, so rounding never builds up.
+#[derive(Clone, Default)]
+struct PendingFlows {
+    /// Amount plus fee of each sender's pending payments, and their number.
+    outflows: HashMap<Address, (f64, usize)>,
+    /// What pending payments credit each recipient, and their number.
+    inflows: HashMap<Address, (f64, usize)>,
+}
+
+impl PendingFlows {
+    fn add(&mut self, transaction: &Transaction) {
+        if let Some(sender) = transaction.sender() {
+            let (total, count) = self.outflows.entry(*sender).or_default();
+            *total += transaction.amount + transaction.fee;
+            *count += 1;
+        }
+        let (total, count) = self.inflows.entry(transaction.recipient).or_default();
+        *total += transaction.credited();
+        *count += 1;
+    }
+
+    fn remove(&mut self, transaction: &Transaction) {
+        if let Some(sender) = transaction.sender() {
+            take(
+                &mut self.outflows,
+                sender,
+                transaction.amount + transaction.fee,
+            );
+        }
+        take(
+            &mut self.inflows,
+            &transaction.recipient,
+            transaction.credited(),
+        );
+    }
+
+    fn outflow(&self, address: &Address) -> f64 {
+        self.outflows.get(address).map_or(0.0, |(total, _)| *total)
+    }
+
+    fn inflow(&self, address: &Address) -> f64 {
+        self.inflows.get(address).map_or(0.0, |(total, _)| *total)
+    }
+}
+
+fn take(totals: &mut HashMap<Address, (f64, usize)>, address: &Address, amount: f64) {
+    if let Some((total, count)) = totals.get_mut(address) {
+        *total -= amount;
+        *count -= 1;
+        if *count == 0 {
+            totals.remove(address);
+        }
+    }
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            config,
+            entries: VecDeque::new(),
+            ids: HashSet::new(),
+            per_sender: HashMap::new(),
+            sequences: HashMap::new(),
+            spends: HashMap::new(),
+            confirmed: HashMap::new(),
+            flows: PendingFlows::default(),
+        }
+    }
+
+    pub fn config(&self) -> &MempoolConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, id: &TxId) -> bool {
+        self.ids.contains(id)
+    }
+
+    pub fn get(&self, id: &TxId) -> Option<&Transaction> {
+        if !self.contains(id) {
+            return None;
+        }
+        self.entries.iter().find(|t| &t.id() == id)
+    }
+
+    /// Number of pending transactions from `sender`.
+    pub fn pending_from(&self, sender: &Address) -> usize {
+        self.per_sender.get(sender).copied().unwrap_or_default()
+    }
+
+    /// One past the highest sequence number among `sender`'s pending
+    /// transactions, or `None` if it has none.
+    pub fn next_sequence(&self, sender: &Address) -> Option<u64> {
+        if !self.per_sender.contains_key(sender) {
+            return None;
+        }
+        self.sequences
+            .keys()
+            .filter(|(pending, _)| pending == sender)
+            .map(|(_, sequence)| sequence + 1)
+            .max()
+    }
+
+    /// The pending transaction from `sender` with `sequence`, if any.
+    pub fn get_by_sequence(&self, sender: &Address, sequence: u64) -> Option<&Transaction> {
+        let id = self.sequences.get(&(*sender, sequence))?;
+        self.entries.iter().find(|t| &t.id() == id)
+    }
+
+    /// Sets the confirmed balance `sender`'s pending payments are checked
+    /// against, leaving out payments to it without enough confirmations.
+    /// Payments from senders without one aren't checked for overspending.
+    pub fn set_confirmed_balance(&mut self, sender: Address, balance: f64) {
+        self.confirmed.insert(sender, balance);
+    }
+
+    /// Pending transactions that can't be confirmed alongside the pending
+    /// transaction `id`: ones with the same sender and sequence number, ones
+    /// spending any of the same outputs and, if its sender's payments
+    /// together exceed what it has, the sender's other payments.
+    pub fn conflicts_for(&self, id: &TxId) -> Vec<TxId> {
+        let Some(transaction) = self.entries.iter().find(|t| &t.id() == id) else {
+            return vec![];
+        };
+        let sender = transaction.sender();
+        let overspent = sender.is_some_and(|sender| self.overspent_by(sender) > BALANCE_EPSILON);
+        self.entries
+            .iter()
+            .filter(|other| {
+                let same_sender = other.sender() == sender;
+                (same_sender && (overspent || other.sequence == transaction.sequence))
+                    || other
+                        .inputs
+                        .iter()
+                        .any(|input| transaction.inputs.contains(input))
+            })
+            .map(Transaction::id)
+            .filter(|other| other != id)
+            .collect()
+    }
+
+    /// Fails if the policy of [`MempoolConfig`] keeps `transaction` out of
+    /// the pool: it is too large, pays no fee where fees are required, or
+    /// pays too low a fee for its size.
+    pub fn check_policy(&self, transaction: &Transaction) -> Result<()> {
+        let id = transaction.id();
+        let size = transaction.size();
+        if size > self.config.max_tx_size {
+            return Err(Error::TransactionTooLarge {
+                id,
+                size,
+                max: self.config.max_tx_size,
+            });
+        }
+        if transaction.fee <= 0.0 && !self.config.accept_zero_fee {
+            return Err(Error::ZeroFee { id });
+        }
+        if transaction.fee_rate() < self.config.min_fee_rate {
+            return Err(Error::FeeRateTooLow {
+                id,
+                rate: transaction.fee_rate(),
+                min: self.config.min_fee_rate,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds a transaction to the pool, returning the transaction evicted to
+    /// make room for it, if any. Fails if the policy keeps it out or it
+    /// conflicts with a pending transaction.
+    pub fn insert(&mut self, transaction: Transaction) -> Result<Option<Transaction>> {
+        let id = transaction.id();
+        if self.ids.contains(&id) {
+            return Err(Error::DuplicateTransaction { id });
+        }
+        self.check_policy(&transaction)?;
+        let Some(sender) = transaction.sender().copied() else {
+            return Err(Error::UnminedCoinbase { id });
+        };
+        if self.sequences.contains_key(&(sender, transaction.sequence)) {
+            return Err(Error::SequenceInUse {
+                sender,
+                sequence: transaction.sequence,
+            });
+        }
+        self.check_spends(&transaction, None)?;
+        if self.pending_from(&sender) >= self.config.max_per_sender {
+            return Err(Error::SenderLimitExceeded { sender });
+        }
+        let mut evicted = None;
+        if self.entries.len() >= self.config.max_size {
+            match self.lowest_fee_position(&sender) {
+                Some(position) if self.entries[position].fee < transaction.fee => {
+                    evicted = self.remove_at(position);
+                }
+                _ => return Err(Error::MempoolFull { id }),
+            }
+        }
+        *self.per_sender.entry(sender).or_default() += 1;
+        self.sequences
+            .insert((sender, transaction.sequence), id.clone());
+        for input in &transaction.inputs {
+            self.spends.insert(input.clone(), id.clone());
+        }
+        self.ids.insert(id);
+        self.flows.add(&transaction);
+        self.entries.push_back(transaction);
+        Ok(evicted)
+    }
+
+    /// Swaps the pending transaction with the same sender and sequence number
+    /// for `transaction`, which must pay a strictly higher fee. The
+    /// replacement takes the original's place in the queue; the original is
+    /// returned.
+    pub fn replace(&mut self, transaction: Transaction) -> Result<Transaction> {
+        let Some(sender) = transaction.sender().copied() else {
+            return Err(Error::UnminedCoinbase {
+                id: transaction.id(),
+            });
+        };
+        let key = (sender, transaction.sequence);
+        let position = self
+            .sequences
+            .get(&key)
+            .and_then(|id| self.entries.iter().position(|t| &t.id() == id))
+            .ok_or(Error::NothingToReplace {
+                sender,
+                sequence: transaction.sequence,
+            })?;
+        self.check_policy(&transaction)?;
+        let pending = &self.entries[position];
+        if transaction.fee <= pending.fee {
+            return Err(Error::ReplacementFeeTooLow {
+                pending: pending.fee,
+                provided: transaction.fee,
+            });
+        }
+        self.check_spends(&transaction, Some(pending))?;
+        let id = transaction.id();
+        for input in &transaction.inputs {
+            self.spends.insert(input.clone(), id.clone());
+        }
+        self.flows.add(&transaction);
+        let replaced = std::mem::replace(&mut self.entries[position], transaction);
+        self.flows.remove(&replaced);
+        for input in &replaced.inputs {
+            if self.spends.get(input) == Some(&replaced.id()) {
+                self.spends.remove(input);
+            }
+        }
+        self.ids.remove(&replaced.id());
+        self.ids.insert(id.clone());
+        self.sequences.insert(key, id);
+        Ok(replaced)
+    }
+
+    pub fn remove(&mut self, id: &TxId) -> Option<Transaction> {
+        if !self.ids.contains(id) {
+            return None;
+        }
+        let position = self.entries.iter().position(|t| &t.id() == id)?;
+        self.remove_at(position)
+    }
+
+    /// Drops every transaction that was included in a newly connected block.
+    pub fn remove_confirmed(&mut self, transactions: &[Transaction]) {
+        for transaction in transactions {
+            self.remove(&transaction.id());
+        }
+    }
+
+    /// Brings the pool in line with the chain's new `state`. Drops
+    /// transactions whose sequence number is already confirmed, such as ones
+    /// that lost out to a conflicting payment mined elsewhere, then each
+    /// sender's latest payments until the rest fit its confirmed balance.
+    /// `unsettled` are the latest blocks, whose payments don't have enough
+    /// confirmations to be spent yet.
+    pub(super) fn sync(&mut self, state: &AccountState, unsettled: &[&Block]) {
+        let stale: Vec<TxId> = self
+            .entries
+            .iter()
+            .filter(|t| {
+                t.sender()
+                    .is_some_and(|sender| t.sequence < state.next_sequence(sender))
+            })
+            .map(Transaction::id)
+            .collect();
+        for id in stale {
+            self.remove(&id);
+        }
+        self.confirmed = self
+            .per_sender
+            .keys()
+            .map(|sender| {
+                let balance = state.settled_balance(sender, unsettled.iter().copied());
+                (*sender, balance)
+            })
+            .collect();
+        // Dropping a payment can leave its recipient overspent in turn.
+        while let Some(id) = self.overspending_payment() {
+            self.remove(&id);
+        }
+    }
+
+    /// Drops the transactions a block at `height` with `timestamp` would be
+    /// too late to include, along with their senders' later payments, which
+    /// could never be mined without them. Returns what was dropped.
+    pub fn evict_expired(&mut self, height: u64, timestamp: i64) -> Vec<Transaction> {
+        // The earliest expired sequence of each sender; everything from it on
+        // goes.
+        let mut cutoffs: HashMap<Address, u64> = HashMap::new();
+        for transaction in &self.entries {
+            if let (Some(sender), Err(_)) = (
+                transaction.sender(),
+                transaction.check_expiry(height, timestamp),
+            ) {
+                let cutoff = cutoffs.entry(*sender).or_insert(transaction.sequence);
+                *cutoff = (*cutoff).min(transaction.sequence);
+            }
+        }
+        let expired: Vec<TxId> = self
+            .entries
+            .iter()
+            .filter(|t| {
+                t.sender()
+                    .and_then(|sender| cutoffs.get(sender))
+                    .is_some_and(|cutoff| t.sequence >= *cutoff)
+            })
+            .map(Transaction::id)
+            .collect();
+        expired.iter().filter_map(|id| self.remove(id)).collect()
+    }
+
+    /// Fails if `transaction` has an invalid amount or fee, reuses an
+    /// output a pending transaction spends or takes its sender's pending
+    /// payments past what it has. `replacing` is the pending transaction it
+    /// would take the place of.
+    fn check_spends(
+        &self,
+        transaction: &Transaction,
+        replacing: Option<&Transaction>,
+    ) -> Result<()> {
+        transaction.check_amounts()?;
+        let replaced_id = replacing.map(Transaction::id);
+        for input in &transaction.inputs {
+            match self.spends.get(input) {
+                Some(spender) if Some(spender) != replaced_id.as_ref() => {
+                    return Err(Error::DoubleSpend {
+                        output: input.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        let Some(sender) = transaction.sender() else {
+            return Ok(());
+        };
+        let released = replacing.map_or(0.0, |t| t.amount + t.fee);
+        if self.overspent_by(sender) + transaction.amount + transaction.fee - released
+            > BALANCE_EPSILON
+        {
+            return Err(Error::AvailableBalanceExceeded { sender: *sender });
+        }
+        Ok(())
+    }
+
+    /// How far `sender`'s pending payments exceed its spendable balance, plus
+    /// pending payments to it if those can be spent. Zero or less if they
+    /// fit, or if the balance isn't known.
+    fn overspent_by(&self, sender: &Address) -> f64 {
+        let Some(confirmed) = self.confirmed.get(sender) else {
+            return f64::NEG_INFINITY;
+        };
+        let inflow = match self.config.min_confirmations {
+            0 => self.flows.inflow(sender),
+            _ => 0.0,
+        };
+        self.flows.outflow(sender) - confirmed - inflow
+    }
+
+    /// Amount plus fee of the payments pending from `sender`.
+    pub fn pending_outflow(&self, sender: &Address) -> f64 {
+        self.flows.outflow(sender)
+    }
+
+    /// The latest payment of some sender whose pending payments don't fit
+    /// its balance.
+    fn overspending_payment(&self) -> Option<TxId> {
+        let sender = self
+            .per_sender
+            .keys()
+            .find(|sender| self.overspent_by(sender) > BALANCE_EPSILON)?;
+        self.entries
+            .iter()
+            .filter(|t| t.sender() == Some(sender))
+            .max_by_key(|t| t.sequence)
+            .map(Transaction::id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.iter()
+    }
+
+    /// Every pending transaction with its place, size, fee rate and
+    /// ancestors, in the order they would be mined.
+    pub fn entries(&self) -> Vec<MempoolEntry> {
+        self.iter_ready()
+            .enumerate()
+            .map(|(position, transaction)| self.entry_at(position, transaction))
+            .collect()
+    }
+
+    /// The pending transaction `id` with its place, size, fee rate and
+    /// ancestors.
+    pub fn entry(&self, id: &TxId) -> Option<MempoolEntry> {
+        if !self.contains(id) {
+            return None;
+        }
+        self.iter_ready()
+            .enumerate()
+            .find(|(_, transaction)| &transaction.id() == id)
+            .map(|(position, transaction)| self.entry_at(position, transaction))
+    }
+
+    /// Size of the pool in transactions and bytes, its limits, and how its
+    /// transactions spread over fee rates.
+    pub fn info(&self) -> MempoolInfo {
+        let mut fee_histogram: Vec<FeeBucket> = FEE_BUCKETS
+            .iter()
+            .map(|&min_fee_rate| FeeBucket {
+                min_fee_rate,
+                ..Default::default()
+            })
+            .collect();
+        let mut bytes = 0;
+        for transaction in &self.entries {
+            let size = transaction.size();
+            let fee_rate = transaction.fee_rate();
+            bytes += size;
+            if let Some(bucket) = fee_histogram
+                .iter_mut()
+                .rev()
+                .find(|bucket| fee_rate >= bucket.min_fee_rate)
+            {
+                bucket.count += 1;
+                bucket.bytes += size;
+            }
+        }
+        MempoolInfo {
+            size: self.len(),
+            bytes,
+            max_size: self.config.max_size,
+            min_fee_rate: self.config.min_fee_rate,
+            fee_histogram,
+        }
+    }
+
+    fn entry_at(&self, position: usize, transaction: &Transaction) -> MempoolEntry {
+        let ancestors = transaction.sender().map_or(0, |sender| {
+            self.entries
+                .iter()
+                .filter(|t| t.sender() == Some(sender) && t.sequence < transaction.sequence)
+                .count()
+        });
+        MempoolEntry {
+            id: transaction.id(),
+            position,
+            size: transaction.size(),
+            fee_rate: transaction.fee_rate(),
+            ancestors,
+            transaction: transaction.clone(),
+        }
+    }
+
+    /// Transactions eligible for the next block, in the order they should be
+    /// included.
+    pub fn iter_ready(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.iter()
+    }
+
+    /// Position of the cheapest transaction that can be evicted to make room
+    /// for one from `incoming`: the last pending payment of some other
+    /// sender, so that no payment is left waiting on one that is gone.
+    fn lowest_fee_position(&self, incoming: &Address) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| {
+                t.sender().is_some_and(|sender| {
+                    sender != incoming && !self.sequences.contains_key(&(*sender, t.sequence + 1))
+                })
+            })
+            .min_by(|(_, a), (_, b)| a.fee.total_cmp(&b.fee))
+            .map(|(position, _)| position)
+    }
+
+    fn remove_at(&mut self, position: usize) -> Option<Transaction> {
+        let transaction = self.entries.remove(position)?;
+        let id = transaction.id();
+        self.ids.remove(&id);
+        self.flows.remove(&transaction);
+        for input in &transaction.inputs {
+            if self.spends.get(input) == Some(&id) {
+                self.spends.remove(input);
+            }
+        }
+        if let Some(sender) = transaction.sender() {
+            self.sequences.remove(&(*sender, transaction.sequence));
+            if let Some(count) = self.per_sender.get_mut(sender) {
+                *count -= 1;
+                if *count == 0 {
+                    self.per_sender.remove(sender);
+                }
+            }
+        }
+        Some(transaction)
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool::new(MempoolConfig::default())
+    }
+}
@@ -0,0 +1,70 @@
+//! How the pool drops payments it can no longer keep, by eviction or
+//! expiry, without leaving a sender's later payments waiting on an earlier
+//! one that is gone.
+
+use super::{Error, Expiry, Transaction};
+use crate::{
+    testing::{self, TestChain},
+    wallet::{UnsignedTransaction, Wallet},
+};
+
+const SEED: u64 = 31;
+
+/// Chain whose pool holds three transactions, with `senders` funded.
+fn small_pool(senders: &[&Wallet]) -> TestChain {
+    let mut config = testing::config();
+    config.mempool.max_size = 3;
+    let mut chain = TestChain::with_config(SEED, &config);
+    for sender in senders {
+        chain.fund(sender.address(), 50.0, &testing::wallet(1));
+    }
+    chain
+}
+
+#[test]
+fn eviction_never_strands_later_payments() {
+    let (mut first, mut second, mut third) =
+        (testing::wallet(2), testing::wallet(3), testing::wallet(4));
+    let mut chain = small_pool(&[&first, &second, &third]);
+    let recipient = *testing::wallet(5).address();
+    // The cheapest payment has a later one waiting on it.
+    chain.pay(&mut first, &recipient, 1.0, 0.01).unwrap();
+    chain.pay(&mut first, &recipient, 1.0, 0.5).unwrap();
+    let tail = chain.pay(&mut second, &recipient, 1.0, 0.3).unwrap();
+
+    assert!(matches!(
+        chain.pay(&mut third, &recipient, 1.0, 0.2),
+        Err(Error::MempoolFull { .. })
+    ));
+    chain.pay(&mut third, &recipient, 1.0, 0.4).unwrap();
+    let pool = chain.blockchain.mempool();
+    assert!(!pool.contains(&tail.id()));
+    assert_eq!(pool.pending_from(first.address()), 2);
+    assert_eq!(chain.blockchain.next_sequence(first.address()).unwrap(), 2);
+}
+
+#[test]
+fn expiry_takes_later_payments_with_it() {
+    let mut sender = testing::wallet(2);
+    let mut chain = small_pool(&[&sender]);
+    let recipient = *testing::wallet(5).address();
+    let height = chain.blockchain.chain_height().unwrap();
+    let expiring = Transaction::new(*sender.address(), recipient, 1.0, 0.1, 0)
+        .with_expiry(Some(Expiry::Height(height + 1)));
+    let expiring = sender
+        .sign_unsigned(&UnsignedTransaction::new(expiring))
+        .unwrap();
+    chain
+        .blockchain
+        .add_transation_to_pool(expiring.transaction().clone())
+        .unwrap();
+    chain.pay(&mut sender, &recipient, 1.0, 0.1).unwrap();
+
+    let expired = chain
+        .blockchain
+        .transaction_pool
+        .evict_expired(height + 2, chain.now());
+    assert_eq!(expired.len(), 2);
+    assert_eq!(chain.blockchain.pending_transaction_count(), 0);
+    assert_eq!(chain.blockchain.next_sequence(sender.address()).unwrap(), 0);
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::transaction::TxId;
+
+pub type Hash = [u8; 32];
+
+/// Root of the binary Merkle tree over the given transaction ids, hex
+/// encoded. A level with an odd number of nodes pairs its last node with
+/// itself, as Bitcoin does, and an empty tree has an all-zero root.
+pub fn merkle_root(ids: &[TxId]) -> String {
+    let mut level: Vec<Hash> = ids.iter().map(TxId::to_bytes).collect();
+    if level.is_empty() {
+        return hex(&[0; 32]);
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+    }
+    hex(&level[0])
+}
+
+/// Whether some level of the tree over `ids` hashes a node with an equal
+/// sibling. Pairing a level's last node with itself means a list with its
+/// tail repeated has the same root as the list without it (CVE-2012-2459),
+/// so a body like that must be refused rather than taken for the block the
+/// header commits to.
+pub fn is_mutated(ids: &[TxId]) -> bool {
+    let mut level: Vec<Hash> = ids.iter().map(TxId::to_bytes).collect();
+    while level.len() > 1 {
+        if level
+            .chunks(2)
+            .any(|pair| pair.len() == 2 && pair[0] == pair[1])
+        {
+            return true;
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+    }
+    false
+}
+
+/// Path from a transaction id to the Merkle root of its block: the sibling
+/// at each level of the tree, bottom up. Lets someone holding only the block
+/// header check that the transaction is in the block.
+///
+/// Siblings serialize as hex, and anything else is refused:
+///
+/// ```
+/// use aeonia::blockchain::MerkleProof;
+///
+/// let proof = |sibling: &str| format!(r#"{{"index":1,"siblings":["{sibling}"]}}"#);
+/// assert!(serde_json::from_str::<MerkleProof>(&proof(&"ab".repeat(32))).is_ok());
+/// // 64 bytes, but not 64 hex digits.
+/// let accented = format!("a{}a", "é".repeat(31));
+/// assert!(serde_json::from_str::<MerkleProof>(&proof(&accented)).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Position of the transaction in the block, whose bits say on which
+    /// side each sibling goes.
+    index: u64,
+    #[serde(with = "hex_hashes")]
+    siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Proof for the transaction at `index` among `ids`, or `None` if there
+    /// is no such transaction.
+    pub fn new(ids: &[TxId], index: usize) -> Option<Self> {
+        if index >= ids.len() {
+            return None;
+        }
+        let mut level: Vec<Hash> = ids.iter().map(TxId::to_bytes).collect();
+        let mut position = index;
+        let mut siblings = vec![];
+        while level.len() > 1 {
+            let sibling = level.get(position ^ 1).unwrap_or(&level[position]);
+            siblings.push(*sibling);
+            level = level
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            position /= 2;
+        }
+        Some(MerkleProof {
+            index: index as u64,
+            siblings,
+        })
+    }
+
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Root of the tree this proof leads to from `id`, hex encoded. The
+    /// proof holds for `id` if this is the block's Merkle root.
+    pub fn root(&self, id: &TxId) -> String {
+        let mut node = id.to_bytes();
+        let mut position = self.index;
+        for sibling in &self.siblings {
+            node = if position.is_multiple_of(2) {
+                hash_pair(&node, sibling)
+            } else {
+                hash_pair(sibling, &node)
+            };
+            position /= 2;
+        }
+        hex(&node)
+    }
+}
+
+pub fn hash_pair(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new()
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Serde adapter writing hashes as hex strings rather than arrays of bytes.
+mod hex_hashes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use super::Hash;
+
+    pub fn serialize<S: Serializer>(hashes: &[Hash], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(hashes.iter().map(|hash| super::hex(hash)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Hash>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hex| {
+                let mut hash = [0; 32];
+                if hex.len() != 64 {
+                    return Err(de::Error::custom("hash is not 64 hex digits"));
+                }
+                for (i, byte) in hash.iter_mut().enumerate() {
+                    let digits = hex.get(2 * i..2 * i + 2).unwrap_or_default();
+                    *byte = u8::from_str_radix(digits, 16).map_err(de::Error::custom)?;
+                }
+                Ok(hash)
+            })
+            .collect()
+    }
+}
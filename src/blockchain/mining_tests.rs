@@ -0,0 +1,97 @@
+//! Proof of work searches at difficulties where a block's nonce range runs
+//! out long before a solution turns up, so the coinbase's extra nonce has to
+//! roll, and at the very top of the nonce space.
+
+use super::{CancelToken, NonceRange, Sha256Hasher, Target};
+use crate::testing::{self, TestChain};
+
+const SEED: u64 = 11;
+/// Four zero hex digits: about 65,000 hashes a block.
+const DIFFICULTY: u8 = 4;
+/// A few hundred nonces, so solving a block takes about 250 rolls.
+const TOP_OF_NONCE_SPACE: NonceRange = NonceRange {
+    start: u64::MAX - 255,
+    end: u64::MAX,
+};
+
+fn chain() -> TestChain {
+    let mut config = testing::config();
+    config.consensus.difficulty = DIFFICULTY;
+    TestChain::with_config(SEED, &config)
+}
+
+#[test]
+fn exhausted_nonce_range_rolls_the_extra_nonce() {
+    let mut chain = chain();
+    let miner = testing::wallet(1);
+    for height in 1..=3 {
+        chain.clock.advance(testing::BLOCK_INTERVAL);
+        let mut block = chain.blockchain.block_candidate(miner.address()).unwrap();
+        block.set_bits(Target::from_zero_digits(DIFFICULTY).to_compact());
+        let hashes = block
+            .mine_range_until(&Sha256Hasher, TOP_OF_NONCE_SPACE, &CancelToken::new())
+            .unwrap();
+        assert!(hashes > 0);
+        assert!(block.meets_target(&Sha256Hasher));
+        assert!(TOP_OF_NONCE_SPACE.contains(block.nonce()));
+        let size = TOP_OF_NONCE_SPACE.end - TOP_OF_NONCE_SPACE.start + 1;
+        assert_eq!(block.extra_nonce(), (hashes - 1) / size);
+        miner.sign_block(&mut block).unwrap();
+        chain.blockchain.connect_block(block).unwrap();
+        assert_eq!(chain.blockchain.chain_height().unwrap(), height);
+    }
+}
+
+#[test]
+fn nonce_wraps_past_the_top_of_the_nonce_space() {
+    let chain = chain();
+    let miner = testing::wallet(1);
+    let mut header = chain
+        .blockchain
+        .block_candidate(miner.address())
+        .unwrap()
+        .header()
+        .clone();
+    header.set_bits(Target::from_zero_digits(DIFFICULTY).to_compact());
+    header.set_nonce(u64::MAX - 3);
+    let hashes = header.mine(&Sha256Hasher);
+    assert!(hashes > 4);
+    assert!(header.meets_target(&Sha256Hasher));
+    assert_eq!(header.nonce(), (u64::MAX - 3).wrapping_add(hashes - 1));
+}
+
+#[test]
+fn single_nonce_range_solves_by_rolling_the_extra_nonce() {
+    let mut chain = chain();
+    let miner = testing::wallet(1);
+    chain.clock.advance(testing::BLOCK_INTERVAL);
+    let mut block = chain.blockchain.block_candidate(miner.address()).unwrap();
+    block.set_bits(Target::from_zero_digits(DIFFICULTY).to_compact());
+    let coinbase = block.transactions()[0].id();
+    let extra_nonce = block.extra_nonce();
+    // A nonce that doesn't solve the block as it stands, so the only way
+    // out of a range of just that nonce is through the extra nonce.
+    let nonce = (0..)
+        .find(|&nonce| {
+            let mut header = block.header().clone();
+            header.set_nonce(nonce);
+            !header.meets_target(&Sha256Hasher)
+        })
+        .unwrap();
+    let range = NonceRange {
+        start: nonce,
+        end: nonce,
+    };
+
+    let hashes = block
+        .mine_range_until(&Sha256Hasher, range, &CancelToken::new())
+        .unwrap();
+    assert!(hashes > 1);
+    assert_eq!(block.nonce(), nonce);
+    assert_eq!(block.extra_nonce(), extra_nonce + hashes - 1);
+    assert_ne!(block.transactions()[0].id(), coinbase);
+    assert!(block.meets_target(&Sha256Hasher));
+    miner.sign_block(&mut block).unwrap();
+    chain.blockchain.connect_block(block).unwrap();
+    assert_eq!(chain.blockchain.chain_height().unwrap(), 1);
+}
@@ -1,222 +1,2484 @@
+mod audit;
+mod authority;
 mod block;
+mod cancel;
+mod checkpoint;
+mod compact;
+#[cfg(test)]
+mod concurrency_tests;
+mod consensus;
+mod emission;
+mod events;
+mod export;
+mod faucet;
+mod fork;
+mod hasher;
+mod history;
+mod htlc;
+mod index;
+mod light;
+mod mempool;
+#[cfg(test)]
+mod mempool_tests;
+mod merkle;
+#[cfg(test)]
+mod mining_tests;
+#[cfg(test)]
+mod proptests;
+mod query;
+mod receipt;
+mod script;
+mod shared;
+mod stake;
+mod state;
+mod stats;
+mod sync;
+mod target;
+mod template;
+mod timestamp;
+mod token;
 mod transaction;
+mod utxo;
+#[cfg(test)]
+mod validation_tests;
+mod witness;
 
-use std::sync::{Arc, Mutex};
+use std::{
+    borrow::Borrow,
+    collections::{HashSet, VecDeque},
+    sync::{mpsc::Receiver, Arc},
+    time::{Duration, Instant},
+};
 
-use block::Block;
-use chrono::Utc;
-use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+pub use audit::{BalanceProof, ProvenTransaction};
+pub use authority::{AuthorityLedger, AuthorityOp};
+pub use block::{Block, BlockBody, BlockHeader};
+pub use cancel::CancelToken;
+use events::EventBus;
+use faucet::Faucet;
+use fork::SideBranches;
+use index::ChainIndex;
+use rand_core::{CryptoRngCore, OsRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use state::{minted, AccountState, BALANCE_EPSILON};
+use stats::FeeTotals;
 
-pub use transaction::Transaction;
+pub use checkpoint::Checkpoint;
+pub use compact::{CompactBlock, Reconstruction};
+pub use consensus::{
+    ConsensusEngine, Engine, FixedInterval, ProducerState, ProofOfAuthority, ProofOfStake,
+    ProofOfWork,
+};
+pub use emission::EmissionSchedule;
+pub use events::ChainEvent;
+pub use export::{BlockRecord, ChainExport, TransactionRecord};
+pub use faucet::FaucetConfig;
+pub use fork::BlockStatus;
+#[cfg(feature = "blake3")]
+pub use hasher::Blake3Hasher;
+pub use hasher::{DoubleSha256Hasher, HashAlgorithm, Hasher, Sha256Hasher};
+pub use history::{Direction, HistoryEntry, HistoryPage};
+pub use htlc::Htlc;
+pub use index::TxLocation;
+pub use light::LightClient;
+pub use mempool::{FeeBucket, Mempool, MempoolConfig, MempoolEntry, MempoolInfo};
+pub use merkle::MerkleProof;
+pub use query::{BlockPage, ListFilter, TransactionPage, MAX_PAGE_LIMIT};
+pub use receipt::Receipt;
+pub use script::{Op, Script, ScriptContext, ScriptError, MAX_GAS, MAX_OPS};
+pub use shared::{BlockIter, SharedBlockchain};
+pub use stake::{DoubleSign, StakeLedger, StakeOp};
+pub use state::Balance;
+pub use stats::{ChainStats, MAX_TOP_HOLDERS};
+pub use sync::SyncStatus;
+pub use target::Target;
+pub use template::{BlockTemplate, NonceRange};
+pub use timestamp::{median_time_past, TimestampConfig};
+pub use token::{Token, TokenId, TokenLedger, TokenOp, MAX_TOKEN_NAME_LEN};
+pub use transaction::{Expiry, Origin, Transaction, TxId, MAX_MEMO_LEN};
+pub use utxo::{Ledger, Lock, OutPoint, TxOut, UtxoSet, UtxoView};
+pub use witness::{Cosignature, Witness};
 
-use crate::wallet::Wallet;
+use crate::{
+    address::Address,
+    clock::{Clock, SystemClock},
+    codec::{self, Decode, Encode, Reader},
+    config::Config,
+    crypto,
+    network::Network,
+    wallet::{self, StoredWallet, Wallet},
+};
 
-const MINING_DIFFICULTY: u8 = 3;
-const MINING_REWARD: f64 = 1.0;
+/// Most recent blocks whose fees [`Blockchain::estimate_fee`] looks at.
+pub const FEE_HISTORY: usize = 20;
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
-    MutexPoison(String),
-    Json(String),
-    Ecdsa(String),
-    InvalidSignature(String),
-    AvailableBalanceExceeded(String),
+    #[error("chain lock poisoned: {0}")]
+    LockPoisoned(String),
+    #[error(transparent)]
+    Wallet(#[from] wallet::Error),
+    #[error(transparent)]
+    Crypto(#[from] crypto::Error),
+    #[error("failed to serialize the chain: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("missing signature from {sender}")]
+    MissingSignature { sender: Address },
+    #[error("signing key does not belong to {sender}")]
+    ForeignKey { sender: Address },
+    #[error("multisig policy does not belong to {sender}")]
+    ForeignPolicy { sender: Address },
+    #[error("HTLC does not belong to {sender}")]
+    ForeignHtlc { sender: Address },
+    #[error("preimage does not unlock the HTLC of {sender}")]
+    WrongPreimage { sender: Address },
+    #[error("HTLC of {sender} can't be refunded before height {timeout}")]
+    HtlcLocked { sender: Address, timeout: u64 },
+    #[error("multisig policy has no key {index}")]
+    UnknownCosigner { index: usize },
+    #[error("multisig transaction has {provided} of the {required} required signatures")]
+    ThresholdNotMet { required: usize, provided: usize },
+    #[error("transaction exceeds available balance for sender {sender}")]
+    AvailableBalanceExceeded { sender: Address },
+    #[error("transaction {id} is already pending or confirmed")]
+    DuplicateTransaction { id: TxId },
+    #[error("too many pending transactions for sender {sender}")]
+    SenderLimitExceeded { sender: Address },
+    #[error("pool is full and transaction {id} pays too low a fee")]
+    MempoolFull { id: TxId },
+    #[error("transaction {id} is {size} bytes, over the pool's limit of {max}")]
+    TransactionTooLarge { id: TxId, size: usize, max: usize },
+    #[error("transaction {id} pays no fee, which the pool doesn't accept")]
+    ZeroFee { id: TxId },
+    #[error("transaction {id} pays {rate} per byte, below the pool's minimum of {min}")]
+    FeeRateTooLow { id: TxId, rate: f64, min: f64 },
+    #[error("block {hash} does not satisfy the proof of work")]
+    InvalidProof { hash: String },
+    #[error("parent block {hash} is unknown")]
+    UnknownParent { hash: String },
+    #[error("cached balance of {address} does not match the chain")]
+    StateMismatch { address: Address },
+    #[error("block {block} has no coinbase")]
+    MissingCoinbase { block: String },
+    #[error("block {block} doesn't start with a coinbase for height {height}")]
+    MisplacedCoinbase { block: String, height: u64 },
+    #[error("block {block} has more than one coinbase")]
+    ExtraCoinbase { block: String },
+    #[error("coinbase of block {block} pays {paid} but at most {allowed} is allowed")]
+    ExcessiveCoinbase {
+        block: String,
+        paid: f64,
+        allowed: f64,
+    },
+    #[error("coinbase {id} can only be created by mining")]
+    UnminedCoinbase { id: TxId },
+    #[error("block {block} has timestamp {timestamp}, not after the median {median} of the previous blocks")]
+    TimestampTooEarly {
+        block: String,
+        timestamp: i64,
+        median: i64,
+    },
+    #[error("block {block} has timestamp {timestamp}, more than {max_drift:?} in the future")]
+    TimestampTooLate {
+        block: String,
+        timestamp: i64,
+        max_drift: Duration,
+    },
+    #[error("faucet is drained, only {available} left")]
+    FaucetDrained { available: f64 },
+    #[error("faucet pays out at most {max} per request")]
+    FaucetLimitExceeded { max: f64 },
+    #[error("faucet already paid {recipient} recently")]
+    FaucetRateLimited { recipient: Address },
+    #[error("next transaction from {sender} must have sequence {expected}, not {provided}")]
+    InvalidSequence {
+        sender: Address,
+        expected: u64,
+        provided: u64,
+    },
+    #[error(
+        "{sender} already has a pending transaction with sequence {sequence}, replace it instead"
+    )]
+    SequenceInUse { sender: Address, sequence: u64 },
+    #[error("{sender} has no pending transaction with sequence {sequence}")]
+    NothingToReplace { sender: Address, sequence: u64 },
+    #[error("replacement fee {provided} must be higher than the pending fee {pending}")]
+    ReplacementFeeTooLow { pending: f64, provided: f64 },
+    #[error("output {output} does not exist or is already spent")]
+    UnknownOutput { output: OutPoint },
+    #[error("output {output} is already being spent")]
+    DoubleSpend { output: OutPoint },
+    #[error("output {output} can't be spent by {sender}")]
+    ForeignOutput { output: OutPoint, sender: Address },
+    #[error("inputs hold {available} but the payment and fee need {required}")]
+    InsufficientFunds { available: f64, required: f64 },
+    #[error("sender {sender} holds {balance} but the payment and fee need {required}")]
+    Overdrawn {
+        sender: Address,
+        balance: f64,
+        required: f64,
+    },
+    #[error("transactions up to height {height} have been pruned from this node")]
+    Pruned { height: u64 },
+    #[error("block {hash} is not on the main chain")]
+    UnknownBlock { hash: String },
+    #[error("proof does not place transaction {id} in the block")]
+    InvalidMerkleProof { id: TxId },
+    #[error("balance proof of {address} is invalid: {reason}")]
+    InvalidBalanceProof {
+        address: Address,
+        reason: &'static str,
+    },
+    #[error("coins in existence would exceed the cap of {cap}")]
+    SupplyCapExceeded { cap: f64 },
+    #[error("memo of {len} bytes is over the limit of {max}")]
+    MemoTooLong { len: usize, max: usize },
+    #[error("transaction {id} has a negative or non-finite amount or fee, or moves nothing")]
+    InvalidAmount { id: TxId },
+    #[error("{block}: {reason}")]
+    InvalidCompactBlock { block: String, reason: &'static str },
+    #[error("transaction {id} expired after {expiry}")]
+    Expired { id: TxId, expiry: Expiry },
+    #[error("transaction {id} can't be confirmed until after height {height}")]
+    LockedUntilHeight { id: TxId, height: u64 },
+    #[error("transaction {id} can't be confirmed until after {}", Expiry::Time(*time))]
+    LockedUntilTime { id: TxId, time: i64 },
+    #[error("transaction {id} issues a token without a name of 1 to {max} bytes or a supply", max = MAX_TOKEN_NAME_LEN)]
+    InvalidToken { id: TxId },
+    #[error("token {id} has not been issued")]
+    UnknownToken { id: TokenId },
+    #[error("{available} of token {id} available but {required} required")]
+    InsufficientTokens {
+        id: TokenId,
+        available: u64,
+        required: u64,
+    },
+    #[error("transaction {id} stakes, unstakes or slashes something it can't")]
+    InvalidStake { id: TxId },
+    #[error("{address} has {available} staked but {required} required")]
+    InsufficientStake {
+        address: Address,
+        available: f64,
+        required: f64,
+    },
+    #[error("transaction {id} slashes without evidence of double-signing")]
+    InvalidEvidence { id: TxId },
+    #[error("transaction {id} changes the authorities in a way it can't")]
+    InvalidAuthority { id: TxId },
+    #[error("block {hash} was produced out of turn; the slot belongs to {expected}")]
+    WrongProducer { hash: String, expected: Address },
+    #[error("block {hash} came too soon after its parent; the earliest allowed is {earliest}")]
+    TooSoon { hash: String, earliest: i64 },
+    #[error("script of transaction {id} failed: {reason}")]
+    ScriptFailed { id: TxId, reason: ScriptError },
+    #[error("output {output} has {confirmations} confirmations but spending it needs {required}")]
+    Unconfirmed {
+        output: OutPoint,
+        confirmations: u64,
+        required: u64,
+    },
+    #[error("block {hash} doesn't extend the tip")]
+    StaleBlock { hash: String },
+    #[error("the genesis block can't be disconnected")]
+    DisconnectGenesis,
+    #[error("genesis records an unknown proof of work hash: {memo:?}")]
+    UnknownHashAlgorithm { memo: String },
+    #[error("the chain is mined with {genesis} but {configured} is configured")]
+    HashAlgorithmMismatch {
+        genesis: HashAlgorithm,
+        configured: HashAlgorithm,
+    },
+    #[error("no block template has merkle root {merkle_root}")]
+    UnknownTemplate { merkle_root: String },
+    #[error("body of block {block} repeats transactions, so other bodies share its merkle root")]
+    MutatedBody { block: String },
+    #[error("mining was cancelled")]
+    MiningCancelled,
+}
+
+impl Error {
+    /// Identifier of the kind of error, stable across releases so RPC
+    /// clients can match on it instead of the message. Errors from another
+    /// module carry that module's code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::LockPoisoned(_) | Error::Json(_) => "internal",
+            Error::Wallet(e) => e.code(),
+            Error::Crypto(e) => e.code(),
+            Error::MissingSignature { .. }
+            | Error::ForeignKey { .. }
+            | Error::ForeignPolicy { .. }
+            | Error::ForeignHtlc { .. }
+            | Error::UnknownCosigner { .. } => "invalid_signature",
+            Error::ThresholdNotMet { .. } => "threshold_not_met",
+            Error::WrongPreimage { .. } => "wrong_preimage",
+            Error::HtlcLocked { .. } => "htlc_locked",
+            Error::AvailableBalanceExceeded { .. }
+            | Error::InsufficientFunds { .. }
+            | Error::Overdrawn { .. } => "insufficient_funds",
+            Error::DuplicateTransaction { .. } => "duplicate_transaction",
+            Error::SenderLimitExceeded { .. } => "sender_limit_exceeded",
+            Error::MempoolFull { .. } => "mempool_full",
+            Error::TransactionTooLarge { .. } => "transaction_too_large",
+            Error::ZeroFee { .. } => "zero_fee",
+            Error::FeeRateTooLow { .. } => "fee_rate_too_low",
+            Error::InvalidProof { .. } => "invalid_proof",
+            Error::UnknownParent { .. } => "unknown_parent",
+            Error::StateMismatch { .. } => "state_mismatch",
+            Error::MissingCoinbase { .. }
+            | Error::MisplacedCoinbase { .. }
+            | Error::ExtraCoinbase { .. }
+            | Error::ExcessiveCoinbase { .. }
+            | Error::UnminedCoinbase { .. } => "invalid_coinbase",
+            Error::TimestampTooEarly { .. } | Error::TimestampTooLate { .. } => "invalid_timestamp",
+            Error::FaucetDrained { .. } => "faucet_drained",
+            Error::FaucetLimitExceeded { .. } => "faucet_limit_exceeded",
+            Error::FaucetRateLimited { .. } => "faucet_rate_limited",
+            Error::InvalidSequence { .. } => "invalid_sequence",
+            Error::SequenceInUse { .. } => "sequence_in_use",
+            Error::NothingToReplace { .. } => "nothing_to_replace",
+            Error::ReplacementFeeTooLow { .. } => "replacement_fee_too_low",
+            Error::UnknownOutput { .. } => "unknown_output",
+            Error::DoubleSpend { .. } => "double_spend",
+            Error::ForeignOutput { .. } => "foreign_output",
+            Error::Pruned { .. } => "pruned",
+            Error::UnknownBlock { .. } => "unknown_block",
+            Error::InvalidMerkleProof { .. } => "invalid_merkle_proof",
+            Error::InvalidBalanceProof { .. } => "invalid_balance_proof",
+            Error::SupplyCapExceeded { .. } => "supply_cap_exceeded",
+            Error::MemoTooLong { .. } => "memo_too_long",
+            Error::InvalidAmount { .. } => "invalid_amount",
+            Error::InvalidCompactBlock { .. } => "invalid_compact_block",
+            Error::Expired { .. } => "expired",
+            Error::LockedUntilHeight { .. } | Error::LockedUntilTime { .. } => "timelocked",
+            Error::InvalidToken { .. } => "invalid_token",
+            Error::UnknownToken { .. } => "unknown_token",
+            Error::InsufficientTokens { .. } => "insufficient_tokens",
+            Error::InvalidStake { .. } => "invalid_stake",
+            Error::InsufficientStake { .. } => "insufficient_stake",
+            Error::InvalidEvidence { .. } => "invalid_evidence",
+            Error::InvalidAuthority { .. } => "invalid_authority",
+            Error::WrongProducer { .. } => "wrong_producer",
+            Error::TooSoon { .. } => "too_soon",
+            Error::ScriptFailed { .. } => "script_failed",
+            Error::Unconfirmed { .. } => "unconfirmed",
+            Error::StaleBlock { .. } => "stale_block",
+            Error::DisconnectGenesis => "disconnect_genesis",
+            Error::UnknownHashAlgorithm { .. } => "unknown_hash_algorithm",
+            Error::HashAlgorithmMismatch { .. } => "hash_algorithm_mismatch",
+            Error::UnknownTemplate { .. } => "unknown_template",
+            Error::MutatedBody { .. } => "mutated_body",
+            Error::MiningCancelled => "mining_cancelled",
+        }
+    }
+
+    pub fn kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+        match self {
+            Error::LockPoisoned(_) | Error::Json(_) => ErrorKind::Other,
+            Error::Wallet(e) => e.kind(),
+            Error::Crypto(_)
+            | Error::MissingSignature { .. }
+            | Error::ForeignKey { .. }
+            | Error::ForeignPolicy { .. }
+            | Error::ForeignHtlc { .. }
+            | Error::UnknownCosigner { .. }
+            | Error::ThresholdNotMet { .. }
+            | Error::WrongPreimage { .. }
+            | Error::InvalidProof { .. }
+            | Error::StateMismatch { .. }
+            | Error::MissingCoinbase { .. }
+            | Error::MisplacedCoinbase { .. }
+            | Error::ExtraCoinbase { .. }
+            | Error::ExcessiveCoinbase { .. }
+            | Error::UnminedCoinbase { .. }
+            | Error::TimestampTooEarly { .. }
+            | Error::TimestampTooLate { .. }
+            | Error::ForeignOutput { .. }
+            | Error::InvalidMerkleProof { .. }
+            | Error::InvalidBalanceProof { .. }
+            | Error::InvalidAmount { .. }
+            | Error::Overdrawn { .. }
+            | Error::SupplyCapExceeded { .. }
+            | Error::InvalidCompactBlock { .. }
+            | Error::WrongProducer { .. }
+            | Error::TooSoon { .. }
+            | Error::StaleBlock { .. }
+            | Error::UnknownHashAlgorithm { .. }
+            | Error::HashAlgorithmMismatch { .. }
+            | Error::MutatedBody { .. } => ErrorKind::InvalidData,
+            Error::AvailableBalanceExceeded { .. }
+            | Error::SenderLimitExceeded { .. }
+            | Error::MempoolFull { .. }
+            | Error::TransactionTooLarge { .. }
+            | Error::ZeroFee { .. }
+            | Error::FeeRateTooLow { .. }
+            | Error::FaucetDrained { .. }
+            | Error::FaucetLimitExceeded { .. }
+            | Error::InvalidSequence { .. }
+            | Error::ReplacementFeeTooLow { .. }
+            | Error::InsufficientFunds { .. }
+            | Error::MemoTooLong { .. }
+            | Error::Expired { .. }
+            | Error::LockedUntilHeight { .. }
+            | Error::LockedUntilTime { .. }
+            | Error::ScriptFailed { .. }
+            | Error::InvalidToken { .. }
+            | Error::InsufficientTokens { .. }
+            | Error::InvalidStake { .. }
+            | Error::InsufficientStake { .. }
+            | Error::InvalidEvidence { .. }
+            | Error::InvalidAuthority { .. }
+            | Error::HtlcLocked { .. }
+            | Error::Unconfirmed { .. }
+            | Error::DisconnectGenesis => ErrorKind::InvalidInput,
+            Error::FaucetRateLimited { .. } => ErrorKind::WouldBlock,
+            Error::Pruned { .. } => ErrorKind::Unsupported,
+            Error::DuplicateTransaction { .. }
+            | Error::SequenceInUse { .. }
+            | Error::DoubleSpend { .. } => ErrorKind::AlreadyExists,
+            Error::UnknownParent { .. }
+            | Error::NothingToReplace { .. }
+            | Error::UnknownOutput { .. }
+            | Error::UnknownToken { .. }
+            | Error::UnknownBlock { .. }
+            | Error::UnknownTemplate { .. } => ErrorKind::NotFound,
+            Error::MiningCancelled => ErrorKind::Interrupted,
+        }
+    }
 }
 
 impl From<Error> for std::io::Error {
     fn from(value: Error) -> Self {
-        match value {
-            Error::MutexPoison(e) => Self::new(std::io::ErrorKind::Other, e),
-            Error::Json(e) => Self::new(std::io::ErrorKind::InvalidData, e),
-            Error::Ecdsa(e) => Self::new(std::io::ErrorKind::Other, e),
-            Error::InvalidSignature(e) => Self::new(std::io::ErrorKind::InvalidData, e),
-            Error::AvailableBalanceExceeded(sender) => Self::new(
-                std::io::ErrorKind::InvalidInput,
-                format!(
-                    "transaction exceeds available balance for sender {}",
-                    sender
-                ),
-            ),
-        }
+        Self::new(value.kind(), value)
     }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Serializable copy of the full blockchain state, used to persist the chain
+/// between runs of the binary. `wallet` holds the faucet account's key.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    wallet: StoredWallet,
+    chain: Vec<Block>,
+    transaction_pool: Vec<Transaction>,
+}
+
+impl Snapshot {
+    pub fn new(
+        wallet: StoredWallet,
+        chain: Vec<Block>,
+        transaction_pool: Vec<Transaction>,
+    ) -> Self {
+        Snapshot {
+            wallet,
+            chain,
+            transaction_pool,
+        }
+    }
+
+    pub fn wallet(&self) -> &StoredWallet {
+        &self.wallet
+    }
+
+    pub fn chain(&self) -> &[Block] {
+        &self.chain
+    }
+
+    pub fn transaction_pool(&self) -> &Vec<Transaction> {
+        &self.transaction_pool
+    }
+}
+
+impl Encode for Snapshot {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.wallet.encode_to(out);
+        self.chain.encode_to(out);
+        self.transaction_pool.encode_to(out);
+    }
+}
+
+impl Decode for Snapshot {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(Snapshot {
+            wallet: StoredWallet::decode_from(reader)?,
+            chain: Vec::decode_from(reader)?,
+            transaction_pool: Vec::decode_from(reader)?,
+        })
+    }
+}
+
+/// Running totals since the chain was loaded, exported as metrics.
+#[derive(Clone, Debug, Default)]
+pub struct Counters {
+    /// Header hashes computed while mining.
+    pub hashes: u64,
+    pub blocks_mined: u64,
+    /// Transactions other than coinbases connected to the main chain.
+    pub transactions_confirmed: u64,
+    pub reorgs: u64,
+    /// Hashes per second achieved mining the most recent block.
+    pub hash_rate: f64,
+}
+
+/// A block whose proof has been filled in and signed by its producer, along
+/// with what finding it took.
+pub(crate) struct Mined {
+    block: Block,
+    hashes: u64,
+    elapsed: Duration,
+}
+
+impl Mined {
+    /// Seals `block` under `consensus`, searching for its proof of work if
+    /// it takes one, and signs it with the key of `miner`. Needs no access
+    /// to the chain, so a shared chain stays usable while the search runs.
+    /// Gives up with [`Error::MiningCancelled`] once `cancel` is tripped.
+    pub(crate) fn new(
+        mut block: Block,
+        consensus: &dyn ConsensusEngine,
+        miner: &Wallet,
+        cancel: &CancelToken,
+    ) -> Result<Self> {
+        let started = Instant::now();
+        let hashes = consensus
+            .seal(&mut block, cancel)
+            .ok_or(Error::MiningCancelled)?;
+        miner.sign_block(&mut block)?;
+        Ok(Mined {
+            block,
+            hashes,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+/// The main chain, its side branches and indexes, and the transaction pool.
+/// It holds no locks: queries take `&self` and anything that changes the
+/// chain `&mut self`. Threads share it through a [`SharedBlockchain`].
 pub struct Blockchain {
     wallet: Wallet,
-    chain: Arc<Mutex<Vec<Arc<Block>>>>,
-    transaction_pool: Arc<Mutex<Vec<Transaction>>>,
+    faucet: Faucet,
+    consensus: Arc<dyn ConsensusEngine>,
+    timestamps: TimestampConfig,
+    checkpoint_interval: u64,
+    prune_depth: u64,
+    /// Number of blocks from genesis whose transactions were discarded.
+    pruned: u64,
+    ledger: Ledger,
+    clock: Arc<dyn Clock>,
+    counters: Counters,
+    chain: Vec<Arc<Block>>,
+    index: ChainIndex,
+    state: AccountState,
+    fees: FeeTotals,
+    /// Only maintained on a [`Ledger::Utxo`] chain.
+    utxos: UtxoSet,
+    side_branches: SideBranches,
+    transaction_pool: Mempool,
+    events: EventBus,
+    /// Height of the header chain being downloaded during initial sync.
+    sync_target: Option<u64>,
+    /// Blocks handed out by [`Blockchain::get_block_template`], oldest
+    /// first, waiting for their proof.
+    templates: VecDeque<Block>,
 }
 
 impl Blockchain {
-    pub fn new(version: u8) -> Result<Self> {
+    /// Creates a chain with the default settings of `network`.
+    pub fn new(network: Network) -> Result<Self> {
+        Self::with_config(&Config::for_network(network))
+    }
+
+    /// Creates a chain using the consensus and mempool settings of `config`,
+    /// with the faucet of its network.
+    pub fn with_config(config: &Config) -> Result<Self> {
+        Self::create(config, config.network.chain.faucet_config())
+    }
+
+    /// Creates a chain whose genesis block funds a faucet account with
+    /// `faucet.genesis_funds`.
+    pub fn with_faucet(version: u8, faucet: FaucetConfig) -> Result<Self> {
+        let config = Config {
+            address_version: version,
+            ..Config::default()
+        };
+        Self::create(&config, faucet)
+    }
+
+    fn create(config: &Config, faucet: FaucetConfig) -> Result<Self> {
+        Self::with_clock_and_rng(config, faucet, Arc::new(SystemClock), &mut OsRng)
+    }
+
+    /// Creates a chain that takes the time from `clock` and its keys from
+    /// `rng`, so that a manual clock and a seeded RNG reproduce it exactly.
+    pub fn with_clock_and_rng<R: CryptoRngCore>(
+        config: &Config,
+        faucet: FaucetConfig,
+        clock: Arc<dyn Clock>,
+        rng: &mut R,
+    ) -> Result<Self> {
+        let genesis_funds = faucet.genesis_funds;
+        let allocations = &config.consensus.genesis_allocations;
+        let allocated: f64 = allocations.iter().map(|allocation| allocation.amount).sum();
+        config
+            .emission_schedule()
+            .check_supply(genesis_funds + allocated)?;
         let mut blockchain = Blockchain {
-            wallet: Wallet::new(version).map_err(|e| Error::Ecdsa(e.to_string()))?,
-            chain: Arc::new(Mutex::new(vec![])),
-            transaction_pool: Arc::new(Mutex::new(vec![])),
+            wallet: Wallet::from_rng(rng, config.address_version)?,
+            faucet: Faucet::new(faucet),
+            consensus: config.consensus_engine(),
+            timestamps: config.timestamp_config(),
+            checkpoint_interval: config.checkpoint_interval,
+            prune_depth: config.prune_depth,
+            pruned: 0,
+            ledger: config.consensus.ledger,
+            clock,
+            counters: Counters::default(),
+            chain: vec![],
+            index: ChainIndex::default(),
+            state: AccountState::default(),
+            fees: FeeTotals::default(),
+            utxos: UtxoSet::default(),
+            side_branches: SideBranches::default(),
+            transaction_pool: Mempool::new(config.mempool_config()),
+            events: EventBus::default(),
+            sync_target: None,
+            templates: VecDeque::new(),
         };
-        let address = blockchain.wallet.address().clone();
-        blockchain.add_block(0, &address)?;
+        let wallet = blockchain.wallet.clone();
+        let faucet_funds = Transaction::coinbase(*wallet.address(), genesis_funds, 0)
+            .with_memo(config.consensus.pow_hash.genesis_memo());
+        // Only genesis may hold coinbases of height 0, so these can't be
+        // mistaken for anything minted later.
+        let transactions =
+            std::iter::once(faucet_funds)
+                .chain(allocations.iter().map(|allocation| {
+                    Transaction::coinbase(allocation.address, allocation.amount, 0)
+                }))
+                .collect();
+        let genesis = blockchain.new_block(wallet.address(), transactions)?;
+        let genesis = Mined::new(
+            genesis,
+            blockchain.consensus.as_ref(),
+            &wallet,
+            &CancelToken::new(),
+        )?;
+        blockchain.append_block(Arc::new(genesis.block))?;
         Ok(blockchain)
     }
 
-    pub fn last_block(&self) -> Option<Arc<Block>> {
-        match self.chain.lock() {
-            Ok(chain) => chain.get(chain.len().saturating_sub(1)).cloned(),
-            Err(_) => None,
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self> {
+        Self::from_snapshot_with_config(snapshot, &Config::default())
+    }
+
+    /// Restores a chain from a snapshot, applying the consensus and mempool
+    /// settings of `config`.
+    pub fn from_snapshot_with_config(snapshot: Snapshot, config: &Config) -> Result<Self> {
+        Self::from_checkpoint(snapshot, None, config)
+    }
+
+    /// Restores a chain from a snapshot, starting from the balances in
+    /// `checkpoint` and applying only the blocks after it. The checkpoint is
+    /// ignored, and the whole chain replayed, if it wasn't taken on this
+    /// chain. A pruned chain can only be restored from a checkpoint taken
+    /// after its pruned blocks.
+    pub fn from_checkpoint(
+        snapshot: Snapshot,
+        checkpoint: Option<&Checkpoint>,
+        config: &Config,
+    ) -> Result<Self> {
+        if let Some(genesis) = snapshot.chain.first() {
+            check_pow_hash(genesis, config)?;
+        }
+        let pruned = snapshot
+            .chain
+            .iter()
+            .take_while(|block| block.is_pruned())
+            .count() as u64;
+        let checkpoint = checkpoint.filter(|c| c.matches(&snapshot.chain));
+        if pruned > 0
+            && (config.consensus.ledger == Ledger::Utxo
+                || checkpoint.is_none_or(|c| c.height() + 1 < pruned))
+        {
+            return Err(Error::Pruned { height: pruned - 1 });
         }
+        let index = ChainIndex::build(&snapshot.chain);
+        let fees = FeeTotals::build(&snapshot.chain);
+        let state = match checkpoint {
+            Some(checkpoint) => {
+                let mut state = checkpoint.state();
+                for block in &snapshot.chain[checkpoint.height() as usize + 1..] {
+                    state.apply_block(block);
+                }
+                state
+            }
+            None => AccountState::build(&snapshot.chain),
+        };
+        // Checkpoints only hold balances, so the UTXO set is always rebuilt.
+        let utxos = match config.consensus.ledger {
+            Ledger::Account => UtxoSet::default(),
+            Ledger::Utxo => UtxoSet::build(&snapshot.chain)?,
+        };
+        let mut transaction_pool = Mempool::new(config.mempool_config());
+        for transaction in snapshot.transaction_pool {
+            // Transactions beyond tighter limits than the pool was saved with
+            // are dropped.
+            let _ = transaction_pool.insert(transaction);
+        }
+        let unsettled = unsettled(&snapshot.chain, config.mempool.min_confirmations);
+        transaction_pool.sync(&state, &as_blocks(unsettled));
+        if let Some(tip) = snapshot.chain.last() {
+            transaction_pool.evict_expired(snapshot.chain.len() as u64, tip.timestamp());
+        }
+        Ok(Blockchain {
+            wallet: Wallet::from_stored(&snapshot.wallet)?,
+            faucet: Faucet::new(config.network.chain.faucet_config()),
+            consensus: config.consensus_engine(),
+            timestamps: config.timestamp_config(),
+            checkpoint_interval: config.checkpoint_interval,
+            prune_depth: config.prune_depth,
+            pruned,
+            ledger: config.consensus.ledger,
+            clock: Arc::new(SystemClock),
+            counters: Counters::default(),
+            chain: snapshot.chain.into_iter().map(Arc::new).collect(),
+            index,
+            state,
+            fees,
+            utxos,
+            side_branches: SideBranches::default(),
+            transaction_pool,
+            events: EventBus::default(),
+            sync_target: None,
+            templates: VecDeque::new(),
+        })
+    }
+
+    /// Rebuilds a chain from the blocks of `snapshot` alone, ignoring any
+    /// checkpoint: every block after genesis is connected with the checks
+    /// of [`Blockchain::connect_block`], rebuilding balances, the UTXO set
+    /// and the transaction and address indexes along the way, and the
+    /// pending transactions are admitted again where they still fit. Fails
+    /// at the first invalid block, and on a pruned chain, whose blocks
+    /// can't be checked.
+    pub fn reindex(snapshot: Snapshot, config: &Config) -> Result<Self> {
+        if let Some(height) = snapshot.chain.iter().rposition(Block::is_pruned) {
+            return Err(Error::Pruned {
+                height: height as u64,
+            });
+        }
+        let mut blocks = snapshot.chain.into_iter();
+        let genesis = Snapshot {
+            wallet: snapshot.wallet,
+            chain: blocks.next().into_iter().collect(),
+            transaction_pool: vec![],
+        };
+        let mut blockchain = Self::from_checkpoint(genesis, None, config)?;
+        for block in blocks {
+            blockchain.connect_block(block)?;
+        }
+        for transaction in snapshot.transaction_pool {
+            let _ = blockchain.try_add_transaction(transaction);
+        }
+        Ok(blockchain)
+    }
+
+    /// Replaces the rules received blocks' timestamps are checked against.
+    pub fn with_timestamp_config(mut self, timestamps: TimestampConfig) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Replaces the clock new blocks are stamped with and received blocks
+    /// are checked against, e.g. on a chain restored from a snapshot.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
-    fn add_block(&mut self, nonce: i32, miner: &String) -> Result<Arc<Block>> {
-        let previous_block = self.last_block().unwrap_or_default();
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Ok(Snapshot {
+            wallet: self.wallet.to_stored()?,
+            chain: self.chain.iter().map(|b| b.as_ref().clone()).collect(),
+            transaction_pool: self.transaction_pool.iter().cloned().collect(),
+        })
+    }
+
+    /// Balances as of the most recent height that is a multiple of the
+    /// checkpoint interval, or `None` before the first one or if
+    /// checkpoints are disabled.
+    pub fn checkpoint(&self) -> Result<Option<Checkpoint>> {
+        if self.checkpoint_interval == 0 {
+            return Ok(None);
+        }
+        let tip_height = self.chain.len().saturating_sub(1) as u64;
+        let height = tip_height - tip_height % self.checkpoint_interval;
+        if height == 0 {
+            return Ok(None);
+        }
+        let mut state = self.state.clone();
+        for block in self.chain[height as usize + 1..].iter().rev() {
+            state.revert_block(block);
+        }
+        Ok(Some(Checkpoint::new(
+            height,
+            &self.chain[height as usize],
+            &state,
+        )))
+    }
+
+    /// Structured copy of every block and pending transaction.
+    pub fn export(&self) -> Result<ChainExport> {
+        self.check_unpruned(0)?;
+        let blocks = self
+            .iter_blocks()
+            .enumerate()
+            .map(|(height, block)| BlockRecord::new(height as u64, &block))
+            .collect();
+        Ok(ChainExport {
+            height: self.chain.len().saturating_sub(1) as u64,
+            blocks,
+            pending: self
+                .pending_transactions()
+                .iter()
+                .map(TransactionRecord::from)
+                .collect(),
+        })
+    }
+
+    pub fn export_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.export()?)?)
+    }
+
+    pub fn export_pretty(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.export()?)?)
+    }
+
+    pub fn last_block(&self) -> Option<Arc<Block>> {
+        self.chain.last().cloned()
+    }
+
+    /// Main chain blocks from genesis on, without copying the chain. Blocks
+    /// older than the pruning depth come without their transactions.
+    pub fn iter_blocks(&self) -> impl Iterator<Item = Arc<Block>> + '_ {
+        self.iter_blocks_from(0)
+    }
+
+    /// Main chain blocks from height `from` on, see
+    /// [`Blockchain::iter_blocks`].
+    pub fn iter_blocks_from(&self, from: u64) -> impl Iterator<Item = Arc<Block>> + '_ {
+        self.chain
+            .iter()
+            .skip(usize::try_from(from).unwrap_or(usize::MAX))
+            .cloned()
+    }
+
+    /// Builds an unmined block of `transactions` on top of the tip, paying
+    /// `miner`.
+    fn new_block(&self, miner: &Address, transactions: Vec<Transaction>) -> Result<Block> {
+        let now = self.clock.now();
+        let previous_block = self
+            .last_block()
+            .unwrap_or_else(|| Arc::new(Block::empty_at(now)));
         let previous_hash = previous_block.hash();
-        let mut transactions: Vec<Transaction> = vec![];
-        let mut transaction_pool_lock = self
+        let median = median_time_past(&self.ancestor_timestamps(&previous_hash)?);
+        // Keep the timestamp valid even if the clock went backwards.
+        let timestamp = match median {
+            Some(median) => now.max(median + 1),
+            None => now,
+        };
+        let block = Block::new(0, previous_hash, transactions, timestamp, *miner);
+        Self::validate_conditions(&block, self.chain.len() as u64)?;
+        Ok(block)
+    }
+
+    /// Block for `miner` to mine next: a reward payment to it followed by
+    /// every ready transaction in the pool that can be confirmed in order.
+    /// The reward covers the fees of the included transactions. It only
+    /// reads the chain, so a [`SharedBlockchain`] can search for its proof
+    /// of work without holding the lock.
+    pub(crate) fn block_candidate(&self, miner: &Address) -> Result<Block> {
+        let height = self.chain_height()?;
+        let now = self.clock.now();
+        // Skip anything expired or held back by its timelock, HTLC or
+        // script, and anything after a gap left by such a transaction.
+        let mut state = self.state.clone();
+        let mut view = UtxoView::new(&self.utxos);
+        let pending: Vec<Transaction> = self
             .transaction_pool
-            .lock()
-            .map_err(|e| Error::MutexPoison(e.to_string()))?;
-        while transaction_pool_lock.iter().len() > 0 {
-            let transaction = match transaction_pool_lock.pop() {
-                Some(transaction) => transaction,
-                None => break,
+            .iter_ready()
+            .filter(|transaction| {
+                let in_order = transaction.check_expiry(height + 1, now).is_ok()
+                    && transaction.check_timelock(height + 1, now).is_ok()
+                    && transaction.check_htlc(height + 1).is_ok()
+                    && transaction.check_script(height + 1, now).is_ok()
+                    && transaction
+                        .sender()
+                        .is_some_and(|sender| state.next_sequence(sender) == transaction.sequence)
+                    && state.tokens().check(transaction, 0).is_ok()
+                    && state.stakes().check(transaction, 0.0).is_ok()
+                    && self
+                        .consensus
+                        .check_transaction(transaction, state.producers())
+                        .is_ok()
+                    && (self.ledger == Ledger::Account || view.spend(transaction).is_ok());
+                if in_order {
+                    state.apply_transaction(transaction);
+                }
+                in_order
+            })
+            .cloned()
+            .collect();
+        let fees: f64 = pending.iter().map(|t| t.fee).sum();
+        let reward = self
+            .consensus
+            .emission()
+            .capped_reward_at(height + 1, state.issued() + fees);
+        let reward = Transaction::coinbase(*miner, reward + fees, height + 1);
+        let block = self.new_block(miner, std::iter::once(reward).chain(pending).collect())?;
+        // Not worth sealing if it isn't the miner's turn.
+        let tip = self.last_block().unwrap_or_default();
+        self.consensus
+            .check_producer(block.header(), tip.header(), self.state.producers())?;
+        Ok(block)
+    }
+
+    /// [`Blockchain::block_candidate`] paying `miner`, for a miner outside
+    /// the node to search a proof for. The node keeps its transactions
+    /// until [`Blockchain::submit_block`] hands the header back, for the
+    /// last few templates on the current tip.
+    pub fn get_block_template(&mut self, miner: &Address) -> Result<BlockTemplate> {
+        let mut block = self.block_candidate(miner)?;
+        let proof_of_work = self.consensus.proof_of_work();
+        if let Some((bits, _)) = proof_of_work {
+            block.set_bits(bits);
+        }
+        self.templates
+            .retain(|template| template.previous_hash() == block.previous_hash());
+        if self.templates.len() >= template::MAX_TEMPLATES {
+            self.templates.pop_front();
+        }
+        self.templates.push_back(block.clone());
+        Ok(BlockTemplate {
+            height: self.chain_height()? + 1,
+            header: block.header().clone(),
+            transactions: block.transactions().clone(),
+            pow_hash: proof_of_work.map(|(_, hash)| hash),
+        })
+    }
+
+    /// Connects the block of a template from
+    /// [`Blockchain::get_block_template`], given its header with the nonce
+    /// found and the miner's signature, and the extra nonce the miner put
+    /// in the coinbase. The header is matched to the template by its Merkle
+    /// root and checked like any block received, so the miner may also have
+    /// moved the timestamp.
+    pub fn submit_block(&mut self, header: BlockHeader, extra_nonce: u64) -> Result<()> {
+        let body = self
+            .templates
+            .iter()
+            .find_map(|template| {
+                let mut transactions = template.transactions().clone();
+                template::set_extra_nonce(&mut transactions, extra_nonce);
+                let body = BlockBody::new(transactions);
+                (body.merkle_root() == header.merkle_root()).then_some(body)
+            })
+            .ok_or_else(|| Error::UnknownTemplate {
+                merkle_root: header.merkle_root().to_string(),
+            })?;
+        let hash = header.hash();
+        let block = Block::from_parts(header, body).ok_or(Error::MutatedBody { block: hash })?;
+        self.connect_block(block)?;
+        self.counters.blocks_mined += 1;
+        self.templates.clear();
+        Ok(())
+    }
+
+    /// Appends a block this node mined, which must still extend the tip, and
+    /// tells listeners.
+    pub(crate) fn connect_mined(&mut self, mined: Mined) -> Result<Arc<Block>> {
+        self.counters.hashes += mined.hashes;
+        self.counters.hash_rate =
+            mined.hashes as f64 / mined.elapsed.as_secs_f64().max(f64::EPSILON);
+        let block = mined.block;
+        if self
+            .last_block()
+            .is_some_and(|tip| *block.previous_hash() != tip.hash())
+        {
+            return Err(Error::StaleBlock { hash: block.hash() });
+        }
+        let block = Arc::new(block);
+        self.append_block(block.clone())?;
+        self.counters.blocks_mined += 1;
+        self.publish(ChainEvent::BlockMined {
+            height: self.chain_height()?,
+            hash: block.hash(),
+        });
+        Ok(block)
+    }
+
+    pub(crate) fn consensus(&self) -> Arc<dyn ConsensusEngine> {
+        self.consensus.clone()
+    }
+
+    /// Swaps in other consensus rules than those of the config, for engines
+    /// the config can't name.
+    pub fn with_consensus(mut self, consensus: Arc<dyn ConsensusEngine>) -> Self {
+        self.consensus = consensus;
+        self
+    }
+
+    fn append_block(&mut self, block: Arc<Block>) -> Result<()> {
+        if self.ledger == Ledger::Utxo {
+            self.utxos.apply_block(&block)?;
+        }
+        self.index.insert(self.chain.len() as u64, &block);
+        self.state.apply_block(&block);
+        self.fees.apply_block(&block);
+        self.chain.push(block.clone());
+        self.counters.transactions_confirmed += confirmed_count(&block);
+        let next_height = self.chain.len() as u64;
+        self.sync_pool(std::slice::from_ref(&block));
+        self.transaction_pool
+            .evict_expired(next_height, block.timestamp());
+        self.prune()
+    }
+
+    /// Discards the transactions of blocks at least `prune_depth` deep that
+    /// the previous checkpoint already covers, so the chain can still be
+    /// restored from whichever checkpoint was last saved. Only the headers
+    /// are kept; balances come from the checkpoint.
+    fn prune(&mut self) -> Result<()> {
+        if self.prune_depth == 0 || self.checkpoint_interval == 0 || self.ledger == Ledger::Utxo {
+            return Ok(());
+        }
+        let tip_height = self.chain.len().saturating_sub(1) as u64;
+        let covered = (tip_height - tip_height % self.checkpoint_interval)
+            .saturating_sub(self.checkpoint_interval);
+        let Some(limit) = tip_height
+            .checked_sub(self.prune_depth)
+            .map(|deepest| deepest.min(covered))
+        else {
+            return Ok(());
+        };
+        if covered == 0 || limit < self.pruned {
+            return Ok(());
+        }
+        for block in &mut self.chain[self.pruned as usize..=limit as usize] {
+            *block = Arc::new(block.pruned());
+        }
+        self.pruned = limit + 1;
+        Ok(())
+    }
+
+    /// Fails if the transactions of the block at `height` were pruned.
+    fn check_unpruned(&self, height: u64) -> Result<()> {
+        if height < self.pruned {
+            return Err(Error::Pruned {
+                height: self.pruned - 1,
+            });
+        }
+        Ok(())
+    }
+
+    /// Accepts a block produced elsewhere. Blocks extending the tip are
+    /// appended directly; blocks on other branches are kept aside until their
+    /// branch has more work than the main chain, at which point the chain is
+    /// reorganized onto it.
+    pub fn receive_block(&mut self, block: Block) -> Result<BlockStatus> {
+        let hash = block.hash();
+        let parent_on_chain = self.index.height_of(block.previous_hash());
+        if self.index.height_of(&hash).is_some() || self.side_branches.contains(&hash) {
+            return Ok(BlockStatus::AlreadyKnown);
+        }
+        self.consensus.check_header(block.header())?;
+        block.verify_signature()?;
+        let (parent, parent_height, parent_work) = match parent_on_chain {
+            Some(height) => (
+                self.block_by_height(height),
+                height,
+                self.index.chain_work(height).unwrap_or_default(),
+            ),
+            None => {
+                let previous = block.previous_hash();
+                let height =
+                    self.side_branches
+                        .height_of(previous)
+                        .ok_or_else(|| Error::UnknownParent {
+                            hash: previous.clone(),
+                        })?;
+                let work = self.side_branches.chain_work(previous).unwrap_or_default();
+                (self.side_branches.get(previous), height, work)
+            }
+        };
+        if let Some(parent) = parent {
+            self.consensus.check_producer(
+                block.header(),
+                parent.header(),
+                self.state.producers(),
+            )?;
+        }
+        let height = parent_height + 1;
+        self.check_contents(&block, height)?;
+        let tip = self.last_block().unwrap_or_default();
+        if *block.previous_hash() == tip.hash() {
+            self.extend_tip(block)?;
+            return Ok(BlockStatus::Extended);
+        }
+        let work = parent_work.saturating_add(block.work());
+        self.side_branches.insert(Arc::new(block), height, work);
+        if work <= self.total_work() {
+            return Ok(BlockStatus::SideBranch);
+        }
+        self.reorganize(&hash)
+    }
+
+    /// Appends a block that must extend the tip, checking it as
+    /// [`Blockchain::receive_block`] does. The inverse of
+    /// [`Blockchain::disconnect_tip`].
+    pub fn connect_block(&mut self, block: Block) -> Result<()> {
+        let tip = self.last_block().unwrap_or_default();
+        if *block.previous_hash() != tip.hash() {
+            return Err(Error::StaleBlock { hash: block.hash() });
+        }
+        self.check_proof(&block, &tip)?;
+        self.check_contents(&block, self.chain.len() as u64)?;
+        self.extend_tip(block)
+    }
+
+    /// Removes the tip block and undoes its effects on balances and the
+    /// UTXO set. Its transactions go back into the pool where they still
+    /// fit, as after a reorg, and the block is kept as a side branch so a
+    /// branch through it with more work can still take over. The inverse of
+    /// [`Blockchain::connect_block`].
+    pub fn disconnect_tip(&mut self) -> Result<Arc<Block>> {
+        let height = self.chain_height()?;
+        if height == 0 {
+            return Err(Error::DisconnectGenesis);
+        }
+        self.check_unpruned(height)?;
+        let Some(block) = self.chain.pop() else {
+            return Err(Error::DisconnectGenesis);
+        };
+        let work = self.total_work();
+        self.index.remove(height, &block);
+        self.state.revert_block(&block);
+        self.fees.revert_block(&block);
+        if self.ledger == Ledger::Utxo {
+            self.utxos.revert_block(&block);
+        }
+        self.side_branches.insert(block.clone(), height, work);
+        self.sync_pool(&[]);
+        for transaction in block.transactions().iter().filter(|t| !t.is_coinbase()) {
+            self.requeue(transaction.clone())?;
+        }
+        self.publish(ChainEvent::BlockDisconnected {
+            height,
+            hash: block.hash(),
+        });
+        Ok(block)
+    }
+
+    /// Checks a block's proof and signature, and that its producer may
+    /// produce it on `parent` given the stakes as of the tip.
+    fn check_proof(&self, block: &Block, parent: &Block) -> Result<()> {
+        self.consensus.check_header(block.header())?;
+        self.consensus
+            .check_producer(block.header(), parent.header(), self.state.producers())?;
+        block.verify_signature()
+    }
+
+    /// Checks what a block at `height` contains against the rules that
+    /// don't depend on which branch is the main chain.
+    fn check_contents(&self, block: &Block, height: u64) -> Result<()> {
+        Self::validate_coinbase(block, height, self.consensus.emission().reward_at(height))?;
+        Self::validate_conditions(block, height)?;
+        Self::validate_signatures(block)?;
+        self.timestamps.validate(
+            &block.hash(),
+            block.timestamp(),
+            &self.ancestor_timestamps(block.previous_hash())?,
+            self.clock.now(),
+        )
+    }
+
+    /// Appends a checked block on top of the tip once its spends and
+    /// issuance hold against the chain.
+    fn extend_tip(&mut self, block: Block) -> Result<()> {
+        self.validate_sequences(&block)?;
+        self.validate_supply(&block)?;
+        let hash = block.hash();
+        // It may be coming back after being disconnected.
+        self.side_branches.remove(&hash);
+        self.append_block(Arc::new(block))?;
+        self.publish(ChainEvent::BlockConnected {
+            height: self.chain_height()?,
+            hash,
+        });
+        Ok(())
+    }
+
+    /// Checks that a block at `height` starts with a coinbase for that height,
+    /// contains no other coinbase, and doesn't pay out more than `reward`
+    /// plus the fees of its transactions.
+    fn validate_coinbase(block: &Block, height: u64, reward: f64) -> Result<()> {
+        let hash = block.hash();
+        let Some((coinbase, rest)) = block.transactions().split_first() else {
+            return Err(Error::MissingCoinbase { block: hash });
+        };
+        if coinbase.origin != (Origin::Coinbase { height }) {
+            return Err(Error::MisplacedCoinbase {
+                block: hash,
+                height,
+            });
+        }
+        if rest.iter().any(Transaction::is_coinbase) {
+            return Err(Error::ExtraCoinbase { block: hash });
+        }
+        let fees: f64 = rest.iter().map(|t| t.fee).sum();
+        if coinbase.amount > reward + fees {
+            return Err(Error::ExcessiveCoinbase {
+                block: hash,
+                paid: coinbase.amount,
+                allowed: reward + fees,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that no transaction in a block at `height` has expired, is
+    /// still timelocked or refunds an HTLC too early, and that all their
+    /// scripts pass.
+    fn validate_conditions(block: &Block, height: u64) -> Result<()> {
+        block.transactions().iter().try_for_each(|transaction| {
+            transaction.check_expiry(height, block.timestamp())?;
+            transaction.check_timelock(height, block.timestamp())?;
+            transaction.check_htlc(height)?;
+            transaction.check_script(height, block.timestamp())
+        })
+    }
+
+    /// Checks the memo and amounts of every transaction in a block and the
+    /// witness of all but the coinbase. Signatures are verified across all
+    /// cores, as they make up most of the cost of validating a full block.
+    fn validate_signatures(block: &Block) -> Result<()> {
+        block.transactions().par_iter().try_for_each(|transaction| {
+            transaction.check_memo()?;
+            transaction.check_amounts()?;
+            match transaction.sender() {
+                Some(sender) => transaction
+                    .witness
+                    .verify(sender, &transaction.signing_bytes()),
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Checks that a block extending the tip spends each sender's sequence
+    /// numbers in order, starting from the next unconfirmed one, so no
+    /// payment is confirmed twice, that on an account ledger no sender pays
+    /// out more than it holds, and that its token and stake operations are
+    /// valid in that order.
+    fn validate_sequences(&self, block: &Block) -> Result<()> {
+        let mut state = self.state.clone();
+        for transaction in block.transactions() {
+            let Some(sender) = transaction.sender() else {
+                continue;
             };
-            transactions.push(transaction.clone());
+            let expected = state.next_sequence(sender);
+            if transaction.sequence != expected {
+                return Err(Error::InvalidSequence {
+                    sender: *sender,
+                    expected,
+                    provided: transaction.sequence,
+                });
+            }
+            let balance = state.balance(sender);
+            let required = transaction.amount + transaction.fee;
+            if self.ledger == Ledger::Account && required > balance + BALANCE_EPSILON {
+                return Err(Error::Overdrawn {
+                    sender: *sender,
+                    balance,
+                    required,
+                });
+            }
+            state.tokens().check(transaction, 0)?;
+            self.check_ledger_stakes(transaction)?;
+            state.stakes().check(transaction, 0.0)?;
+            self.consensus
+                .check_transaction(transaction, state.producers())?;
+            state.apply_transaction(transaction);
+        }
+        Ok(())
+    }
+
+    /// Checks that a block extending the tip keeps the coins in existence
+    /// within the supply cap.
+    fn validate_supply(&self, block: &Block) -> Result<()> {
+        let issued = self.state.issued();
+        let created: f64 = block.transactions().iter().map(minted).sum();
+        self.consensus.emission().check_supply(issued + created)
+    }
+
+    /// Timestamps of up to `median_window` blocks ending at `parent`, which
+    /// may be on the main chain or on a side branch.
+    fn ancestor_timestamps(&self, parent: &str) -> Result<Vec<i64>> {
+        let window = self.timestamps.median_window;
+        let branch = self.side_branches.branch_to(parent);
+        let join = branch
+            .first()
+            .map(|block| block.previous_hash().as_str())
+            .unwrap_or(parent);
+        let mut timestamps: Vec<i64> = branch.iter().rev().map(|b| b.timestamp()).collect();
+        if let Some(height) = self.index.height_of(join) {
+            timestamps.extend(
+                self.chain[..=height as usize]
+                    .iter()
+                    .rev()
+                    .map(|b| b.timestamp()),
+            );
+        }
+        timestamps.truncate(window);
+        Ok(timestamps)
+    }
+
+    /// Height of the tip of the main chain, genesis being at height 0.
+    pub fn chain_height(&self) -> Result<u64> {
+        Ok(self.chain.len().saturating_sub(1) as u64)
+    }
+
+    /// Work of the main chain, the sum of [`Block::work`] over its blocks.
+    /// The branch with the most of it is the main chain.
+    pub fn total_work(&self) -> u128 {
+        self.index.total_work()
+    }
+
+    /// Work of the main chain up to and including the block at `height`.
+    pub fn chain_work(&self, height: u64) -> Option<u128> {
+        self.index.chain_work(height)
+    }
+
+    /// Switches the main chain to the side branch ending at `tip`. Blocks
+    /// after the fork point move to the side branches, and their transactions
+    /// that the new branch doesn't confirm go back into the pool.
+    fn reorganize(&mut self, tip: &str) -> Result<BlockStatus> {
+        let branch = self.side_branches.branch_to(tip);
+        let fork_hash = match branch.first() {
+            Some(block) => block.previous_hash().clone(),
+            None => {
+                return Err(Error::UnknownParent {
+                    hash: tip.to_string(),
+                })
+            }
+        };
+        let fork_height = self
+            .index
+            .height_of(&fork_hash)
+            .ok_or_else(|| Error::UnknownParent {
+                hash: fork_hash.clone(),
+            })?;
+        self.check_unpruned(fork_height + 1)?;
+        let saved = (self.state.clone(), self.fees.clone(), self.utxos.clone());
+        let disconnected = self.chain.split_off(fork_height as usize + 1);
+        for block in disconnected.iter().rev() {
+            self.state.revert_block(block);
+            self.fees.revert_block(block);
+            if self.ledger == Ledger::Utxo {
+                self.utxos.revert_block(block);
+            }
         }
-        let timestamp = Utc::now().timestamp_nanos_opt().unwrap();
-        let b = Arc::new(Block::new(
-            nonce,
-            previous_hash,
+        if let Err(error) = self.replay_branch(&branch) {
+            // Put the old tip back and forget the branch from the first
+            // block that failed, since nothing can build on it.
+            let connected = self.chain.len() - (fork_height as usize + 1);
+            for block in &branch[connected..] {
+                self.side_branches.remove(&block.hash());
+            }
+            self.chain.truncate(fork_height as usize + 1);
+            self.chain.extend(disconnected);
+            (self.state, self.fees, self.utxos) = saved;
+            return Err(error);
+        }
+        for (offset, block) in disconnected.iter().enumerate() {
+            let height = fork_height + 1 + offset as u64;
+            let work = self.index.chain_work(height).unwrap_or_default();
+            self.side_branches.insert(block.clone(), height, work);
+        }
+        for block in &branch {
+            self.side_branches.remove(&block.hash());
+        }
+        self.index = ChainIndex::build(self.chain.iter().map(|b| b.as_ref()));
+        self.counters.reorgs += 1;
+        self.counters.transactions_confirmed +=
+            branch.iter().map(|b| confirmed_count(b)).sum::<u64>();
+        self.sync_pool(&branch);
+        if let Some(tip) = branch.last() {
+            self.transaction_pool
+                .evict_expired(fork_height + branch.len() as u64 + 1, tip.timestamp());
+        }
+        for transaction in disconnected
+            .iter()
+            .flat_map(|block| block.transactions())
+            .filter(|transaction| !transaction.is_coinbase())
+        {
+            self.requeue(transaction.clone())?;
+        }
+        self.publish(ChainEvent::Reorg {
+            fork_height,
+            disconnected: disconnected.iter().map(|b| b.hash()).collect(),
+            connected: branch.iter().map(|b| b.hash()).collect(),
+        });
+        Ok(BlockStatus::Reorganized {
+            disconnected: disconnected.len(),
+            connected: branch.len(),
+        })
+    }
+
+    /// Connects the blocks of a branch on top of the fork point, checking
+    /// each against the state it lands on exactly as
+    /// [`Blockchain::extend_tip`] does. Stops at the first block that fails,
+    /// leaving the blocks before it connected.
+    fn replay_branch(&mut self, branch: &[Arc<Block>]) -> Result<()> {
+        for block in branch {
+            let parent = self.last_block().unwrap_or_default();
+            self.check_proof(block, &parent)?;
+            self.validate_sequences(block)?;
+            self.validate_supply(block)?;
+            if self.ledger == Ledger::Utxo {
+                self.utxos.apply_block(block)?;
+            }
+            self.state.apply_block(block);
+            self.fees.apply_block(block);
+            self.chain.push(block.clone());
+        }
+        Ok(())
+    }
+
+    /// Puts a transaction from a disconnected block back into the pool if it
+    /// isn't confirmed on the new chain, is its sender's next payment and
+    /// doesn't conflict with what is already pending.
+    fn requeue(&mut self, transaction: Transaction) -> Result<()> {
+        if self.index.locate(&transaction.id()).is_some() {
+            return Ok(());
+        }
+        let Some(sender) = transaction.sender().copied() else {
+            return Ok(());
+        };
+        if transaction.sequence != self.next_sequence(&sender)? {
+            return Ok(());
+        }
+        if self.check_conditions(&transaction).is_err()
+            || self.check_spend(&transaction, None).is_err()
+            || self.check_tokens(&transaction, None).is_err()
+            || self.check_stake(&transaction, None).is_err()
+            || self
+                .consensus
+                .check_transaction(&transaction, self.state.producers())
+                .is_err()
+        {
+            return Ok(());
+        }
+        let confirmed = self.spendable_balance(&sender);
+        self.transaction_pool
+            .set_confirmed_balance(sender, confirmed);
+        // A pool that is full, already holds the transaction or finds the
+        // sender can no longer afford it simply doesn't take it back.
+        let _ = self.transaction_pool.insert(transaction);
+        Ok(())
+    }
+
+    /// The main chain block at `height`. Blocks older than the pruning depth
+    /// come back without their transactions, see [`Block::is_pruned`].
+    pub fn block_by_height(&self, height: u64) -> Option<Arc<Block>> {
+        self.chain.get(usize::try_from(height).ok()?).cloned()
+    }
+
+    pub fn block_by_hash(&self, hash: &str) -> Option<Arc<Block>> {
+        let height = self.index.height_of(hash)?;
+        self.block_by_height(height)
+    }
+
+    /// Height of the main chain block with `hash`, if it is on the main
+    /// chain.
+    pub fn height_of(&self, hash: &str) -> Option<u64> {
+        self.index.height_of(hash)
+    }
+
+    /// Looks up a confirmed transaction along with where it sits in the chain.
+    /// Fails with [`Error::Pruned`] if it isn't found and may have been in a
+    /// pruned block.
+    pub fn transaction_by_id(&self, id: &TxId) -> Result<Option<(Transaction, TxLocation)>> {
+        let location = self.index.locate(id);
+        let Some(location) = location else {
+            self.check_unpruned(0)?;
+            return Ok(None);
+        };
+        self.check_unpruned(location.height)?;
+        Ok(self.block_by_height(location.height).and_then(|block| {
+            let transaction = block.transactions().get(location.index)?.clone();
+            Some((transaction, location))
+        }))
+    }
+
+    /// Proof that the confirmed transaction `id` is in its block, along with
+    /// the block's hash, for a [`LightClient`] to check.
+    pub fn merkle_proof(&self, id: &TxId) -> Result<Option<(String, MerkleProof)>> {
+        Ok(self
+            .receipt(id)?
+            .map(|receipt| (receipt.block_hash, receipt.proof)))
+    }
+
+    /// [`Receipt`] of the confirmed transaction `id`, `None` if it isn't
+    /// confirmed. Fails with [`Error::Pruned`] if it may have been in a
+    /// pruned block.
+    pub fn receipt(&self, id: &TxId) -> Result<Option<Receipt>> {
+        let location = self.index.locate(id);
+        let Some(location) = location else {
+            self.check_unpruned(0)?;
+            return Ok(None);
+        };
+        self.receipt_at(location)
+    }
+
+    fn receipt_at(&self, location: TxLocation) -> Result<Option<Receipt>> {
+        self.check_unpruned(location.height)?;
+        Ok(self.block_by_height(location.height).and_then(|block| {
+            let ids: Vec<TxId> = block.transactions().iter().map(Transaction::id).collect();
+            Some(Receipt {
+                txid: ids.get(location.index)?.clone(),
+                block_hash: block.hash(),
+                height: location.height,
+                index: location.index as u64,
+                merkle_root: block.merkle_root().to_string(),
+                proof: MerkleProof::new(&ids, location.index)?,
+            })
+        }))
+    }
+
+    /// [`BalanceProof`] of every transaction to or from `address` up to
+    /// the main chain block at `height`. Fails with [`Error::Pruned`] if some of its
+    /// transactions may have been pruned.
+    pub fn balance_proof(&self, address: &Address, height: u64) -> Result<BalanceProof> {
+        if height > self.chain_height()? {
+            return Err(Error::UnknownBlock {
+                hash: height.to_string(),
+            });
+        }
+        self.check_unpruned(0)?;
+        let mut received = 0.0;
+        let mut spent = 0.0;
+        let mut next_sequence = 0;
+        let mut transactions = vec![];
+        for &location in self.index.locations_for(address) {
+            if location.height > height {
+                break;
+            }
+            let transaction =
+                self.chain[location.height as usize].transactions()[location.index].clone();
+            if transaction.recipient == *address {
+                received += transaction.credited();
+            }
+            if transaction.sender() == Some(address) {
+                spent += transaction.amount + transaction.fee;
+                next_sequence += 1;
+            }
+            let receipt = self
+                .receipt_at(location)?
+                .ok_or_else(|| Error::UnknownBlock {
+                    hash: location.height.to_string(),
+                })?;
+            transactions.push(ProvenTransaction {
+                transaction,
+                receipt,
+            });
+        }
+        Ok(BalanceProof {
+            address: *address,
+            height,
+            received,
+            spent,
+            next_sequence,
+            headers: self.headers(1, height as usize)?,
             transactions,
-            timestamp,
-            miner.clone(),
-        ));
-        let mut chain_lock = self
+        })
+    }
+
+    /// Headers of up to `limit` main chain blocks from height `from` on,
+    /// for a [`LightClient`] to sync.
+    pub fn headers(&self, from: u64, limit: usize) -> Result<Vec<BlockHeader>> {
+        Ok(self
             .chain
-            .lock()
-            .map_err(|e| Error::MutexPoison(e.to_string()))?;
-        chain_lock.push(b.clone());
-        Ok(b)
+            .iter()
+            .skip(usize::try_from(from).unwrap_or(usize::MAX))
+            .take(limit)
+            .map(|block| block.header().clone())
+            .collect())
     }
 
-    pub fn add_transation_to_pool(
-        &mut self,
-        transaction: Transaction,
-        signature: Signature,
-        verifying_key: VerifyingKey,
-    ) -> Result<Transaction> {
-        if let Err(e) = verifying_key.verify(transaction.to_string().as_bytes(), &signature) {
-            Err(Error::InvalidSignature(e.to_string()))
-        } else {
-            let sender = transaction.clone().sender;
-            if &sender.clone() != self.wallet.address() {
-                let sender_balance = self.calculate_transactions_total(sender.clone())?;
-                if sender_balance < transaction.amount {
-                    return Err(Error::AvailableBalanceExceeded(sender));
-                }
+    /// Hashes of main chain blocks, from the tip back to genesis with
+    /// growing gaps, for a peer to find where its chain and this one part
+    /// with [`Blockchain::headers_after`].
+    pub fn locator(&self) -> Result<Vec<String>> {
+        let chain = &self.chain;
+        let tip = chain.len().saturating_sub(1) as u64;
+        Ok(sync::locator_heights(tip)
+            .into_iter()
+            .filter_map(|height| chain.get(height as usize))
+            .map(|block| block.hash())
+            .collect())
+    }
+
+    /// Headers of up to `limit` main chain blocks following the first
+    /// block of `locator` on the main chain. Empty if none of it is, as
+    /// happens when the peer follows another chain altogether.
+    pub fn headers_after(&self, locator: &[String], limit: usize) -> Result<Vec<BlockHeader>> {
+        let Some(height) = locator.iter().find_map(|hash| self.height_of(hash)) else {
+            return Ok(vec![]);
+        };
+        self.headers(height + 1, limit)
+    }
+
+    /// Checks that `headers` form a chain off a main chain block, each
+    /// with valid proof of work and miner signature, returning the height
+    /// of the last one. Transactions and timestamps are checked once the
+    /// blocks themselves arrive.
+    pub fn check_headers(&self, headers: &[BlockHeader]) -> Result<u64> {
+        let Some(first) = headers.first() else {
+            return self.chain_height();
+        };
+        let mut height =
+            self.height_of(first.previous_hash())
+                .ok_or_else(|| Error::UnknownParent {
+                    hash: first.previous_hash().clone(),
+                })?;
+        let mut previous = first.previous_hash().clone();
+        for header in headers {
+            let hash = header.hash();
+            if *header.previous_hash() != previous {
+                return Err(Error::UnknownParent {
+                    hash: header.previous_hash().clone(),
+                });
             }
-            let mut transaction_pool_lock = self
-                .transaction_pool
-                .lock()
-                .map_err(|e| Error::MutexPoison(e.to_string()))?;
-            transaction_pool_lock.push(transaction.clone());
-            Ok(transaction)
+            self.consensus.check_header(header)?;
+            header.verify_signature()?;
+            previous = hash;
+            height += 1;
         }
+        Ok(height)
     }
 
-    pub fn deposit_to_wallet(&mut self, recipient: &String, amount: f64) -> Result<Transaction> {
-        let (transaction, signature, v_key) = self.wallet.sign_transaction(recipient, amount).map_err(|e| Error::Ecdsa(e.to_string()))?;
-        self.add_transation_to_pool(transaction, signature, v_key)
+    /// Where initial sync stands.
+    pub fn sync_status(&self) -> Result<SyncStatus> {
+        let height = self.chain_height()?;
+        Ok(SyncStatus {
+            height,
+            target: self.sync_target.unwrap_or(height).max(height),
+            syncing: self.sync_target.is_some_and(|target| target > height),
+        })
+    }
+
+    /// Records the height initial sync is downloading blocks up to, or that
+    /// it stopped when `None`, and tells listeners how far along it is.
+    pub fn set_sync_target(&mut self, target: Option<u64>) -> Result<()> {
+        self.sync_target = target;
+        let status = self.sync_status()?;
+        self.publish(ChainEvent::SyncProgress {
+            height: status.height,
+            target: status.target,
+        });
+        Ok(())
     }
 
-    fn valid_proof(
+    /// Returns every confirmed transaction sent or received by `address`,
+    /// oldest first. Fails on a pruned chain, whose history is incomplete.
+    pub fn transactions_for_address(
         &self,
-        nonce: i32,
-        previous_hash: String,
-        transactions: Vec<Transaction>,
-    ) -> bool {
-        let zeros = vec!["0"; MINING_DIFFICULTY as usize].join("");
-        let guess_block = Block::new(nonce, previous_hash, transactions, 0, "none".into());
-        if let Ok(guess_json) = serde_json::to_string(&guess_block) {
-            let guess_hash = sha256::digest(guess_json);
-            guess_hash.starts_with(&zeros)
-        } else {
-            false
+        address: &Address,
+    ) -> Result<Vec<(Transaction, TxLocation)>> {
+        self.check_unpruned(0)?;
+        let chain = &self.chain;
+        let index = &self.index;
+        Ok(index
+            .locations_for(address)
+            .iter()
+            .filter_map(|location| {
+                let block = chain.get(location.height as usize)?;
+                let transaction = block.transactions().get(location.index)?;
+                Some((transaction.clone(), *location))
+            })
+            .collect())
+    }
+
+    /// Page `page` (from 0) of the confirmed transactions sent or received
+    /// by `address`, newest first, `page_size` to a page. Looked up in the
+    /// address index, so only the page's transactions are read. Fails if
+    /// the page reaches into pruned blocks.
+    pub fn history(&self, address: &Address, page: usize, page_size: usize) -> Result<HistoryPage> {
+        let chain = &self.chain;
+        let index = &self.index;
+        let locations = index.locations_for(address);
+        let tip = chain.len() as u64;
+        let entries = locations
+            .iter()
+            .rev()
+            .skip(page.saturating_mul(page_size))
+            .take(page_size)
+            .map(|location| {
+                self.check_unpruned(location.height)?;
+                let transaction = chain
+                    .get(location.height as usize)
+                    .and_then(|block| block.transactions().get(location.index))
+                    .ok_or_else(|| Error::UnknownBlock {
+                        hash: location.height.to_string(),
+                    })?;
+                Ok(HistoryEntry {
+                    txid: transaction.id(),
+                    direction: Direction::of(transaction, address),
+                    height: location.height,
+                    confirmations: tip - location.height,
+                    transaction: transaction.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(HistoryPage {
+            entries,
+            total: locations.len(),
+        })
+    }
+
+    /// Up to `limit` main-chain blocks matching `filter`, from
+    /// `from_height` up, see [`MAX_PAGE_LIMIT`]. Heights don't move as the
+    /// chain grows, so paging with [`BlockPage::next_height`] neither skips
+    /// nor repeats blocks unless a reorg replaces them. Pruned blocks are
+    /// listed by their headers, but filtering them by address fails.
+    pub fn list_blocks(
+        &self,
+        from_height: u64,
+        limit: usize,
+        filter: &ListFilter,
+    ) -> Result<BlockPage> {
+        if filter.address.is_some() {
+            self.check_unpruned(from_height)?;
+        }
+        let limit = query::page_limit(limit);
+        let mut blocks = vec![];
+        for (height, block) in self.chain.iter().enumerate().skip(from_height as usize) {
+            let height = height as u64;
+            if blocks.len() == limit {
+                return Ok(BlockPage {
+                    blocks,
+                    next_height: Some(height),
+                });
+            }
+            if filter.matches_block(block) {
+                blocks.push((height, block.clone()));
+            }
         }
+        Ok(BlockPage {
+            blocks,
+            next_height: None,
+        })
     }
 
-    fn proof_of_work(&mut self) -> Result<i32> {
-        let transaction_pool_lock = self
-            .transaction_pool
-            .lock()
-            .map_err(|e| Error::MutexPoison(e.to_string()))?;
-        let last_block = self.last_block().unwrap();
-        let previous_hash = last_block.hash();
-        let mut nonce = 0;
-        while !self.valid_proof(nonce, previous_hash.clone(), transaction_pool_lock.clone()) {
-            nonce += 1;
+    /// Up to `limit` confirmed transactions matching `filter`, in chain
+    /// order from `from` on, see [`MAX_PAGE_LIMIT`]. Filtering by address
+    /// reads the address index, so only matching transactions are visited.
+    /// Fails if `from` is in pruned blocks.
+    pub fn list_transactions(
+        &self,
+        from: TxLocation,
+        limit: usize,
+        filter: &ListFilter,
+    ) -> Result<TransactionPage> {
+        self.check_unpruned(from.height)?;
+        let limit = query::page_limit(limit);
+        let locations: Box<dyn Iterator<Item = TxLocation>> = match &filter.address {
+            Some(address) => {
+                let locations = self.index.locations_for(address);
+                let start = locations.partition_point(|location| *location < from);
+                Box::new(locations[start..].iter().copied())
+            }
+            None => Box::new(
+                self.chain
+                    .iter()
+                    .enumerate()
+                    .skip(from.height as usize)
+                    .flat_map(|(height, block)| {
+                        (0..block.transactions().len()).map(move |index| TxLocation {
+                            height: height as u64,
+                            index,
+                        })
+                    })
+                    .skip_while(move |location| *location < from),
+            ),
+        };
+        let mut transactions = vec![];
+        for location in locations {
+            if transactions.len() == limit {
+                return Ok(TransactionPage {
+                    transactions,
+                    next: Some(location),
+                });
+            }
+            let Some(block) = self.chain.get(location.height as usize) else {
+                continue;
+            };
+            if !filter.matches_time(block.timestamp()) {
+                continue;
+            }
+            if let Some(transaction) = block.transactions().get(location.index) {
+                transactions.push((transaction.clone(), location));
+            }
         }
-        Ok(nonce)
+        Ok(TransactionPage {
+            transactions,
+            next: None,
+        })
     }
 
-    pub fn mining(&mut self, miner: &String) -> bool {
-        if let Ok((transaction, signature, v_key)) =
-            self.wallet.sign_transaction(miner, MINING_REWARD)
-        {
-            if self
-                .add_transation_to_pool(transaction, signature, v_key)
-                .is_ok()
-            {
-                if let Ok(nonce) = self.proof_of_work() {
-                    self.add_block(nonce, miner).is_ok()
-                } else {
-                    false
+    /// Validates a signed transaction and adds it to the pool. One already
+    /// pending or confirmed is refused with [`Error::DuplicateTransaction`].
+    ///
+    /// ```
+    /// use aeonia::{
+    ///     blockchain::{Blockchain, Error},
+    ///     config::Config,
+    ///     network::Network,
+    ///     wallet::Wallet,
+    /// };
+    ///
+    /// let mut config = Config::default();
+    /// config.consensus.difficulty = 1;
+    /// let mut blockchain = Blockchain::with_config(&config).unwrap();
+    /// let wallet = Wallet::new(Network::Regtest).unwrap();
+    /// let payment = blockchain.request_from_faucet(wallet.address(), 1.0).unwrap();
+    /// let resent = blockchain.add_transation_to_pool(payment.clone());
+    /// assert!(matches!(resent, Err(Error::DuplicateTransaction { .. })));
+    ///
+    /// assert!(blockchain.mining(&wallet));
+    /// let resent = blockchain.add_transation_to_pool(payment);
+    /// assert!(matches!(resent, Err(Error::DuplicateTransaction { .. })));
+    /// ```
+    pub fn add_transation_to_pool(&mut self, transaction: Transaction) -> Result<Transaction> {
+        let id = transaction.id();
+        let result = self.try_add_transaction(transaction);
+        match &result {
+            Ok(_) => self.publish(ChainEvent::TransactionAccepted { id }),
+            Err(e) => self.publish(ChainEvent::TransactionRejected {
+                id,
+                reason: e.to_string(),
+            }),
+        }
+        result
+    }
+
+    /// Validates signed transactions and adds them to the pool, in order,
+    /// all or none: if one is rejected, the pool is left as it was and its
+    /// error returned. Later transactions may build on earlier ones, such
+    /// as payments with consecutive sequence numbers from one sender.
+    pub fn add_batch_to_pool(
+        &mut self,
+        transactions: Vec<Transaction>,
+    ) -> Result<Vec<Transaction>> {
+        let pool = self.transaction_pool.clone();
+        let mut added = Vec::with_capacity(transactions.len());
+        for transaction in transactions {
+            let id = transaction.id();
+            match self.try_add_transaction(transaction) {
+                Ok(transaction) => added.push(transaction),
+                Err(e) => {
+                    self.transaction_pool = pool;
+                    self.publish(ChainEvent::TransactionRejected {
+                        id,
+                        reason: e.to_string(),
+                    });
+                    return Err(e);
                 }
-            } else {
-                false
             }
+        }
+        for transaction in &added {
+            self.publish(ChainEvent::TransactionAccepted {
+                id: transaction.id(),
+            });
+        }
+        Ok(added)
+    }
+
+    /// Whether [`Blockchain::add_transation_to_pool`] would accept
+    /// `transaction` now, without adding it: the error it would fail with,
+    /// if any.
+    pub fn test_accept(&self, transaction: &Transaction) -> Result<()> {
+        let sender = self.check_admission(transaction)?;
+        let mut pool = self.transaction_pool.clone();
+        pool.set_confirmed_balance(sender, self.spendable_balance(&sender));
+        pool.insert(transaction.clone())?;
+        Ok(())
+    }
+
+    fn try_add_transaction(&mut self, transaction: Transaction) -> Result<Transaction> {
+        let sender = self.check_admission(&transaction)?;
+        let confirmed = self.spendable_balance(&sender);
+        self.transaction_pool
+            .set_confirmed_balance(sender, confirmed);
+        self.transaction_pool.insert(transaction.clone())?;
+        Ok(transaction)
+    }
+
+    /// Checks a transaction submitted to the pool against the chain,
+    /// returning its sender. What the pool itself checks is left to
+    /// [`Mempool::insert`].
+    fn check_admission(&self, transaction: &Transaction) -> Result<Address> {
+        self.check_unseen(transaction)?;
+        let Some(sender) = transaction.sender().copied() else {
+            return Err(Error::UnminedCoinbase {
+                id: transaction.id(),
+            });
+        };
+        transaction.check_memo()?;
+        transaction.check_amounts()?;
+        self.check_conditions(transaction)?;
+        transaction
+            .witness
+            .verify(&sender, &transaction.signing_bytes())?;
+        let confirmed = self.state.next_sequence(&sender);
+        let expected = self.next_sequence(&sender)?;
+        if transaction.sequence != expected {
+            if (confirmed..expected).contains(&transaction.sequence) {
+                return Err(Error::SequenceInUse {
+                    sender,
+                    sequence: transaction.sequence,
+                });
+            }
+            return Err(Error::InvalidSequence {
+                sender,
+                expected,
+                provided: transaction.sequence,
+            });
+        }
+        self.check_spend(transaction, None)?;
+        self.check_tokens(transaction, None)?;
+        self.check_stake(transaction, None)?;
+        self.consensus
+            .check_transaction(transaction, self.state.producers())?;
+        Ok(sender)
+    }
+
+    /// Fails with [`Error::DuplicateTransaction`] if a transaction with the
+    /// same id is pending or confirmed on the main chain, ahead of the
+    /// sequence checks it would otherwise fail less plainly. Transactions of
+    /// blocks pruned before the chain was loaded aren't indexed, so those
+    /// are left to the sequence checks.
+    fn check_unseen(&self, transaction: &Transaction) -> Result<()> {
+        let id = transaction.id();
+        if self.transaction_pool.contains(&id) || self.index.locate(&id).is_some() {
+            return Err(Error::DuplicateTransaction { id });
+        }
+        Ok(())
+    }
+
+    /// Replaces the pending transaction with the same sender and sequence
+    /// number by `transaction`, which must be signed and pay a higher fee.
+    /// Paying the sender back with a higher fee cancels the original.
+    /// Returns the transaction that was replaced.
+    pub fn replace_transaction(&mut self, transaction: Transaction) -> Result<Transaction> {
+        self.check_unseen(&transaction)?;
+        let Some(sender) = transaction.sender().copied() else {
+            return Err(Error::UnminedCoinbase {
+                id: transaction.id(),
+            });
+        };
+        transaction.check_memo()?;
+        transaction.check_amounts()?;
+        self.check_conditions(&transaction)?;
+        transaction
+            .witness
+            .verify(&sender, &transaction.signing_bytes())?;
+        self.check_spend(&transaction, Some(transaction.sequence))?;
+        self.check_tokens(&transaction, Some(transaction.sequence))?;
+        self.check_stake(&transaction, Some(transaction.sequence))?;
+        self.consensus
+            .check_transaction(&transaction, self.state.producers())?;
+        let confirmed = self.spendable_balance(&sender);
+        let id = transaction.id();
+        self.transaction_pool
+            .set_confirmed_balance(sender, confirmed);
+        let replaced = self.transaction_pool.replace(transaction)?;
+        self.publish(ChainEvent::TransactionReplaced {
+            replaced: replaced.id(),
+            by: id,
+        });
+        Ok(replaced)
+    }
+
+    /// Fails if `transaction` has expired for the next block, is still
+    /// timelocked or refunds an HTLC too early for it, or its script doesn't
+    /// pass in it, going by the current time.
+    fn check_conditions(&self, transaction: &Transaction) -> Result<()> {
+        let height = self.chain_height()? + 1;
+        transaction.check_expiry(height, self.clock.now())?;
+        transaction.check_timelock(height, self.clock.now())?;
+        transaction.check_htlc(height)?;
+        transaction.check_script(height, self.clock.now())
+    }
+
+    /// Sequence number the next payment from `sender` must carry: one past
+    /// its highest pending payment, or its next confirmed one if it has
+    /// none pending.
+    pub fn next_sequence(&self, sender: &Address) -> Result<u64> {
+        let confirmed = self.state.next_sequence(sender);
+        let pending = self.transaction_pool.next_sequence(sender);
+        Ok(pending.map_or(confirmed, |pending| pending.max(confirmed)))
+    }
+
+    /// Balance of `address`, with payments to it counted as confirmed once
+    /// they have `min_confirmations`, the tip's transactions having one. At
+    /// 0, pending payments count as confirmed too. Payments out of it count
+    /// as soon as they are mined.
+    pub fn balance(&self, address: &Address, min_confirmations: u64) -> Result<Balance> {
+        let unsettled = unsettled(&self.chain, min_confirmations);
+        if !unsettled.is_empty() {
+            self.check_unpruned(self.chain_height()? + 1 - unsettled.len() as u64)?;
+        }
+        let settled = self.state.settled_balance(address, as_blocks(unsettled));
+        let mut total = self.state.balance(address);
+        for transaction in self.transaction_pool.iter() {
+            if &transaction.recipient == address {
+                total += transaction.credited();
+            }
+            if transaction.sender() == Some(address) {
+                total -= transaction.amount + transaction.fee;
+            }
+        }
+        let confirmed = if min_confirmations == 0 {
+            total
         } else {
-            false
+            settled
+        };
+        Ok(Balance {
+            confirmed,
+            pending: total - confirmed,
+        })
+    }
+
+    /// What `address` can spend on an account chain before its pending
+    /// payments: its balance counting only payments to it with the pool's
+    /// [`MempoolConfig::min_confirmations`].
+    fn spendable_balance(&self, address: &Address) -> f64 {
+        let unsettled = unsettled(&self.chain, self.min_confirmations());
+        self.state.settled_balance(address, as_blocks(unsettled))
+    }
+
+    fn min_confirmations(&self) -> u64 {
+        self.transaction_pool.config().min_confirmations
+    }
+
+    /// Drops the transactions of the newly `connected` blocks from the pool
+    /// and brings what it takes as confirmed up to date with the chain.
+    fn sync_pool(&mut self, connected: &[Arc<Block>]) {
+        for block in connected {
+            self.transaction_pool.remove_confirmed(block.transactions());
+        }
+        let unsettled = unsettled(&self.chain, self.min_confirmations());
+        self.transaction_pool
+            .sync(&self.state, &as_blocks(unsettled));
+    }
+
+    /// Times the transaction `id` has been confirmed: 1 in the tip, 0 if it
+    /// isn't on the main chain.
+    pub fn confirmations(&self, id: &TxId) -> u64 {
+        match self.index.locate(id) {
+            Some(location) => self.chain.len() as u64 - location.height,
+            None => 0,
         }
     }
 
-    pub fn calculate_transactions_total(&mut self, address: String) -> Result<f64> {
-        let mut total_amount = 0.0;
-        let chain_lock = self
+    /// Fee a payment should pay to be confirmed within `target_blocks`
+    /// blocks, taken as 1 if 0. It is a percentile of the fees paid by the
+    /// transactions confirmed in the last [`FEE_HISTORY`] blocks and those
+    /// pending: the 90th for the next block, dropping as the target grows.
+    /// While the pool is full it is at least enough to evict the cheapest
+    /// pending transaction, since anything less isn't admitted at all.
+    /// 0 while nobody pays fees.
+    pub fn estimate_fee(&self, target_blocks: u64) -> f64 {
+        let mut fees: Vec<f64> = self
             .chain
-            .lock()
-            .map_err(|e| Error::MutexPoison(e.to_string()))?;
-        for block in chain_lock.iter() {
-            for transaction in block.transactions() {
-                if transaction.recipient == address {
-                    total_amount += transaction.amount;
-                }
-                if transaction.sender == address {
-                    total_amount -= transaction.amount;
-                }
+            .iter()
+            .rev()
+            .take(FEE_HISTORY)
+            .flat_map(|block| block.transactions())
+            .chain(self.transaction_pool.iter())
+            .filter(|transaction| !transaction.is_coinbase())
+            .map(|transaction| transaction.fee)
+            .collect();
+        fees.sort_by(f64::total_cmp);
+        let percentile = 0.9 / target_blocks.max(1) as f64;
+        let mut fee = match fees.len() {
+            0 => 0.0,
+            len => fees[((len - 1) as f64 * percentile).round() as usize],
+        };
+        if self.transaction_pool.len() >= self.transaction_pool.config().max_size {
+            let lowest = self
+                .transaction_pool
+                .iter()
+                .map(|transaction| transaction.fee)
+                .min_by(f64::total_cmp);
+            if let Some(lowest) = lowest {
+                fee = fee.max(lowest.next_up());
             }
         }
-        let transaction_pool_lock = self.transaction_pool.lock().map_err(|e| Error::MutexPoison(e.to_string()))?;
-        for transaction in transaction_pool_lock.iter() {
-            if transaction.recipient == address {
-                total_amount += transaction.amount;
+        fee
+    }
+
+    /// Reward the next block may pay its miner on top of fees.
+    pub fn current_reward(&self) -> Result<f64> {
+        let height = self.chain_height()?;
+        Ok(self
+            .consensus
+            .emission()
+            .capped_reward_at(height + 1, self.total_supply()?))
+    }
+
+    /// Every coin created so far: the genesis allocation plus the block
+    /// rewards the miners have claimed. Fees only move existing coins.
+    pub fn total_supply(&self) -> Result<f64> {
+        Ok(self.state.issued())
+    }
+
+    /// Coins in existence that have left the genesis allocation, i.e. the
+    /// total supply less what the faucet still holds.
+    pub fn circulating_supply(&self) -> Result<f64> {
+        Ok(self.total_supply()? - self.state.balance(self.wallet.address()))
+    }
+
+    /// Most coins that may ever exist, or `None` if there is no cap.
+    pub fn max_supply(&self) -> Option<f64> {
+        let emission = self.consensus.emission();
+        (emission.max_supply > 0.0).then_some(emission.max_supply)
+    }
+
+    /// Supply, how the coins are spread and how fast blocks and fees have
+    /// been coming, with the `top` largest holders (at most
+    /// [`MAX_TOP_HOLDERS`]). Worked out from the running balances and fee
+    /// totals, without walking the chain.
+    pub fn stats(&self, top: usize) -> Result<ChainStats> {
+        let height = self.chain_height()?;
+        let mut holders: Vec<_> = self
+            .state
+            .balances()
+            .into_iter()
+            .filter(|(_, balance)| *balance > BALANCE_EPSILON)
+            .collect();
+        let active_addresses = holders.len() as u64;
+        holders.sort_by(|(a, x), (b, y)| y.total_cmp(x).then(a.cmp(b)));
+        holders.truncate(top.min(MAX_TOP_HOLDERS));
+        let average_block_interval = match (self.chain.first(), self.chain.last()) {
+            (Some(genesis), Some(tip)) if height > 0 => {
+                (tip.timestamp() - genesis.timestamp()) as f64 / height as f64 / 1e9
             }
-            if transaction.sender == address {
-                total_amount -= transaction.amount;
+            _ => 0.0,
+        };
+        Ok(ChainStats {
+            height,
+            total_supply: self.total_supply()?,
+            circulating_supply: self.circulating_supply()?,
+            active_addresses,
+            top_holders: holders,
+            average_block_interval,
+            transactions: self.fees.transactions(),
+            average_fee: self.fees.average(),
+        })
+    }
+
+    /// Confirmed balance of every address that has appeared on the main
+    /// chain, ignoring the pool.
+    pub fn confirmed_balances(&self) -> Result<Vec<(Address, f64)>> {
+        Ok(self.state.balances())
+    }
+
+    /// Pending transactions that can't be confirmed together with the
+    /// pending transaction `id`, see [`Mempool::conflicts_for`].
+    pub fn conflicts_for(&self, id: &TxId) -> Result<Vec<TxId>> {
+        Ok(self.transaction_pool.conflicts_for(id))
+    }
+
+    pub fn ledger(&self) -> Ledger {
+        self.ledger
+    }
+
+    /// Outputs `address` can spend once its pending payments confirm,
+    /// including change from those payments. Empty on an account chain.
+    pub fn unspent_outputs(&self, address: &Address) -> Result<Vec<(OutPoint, TxOut)>> {
+        let pending = self.pending_transactions();
+        Ok(pending_view(&self.utxos, &pending, None).unspent_for(address))
+    }
+
+    /// Picks outputs of `sender` not spent by pending payments that cover
+    /// `amount`, for a new payment on a UTXO chain. Only outputs with the
+    /// pool's [`MempoolConfig::min_confirmations`] are picked. Always empty on
+    /// an account chain, where payments have no inputs.
+    pub fn select_inputs(&self, sender: &Address, amount: f64) -> Result<Vec<OutPoint>> {
+        if self.ledger == Ledger::Account {
+            return Ok(vec![]);
+        }
+        let pending = self.pending_transactions();
+        let min_confirmations = self.min_confirmations();
+        // Transactions whose outputs don't have enough confirmations yet.
+        let mut young: HashSet<TxId> = unsettled(&self.chain, min_confirmations)
+            .iter()
+            .flat_map(|block| block.transactions())
+            .map(Transaction::id)
+            .collect();
+        if min_confirmations > 0 {
+            young.extend(pending.iter().map(Transaction::id));
+        }
+        pending_view(&self.utxos, &pending, None)
+            .select(sender, amount, |outpoint| !young.contains(&outpoint.txid))
+    }
+
+    /// On a UTXO chain, checks that `transaction` can spend its inputs after
+    /// the pending transactions, leaving out the one from the same sender
+    /// with sequence `replacing`, if given, and that every input has the
+    /// pool's [`MempoolConfig::min_confirmations`].
+    fn check_spend(&self, transaction: &Transaction, replacing: Option<u64>) -> Result<()> {
+        if self.ledger == Ledger::Account {
+            return Ok(());
+        }
+        let pending = self.pending_transactions();
+        let replaced = replacing.zip(transaction.sender().copied());
+        pending_view(&self.utxos, &pending, replaced).spend(transaction)?;
+        let required = self.min_confirmations();
+        for input in &transaction.inputs {
+            let confirmations = self.confirmations(&input.txid);
+            if confirmations < required {
+                return Err(Error::Unconfirmed {
+                    output: input.clone(),
+                    confirmations,
+                    required,
+                });
             }
         }
-        Ok(total_amount)
+        Ok(())
+    }
+
+    /// Checks that `transaction` issues a valid token or transfers no more of
+    /// one than its sender holds net of its pending transfers, leaving out
+    /// the one with sequence `replacing`, if given.
+    fn check_tokens(&self, transaction: &Transaction, replacing: Option<u64>) -> Result<()> {
+        let pending = match (&transaction.token, transaction.sender()) {
+            (Some(TokenOp::Transfer { id, .. }), Some(sender)) => self
+                .transaction_pool
+                .iter()
+                .filter(|t| t.sender() == Some(sender) && Some(t.sequence) != replacing)
+                .filter_map(|t| match &t.token {
+                    Some(TokenOp::Transfer { id: other, amount }) if other == id => Some(*amount),
+                    _ => None,
+                })
+                .sum(),
+            _ => 0,
+        };
+        self.state.tokens().check(transaction, pending)
+    }
+
+    /// Checks that `transaction` stakes, unstakes or slashes only what it
+    /// may, with no more unstaked than its sender has staked net of its
+    /// pending unstakes, leaving out the one with sequence `replacing`, if
+    /// given.
+    fn check_stake(&self, transaction: &Transaction, replacing: Option<u64>) -> Result<()> {
+        self.check_ledger_stakes(transaction)?;
+        let pending = match (&transaction.stake, transaction.sender()) {
+            (Some(StakeOp::Unstake { .. }), Some(sender)) => self
+                .transaction_pool
+                .iter()
+                .filter(|t| t.sender() == Some(sender) && Some(t.sequence) != replacing)
+                .filter_map(|t| match &t.stake {
+                    Some(StakeOp::Unstake { amount }) => Some(*amount),
+                    _ => None,
+                })
+                .sum(),
+            _ => 0.0,
+        };
+        self.state.stakes().check(transaction, pending)
+    }
+
+    /// Fails if `transaction` touches stake on a [`Ledger::Utxo`] chain,
+    /// whose outputs can't be locked up as stake.
+    fn check_ledger_stakes(&self, transaction: &Transaction) -> Result<()> {
+        if self.ledger == Ledger::Utxo && transaction.stake.is_some() {
+            return Err(Error::InvalidStake {
+                id: transaction.id(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Confirmed stake of `address`.
+    pub fn stake(&self, address: &Address) -> f64 {
+        self.state.stakes().stake(address)
+    }
+
+    /// Every validator and its confirmed stake, sorted by address.
+    pub fn stakes(&self) -> Vec<(Address, f64)> {
+        self.state.stakes().stakes()
+    }
+
+    /// The authorities taking turns producing blocks as of the tip, sorted
+    /// by address. Empty unless the consensus engine has any.
+    pub fn authorities(&self) -> Vec<Address> {
+        self.consensus.authorities(self.state.producers())
+    }
+
+    /// Evidence that the blocks `first` and `second`, on the main chain or a
+    /// side branch, were signed by the same producer on the same parent.
+    pub fn double_sign(&self, first: &str, second: &str) -> Result<DoubleSign> {
+        let find = |hash: &str| {
+            self.block_by_hash(hash)
+                .or_else(|| self.side_branches.get(hash))
+                .ok_or_else(|| Error::UnknownBlock {
+                    hash: hash.to_string(),
+                })
+        };
+        let (first, second) = (find(first)?, find(second)?);
+        Ok(DoubleSign::new(&first, &second))
+    }
+
+    /// Issued token `id`, if it is confirmed.
+    pub fn token(&self, id: &TokenId) -> Option<Token> {
+        self.state.tokens().token(id).cloned()
+    }
+
+    /// Confirmed amount of token `id` held by `address`.
+    pub fn token_balance(&self, id: &TokenId, address: &Address) -> u64 {
+        self.state.tokens().balance(id, address)
+    }
+
+    /// Every token `address` holds some of, confirmed, sorted by id.
+    pub fn token_balances(&self, address: &Address) -> Vec<(TokenId, u64)> {
+        self.state.tokens().balances_of(address)
+    }
+
+    /// Pays `amount` out of the faucet account funded in genesis, subject to
+    /// the faucet's per-request cap and per-recipient cooldown.
+    pub fn request_from_faucet(&mut self, recipient: &Address, amount: f64) -> Result<Transaction> {
+        let now = self.clock.now();
+        self.faucet.check(recipient, amount, now)?;
+        let available = self.faucet_balance()?;
+        if available < amount {
+            return Err(Error::FaucetDrained { available });
+        }
+        let sequence = self.next_sequence(self.wallet.address())?;
+        let inputs = self.select_inputs(self.wallet.address(), amount)?;
+        let transaction =
+            self.wallet
+                .sign_transaction(recipient, amount, 0.0, sequence, inputs, vec![])?;
+        let transaction = self.add_transation_to_pool(transaction)?;
+        self.faucet.record_payout(*recipient, now);
+        Ok(transaction)
+    }
+
+    /// What the faucet can still pay out, net of pending payouts.
+    pub fn faucet_balance(&self) -> Result<f64> {
+        let address = *self.wallet.address();
+        self.calculate_transactions_total(&address)
+    }
+
+    /// Mines the [`Blockchain::block_candidate`] paying `miner` and signs it
+    /// with the miner's key. Expired transactions are left out and dropped
+    /// from the pool once the block is in.
+    pub fn mining(&mut self, miner: &Wallet) -> bool {
+        self.block_candidate(miner.address())
+            .and_then(|block| {
+                Mined::new(block, self.consensus.as_ref(), miner, &CancelToken::new())
+            })
+            .and_then(|mined| self.connect_mined(mined))
+            .is_ok()
+    }
+
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    /// Registers a new listener for chain and pool events.
+    pub fn subscribe(&mut self) -> Receiver<ChainEvent> {
+        self.events.subscribe()
+    }
+
+    /// Tells every listener the node is going away, closes the event bus
+    /// and returns the final snapshot to persist.
+    pub fn shutdown(&mut self) -> Result<Snapshot> {
+        self.events.publish(ChainEvent::Shutdown);
+        self.events.close();
+        self.snapshot()
+    }
+
+    fn publish(&mut self, event: ChainEvent) {
+        self.events.publish(event);
+    }
+
+    pub fn pending_transaction_count(&self) -> usize {
+        self.transaction_pool.len()
+    }
+
+    /// Rebuilds a block announced as `compact` from the pool and `extra`,
+    /// the transactions fetched for it.
+    pub fn reconstruct_block(
+        &self,
+        compact: &CompactBlock,
+        extra: &[Transaction],
+    ) -> Result<Reconstruction> {
+        compact.reconstruct(|id| {
+            extra
+                .iter()
+                .find(|transaction| &transaction.id() == id)
+                .or_else(|| self.transaction_pool.get(id))
+                .cloned()
+        })
+    }
+
+    /// The transaction `id` if it is still waiting in the pool.
+    pub fn pending_transaction(&self, id: &TxId) -> Option<Transaction> {
+        self.transaction_pool.get(id).cloned()
+    }
+
+    /// Transactions waiting in the pool, in the order they would be mined.
+    pub fn pending_transactions(&self) -> Vec<Transaction> {
+        self.transaction_pool.iter_ready().cloned().collect()
+    }
+
+    /// The pool, for inspecting what is pending and why.
+    pub fn mempool(&self) -> &Mempool {
+        &self.transaction_pool
+    }
+
+    /// What `address` will hold once the pool confirms, see
+    /// [`Balance::total`].
+    pub fn calculate_transactions_total(&self, address: &Address) -> Result<f64> {
+        Ok(self.balance(address, 0)?.total())
+    }
+
+    /// Rebuilds the balance state from the main chain and checks that the
+    /// incrementally maintained state agrees with it. Not possible once
+    /// blocks have been pruned.
+    pub fn verify_state_against_chain(&self) -> Result<()> {
+        self.check_unpruned(0)?;
+        let rebuilt = AccountState::build(self.chain.iter().map(|b| b.as_ref()));
+        match self.state.first_difference(&rebuilt) {
+            Some(address) => Err(Error::StateMismatch { address }),
+            None => Ok(()),
+        }
     }
 }
 
 impl Default for Blockchain {
     fn default() -> Self {
-        match Blockchain::new(0x00) {
+        match Blockchain::new(Network::default()) {
             Ok(blockchain) => blockchain,
             Err(e) => {
                 let mut retries = 3;
                 while retries >= 0 {
-                    if let Ok(blockchain) = Blockchain::new(0x00) {
+                    if let Ok(blockchain) = Blockchain::new(Network::default()) {
                         return blockchain;
                     } else {
                         retries -= 1;
@@ -230,28 +2492,66 @@ impl Default for Blockchain {
 
 impl std::fmt::Display for Blockchain {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let chain_lock = self.chain.lock().unwrap();
-        for block in chain_lock.iter() {
-            writeln!(f, "{}", vec!["="; 100].join(""))?;
-            writeln!(f, "\tnonce: {}", block.nonce())?;
-            writeln!(f, "\tprevious_hash: {}", block.previous_hash())?;
-            writeln!(f, "\ttimestamp: {}", block.timestamp())?;
-            writeln!(f, "\ttransactions: {:?}", block.transactions())?;
-            writeln!(f, "\tminer: {:?}", block.miner())?;
-            writeln!(f, "{}", vec!["="; 100].join(""))?;
-        }
-        writeln!(f)?;
-        if let Ok(transaction_pool) = self.transaction_pool.lock() {
-            writeln!(f, "transaction pool")?;
-            for transaction in transaction_pool.iter() {
-                writeln!(f, "{}", vec!["-"; 50].join(""))?;
-                writeln!(f, "\tsender: {}", transaction.sender)?;
-                writeln!(f, "\trecipient: {}", transaction.recipient)?;
-                writeln!(f, "\tamount: {}", transaction.amount)?;
-                writeln!(f, "{}", vec!["-"; 50].join(""))?;
-            }
-        };
-        writeln!(f, "end\n")?;
+        for (height, block) in self.chain.iter().enumerate() {
+            writeln!(f, "#{} {}", height, block)?;
+        }
+        writeln!(f, "total work {}", self.total_work())?;
+        let pending = self.pending_transactions();
+        writeln!(f, "{} pending transactions", pending.len())?;
+        for transaction in &pending {
+            writeln!(f, "  {}", transaction)?;
+        }
         Ok(())
     }
 }
+
+/// `utxos` after every pending transaction that can still spend its inputs,
+/// skipping the one from `replaced`'s sender and sequence, if given.
+fn pending_view<'a>(
+    utxos: &'a UtxoSet,
+    pending: &[Transaction],
+    replaced: Option<(u64, Address)>,
+) -> UtxoView<'a> {
+    let mut view = UtxoView::new(utxos);
+    for transaction in pending {
+        let is_replaced = replaced.is_some_and(|(sequence, sender)| {
+            transaction.sender() == Some(&sender) && transaction.sequence == sequence
+        });
+        if !is_replaced {
+            // Transactions orphaned by a replacement are skipped when mining.
+            let _ = view.spend(transaction);
+        }
+    }
+    view
+}
+
+/// The blocks at the end of `chain` with fewer than `min_confirmations`.
+fn unsettled<B: Borrow<Block>>(chain: &[B], min_confirmations: u64) -> &[B] {
+    let count = (min_confirmations.saturating_sub(1) as usize).min(chain.len());
+    &chain[chain.len() - count..]
+}
+
+fn as_blocks<B: Borrow<Block>>(blocks: &[B]) -> Vec<&Block> {
+    blocks.iter().map(Borrow::borrow).collect()
+}
+
+fn confirmed_count(block: &Block) -> u64 {
+    block
+        .transactions()
+        .iter()
+        .filter(|t| !t.is_coinbase())
+        .count() as u64
+}
+
+/// Fails if `genesis` records another proof of work hash than `config`
+/// asks for, which would fail every block after it.
+fn check_pow_hash(genesis: &Block, config: &Config) -> Result<()> {
+    let configured = config.consensus.pow_hash;
+    match HashAlgorithm::of_genesis(genesis).transpose()? {
+        Some(genesis) if genesis != configured => Err(Error::HashAlgorithmMismatch {
+            genesis,
+            configured,
+        }),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,244 @@
+use proptest::prelude::*;
+
+use super::{state::BALANCE_EPSILON, Blockchain, Sha256Hasher};
+use crate::{
+    testing::{self, TestChain},
+    wallet::Wallet,
+};
+
+const SEED: u64 = 42;
+const WALLETS: usize = 4;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Fund {
+        to: usize,
+        amount: f64,
+    },
+    Pay {
+        from: usize,
+        to: usize,
+        amount: f64,
+        fee: f64,
+    },
+    Mine {
+        miner: usize,
+    },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (0..WALLETS, 1..=1_000u32).prop_map(|(to, tenths)| Op::Fund {
+            to,
+            amount: f64::from(tenths) / 10.0,
+        }),
+        (0..WALLETS, 0..WALLETS, 1..=500u32, 0..=10u32).prop_map(|(from, to, tenths, fee)| {
+            Op::Pay {
+                from,
+                to,
+                amount: f64::from(tenths) / 10.0,
+                fee: f64::from(fee) / 10.0,
+            }
+        }),
+        (0..WALLETS).prop_map(|miner| Op::Mine { miner }),
+    ]
+}
+
+fn wallets() -> Vec<Wallet> {
+    (0..WALLETS as u64).map(testing::wallet).collect()
+}
+
+/// Applies `op`, ignoring payments the chain rejects, which random histories
+/// are full of.
+fn apply(chain: &mut TestChain, wallets: &mut [Wallet], op: &Op) {
+    match *op {
+        Op::Fund { to, amount } => {
+            let recipient = *wallets[to].address();
+            let _ = chain.blockchain.request_from_faucet(&recipient, amount);
+        }
+        Op::Pay {
+            from,
+            to,
+            amount,
+            fee,
+        } => {
+            let recipient = *wallets[to].address();
+            let _ = chain.pay(&mut wallets[from], &recipient, amount, fee);
+        }
+        Op::Mine { miner } => {
+            chain.mine(&wallets[miner]);
+        }
+    }
+}
+
+/// Every block links to the one before it and meets its target, no
+/// balance is negative, the balances add up to what coinbases created, and
+/// the cached state and running stats match a replay of the chain.
+fn check_invariants(blockchain: &Blockchain) -> Result<(), TestCaseError> {
+    let height = blockchain.chain_height().unwrap();
+    let mut created = 0.0;
+    let mut fees = vec![];
+    for h in 0..=height {
+        let block = blockchain.block_by_height(h).unwrap();
+        if h > 0 {
+            let parent = blockchain.block_by_height(h - 1).unwrap();
+            prop_assert_eq!(block.previous_hash(), &parent.hash());
+            prop_assert!(block.meets_target(&Sha256Hasher));
+        }
+        // Fees leave their payers and come back through the coinbase, so
+        // only the rest of a coinbase is new money.
+        created += block
+            .transactions()
+            .iter()
+            .map(|t| if t.is_coinbase() { t.amount } else { -t.fee })
+            .sum::<f64>();
+        fees.extend(
+            block
+                .transactions()
+                .iter()
+                .filter(|t| !t.is_coinbase())
+                .map(|t| t.fee),
+        );
+    }
+    let balances = blockchain.confirmed_balances().unwrap();
+    for (address, balance) in &balances {
+        prop_assert!(
+            *balance >= -BALANCE_EPSILON,
+            "{} has balance {}",
+            address,
+            balance
+        );
+    }
+    let total: f64 = balances.iter().map(|(_, balance)| balance).sum();
+    prop_assert!(
+        (total - created).abs() <= created * 1e-12,
+        "balances add up to {} but {} was created",
+        total,
+        created
+    );
+    prop_assert!(blockchain.verify_state_against_chain().is_ok());
+    let stats = blockchain.stats(WALLETS).unwrap();
+    prop_assert_eq!(stats.transactions, fees.len() as u64);
+    let average_fee = fees.iter().sum::<f64>() / fees.len().max(1) as f64;
+    prop_assert!((stats.average_fee - average_fee).abs() <= BALANCE_EPSILON);
+    let holding = balances
+        .iter()
+        .filter(|(_, b)| *b > BALANCE_EPSILON)
+        .count();
+    prop_assert_eq!(stats.active_addresses, holding as u64);
+    prop_assert!(stats
+        .top_holders
+        .windows(2)
+        .all(|pair| pair[0].1 >= pair[1].1));
+    // The pool's running totals match summing its transactions.
+    let pool = blockchain.mempool();
+    for transaction in pool.iter() {
+        let Some(sender) = transaction.sender() else {
+            continue;
+        };
+        let outflow: f64 = pool
+            .iter()
+            .filter(|t| t.sender() == Some(sender))
+            .map(|t| t.amount + t.fee)
+            .sum();
+        prop_assert!((pool.pending_outflow(sender) - outflow).abs() <= BALANCE_EPSILON);
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn random_histories_keep_invariants(ops in prop::collection::vec(op(), 1..40)) {
+        let mut chain = TestChain::new(SEED);
+        let mut wallets = wallets();
+        for op in &ops {
+            apply(&mut chain, &mut wallets, op);
+            if matches!(op, Op::Mine { .. }) {
+                check_invariants(&chain.blockchain)?;
+            }
+        }
+        chain.mine(&wallets[0]);
+        check_invariants(&chain.blockchain)?;
+        prop_assert_eq!(chain.blockchain.pending_transaction_count(), 0);
+    }
+
+    #[test]
+    fn disconnecting_and_reconnecting_the_tip_round_trips(
+        ops in prop::collection::vec(op(), 0..30),
+    ) {
+        let mut chain = TestChain::new(SEED);
+        let mut wallets = wallets();
+        for op in &ops {
+            apply(&mut chain, &mut wallets, op);
+        }
+        chain.mine(&wallets[0]);
+        let height = chain.blockchain.chain_height().unwrap();
+        let balances = chain.blockchain.confirmed_balances().unwrap();
+        let tip = chain.blockchain.disconnect_tip().unwrap();
+        prop_assert_eq!(chain.blockchain.chain_height().unwrap(), height - 1);
+        check_invariants(&chain.blockchain)?;
+        chain.blockchain.connect_block(tip.as_ref().clone()).unwrap();
+        prop_assert_eq!(chain.blockchain.last_block().unwrap().hash(), tip.hash());
+        prop_assert_eq!(chain.blockchain.confirmed_balances().unwrap().len(), balances.len());
+        check_invariants(&chain.blockchain)?;
+    }
+
+    #[test]
+    fn reorgs_onto_a_longer_branch_keep_invariants(
+        prefix in prop::collection::vec(op(), 0..15),
+        ours in prop::collection::vec(op(), 0..10),
+        theirs in prop::collection::vec(op(), 0..10),
+        lead in 1..3u64,
+    ) {
+        let mut local = TestChain::new(SEED);
+        let mut remote = TestChain::new(SEED);
+        let mut local_wallets = wallets();
+        let mut remote_wallets = wallets();
+        for op in &prefix {
+            apply(&mut local, &mut local_wallets, op);
+            apply(&mut remote, &mut remote_wallets, op);
+        }
+        let fork_height = local.blockchain.chain_height().unwrap();
+        prop_assert_eq!(
+            local.blockchain.last_block().unwrap().hash(),
+            remote.blockchain.last_block().unwrap().hash()
+        );
+
+        // Different miners make sure the branches really diverge.
+        local.mine(&local_wallets[0]);
+        remote.mine(&remote_wallets[1]);
+        for op in &ours {
+            apply(&mut local, &mut local_wallets, op);
+        }
+        for op in &theirs {
+            apply(&mut remote, &mut remote_wallets, op);
+        }
+        let target = local.blockchain.chain_height().unwrap() + lead;
+        while remote.blockchain.chain_height().unwrap() < target {
+            remote.mine(&remote_wallets[1]);
+        }
+
+        for height in fork_height + 1..=remote.blockchain.chain_height().unwrap() {
+            let block = remote.blockchain.block_by_height(height).unwrap();
+            prop_assert!(local.blockchain.receive_block(block.as_ref().clone()).is_ok());
+        }
+        prop_assert_eq!(
+            local.blockchain.last_block().unwrap().hash(),
+            remote.blockchain.last_block().unwrap().hash()
+        );
+        check_invariants(&local.blockchain)?;
+        let mut local_balances = local.blockchain.confirmed_balances().unwrap();
+        let mut remote_balances = remote.blockchain.confirmed_balances().unwrap();
+        local_balances.retain(|(_, balance)| balance.abs() > BALANCE_EPSILON);
+        remote_balances.retain(|(_, balance)| balance.abs() > BALANCE_EPSILON);
+        local_balances.sort_by_key(|(address, _)| *address);
+        remote_balances.sort_by_key(|(address, _)| *address);
+        prop_assert_eq!(local_balances.len(), remote_balances.len());
+        for ((a, x), (b, y)) in local_balances.iter().zip(&remote_balances) {
+            prop_assert_eq!(a, b);
+            prop_assert!((x - y).abs() <= BALANCE_EPSILON, "{} has {} and {}", a, x, y);
+        }
+    }
+}
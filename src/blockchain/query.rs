@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use super::{block::Block, index::TxLocation, transaction::Transaction};
+use crate::address::Address;
+
+/// Most blocks or transactions a page holds, whatever limit is asked for.
+pub const MAX_PAGE_LIMIT: usize = 1000;
+
+/// Narrows what [`Blockchain::list_blocks`](super::Blockchain::list_blocks)
+/// and [`Blockchain::list_transactions`](super::Blockchain::list_transactions)
+/// return. The default matches everything.
+///
+/// ```
+/// use aeonia::{
+///     blockchain::{Blockchain, ListFilter, TxLocation},
+///     config::Config,
+/// };
+///
+/// let blockchain = Blockchain::with_config(&Config::default()).unwrap();
+/// let mut from = TxLocation::default();
+/// let mut listed = vec![];
+/// loop {
+///     let page = blockchain.list_transactions(from, 1, &ListFilter::default()).unwrap();
+///     listed.extend(page.transactions);
+///     match page.next {
+///         Some(next) => from = next,
+///         None => break,
+///     }
+/// }
+/// let genesis = blockchain.block_by_height(0).unwrap();
+/// assert_eq!(listed.len(), genesis.transactions().len());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ListFilter {
+    /// Only blocks stamped at or after this, in nanoseconds since the
+    /// epoch, and the transactions in them.
+    pub since: Option<i64>,
+    /// Only blocks stamped before this, and the transactions in them.
+    pub until: Option<i64>,
+    /// Only transactions sent or received by this address, and the blocks
+    /// holding one or mined by it.
+    pub address: Option<Address>,
+}
+
+impl ListFilter {
+    pub(super) fn matches_time(&self, timestamp: i64) -> bool {
+        self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp < until)
+    }
+
+    pub(super) fn matches_block(&self, block: &Block) -> bool {
+        self.matches_time(block.timestamp())
+            && self.address.is_none_or(|address| {
+                *block.miner() == address
+                    || block
+                        .transactions()
+                        .iter()
+                        .any(|transaction| involves(transaction, &address))
+            })
+    }
+}
+
+fn involves(transaction: &Transaction, address: &Address) -> bool {
+    transaction.sender() == Some(address) || transaction.recipient == *address
+}
+
+/// The page size to use for a requested `limit`: 0 takes the maximum.
+pub(super) fn page_limit(limit: usize) -> usize {
+    match limit {
+        0 => MAX_PAGE_LIMIT,
+        limit => limit.min(MAX_PAGE_LIMIT),
+    }
+}
+
+/// One page of main-chain blocks, oldest first.
+#[derive(Clone, Debug)]
+pub struct BlockPage {
+    /// Each block after its height.
+    pub blocks: Vec<(u64, Arc<Block>)>,
+    /// Height to list the next page from, `None` once the tip was reached.
+    pub next_height: Option<u64>,
+}
+
+/// One page of confirmed transactions, in chain order.
+#[derive(Clone, Debug)]
+pub struct TransactionPage {
+    pub transactions: Vec<(Transaction, TxLocation)>,
+    /// Where to list the next page from, `None` once the tip was reached.
+    pub next: Option<TxLocation>,
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use super::{block::BlockHeader, merkle::MerkleProof, transaction::TxId, Error, Result};
+
+/// Evidence that a transaction was confirmed: where it sits in the chain
+/// and a Merkle proof tying it to its block's header. It serializes to
+/// JSON, so a payer can hand it to the recipient, who checks it against a
+/// header they trust, from their own node or a
+/// [`LightClient`](super::LightClient), without the block itself.
+///
+/// ```
+/// use aeonia::{blockchain::Blockchain, config::Config};
+///
+/// let blockchain = Blockchain::with_config(&Config::default()).unwrap();
+/// let genesis = blockchain.block_by_height(0).unwrap();
+/// let coinbase = genesis.transactions()[0].id();
+///
+/// let receipt = blockchain.receipt(&coinbase).unwrap().unwrap();
+/// let json = serde_json::to_string(&receipt).unwrap();
+/// let received: aeonia::blockchain::Receipt = serde_json::from_str(&json).unwrap();
+/// assert_eq!(received.height, 0);
+/// assert!(received.verify(genesis.header()).is_ok());
+///
+/// let truncated = json.replace(&coinbase.to_string(), "ab");
+/// assert!(serde_json::from_str::<aeonia::blockchain::Receipt>(&truncated).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub txid: TxId,
+    pub block_hash: String,
+    pub height: u64,
+    /// Position of the transaction in its block.
+    pub index: u64,
+    pub merkle_root: String,
+    pub proof: MerkleProof,
+}
+
+impl Receipt {
+    /// Checks that the receipt is for the block of `header` and that its
+    /// proof leads from the transaction to the header's Merkle root.
+    pub fn verify(&self, header: &BlockHeader) -> Result<()> {
+        if header.hash() != self.block_hash
+            || header.merkle_root() != self.merkle_root
+            || self.proof.index() != self.index
+            || self.proof.root(&self.txid) != self.merkle_root
+        {
+            return Err(Error::InvalidMerkleProof {
+                id: self.txid.clone(),
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,483 @@
+//! Conditions a payment must meet before a block may confirm it, written in
+//! a small stack language.
+//!
+//! A script runs once for each block that tries to include its transaction,
+//! starting with the transaction's arguments on the stack, first argument at
+//! the bottom. It passes if it runs to the end and leaves a non-zero number
+//! on top. There are no loops, every instruction costs gas, and the only
+//! inputs are the arguments and the height and timestamp of the block, so a
+//! script gives the same answer on every node.
+//!
+//! Scripts are written as space separated instructions, numbers in decimal
+//! and byte strings in hex after `0x`. This one lets the payment through
+//! once someone supplies the preimage of a hash, or unconditionally from
+//! height 1000 on:
+//!
+//! ```text
+//! HEIGHT 1000 GE IF 1 ELSE SHA256 0x9f86...0a08 EQUAL ENDIF
+//! ```
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::codec::{self, Decode, Encode, Reader};
+
+/// Most gas a script may use.
+pub const MAX_GAS: u64 = 1_000;
+/// Most instructions in a script.
+pub const MAX_OPS: usize = 100;
+/// Most items on the stack at once, arguments included.
+const MAX_STACK: usize = 32;
+/// Longest byte string a script may push or hash.
+const MAX_ITEM_LEN: usize = 128;
+
+/// Why a script didn't pass.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ScriptError {
+    #[error("script has {len} instructions, over the limit of {max}")]
+    TooLong { len: usize, max: usize },
+    #[error("out of gas after {used} of {limit}")]
+    OutOfGas { used: u64, limit: u64 },
+    #[error("{op} needs more items than are on the stack")]
+    StackUnderflow { op: &'static str },
+    #[error("stack is over the limit of {max} items")]
+    StackOverflow { max: usize },
+    #[error("byte string of {len} bytes is over the limit of {max}")]
+    ItemTooLong { len: usize, max: usize },
+    #[error("{op} expects {expected}")]
+    TypeMismatch {
+        op: &'static str,
+        expected: &'static str,
+    },
+    #[error("{op} overflowed")]
+    Overflow { op: &'static str },
+    #[error("unbalanced IF, ELSE or ENDIF")]
+    UnbalancedIf,
+    #[error("VERIFY failed")]
+    VerifyFailed,
+    #[error("script finished without a non-zero number on top of the stack")]
+    Rejected,
+}
+
+/// One instruction of a [`Script`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Pushes a byte string.
+    Bytes(Vec<u8>),
+    /// Pushes a number.
+    Num(i64),
+    Dup,
+    Drop,
+    Swap,
+    /// Replaces the top two items with 1 if they are equal, 0 otherwise.
+    Equal,
+    /// Fails unless the top item is a non-zero number, which it pops.
+    Verify,
+    Not,
+    And,
+    Or,
+    Add,
+    Sub,
+    /// Replaces `a b` with 1 if `a < b`, 0 otherwise.
+    Lt,
+    /// Replaces `a b` with 1 if `a >= b`, 0 otherwise.
+    Ge,
+    /// Replaces a byte string with its SHA-256 hash.
+    Sha256,
+    /// Pushes the height of the block including the transaction.
+    Height,
+    /// Pushes the timestamp of the block including the transaction, in
+    /// nanoseconds since the Unix epoch.
+    Time,
+    /// Pops a number and runs what follows up to the matching `ELSE` or
+    /// `ENDIF` only if it is non-zero.
+    If,
+    Else,
+    EndIf,
+}
+
+impl Op {
+    /// Gas the instruction costs to run.
+    fn gas(&self) -> u64 {
+        match self {
+            Op::Bytes(bytes) => 1 + bytes.len() as u64 / 32,
+            Op::Sha256 => 20,
+            _ => 1,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Op::Bytes(_) => "PUSH",
+            Op::Num(_) => "NUM",
+            Op::Dup => "DUP",
+            Op::Drop => "DROP",
+            Op::Swap => "SWAP",
+            Op::Equal => "EQUAL",
+            Op::Verify => "VERIFY",
+            Op::Not => "NOT",
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Add => "ADD",
+            Op::Sub => "SUB",
+            Op::Lt => "LT",
+            Op::Ge => "GE",
+            Op::Sha256 => "SHA256",
+            Op::Height => "HEIGHT",
+            Op::Time => "TIME",
+            Op::If => "IF",
+            Op::Else => "ELSE",
+            Op::EndIf => "ENDIF",
+        }
+    }
+
+    /// Instructions without operands, in the order of their encoding tags
+    /// from 2 on.
+    const PLAIN: [Op; 18] = [
+        Op::Dup,
+        Op::Drop,
+        Op::Swap,
+        Op::Equal,
+        Op::Verify,
+        Op::Not,
+        Op::And,
+        Op::Or,
+        Op::Add,
+        Op::Sub,
+        Op::Lt,
+        Op::Ge,
+        Op::Sha256,
+        Op::Height,
+        Op::Time,
+        Op::If,
+        Op::Else,
+        Op::EndIf,
+    ];
+}
+
+/// Item on the stack of a running script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Value {
+    Bytes(Vec<u8>),
+    Num(i64),
+}
+
+/// Block a script is run against.
+#[derive(Clone, Copy, Debug)]
+pub struct ScriptContext {
+    pub height: u64,
+    pub timestamp: i64,
+}
+
+/// Condition on a transaction, see the [module docs](self).
+///
+/// ```
+/// use aeonia::blockchain::{Script, ScriptContext};
+///
+/// let script: Script = "HEIGHT 10 GE".parse().unwrap();
+/// let at = |height| ScriptContext { height, timestamp: 0 };
+/// assert!(script.eval(&[], at(9)).is_err());
+/// assert!(script.eval(&[], at(10)).is_ok());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Script(Vec<Op>);
+
+impl Script {
+    pub fn new(ops: Vec<Op>) -> Self {
+        Script(ops)
+    }
+
+    pub fn ops(&self) -> &[Op] {
+        &self.0
+    }
+
+    /// Runs the script with `args` on the stack, returning the gas it used
+    /// if it passes.
+    pub fn eval(
+        &self,
+        args: &[Vec<u8>],
+        context: ScriptContext,
+    ) -> std::result::Result<u64, ScriptError> {
+        if self.0.len() > MAX_OPS {
+            return Err(ScriptError::TooLong {
+                len: self.0.len(),
+                max: MAX_OPS,
+            });
+        }
+        let mut machine = Machine::default();
+        for arg in args {
+            machine.push(Value::Bytes(check_len(arg)?.to_vec()))?;
+        }
+        for op in &self.0 {
+            machine.step(op, context)?;
+        }
+        if !machine.branches.is_empty() {
+            return Err(ScriptError::UnbalancedIf);
+        }
+        match machine.stack.last() {
+            Some(Value::Num(n)) if *n != 0 => Ok(machine.gas),
+            _ => Err(ScriptError::Rejected),
+        }
+    }
+}
+
+fn check_len(bytes: &[u8]) -> std::result::Result<&[u8], ScriptError> {
+    if bytes.len() > MAX_ITEM_LEN {
+        return Err(ScriptError::ItemTooLong {
+            len: bytes.len(),
+            max: MAX_ITEM_LEN,
+        });
+    }
+    Ok(bytes)
+}
+
+#[derive(Default)]
+struct Machine {
+    stack: Vec<Value>,
+    /// Whether each enclosing `IF` branch is being run.
+    branches: Vec<bool>,
+    gas: u64,
+}
+
+impl Machine {
+    fn push(&mut self, value: Value) -> std::result::Result<(), ScriptError> {
+        if self.stack.len() >= MAX_STACK {
+            return Err(ScriptError::StackOverflow { max: MAX_STACK });
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self, op: &Op) -> std::result::Result<Value, ScriptError> {
+        self.stack
+            .pop()
+            .ok_or(ScriptError::StackUnderflow { op: op.name() })
+    }
+
+    fn pop_num(&mut self, op: &Op) -> std::result::Result<i64, ScriptError> {
+        match self.pop(op)? {
+            Value::Num(n) => Ok(n),
+            Value::Bytes(_) => Err(ScriptError::TypeMismatch {
+                op: op.name(),
+                expected: "a number",
+            }),
+        }
+    }
+
+    /// Pops `b` then `a` for an instruction taking `a b`.
+    fn pop_nums(&mut self, op: &Op) -> std::result::Result<(i64, i64), ScriptError> {
+        let b = self.pop_num(op)?;
+        Ok((self.pop_num(op)?, b))
+    }
+
+    fn step(&mut self, op: &Op, context: ScriptContext) -> std::result::Result<(), ScriptError> {
+        self.gas += op.gas();
+        if self.gas > MAX_GAS {
+            return Err(ScriptError::OutOfGas {
+                used: self.gas,
+                limit: MAX_GAS,
+            });
+        }
+        let running = self.branches.iter().all(|b| *b);
+        match op {
+            Op::If => {
+                let taken = running && self.pop_num(op)? != 0;
+                self.branches.push(taken);
+                return Ok(());
+            }
+            Op::Else => {
+                let outer = self.branches.len().saturating_sub(1);
+                let outer_running = self.branches[..outer].iter().all(|b| *b);
+                let taken = self.branches.last_mut().ok_or(ScriptError::UnbalancedIf)?;
+                *taken = outer_running && !*taken;
+                return Ok(());
+            }
+            Op::EndIf => {
+                self.branches.pop().ok_or(ScriptError::UnbalancedIf)?;
+                return Ok(());
+            }
+            _ if !running => return Ok(()),
+            _ => {}
+        }
+        let flag = |b: bool| Value::Num(i64::from(b));
+        let overflow = ScriptError::Overflow { op: op.name() };
+        let value = match op {
+            Op::Bytes(bytes) => Value::Bytes(check_len(bytes)?.to_vec()),
+            Op::Num(n) => Value::Num(*n),
+            Op::Dup => {
+                let top = self.pop(op)?;
+                self.push(top.clone())?;
+                top
+            }
+            Op::Drop => {
+                self.pop(op)?;
+                return Ok(());
+            }
+            Op::Swap => {
+                let b = self.pop(op)?;
+                let a = self.pop(op)?;
+                self.push(b)?;
+                a
+            }
+            Op::Equal => {
+                let b = self.pop(op)?;
+                flag(self.pop(op)? == b)
+            }
+            Op::Verify => {
+                if self.pop_num(op)? == 0 {
+                    return Err(ScriptError::VerifyFailed);
+                }
+                return Ok(());
+            }
+            Op::Not => flag(self.pop_num(op)? == 0),
+            Op::And => {
+                let (a, b) = self.pop_nums(op)?;
+                flag(a != 0 && b != 0)
+            }
+            Op::Or => {
+                let (a, b) = self.pop_nums(op)?;
+                flag(a != 0 || b != 0)
+            }
+            Op::Add => {
+                let (a, b) = self.pop_nums(op)?;
+                Value::Num(a.checked_add(b).ok_or(overflow)?)
+            }
+            Op::Sub => {
+                let (a, b) = self.pop_nums(op)?;
+                Value::Num(a.checked_sub(b).ok_or(overflow)?)
+            }
+            Op::Lt => {
+                let (a, b) = self.pop_nums(op)?;
+                flag(a < b)
+            }
+            Op::Ge => {
+                let (a, b) = self.pop_nums(op)?;
+                flag(a >= b)
+            }
+            Op::Sha256 => match self.pop(op)? {
+                Value::Bytes(bytes) => Value::Bytes(Sha256::digest(bytes).to_vec()),
+                Value::Num(_) => {
+                    return Err(ScriptError::TypeMismatch {
+                        op: op.name(),
+                        expected: "a byte string",
+                    })
+                }
+            },
+            Op::Height => Value::Num(i64::try_from(context.height).map_err(|_| overflow)?),
+            Op::Time => Value::Num(context.timestamp),
+            Op::If | Op::Else | Op::EndIf => unreachable!("handled above"),
+        };
+        self.push(value)
+    }
+}
+
+impl std::fmt::Display for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, op) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            match op {
+                Op::Bytes(bytes) => {
+                    write!(f, "0x")?;
+                    for byte in bytes {
+                        write!(f, "{:02x}", byte)?;
+                    }
+                }
+                Op::Num(n) => write!(f, "{}", n)?,
+                op => write!(f, "{}", op.name())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Script {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse = |token: &str| {
+            if let Some(hex) = token.strip_prefix("0x") {
+                if hex.len() % 2 != 0 {
+                    return Err(format!("odd length hex {}", token));
+                }
+                return (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                    .collect::<std::result::Result<_, _>>()
+                    .map(Op::Bytes)
+                    .map_err(|_| format!("invalid hex {}", token));
+            }
+            if let Ok(n) = token.parse() {
+                return Ok(Op::Num(n));
+            }
+            let name = token.to_ascii_uppercase();
+            Op::PLAIN
+                .into_iter()
+                .find(|op| op.name() == name)
+                .ok_or_else(|| format!("unknown instruction {}", token))
+        };
+        s.split_whitespace()
+            .map(parse)
+            .collect::<std::result::Result<_, _>>()
+            .map(Script)
+    }
+}
+
+impl Serialize for Script {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Script {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Encode for Op {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Op::Bytes(bytes) => {
+                0u8.encode_to(out);
+                bytes.encode_to(out);
+            }
+            Op::Num(n) => {
+                1u8.encode_to(out);
+                n.encode_to(out);
+            }
+            op => {
+                let index = Op::PLAIN.iter().position(|plain| plain == op);
+                (index.unwrap_or_default() as u8 + 2).encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for Op {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(Op::Bytes(Vec::decode_from(reader)?)),
+            1 => Ok(Op::Num(i64::decode_from(reader)?)),
+            tag => Op::PLAIN
+                .get(usize::from(tag) - 2)
+                .cloned()
+                .ok_or(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
+
+impl Encode for Script {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+    }
+}
+
+impl Decode for Script {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(Script(Vec::decode_from(reader)?))
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::{mpsc::Receiver, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{
+    Balance, Block, BlockHeader, BlockStatus, BlockTemplate, Blockchain, CancelToken, ChainEvent,
+    Error, Mined, Result, Snapshot, Transaction, TxId,
+};
+use crate::{address::Address, wallet::Wallet};
+
+/// Handle to a [`Blockchain`] shared between the miner, the RPC servers and
+/// the peer network. Cloning it shares the same chain.
+///
+/// The chain itself holds no locks: everything that changes it takes
+/// `&mut self`, so it can only be changed through [`SharedBlockchain::write`],
+/// while any number of readers may hold [`SharedBlockchain::read`] at once.
+/// The other methods each take the lock for a single call. The lock isn't
+/// reentrant, so they must not be called on a thread that already holds a
+/// guard.
+///
+/// ```
+/// use aeonia::{blockchain::{Blockchain, SharedBlockchain}, network::Network};
+///
+/// let blockchain = SharedBlockchain::new(Blockchain::new(Network::Regtest).unwrap());
+/// let miner = blockchain.clone();
+/// std::thread::spawn(move || {
+///     let wallet = aeonia::wallet::Wallet::new(Network::Regtest).unwrap();
+///     assert!(miner.mine(&wallet));
+/// })
+/// .join()
+/// .unwrap();
+/// assert_eq!(blockchain.chain_height().unwrap(), 1);
+/// ```
+#[derive(Clone)]
+pub struct SharedBlockchain {
+    inner: Arc<RwLock<Blockchain>>,
+}
+
+impl SharedBlockchain {
+    pub fn new(blockchain: Blockchain) -> Self {
+        SharedBlockchain {
+            inner: Arc::new(RwLock::new(blockchain)),
+        }
+    }
+
+    /// Read access to the chain, shared with other readers.
+    pub fn read(&self) -> Result<RwLockReadGuard<'_, Blockchain>> {
+        self.inner
+            .read()
+            .map_err(|e| Error::LockPoisoned(e.to_string()))
+    }
+
+    /// Exclusive access to the chain, for changing it.
+    pub fn write(&self) -> Result<RwLockWriteGuard<'_, Blockchain>> {
+        self.inner
+            .write()
+            .map_err(|e| Error::LockPoisoned(e.to_string()))
+    }
+
+    /// See [`Blockchain::add_transation_to_pool`].
+    pub fn add_transaction(&self, transaction: Transaction) -> Result<Transaction> {
+        self.write()?.add_transation_to_pool(transaction)
+    }
+
+    /// See [`Blockchain::add_batch_to_pool`].
+    pub fn add_batch(&self, transactions: Vec<Transaction>) -> Result<Vec<Transaction>> {
+        self.write()?.add_batch_to_pool(transactions)
+    }
+
+    /// See [`Blockchain::replace_transaction`].
+    pub fn replace_transaction(&self, transaction: Transaction) -> Result<Transaction> {
+        self.write()?.replace_transaction(transaction)
+    }
+
+    /// See [`Blockchain::receive_block`].
+    pub fn receive_block(&self, block: Block) -> Result<BlockStatus> {
+        self.write()?.receive_block(block)
+    }
+
+    /// Mines a block paying `miner`, see [`Blockchain::mining`]. The proof
+    /// of work, if any, is searched for without holding the lock, so the
+    /// chain stays usable meanwhile. If another block extends the tip first, the mined
+    /// block is dropped and `false` returned.
+    pub fn mine(&self, miner: &Wallet) -> bool {
+        self.mine_until(miner, &CancelToken::new())
+    }
+
+    /// Like [`SharedBlockchain::mine`], but gives up the search as soon as
+    /// `cancel` is tripped, so a miner can start over on a new tip.
+    pub fn mine_until(&self, miner: &Wallet, cancel: &CancelToken) -> bool {
+        self.read()
+            .and_then(|blockchain| {
+                let block = blockchain.block_candidate(miner.address())?;
+                Ok((block, blockchain.consensus()))
+            })
+            .and_then(|(block, consensus)| Mined::new(block, consensus.as_ref(), miner, cancel))
+            .and_then(|mined| self.write()?.connect_mined(mined))
+            .is_ok()
+    }
+
+    /// See [`Blockchain::get_block_template`].
+    pub fn get_block_template(&self, miner: &Address) -> Result<BlockTemplate> {
+        self.write()?.get_block_template(miner)
+    }
+
+    /// See [`Blockchain::submit_block`].
+    pub fn submit_block(&self, header: BlockHeader, extra_nonce: u64) -> Result<()> {
+        self.write()?.submit_block(header, extra_nonce)
+    }
+
+    pub fn balance(&self, address: &Address, min_confirmations: u64) -> Result<Balance> {
+        self.read()?.balance(address, min_confirmations)
+    }
+
+    pub fn next_sequence(&self, sender: &Address) -> Result<u64> {
+        self.read()?.next_sequence(sender)
+    }
+
+    pub fn chain_height(&self) -> Result<u64> {
+        self.read()?.chain_height()
+    }
+
+    pub fn last_block(&self) -> Option<Arc<Block>> {
+        self.read().ok()?.last_block()
+    }
+
+    /// Main chain blocks from genesis on, fetched one at a time so the lock
+    /// is only held while each is read. Exporters and indexers can walk the
+    /// whole chain this way without stalling the miner or copying it.
+    pub fn iter_blocks(&self) -> BlockIter {
+        self.iter_blocks_from(0)
+    }
+
+    /// Main chain blocks from height `from` on, see
+    /// [`SharedBlockchain::iter_blocks`].
+    pub fn iter_blocks_from(&self, from: u64) -> BlockIter {
+        BlockIter {
+            blockchain: self.clone(),
+            height: from,
+        }
+    }
+
+    pub fn pending_transaction(&self, id: &TxId) -> Option<Transaction> {
+        self.read().ok()?.pending_transaction(id)
+    }
+
+    pub fn pending_transactions(&self) -> Vec<Transaction> {
+        match self.read() {
+            Ok(blockchain) => blockchain.pending_transactions(),
+            Err(_) => vec![],
+        }
+    }
+
+    pub fn pending_transaction_count(&self) -> usize {
+        match self.read() {
+            Ok(blockchain) => blockchain.pending_transaction_count(),
+            Err(_) => 0,
+        }
+    }
+
+    /// See [`Blockchain::subscribe`].
+    pub fn subscribe(&self) -> Result<Receiver<ChainEvent>> {
+        Ok(self.write()?.subscribe())
+    }
+
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        self.read()?.snapshot()
+    }
+
+    /// See [`Blockchain::shutdown`].
+    pub fn shutdown(&self) -> Result<Snapshot> {
+        self.write()?.shutdown()
+    }
+}
+
+/// Iterator over the blocks of a [`SharedBlockchain`]. Each block is the
+/// one at its height on the main chain when it is fetched, so after a reorg
+/// the rest of the iteration follows the new branch. It ends at the tip as
+/// of the last block fetched.
+pub struct BlockIter {
+    blockchain: SharedBlockchain,
+    height: u64,
+}
+
+impl Iterator for BlockIter {
+    type Item = Arc<Block>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block = self.blockchain.read().ok()?.block_by_height(self.height)?;
+        self.height += 1;
+        Some(block)
+    }
+}
+
+impl From<Blockchain> for SharedBlockchain {
+    fn from(blockchain: Blockchain) -> Self {
+        SharedBlockchain::new(blockchain)
+    }
+}
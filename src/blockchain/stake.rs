@@ -0,0 +1,300 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    block::{Block, BlockHeader},
+    state::BALANCE_EPSILON,
+    transaction::Transaction,
+    Error, Result,
+};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode, Reader},
+};
+
+/// What a transaction does with its sender's stake, besides paying its
+/// coin amount and fee.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StakeOp {
+    /// Locks the amount of a payment from the sender to itself as stake
+    /// instead of crediting it.
+    Stake,
+    /// Releases `amount` of the sender's stake back into its balance. The
+    /// payment is to the sender itself.
+    Unstake { amount: f64 },
+    /// Burns the whole stake, `amount`, of a validator caught signing two
+    /// blocks on the same parent.
+    Slash {
+        evidence: Box<DoubleSign>,
+        amount: f64,
+    },
+}
+
+/// Two different blocks on the same parent signed by the same producer,
+/// which an honest validator never makes. Only their headers are kept.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DoubleSign {
+    pub first: BlockHeader,
+    pub second: BlockHeader,
+}
+
+impl DoubleSign {
+    pub fn new(first: &Block, second: &Block) -> Self {
+        DoubleSign {
+            first: first.header().clone(),
+            second: second.header().clone(),
+        }
+    }
+
+    /// The validator the evidence convicts, if it holds up.
+    pub fn offender(&self) -> Option<Address> {
+        let (first, second) = (&self.first, &self.second);
+        let holds = first.hash() != second.hash()
+            && first.previous_hash() == second.previous_hash()
+            && first.miner() == second.miner()
+            && first.verify_signature().is_ok()
+            && second.verify_signature().is_ok();
+        holds.then(|| *first.miner())
+    }
+
+    /// Hashes of the two blocks, in order, the same whichever way round the
+    /// evidence lists them.
+    pub fn key(&self) -> (String, String) {
+        let (first, second) = (self.first.hash(), self.second.hash());
+        if first <= second {
+            (first, second)
+        } else {
+            (second, first)
+        }
+    }
+}
+
+/// How much every validator has staked on the main chain, and the
+/// evidence that has already slashed someone, by [`DoubleSign::key`], so it
+/// can't slash them again once they stake anew.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StakeLedger {
+    stakes: HashMap<Address, f64>,
+    slashed: HashSet<(String, String)>,
+}
+
+impl StakeLedger {
+    pub fn from_parts<S, E>(stakes: S, slashed: E) -> Self
+    where
+        S: IntoIterator<Item = (Address, f64)>,
+        E: IntoIterator<Item = (String, String)>,
+    {
+        StakeLedger {
+            stakes: stakes.into_iter().collect(),
+            slashed: slashed.into_iter().collect(),
+        }
+    }
+
+    /// Every validator and its stake, sorted by address.
+    pub fn stakes(&self) -> Vec<(Address, f64)> {
+        let mut stakes: Vec<_> = self
+            .stakes
+            .iter()
+            .map(|(address, stake)| (*address, *stake))
+            .collect();
+        stakes.sort_by_key(|(address, _)| *address);
+        stakes
+    }
+
+    /// Keys of the evidence that has slashed someone, sorted.
+    pub fn slashed(&self) -> Vec<(String, String)> {
+        let mut slashed: Vec<_> = self.slashed.iter().cloned().collect();
+        slashed.sort();
+        slashed
+    }
+
+    pub fn stake(&self, address: &Address) -> f64 {
+        self.stakes.get(address).copied().unwrap_or_default()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.stakes.values().sum()
+    }
+
+    /// Draws a validator with a chance proportional to its stake, using the
+    /// first 8 bytes of `seed` as the draw. `None` if nothing is staked.
+    pub fn select(&self, seed: &[u8]) -> Option<Address> {
+        let stakes = self.stakes();
+        let total = self.total();
+        if total <= BALANCE_EPSILON {
+            return None;
+        }
+        let mut draw = [0; 8];
+        let len = seed.len().min(8);
+        draw[..len].copy_from_slice(&seed[..len]);
+        let mut target = u64::from_le_bytes(draw) as f64 / u64::MAX as f64 * total;
+        for (address, stake) in &stakes {
+            if target < *stake {
+                return Some(*address);
+            }
+            target -= stake;
+        }
+        stakes.last().map(|(address, _)| *address)
+    }
+
+    /// Fails if `transaction` stakes, unstakes or slashes anything it can't,
+    /// with `pending` of its sender's stake already being unstaked.
+    pub fn check(&self, transaction: &Transaction, pending: f64) -> Result<()> {
+        let Some(op) = &transaction.stake else {
+            return Ok(());
+        };
+        let invalid = || Error::InvalidStake {
+            id: transaction.id(),
+        };
+        let Some(sender) = transaction.sender() else {
+            return Err(invalid());
+        };
+        match op {
+            StakeOp::Stake => {
+                if transaction.recipient != *sender || transaction.amount <= 0.0 {
+                    return Err(invalid());
+                }
+                Ok(())
+            }
+            StakeOp::Unstake { amount } => {
+                if transaction.recipient != *sender || !amount.is_finite() || *amount <= 0.0 {
+                    return Err(invalid());
+                }
+                let available = self.stake(sender) - pending;
+                if *amount > available + BALANCE_EPSILON {
+                    return Err(Error::InsufficientStake {
+                        address: *sender,
+                        available,
+                        required: *amount,
+                    });
+                }
+                Ok(())
+            }
+            StakeOp::Slash { evidence, amount } => {
+                let offender = evidence
+                    .offender()
+                    .filter(|_| !self.slashed.contains(&evidence.key()))
+                    .ok_or(Error::InvalidEvidence {
+                        id: transaction.id(),
+                    })?;
+                let stake = self.stake(&offender);
+                if !amount.is_finite()
+                    || stake <= BALANCE_EPSILON
+                    || (amount - stake).abs() > BALANCE_EPSILON
+                {
+                    return Err(invalid());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies a transaction [`StakeLedger::check`] accepted.
+    pub fn apply(&mut self, transaction: &Transaction) {
+        let (Some(op), Some(sender)) = (&transaction.stake, transaction.sender()) else {
+            return;
+        };
+        match op {
+            StakeOp::Stake => self.add(*sender, transaction.amount),
+            StakeOp::Unstake { amount } => self.add(*sender, -amount),
+            StakeOp::Slash { evidence, amount } => {
+                if let Some(offender) = evidence.offender() {
+                    self.add(offender, -amount);
+                    self.slashed.insert(evidence.key());
+                }
+            }
+        }
+    }
+
+    pub fn revert(&mut self, transaction: &Transaction) {
+        let (Some(op), Some(sender)) = (&transaction.stake, transaction.sender()) else {
+            return;
+        };
+        match op {
+            StakeOp::Stake => self.add(*sender, -transaction.amount),
+            StakeOp::Unstake { amount } => self.add(*sender, *amount),
+            StakeOp::Slash { evidence, amount } => {
+                if let Some(offender) = evidence.offender() {
+                    self.add(offender, *amount);
+                    self.slashed.remove(&evidence.key());
+                }
+            }
+        }
+    }
+
+    /// The first validator whose stake differs between the two ledgers.
+    pub fn first_difference(&self, other: &StakeLedger) -> Option<Address> {
+        self.stakes
+            .keys()
+            .chain(other.stakes.keys())
+            .find(|address| (self.stake(address) - other.stake(address)).abs() > BALANCE_EPSILON)
+            .copied()
+    }
+
+    fn add(&mut self, address: Address, amount: f64) {
+        let stake = self.stakes.entry(address).or_default();
+        *stake += amount;
+        if stake.abs() <= BALANCE_EPSILON {
+            self.stakes.remove(&address);
+        }
+    }
+}
+
+impl Encode for StakeOp {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            StakeOp::Stake => 0u8.encode_to(out),
+            StakeOp::Unstake { amount } => {
+                1u8.encode_to(out);
+                amount.encode_to(out);
+            }
+            StakeOp::Slash { evidence, amount } => {
+                // Evidence from before nonces were widened keeps its layout.
+                match (
+                    evidence.first.encode_narrow(),
+                    evidence.second.encode_narrow(),
+                ) {
+                    (Some(first), Some(second)) => {
+                        2u8.encode_to(out);
+                        out.extend(first);
+                        out.extend(second);
+                    }
+                    _ => {
+                        3u8.encode_to(out);
+                        evidence.first.encode_to(out);
+                        evidence.second.encode_to(out);
+                    }
+                }
+                amount.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for StakeOp {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(StakeOp::Stake),
+            1 => Ok(StakeOp::Unstake {
+                amount: f64::decode_from(reader)?,
+            }),
+            2 => Ok(StakeOp::Slash {
+                evidence: Box::new(DoubleSign {
+                    first: BlockHeader::decode_narrow(reader)?,
+                    second: BlockHeader::decode_narrow(reader)?,
+                }),
+                amount: f64::decode_from(reader)?,
+            }),
+            3 => Ok(StakeOp::Slash {
+                evidence: Box::new(DoubleSign {
+                    first: BlockHeader::decode_from(reader)?,
+                    second: BlockHeader::decode_from(reader)?,
+                }),
+                amount: f64::decode_from(reader)?,
+            }),
+            tag => Err(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use super::{
+    authority::AuthorityLedger, block::Block, consensus::ProducerState, stake::StakeLedger,
+    token::TokenLedger, transaction::Transaction,
+};
+use crate::address::Address;
+
+/// Balances below this magnitude are treated as equal when comparing states,
+/// since reverting a block doesn't always cancel floating point sums exactly.
+pub(super) const BALANCE_EPSILON: f64 = 1e-9;
+
+/// Balance of an address split by how settled its funds are, see
+/// [`Blockchain::balance`](super::Blockchain::balance).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Balance {
+    /// Payments to the address with enough confirmations, less every
+    /// confirmed payment out of it.
+    pub confirmed: f64,
+    /// Net change still settling: payments to the address in blocks that
+    /// are too recent or in the pool, less its pending payments out.
+    pub pending: f64,
+}
+
+impl Balance {
+    /// What the address will hold once everything pending confirms.
+    pub fn total(&self) -> f64 {
+        self.confirmed + self.pending
+    }
+}
+
+impl std::fmt::Display for Balance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} confirmed, {} pending", self.confirmed, self.pending)
+    }
+}
+
+/// Confirmed balance of every address that has appeared on the main chain,
+/// the next sequence number of every account that has sent a payment, and
+/// the tokens and stake each holds and the changes to the authorities,
+/// updated block by block instead of rescanning the chain.
+#[derive(Clone, Default)]
+pub struct AccountState {
+    balances: HashMap<Address, f64>,
+    sequences: HashMap<Address, u64>,
+    tokens: TokenLedger,
+    stakes: StakeLedger,
+    authorities: AuthorityLedger,
+    /// Coins created so far, i.e. the sum of all balances and stakes.
+    issued: f64,
+}
+
+impl AccountState {
+    pub fn build<'a, I: IntoIterator<Item = &'a Block>>(blocks: I) -> Self {
+        let mut state = AccountState::default();
+        for block in blocks {
+            state.apply_block(block);
+        }
+        state
+    }
+
+    pub fn from_parts<B, S>(
+        balances: B,
+        sequences: S,
+        tokens: TokenLedger,
+        stakes: StakeLedger,
+        authorities: AuthorityLedger,
+    ) -> Self
+    where
+        B: IntoIterator<Item = (Address, f64)>,
+        S: IntoIterator<Item = (Address, u64)>,
+    {
+        let balances: HashMap<Address, f64> = balances.into_iter().collect();
+        AccountState {
+            issued: balances.values().sum::<f64>() + stakes.total(),
+            balances,
+            sequences: sequences.into_iter().collect(),
+            tokens,
+            stakes,
+            authorities,
+        }
+    }
+
+    pub fn balances(&self) -> Vec<(Address, f64)> {
+        self.balances
+            .iter()
+            .map(|(address, balance)| (*address, *balance))
+            .collect()
+    }
+
+    pub fn sequences(&self) -> Vec<(Address, u64)> {
+        self.sequences
+            .iter()
+            .map(|(address, sequence)| (*address, *sequence))
+            .collect()
+    }
+
+    pub fn tokens(&self) -> &TokenLedger {
+        &self.tokens
+    }
+
+    pub fn stakes(&self) -> &StakeLedger {
+        &self.stakes
+    }
+
+    pub fn authorities(&self) -> &AuthorityLedger {
+        &self.authorities
+    }
+
+    /// What consensus engines go by to tell who may produce a block on top
+    /// of this state.
+    pub fn producers(&self) -> ProducerState<'_> {
+        ProducerState {
+            stakes: &self.stakes,
+            authorities: &self.authorities,
+        }
+    }
+
+    pub fn issued(&self) -> f64 {
+        self.issued
+    }
+
+    pub fn balance(&self, address: &Address) -> f64 {
+        self.balances.get(address).copied().unwrap_or_default()
+    }
+
+    /// `address`'s balance without the payments to it in `unsettled`, the
+    /// latest blocks, which haven't been confirmed often enough to spend.
+    /// Payments out of it in those blocks still count.
+    pub fn settled_balance<'a, I: IntoIterator<Item = &'a Block>>(
+        &self,
+        address: &Address,
+        unsettled: I,
+    ) -> f64 {
+        let received: f64 = unsettled
+            .into_iter()
+            .flat_map(Block::transactions)
+            .filter(|transaction| &transaction.recipient == address)
+            .map(Transaction::credited)
+            .sum();
+        self.balance(address) - received
+    }
+
+    /// Sequence number the next payment out of `address` must carry.
+    pub fn next_sequence(&self, address: &Address) -> u64 {
+        self.sequences.get(address).copied().unwrap_or_default()
+    }
+
+    pub fn apply_block(&mut self, block: &Block) {
+        for transaction in block.transactions() {
+            self.apply_transaction(transaction);
+        }
+    }
+
+    pub fn apply_transaction(&mut self, transaction: &Transaction) {
+        *self.balances.entry(transaction.recipient).or_default() += transaction.credited();
+        self.issued += minted(transaction);
+        self.tokens.apply(transaction);
+        self.stakes.apply(transaction);
+        self.authorities.apply(transaction);
+        if let Some(sender) = transaction.sender() {
+            *self.balances.entry(*sender).or_default() -= transaction.amount + transaction.fee;
+            *self.sequences.entry(*sender).or_default() += 1;
+        }
+    }
+
+    pub fn revert_block(&mut self, block: &Block) {
+        for transaction in block.transactions().iter().rev() {
+            self.tokens.revert(transaction);
+            self.stakes.revert(transaction);
+            self.authorities.revert(transaction);
+            if let Some(sender) = transaction.sender() {
+                *self.balances.entry(*sender).or_default() += transaction.amount + transaction.fee;
+                if let Some(sequence) = self.sequences.get_mut(sender) {
+                    *sequence -= 1;
+                    if *sequence == 0 {
+                        self.sequences.remove(sender);
+                    }
+                }
+            }
+            *self.balances.entry(transaction.recipient).or_default() -= transaction.credited();
+            self.issued -= minted(transaction);
+        }
+    }
+
+    /// Returns the first address whose balance, sequence number, token
+    /// holdings, stake or authority differ between the two states.
+    pub fn first_difference(&self, other: &AccountState) -> Option<Address> {
+        self.balances
+            .keys()
+            .chain(other.balances.keys())
+            .find(|address| {
+                (self.balance(address) - other.balance(address)).abs() > BALANCE_EPSILON
+                    || self.next_sequence(address) != other.next_sequence(address)
+            })
+            .copied()
+            .or_else(|| self.tokens.first_difference(&other.tokens))
+            .or_else(|| self.stakes.first_difference(&other.stakes))
+            .or_else(|| self.authorities.first_difference(&other.authorities))
+    }
+}
+
+/// Change `transaction` makes to the number of coins in existence: a coinbase
+/// creates its amount, a fee leaves its payer to be paid out again by a
+/// coinbase, and slashed stake is gone.
+pub(super) fn minted(transaction: &Transaction) -> f64 {
+    if transaction.is_coinbase() {
+        transaction.amount
+    } else {
+        -transaction.fee - transaction.burned()
+    }
+}
@@ -0,0 +1,78 @@
+use super::{block::Block, transaction::Transaction};
+use crate::address::Address;
+
+/// Most holders [`Blockchain::stats`](super::Blockchain::stats) lists.
+pub const MAX_TOP_HOLDERS: usize = 1000;
+
+/// Fees paid on the main chain, kept up to date block by block so
+/// [`Blockchain::stats`](super::Blockchain::stats) never walks the chain.
+/// Blocks already pruned when the chain was loaded don't count.
+#[derive(Clone, Default)]
+pub(super) struct FeeTotals {
+    fees: f64,
+    /// Confirmed transactions other than coinbases.
+    transactions: u64,
+}
+
+impl FeeTotals {
+    pub(super) fn build<'a, I: IntoIterator<Item = &'a Block>>(blocks: I) -> Self {
+        let mut totals = FeeTotals::default();
+        for block in blocks {
+            totals.apply_block(block);
+        }
+        totals
+    }
+
+    pub(super) fn apply_block(&mut self, block: &Block) {
+        for transaction in payments(block) {
+            self.fees += transaction.fee;
+            self.transactions += 1;
+        }
+    }
+
+    pub(super) fn revert_block(&mut self, block: &Block) {
+        for transaction in payments(block) {
+            self.fees -= transaction.fee;
+            self.transactions -= 1;
+        }
+    }
+
+    pub(super) fn transactions(&self) -> u64 {
+        self.transactions
+    }
+
+    pub(super) fn average(&self) -> f64 {
+        match self.transactions {
+            0 => 0.0,
+            n => self.fees / n as f64,
+        }
+    }
+}
+
+fn payments(block: &Block) -> impl Iterator<Item = &Transaction> {
+    block
+        .transactions()
+        .iter()
+        .filter(|transaction| !transaction.is_coinbase())
+}
+
+/// How the coins on the main chain are spread and how the chain is moving,
+/// see [`Blockchain::stats`](super::Blockchain::stats).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainStats {
+    pub height: u64,
+    pub total_supply: f64,
+    /// Total supply less what the genesis faucet still holds.
+    pub circulating_supply: f64,
+    /// Addresses holding a positive confirmed balance.
+    pub active_addresses: u64,
+    /// Largest confirmed balances, largest first.
+    pub top_holders: Vec<(Address, f64)>,
+    /// Mean time between the blocks since genesis, in seconds.
+    pub average_block_interval: f64,
+    /// Confirmed transactions other than coinbases, leaving out blocks
+    /// pruned before the chain was loaded.
+    pub transactions: u64,
+    /// Mean fee of those transactions.
+    pub average_fee: f64,
+}
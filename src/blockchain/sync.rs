@@ -0,0 +1,41 @@
+/// How far along the node is in catching up with the chain its peers
+/// announce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncStatus {
+    pub height: u64,
+    /// Height of the best header chain received from a peer; the same as
+    /// `height` when not syncing.
+    pub target: u64,
+    /// Whether block bodies are still being downloaded.
+    pub syncing: bool,
+}
+
+impl SyncStatus {
+    /// Fraction of the blocks up to `target` the node has, between 0 and 1.
+    pub fn progress(&self) -> f64 {
+        if self.target == 0 {
+            return 1.0;
+        }
+        (self.height as f64 / self.target as f64).min(1.0)
+    }
+}
+
+/// Heights of the blocks a locator names for a chain with tip at `tip`: the
+/// last ten, then back with steps doubling each time, ending at genesis. A
+/// peer finds the fork point from it in a logarithmic number of hashes.
+pub(super) fn locator_heights(tip: u64) -> Vec<u64> {
+    let mut heights = vec![];
+    let mut height = tip;
+    let mut step = 1;
+    loop {
+        heights.push(height);
+        if height == 0 {
+            break;
+        }
+        if heights.len() >= 10 {
+            step *= 2;
+        }
+        height = height.saturating_sub(step);
+    }
+    heights
+}
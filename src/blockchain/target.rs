@@ -0,0 +1,110 @@
+/// A 256-bit number a block hash, read as a big-endian integer, must not
+/// exceed under proof of work. Headers carry it in the compact form of
+/// [`Target::to_compact`].
+///
+/// ```
+/// use aeonia::blockchain::Target;
+///
+/// let target = Target::from_zero_digits(5);
+/// assert_eq!(target.to_compact(), 0x1e0fffff);
+/// // Only the leading bytes survive, rounding the target down.
+/// let rounded = Target::from_compact(0x1e0fffff).unwrap();
+/// assert!(rounded < target);
+/// assert_eq!(rounded.to_compact(), 0x1e0fffff);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Target([u8; 32]);
+
+impl Target {
+    pub const MAX: Target = Target([0xff; 32]);
+
+    /// The target of hashes starting with `digits` zero hex digits, the
+    /// way difficulty is configured.
+    pub fn from_zero_digits(digits: u8) -> Self {
+        let mut bytes = [0xff; 32];
+        let digits = usize::from(digits).min(64);
+        bytes[..digits / 2].fill(0);
+        if digits % 2 == 1 {
+            bytes[digits / 2] = 0x0f;
+        }
+        Target(bytes)
+    }
+
+    /// Decodes the compact form: the top byte is the length of the number
+    /// in bytes and the other three its leading bytes. `None` if the sign
+    /// bit is set or the number doesn't fit in 256 bits.
+    pub fn from_compact(bits: u32) -> Option<Self> {
+        if bits & 0x0080_0000 != 0 {
+            return None;
+        }
+        let size = (bits >> 24) as isize;
+        let mantissa = (bits & 0x007f_ffff).to_be_bytes();
+        let mut bytes = [0; 32];
+        for (i, byte) in mantissa[1..].iter().enumerate() {
+            let position = 32 - size + i as isize;
+            match usize::try_from(position) {
+                Ok(position) if position < 32 => bytes[position] = *byte,
+                Ok(_) => {}
+                Err(_) if *byte != 0 => return None,
+                Err(_) => {}
+            }
+        }
+        Some(Target(bytes))
+    }
+
+    /// The compact form, keeping the three leading bytes of the number, so
+    /// decoding it gives back the target rounded down.
+    pub fn to_compact(&self) -> u32 {
+        let Some(first) = self.0.iter().position(|byte| *byte != 0) else {
+            return 0;
+        };
+        let mut size = 32 - first;
+        let byte = |i: usize| u32::from(self.0.get(i).copied().unwrap_or_default());
+        let mut mantissa = byte(first) << 16 | byte(first + 1) << 8 | byte(first + 2);
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            size += 1;
+        }
+        (size as u32) << 24 | mantissa
+    }
+
+    /// Expected number of hashes it takes to meet the target, 2^256 over
+    /// the target plus one, saturating at `u128::MAX`. Computed from the
+    /// compact form, so equal headers always count the same.
+    pub fn work(&self) -> u128 {
+        let bits = self.to_compact();
+        let mantissa = u128::from(bits & 0x007f_ffff);
+        if mantissa == 0 {
+            return u128::MAX;
+        }
+        // The target is the mantissa times 256^(size - 3).
+        let shift = 280u32.saturating_sub(8 * (bits >> 24));
+        if shift <= 127 {
+            return ((1u128 << shift) / mantissa).max(1);
+        }
+        let work = (1u128 << 127) / mantissa;
+        let extra = shift - 127;
+        if work.leading_zeros() < extra {
+            return u128::MAX;
+        }
+        work << extra
+    }
+
+    /// Whether `hash` is at or below the target.
+    pub fn is_met_by(&self, hash: &[u8; 32]) -> bool {
+        *hash <= self.0
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
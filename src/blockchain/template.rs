@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    block::{BlockBody, BlockHeader},
+    hasher::HashAlgorithm,
+    target::Target,
+    transaction::Transaction,
+};
+
+/// Most templates kept waiting for a solution, oldest dropped first.
+pub(super) const MAX_TEMPLATES: usize = 16;
+
+/// The nonces `start` through `end`, both included, for one of several
+/// workers sharing a template to search without going over another's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl NonceRange {
+    /// Every nonce.
+    pub const FULL: NonceRange = NonceRange {
+        start: 0,
+        end: u64::MAX,
+    };
+
+    /// Part `index` of the nonce space cut into `count` parts of the same
+    /// size, the last taking what's left over. `None` if `index` isn't
+    /// below `count`.
+    pub fn part(index: u64, count: u64) -> Option<NonceRange> {
+        if index >= count {
+            return None;
+        }
+        let size = u64::MAX / count;
+        let start = index * size;
+        let end = match index + 1 == count {
+            true => u64::MAX,
+            false => start + size - 1,
+        };
+        Some(NonceRange { start, end })
+    }
+
+    /// The nonce space cut into `count` parts, see [`NonceRange::part`].
+    pub fn split(count: u64) -> Vec<NonceRange> {
+        (0..count)
+            .filter_map(|index| Self::part(index, count))
+            .collect()
+    }
+
+    pub fn contains(&self, nonce: u64) -> bool {
+        (self.start..=self.end).contains(&nonce)
+    }
+}
+
+/// A block for a miner outside the node to work on, from
+/// [`Blockchain::get_block_template`]. The miner searches for a nonce that
+/// makes the header meet its target under [`BlockTemplate::pow_hash`], signs
+/// the header with the key of its miner address and hands it back to
+/// [`Blockchain::submit_block`] with the template's extra nonce, which puts
+/// it back together with the transactions the node kept.
+///
+/// Workers sharing a template keep out of each other's way by each taking
+/// a different extra nonce, which changes the header's Merkle root, or a
+/// different [`NonceRange`] of the same header.
+///
+/// ```
+/// use aeonia::{
+///     blockchain::{Blockchain, NonceRange},
+///     config::Config,
+///     network::Network,
+///     wallet::Wallet,
+/// };
+///
+/// let mut config = Config::default();
+/// config.consensus.difficulty = 1;
+/// let mut blockchain = Blockchain::with_config(&config).unwrap();
+/// let miner = Wallet::new(Network::Regtest).unwrap();
+///
+/// let mut template = blockchain.get_block_template(miner.address()).unwrap();
+/// template.set_extra_nonce(7);
+/// let hasher = template.pow_hash.unwrap().hasher().unwrap();
+/// let mut header = template.header.clone();
+/// let (found, _) = header.mine_range(hasher, NonceRange::part(1, 4).unwrap());
+/// assert!(found);
+/// miner.sign_header(&mut header).unwrap();
+/// blockchain.submit_block(header, template.extra_nonce()).unwrap();
+/// assert_eq!(blockchain.chain_height().unwrap(), template.height);
+/// ```
+///
+/// [`Blockchain::get_block_template`]: super::Blockchain::get_block_template
+/// [`Blockchain::submit_block`]: super::Blockchain::submit_block
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockTemplate {
+    /// Height the block will have once connected.
+    pub height: u64,
+    /// The header to search a nonce for, unsigned. Its bits hold the
+    /// target.
+    pub header: BlockHeader,
+    /// The coinbase paying the miner and the pending transactions
+    /// selected, in block order.
+    pub transactions: Vec<Transaction>,
+    /// Hash the proof of work is computed with, `None` under engines that
+    /// take none, where signing the header is all there is to do.
+    pub pow_hash: Option<HashAlgorithm>,
+}
+
+impl BlockTemplate {
+    /// The target the header's proof of work must meet.
+    pub fn target(&self) -> Option<Target> {
+        self.header.target()
+    }
+
+    /// The extra nonce the coinbase carries, 0 until set.
+    pub fn extra_nonce(&self) -> u64 {
+        self.transactions.first().map_or(0, extra_nonce)
+    }
+
+    /// Puts `extra_nonce` in the coinbase and updates the header's Merkle
+    /// root, giving the header a nonce space of its own.
+    pub fn set_extra_nonce(&mut self, extra_nonce: u64) {
+        set_extra_nonce(&mut self.transactions, extra_nonce);
+        self.header
+            .set_merkle_root(BlockBody::new(self.transactions.clone()).merkle_root());
+    }
+}
+
+/// The extra nonce in a coinbase's memo: its little-endian bytes, with
+/// none for 0 so coinbases that don't roll one stay as they were.
+pub(super) fn extra_nonce(coinbase: &Transaction) -> u64 {
+    let mut bytes = [0; 8];
+    let len = coinbase.memo.len().min(8);
+    bytes[..len].copy_from_slice(&coinbase.memo[..len]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Puts `extra_nonce` in the memo of the coinbase leading `transactions`.
+pub(super) fn set_extra_nonce(transactions: &mut [Transaction], extra_nonce: u64) {
+    if let Some(coinbase) = transactions.first_mut() {
+        coinbase.memo = match extra_nonce {
+            0 => vec![],
+            _ => extra_nonce.to_le_bytes().to_vec(),
+        };
+    }
+}
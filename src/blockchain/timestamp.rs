@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use super::{Error, Result};
+
+const DEFAULT_MEDIAN_WINDOW: usize = 11;
+const DEFAULT_MAX_FUTURE_DRIFT: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Consensus rules on block timestamps, which are nanoseconds since the Unix
+/// epoch.
+#[derive(Clone, Debug)]
+pub struct TimestampConfig {
+    /// Number of preceding blocks whose median timestamp a new block must
+    /// exceed.
+    pub median_window: usize,
+    /// How far past the local clock a block's timestamp may be.
+    pub max_future_drift: Duration,
+}
+
+impl Default for TimestampConfig {
+    fn default() -> Self {
+        TimestampConfig {
+            median_window: DEFAULT_MEDIAN_WINDOW,
+            max_future_drift: DEFAULT_MAX_FUTURE_DRIFT,
+        }
+    }
+}
+
+impl TimestampConfig {
+    /// Checks the timestamp of block `hash` against the timestamps of up to
+    /// `median_window` of its ancestors and the current time `now`.
+    pub(super) fn validate(
+        &self,
+        hash: &str,
+        timestamp: i64,
+        ancestors: &[i64],
+        now: i64,
+    ) -> Result<()> {
+        if let Some(median) = median_time_past(ancestors) {
+            if timestamp <= median {
+                return Err(Error::TimestampTooEarly {
+                    block: hash.to_string(),
+                    timestamp,
+                    median,
+                });
+            }
+        }
+        let drift = i64::try_from(self.max_future_drift.as_nanos()).unwrap_or(i64::MAX);
+        if timestamp > now.saturating_add(drift) {
+            return Err(Error::TimestampTooLate {
+                block: hash.to_string(),
+                timestamp,
+                max_drift: self.max_future_drift,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Median of a set of block timestamps, taking the upper middle element when
+/// there is an even number of them.
+pub fn median_time_past(timestamps: &[i64]) -> Option<i64> {
+    let mut sorted = timestamps.to_vec();
+    sorted.sort_unstable();
+    sorted.get(sorted.len() / 2).copied()
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    transaction::{Transaction, TxId},
+    Error, Result,
+};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode, Reader},
+};
+
+/// Longest token name, in bytes.
+pub const MAX_TOKEN_NAME_LEN: usize = 32;
+
+/// Identifier of a token: the id of the transaction that issued it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TokenId(TxId);
+
+impl TokenId {
+    pub fn issued_by(transaction: &Transaction) -> Self {
+        TokenId(transaction.id())
+    }
+}
+
+impl std::str::FromStr for TokenId {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.parse().map(TokenId)
+    }
+}
+
+impl std::fmt::Display for TokenId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// What a transaction does with tokens, besides paying its coin amount and
+/// fee.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenOp {
+    /// Creates a token named `name` and credits its whole `supply` to the
+    /// recipient. No more of it can ever be issued.
+    Issue { name: String, supply: u64 },
+    /// Moves `amount` of token `id` from the sender to the recipient.
+    Transfer { id: TokenId, amount: u64 },
+}
+
+/// A token issued on the chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub name: String,
+    pub supply: u64,
+    pub issuer: Address,
+}
+
+/// Every token issued on the main chain and how much of each every address
+/// holds, kept alongside the coin balances.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenLedger {
+    tokens: HashMap<TokenId, Token>,
+    balances: HashMap<(TokenId, Address), u64>,
+}
+
+impl TokenLedger {
+    pub fn from_parts<T, B>(tokens: T, balances: B) -> Self
+    where
+        T: IntoIterator<Item = (TokenId, Token)>,
+        B: IntoIterator<Item = ((TokenId, Address), u64)>,
+    {
+        TokenLedger {
+            tokens: tokens.into_iter().collect(),
+            balances: balances.into_iter().collect(),
+        }
+    }
+
+    /// Every token issued, sorted by id.
+    pub fn tokens(&self) -> Vec<(TokenId, Token)> {
+        let mut tokens: Vec<_> = self
+            .tokens
+            .iter()
+            .map(|(id, token)| (id.clone(), token.clone()))
+            .collect();
+        tokens.sort_by(|(a, _), (b, _)| a.cmp(b));
+        tokens
+    }
+
+    /// Every non-zero holding, sorted by token and holder.
+    pub fn balances(&self) -> Vec<((TokenId, Address), u64)> {
+        let mut balances: Vec<_> = self
+            .balances
+            .iter()
+            .map(|(key, balance)| (key.clone(), *balance))
+            .collect();
+        balances.sort_by(|(a, _), (b, _)| a.cmp(b));
+        balances
+    }
+
+    pub fn token(&self, id: &TokenId) -> Option<&Token> {
+        self.tokens.get(id)
+    }
+
+    pub fn balance(&self, id: &TokenId, address: &Address) -> u64 {
+        self.balances
+            .get(&(id.clone(), *address))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Every token `address` holds some of, sorted by id.
+    pub fn balances_of(&self, address: &Address) -> Vec<(TokenId, u64)> {
+        let mut balances: Vec<_> = self
+            .balances
+            .iter()
+            .filter(|((_, holder), _)| holder == address)
+            .map(|((id, _), balance)| (id.clone(), *balance))
+            .collect();
+        balances.sort();
+        balances
+    }
+
+    /// Fails if `transaction` issues an invalid token or transfers more of
+    /// one than its sender holds here, less `pending` of it already spoken
+    /// for.
+    pub fn check(&self, transaction: &Transaction, pending: u64) -> Result<()> {
+        let Some(token) = &transaction.token else {
+            return Ok(());
+        };
+        let Some(sender) = transaction.sender() else {
+            return Err(Error::InvalidToken {
+                id: transaction.id(),
+            });
+        };
+        match token {
+            TokenOp::Issue { name, supply } => {
+                if name.is_empty() || name.len() > MAX_TOKEN_NAME_LEN || *supply == 0 {
+                    return Err(Error::InvalidToken {
+                        id: transaction.id(),
+                    });
+                }
+                Ok(())
+            }
+            TokenOp::Transfer { id, amount } => {
+                if !self.tokens.contains_key(id) {
+                    return Err(Error::UnknownToken { id: id.clone() });
+                }
+                let available = self.balance(id, sender).saturating_sub(pending);
+                if *amount > available {
+                    return Err(Error::InsufficientTokens {
+                        id: id.clone(),
+                        available,
+                        required: *amount,
+                    });
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies a transaction [`TokenLedger::check`] accepted.
+    pub fn apply(&mut self, transaction: &Transaction) {
+        let (Some(token), Some(sender)) = (&transaction.token, transaction.sender()) else {
+            return;
+        };
+        match token {
+            TokenOp::Issue { name, supply } => {
+                let id = TokenId::issued_by(transaction);
+                self.tokens.insert(
+                    id.clone(),
+                    Token {
+                        name: name.clone(),
+                        supply: *supply,
+                        issuer: *sender,
+                    },
+                );
+                self.credit(id, transaction.recipient, *supply);
+            }
+            TokenOp::Transfer { id, amount } => {
+                self.debit(id.clone(), *sender, *amount);
+                self.credit(id.clone(), transaction.recipient, *amount);
+            }
+        }
+    }
+
+    pub fn revert(&mut self, transaction: &Transaction) {
+        let (Some(token), Some(sender)) = (&transaction.token, transaction.sender()) else {
+            return;
+        };
+        match token {
+            TokenOp::Issue { supply, .. } => {
+                let id = TokenId::issued_by(transaction);
+                self.debit(id.clone(), transaction.recipient, *supply);
+                self.tokens.remove(&id);
+            }
+            TokenOp::Transfer { id, amount } => {
+                self.debit(id.clone(), transaction.recipient, *amount);
+                self.credit(id.clone(), *sender, *amount);
+            }
+        }
+    }
+
+    /// The first holder whose balance of some token differs between the two
+    /// ledgers.
+    pub fn first_difference(&self, other: &TokenLedger) -> Option<Address> {
+        self.balances
+            .keys()
+            .chain(other.balances.keys())
+            .find(|(id, holder)| self.balance(id, holder) != other.balance(id, holder))
+            .map(|(_, holder)| *holder)
+    }
+
+    fn credit(&mut self, id: TokenId, holder: Address, amount: u64) {
+        *self.balances.entry((id, holder)).or_default() += amount;
+    }
+
+    fn debit(&mut self, id: TokenId, holder: Address, amount: u64) {
+        let key = (id, holder);
+        if let Some(balance) = self.balances.get_mut(&key) {
+            *balance = balance.saturating_sub(amount);
+            if *balance == 0 {
+                self.balances.remove(&key);
+            }
+        }
+    }
+}
+
+impl Encode for TokenId {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+    }
+}
+
+impl Decode for TokenId {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(TokenId(TxId::decode_from(reader)?))
+    }
+}
+
+impl Encode for TokenOp {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            TokenOp::Issue { name, supply } => {
+                0u8.encode_to(out);
+                name.encode_to(out);
+                supply.encode_to(out);
+            }
+            TokenOp::Transfer { id, amount } => {
+                1u8.encode_to(out);
+                id.encode_to(out);
+                amount.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for TokenOp {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(TokenOp::Issue {
+                name: String::decode_from(reader)?,
+                supply: u64::decode_from(reader)?,
+            }),
+            1 => Ok(TokenOp::Transfer {
+                id: TokenId::decode_from(reader)?,
+                amount: u64::decode_from(reader)?,
+            }),
+            tag => Err(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
+
+impl Encode for Token {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.name.encode_to(out);
+        self.supply.encode_to(out);
+        self.issuer.encode_to(out);
+    }
+}
+
+impl Decode for Token {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(Token {
+            name: String::decode_from(reader)?,
+            supply: u64::decode_from(reader)?,
+            issuer: Address::decode_from(reader)?,
+        })
+    }
+}
@@ -1,33 +1,642 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    authority::AuthorityOp,
+    script::{Script, ScriptContext},
+    stake::StakeOp,
+    token::TokenOp,
+    utxo::OutPoint,
+    witness::Witness,
+    Error, Result,
+};
+use crate::{
+    address::{Address, MultisigAddress},
+    codec::{self, Decode, Encode, Reader},
+};
+
+/// Longest memo a transaction may carry, in bytes.
+pub const MAX_MEMO_LEN: usize = 80;
+
+/// Identifier of a transaction, the SHA-256 hash of its signed fields,
+/// written as 64 hex digits. The witness is left out, so signing doesn't
+/// change the id.
+///
+/// Ids are held as the hash itself, so one read from JSON must be exactly
+/// that:
+///
+/// ```
+/// use aeonia::blockchain::TxId;
+///
+/// let id = |hex: &str| serde_json::from_str::<TxId>(&format!("\"{hex}\""));
+/// assert!(id(&"ab".repeat(32)).is_ok());
+/// assert!(id("ab").is_err());
+/// assert!(id(&format!("a{}a", "é".repeat(31))).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TxId([u8; 32]);
+
+impl TxId {
+    /// The raw 32 byte hash behind the hex string.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+}
+
+impl std::str::FromStr for TxId {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("invalid transaction id {}", s);
+        if s.len() != 64 {
+            return Err(invalid());
+        }
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let digits = s.get(2 * i..2 * i + 2).ok_or_else(invalid)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        }
+        Ok(TxId(bytes))
+    }
+}
+
+impl TryFrom<String> for TxId {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<TxId> for String {
+    fn from(id: TxId) -> Self {
+        id.to_string()
+    }
+}
+
+impl std::fmt::Display for TxId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl Encode for TxId {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0);
+    }
+}
+
+impl Decode for TxId {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(reader.take(32)?);
+        Ok(TxId(bytes))
+    }
+}
+
+/// Where the funds of a transaction come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Origin {
+    /// Newly minted block reward. Only valid as the first transaction of the
+    /// block at `height`, which also keeps every coinbase's id unique.
+    Coinbase { height: u64 },
+    /// Payment out of an account, authorized by the account's signature.
+    Account(Address),
+}
+
+impl Encode for Origin {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Origin::Coinbase { height } => {
+                0u8.encode_to(out);
+                height.encode_to(out);
+            }
+            Origin::Account(address) => {
+                1u8.encode_to(out);
+                address.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for Origin {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(Origin::Coinbase {
+                height: u64::decode_from(reader)?,
+            }),
+            1 => Ok(Origin::Account(Address::decode_from(reader)?)),
+            tag => Err(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
+
+/// Point past which a transaction can no longer be confirmed, so one that
+/// never makes it into a block doesn't linger in pools forever.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Expiry {
+    /// Last block height that may include the transaction.
+    Height(u64),
+    /// Latest block timestamp that may include the transaction, in
+    /// nanoseconds since the Unix epoch.
+    Time(i64),
+}
+
+impl Expiry {
+    /// Whether a block at `height` with `timestamp` is too late to include
+    /// the transaction.
+    pub fn is_expired(&self, height: u64, timestamp: i64) -> bool {
+        match *self {
+            Expiry::Height(last) => height > last,
+            Expiry::Time(last) => timestamp > last,
+        }
+    }
+}
+
+impl std::fmt::Display for Expiry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Expiry::Height(height) => write!(f, "height {}", height),
+            Expiry::Time(time) => write!(
+                f,
+                "{}",
+                chrono::DateTime::from_timestamp_nanos(time).to_rfc3339()
+            ),
+        }
+    }
+}
+
+impl Encode for Expiry {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Expiry::Height(height) => {
+                0u8.encode_to(out);
+                height.encode_to(out);
+            }
+            Expiry::Time(time) => {
+                1u8.encode_to(out);
+                time.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for Expiry {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(Expiry::Height(u64::decode_from(reader)?)),
+            1 => Ok(Expiry::Time(i64::decode_from(reader)?)),
+            tag => Err(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Transaction {
-    pub sender: String,
-    pub recipient: String,
+    pub origin: Origin,
+    pub recipient: Address,
     pub amount: f64,
+    pub fee: f64,
+    /// Position of this payment among those sent by its account, starting at
+    /// 0. Each sequence number can be confirmed only once, and a pending
+    /// payment can be replaced by another with the same number and a higher
+    /// fee. Always 0 for a coinbase.
+    #[serde(default)]
+    pub sequence: u64,
+    /// Outputs this payment spends, which must be locked to its sender.
+    /// Only checked on a [`Ledger::Utxo`] chain and otherwise left empty.
+    ///
+    /// [`Ledger::Utxo`]: super::Ledger::Utxo
+    #[serde(default)]
+    pub inputs: Vec<OutPoint>,
+    /// Free-form data for the recipient, such as an invoice number. Signed
+    /// along with the payment and at most [`MAX_MEMO_LEN`] bytes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memo: Vec<u8>,
+    /// When the payment stops being valid, if ever. Signed along with the
+    /// payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<Expiry>,
+    /// Only blocks above this height may confirm the payment. Signed along
+    /// with the payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_after_height: Option<u64>,
+    /// Only blocks with a later timestamp may confirm the payment, in
+    /// nanoseconds since the Unix epoch. Signed along with the payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_after_time: Option<i64>,
+    /// Token issued or moved along with the coins. Signed along with the
+    /// payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<TokenOp>,
+    /// Stake locked, released or slashed along with the payment. Signed
+    /// along with the payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stake: Option<StakeOp>,
+    /// Change to the proof-of-authority set made along with the payment.
+    /// Signed along with the payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authority: Option<AuthorityOp>,
+    /// Condition a block must meet to confirm the payment. Signed along
+    /// with the payment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+    pub witness: Witness,
+    /// What the script starts with on its stack, such as the preimage of a
+    /// hash it checks. Like the witness they aren't signed, so they can be
+    /// supplied by whoever learns them after the payment was signed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub script_args: Vec<Vec<u8>>,
 }
 
 impl Transaction {
-    pub fn new(sender: String, recipient: String, amount: f64) -> Self {
+    /// Creates an unsigned payment out of `sender`.
+    pub fn new(sender: Address, recipient: Address, amount: f64, fee: f64, sequence: u64) -> Self {
         Transaction {
-            sender,
+            origin: Origin::Account(sender),
+            recipient,
+            amount,
+            fee,
+            sequence,
+            inputs: vec![],
+            memo: vec![],
+            expiry: None,
+            valid_after_height: None,
+            valid_after_time: None,
+            token: None,
+            stake: None,
+            authority: None,
+            script: None,
+            witness: Witness::Unsigned,
+            script_args: vec![],
+        }
+    }
+
+    /// Creates a payment out of the multisig address of `policy`, ready to
+    /// collect signatures from its keys with [`Wallet::cosign`].
+    ///
+    /// [`Wallet::cosign`]: crate::wallet::Wallet::cosign
+    pub fn new_multisig(
+        policy: MultisigAddress,
+        version: u8,
+        recipient: Address,
+        amount: f64,
+        fee: f64,
+        sequence: u64,
+    ) -> Self {
+        Transaction {
+            origin: Origin::Account(policy.address(version)),
             recipient,
             amount,
+            fee,
+            sequence,
+            inputs: vec![],
+            memo: vec![],
+            expiry: None,
+            valid_after_height: None,
+            valid_after_time: None,
+            token: None,
+            stake: None,
+            authority: None,
+            script: None,
+            witness: Witness::Multisig {
+                policy,
+                signatures: vec![],
+            },
+            script_args: vec![],
         }
     }
+
+    /// Creates the reward transaction for the block at `height`.
+    pub fn coinbase(recipient: Address, amount: f64, height: u64) -> Self {
+        Transaction {
+            origin: Origin::Coinbase { height },
+            recipient,
+            amount,
+            fee: 0.0,
+            sequence: 0,
+            inputs: vec![],
+            memo: vec![],
+            expiry: None,
+            valid_after_height: None,
+            valid_after_time: None,
+            token: None,
+            stake: None,
+            authority: None,
+            script: None,
+            witness: Witness::Unsigned,
+            script_args: vec![],
+        }
+    }
+
+    /// Sets the outputs this payment spends. Must be called before signing.
+    pub fn with_inputs(mut self, inputs: Vec<OutPoint>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    /// Attaches a memo. Must be called before signing.
+    pub fn with_memo(mut self, memo: Vec<u8>) -> Self {
+        self.memo = memo;
+        self
+    }
+
+    /// Sets when the payment expires. Must be called before signing.
+    pub fn with_expiry(mut self, expiry: Option<Expiry>) -> Self {
+        self.expiry = expiry;
+        self
+    }
+
+    /// Sets the height only blocks above which may confirm the payment.
+    /// Must be called before signing.
+    pub fn with_valid_after_height(mut self, height: Option<u64>) -> Self {
+        self.valid_after_height = height;
+        self
+    }
+
+    /// Sets the time only blocks after which may confirm the payment. Must
+    /// be called before signing.
+    pub fn with_valid_after_time(mut self, time: Option<i64>) -> Self {
+        self.valid_after_time = time;
+        self
+    }
+
+    /// Sets the token the payment issues or moves. Must be called before
+    /// signing.
+    pub fn with_token(mut self, token: Option<TokenOp>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Sets what the payment does with stake. Must be called before
+    /// signing.
+    pub fn with_stake(mut self, stake: Option<StakeOp>) -> Self {
+        self.stake = stake;
+        self
+    }
+
+    /// Sets how the payment changes the authorities. Must be called before
+    /// signing.
+    pub fn with_authority(mut self, authority: Option<AuthorityOp>) -> Self {
+        self.authority = authority;
+        self
+    }
+
+    /// Sets the condition a block must meet to confirm the payment. Must be
+    /// called before signing.
+    pub fn with_script(mut self, script: Option<Script>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// Sets what the script starts with on its stack. May be called after
+    /// signing.
+    pub fn with_script_args(mut self, args: Vec<Vec<u8>>) -> Self {
+        self.script_args = args;
+        self
+    }
+
+    /// Fails unless the script, if there is one, passes in a block at
+    /// `height` with `timestamp`.
+    pub fn check_script(&self, height: u64, timestamp: i64) -> Result<()> {
+        let Some(script) = &self.script else {
+            return Ok(());
+        };
+        let context = ScriptContext { height, timestamp };
+        match script.eval(&self.script_args, context) {
+            Ok(_) => Ok(()),
+            Err(reason) => Err(Error::ScriptFailed {
+                id: self.id(),
+                reason,
+            }),
+        }
+    }
+
+    /// Fails if the payment refunds an HTLC that a block at `height` can't
+    /// refund yet.
+    pub fn check_htlc(&self, height: u64) -> Result<()> {
+        match self.sender() {
+            Some(sender) => self.witness.check_height(sender, height),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails if a block at `height` with `timestamp` is too early to include
+    /// this payment.
+    pub fn check_timelock(&self, height: u64, timestamp: i64) -> Result<()> {
+        match (self.valid_after_height, self.valid_after_time) {
+            (Some(after), _) if height <= after => Err(Error::LockedUntilHeight {
+                id: self.id(),
+                height: after,
+            }),
+            (_, Some(after)) if timestamp <= after => Err(Error::LockedUntilTime {
+                id: self.id(),
+                time: after,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a block at `height` with `timestamp` is too late to include
+    /// this payment.
+    pub fn check_expiry(&self, height: u64, timestamp: i64) -> Result<()> {
+        match self.expiry {
+            Some(expiry) if expiry.is_expired(height, timestamp) => Err(Error::Expired {
+                id: self.id(),
+                expiry,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if the memo is longer than [`MAX_MEMO_LEN`].
+    pub fn check_memo(&self) -> Result<()> {
+        if self.memo.len() > MAX_MEMO_LEN {
+            return Err(Error::MemoTooLong {
+                len: self.memo.len(),
+                max: MAX_MEMO_LEN,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fails unless the amount and fee are finite and not negative, any
+    /// stake unstaked or slashed is finite and positive, and a payment moves
+    /// coins or does something else: only coinbases, payments back to their
+    /// sender, as cancelling one does, and payments carrying a token, stake
+    /// or authority operation may have no amount.
+    pub fn check_amounts(&self) -> Result<()> {
+        let moves_nothing = self.amount == 0.0
+            && !self.is_coinbase()
+            && self.sender() != Some(&self.recipient)
+            && self.token.is_none()
+            && self.stake.is_none()
+            && self.authority.is_none();
+        let staked = match &self.stake {
+            Some(StakeOp::Unstake { amount } | StakeOp::Slash { amount, .. }) => Some(*amount),
+            Some(StakeOp::Stake) | None => None,
+        };
+        let invalid_stake = staked.is_some_and(|amount| !amount.is_finite() || amount <= 0.0);
+        if !self.amount.is_finite()
+            || self.amount < 0.0
+            || !self.fee.is_finite()
+            || self.fee < 0.0
+            || invalid_stake
+            || moves_nothing
+        {
+            return Err(Error::InvalidAmount { id: self.id() });
+        }
+        Ok(())
+    }
+
+    /// What the recipient's spendable balance gains: the amount, unless
+    /// staking locks it, plus whatever unstaking releases.
+    pub fn credited(&self) -> f64 {
+        match &self.stake {
+            Some(StakeOp::Stake) => 0.0,
+            Some(StakeOp::Unstake { amount }) => self.amount + amount,
+            _ => self.amount,
+        }
+    }
+
+    /// Stake the payment destroys by slashing a validator.
+    pub fn burned(&self) -> f64 {
+        match &self.stake {
+            Some(StakeOp::Slash { amount, .. }) => *amount,
+            _ => 0.0,
+        }
+    }
+
+    /// The paying account, or `None` for a coinbase.
+    pub fn sender(&self) -> Option<&Address> {
+        match &self.origin {
+            Origin::Account(address) => Some(address),
+            Origin::Coinbase { .. } => None,
+        }
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        matches!(self.origin, Origin::Coinbase { .. })
+    }
+
+    pub fn id(&self) -> TxId {
+        TxId(Sha256::digest(self.signing_bytes()).into())
+    }
+
+    /// Length of the encoded transaction in bytes, which the pool's fee
+    /// rate is measured against.
+    pub fn size(&self) -> usize {
+        self.encode().len()
+    }
+
+    /// Fee paid per byte of [`Transaction::size`].
+    pub fn fee_rate(&self) -> f64 {
+        self.fee / self.size() as f64
+    }
+
+    /// The bytes signatures are made over: every field but the witness and
+    /// the script's arguments.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.origin.encode_to(&mut out);
+        self.recipient.encode_to(&mut out);
+        self.amount.encode_to(&mut out);
+        self.fee.encode_to(&mut out);
+        self.sequence.encode_to(&mut out);
+        self.inputs.encode_to(&mut out);
+        self.memo.encode_to(&mut out);
+        self.expiry.encode_to(&mut out);
+        self.valid_after_height.encode_to(&mut out);
+        self.valid_after_time.encode_to(&mut out);
+        self.token.encode_to(&mut out);
+        self.stake.encode_to(&mut out);
+        self.authority.encode_to(&mut out);
+        self.script.encode_to(&mut out);
+        out
+    }
+}
+
+impl Encode for Transaction {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.signing_bytes());
+        self.witness.encode_to(out);
+        self.script_args.encode_to(out);
+    }
 }
 
-impl ToString for Transaction {
-    fn to_string(&self) -> String {
-        format!(
-            r#"
-        {{
-            "sender": "{}",
-            "recipient": "{}",
-            "amount": {}
-        }}
-        "#,
-            self.sender, self.recipient, self.amount
-        )
+impl Decode for Transaction {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(Transaction {
+            origin: Origin::decode_from(reader)?,
+            recipient: Address::decode_from(reader)?,
+            amount: f64::decode_from(reader)?,
+            fee: f64::decode_from(reader)?,
+            sequence: u64::decode_from(reader)?,
+            inputs: Vec::decode_from(reader)?,
+            memo: Vec::decode_from(reader)?,
+            expiry: Option::decode_from(reader)?,
+            valid_after_height: Option::decode_from(reader)?,
+            valid_after_time: Option::decode_from(reader)?,
+            token: Option::decode_from(reader)?,
+            stake: Option::decode_from(reader)?,
+            authority: Option::decode_from(reader)?,
+            script: Option::decode_from(reader)?,
+            witness: Witness::decode_from(reader)?,
+            script_args: Vec::decode_from(reader)?,
+        })
+    }
+}
+
+impl std::fmt::Display for Transaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sender = match &self.origin {
+            Origin::Account(address) => format!("{}#{}", address, self.sequence),
+            Origin::Coinbase { height } => format!("coinbase:{}", height),
+        };
+        write!(
+            f,
+            "{} {} -> {} {} (fee {})",
+            self.id(),
+            sender,
+            self.recipient,
+            self.amount,
+            self.fee
+        )?;
+        if !self.memo.is_empty() {
+            write!(f, " memo {:?}", String::from_utf8_lossy(&self.memo))?;
+        }
+        if let Some(expiry) = &self.expiry {
+            write!(f, " expires after {}", expiry)?;
+        }
+        if let Some(height) = self.valid_after_height {
+            write!(f, " valid after height {}", height)?;
+        }
+        if let Some(time) = self.valid_after_time {
+            write!(f, " valid after {}", Expiry::Time(time))?;
+        }
+        match &self.token {
+            Some(TokenOp::Issue { name, supply }) => write!(f, " issues {} {}", supply, name)?,
+            Some(TokenOp::Transfer { id, amount }) => write!(f, " moves {} of {}", amount, id)?,
+            None => {}
+        }
+        match &self.stake {
+            Some(StakeOp::Stake) => write!(f, " stakes it")?,
+            Some(StakeOp::Unstake { amount }) => write!(f, " unstakes {}", amount)?,
+            Some(StakeOp::Slash { evidence, amount }) => {
+                write!(f, " slashes {} of {}", amount, evidence.first.miner())?
+            }
+            None => {}
+        }
+        match &self.authority {
+            Some(AuthorityOp::Add { address }) => write!(f, " adds authority {}", address)?,
+            Some(AuthorityOp::Remove { address }) => write!(f, " removes authority {}", address)?,
+            None => {}
+        }
+        if let Some(script) = &self.script {
+            write!(f, " if {}", script)?;
+        }
+        Ok(())
     }
 }
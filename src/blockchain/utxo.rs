@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    block::Block,
+    transaction::{Transaction, TxId},
+    Error, Result,
+};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode, Reader},
+};
+
+/// How balances are tracked and spends are checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ledger {
+    /// Each account has a balance and payments are ordered by sequence
+    /// number.
+    #[default]
+    Account,
+    /// Payments also spend earlier outputs by reference, so conflicting
+    /// spends are detectable from the transactions alone.
+    Utxo,
+}
+
+impl std::str::FromStr for Ledger {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "account" => Ok(Ledger::Account),
+            "utxo" => Ok(Ledger::Utxo),
+            _ => Err(format!("unknown ledger {}", s)),
+        }
+    }
+}
+
+/// Reference to an output: the transaction that created it and its position
+/// among that transaction's outputs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: TxId,
+    pub index: u32,
+}
+
+impl std::fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.txid, self.index)
+    }
+}
+
+impl Encode for OutPoint {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.txid.encode_to(out);
+        self.index.encode_to(out);
+    }
+}
+
+impl Decode for OutPoint {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(OutPoint {
+            txid: TxId::decode_from(reader)?,
+            index: u32::decode_from(reader)?,
+        })
+    }
+}
+
+/// Condition an output's spender has to meet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Lock {
+    /// Spendable by a transaction from this address, whose witness already
+    /// proves control of it.
+    Address(Address),
+}
+
+impl Lock {
+    pub fn unlocked_by(&self, sender: &Address) -> bool {
+        match self {
+            Lock::Address(address) => address == sender,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TxOut {
+    pub value: f64,
+    pub lock: Lock,
+}
+
+/// Every unspent output on the main chain.
+///
+/// Outputs are implied by the transactions creating them: output 0 pays the
+/// recipient and, for payments, output 1 returns whatever the inputs hold
+/// beyond the amount and fee to the sender.
+#[derive(Clone, Default)]
+pub struct UtxoSet {
+    outputs: HashMap<OutPoint, TxOut>,
+    /// Outputs each connected block spent, so it can be disconnected again.
+    undo: HashMap<String, Vec<(OutPoint, TxOut)>>,
+}
+
+impl UtxoSet {
+    pub fn build<'a, I: IntoIterator<Item = &'a Block>>(blocks: I) -> Result<Self> {
+        let mut set = UtxoSet::default();
+        for block in blocks {
+            set.apply_block(block)?;
+        }
+        Ok(set)
+    }
+
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOut> {
+        self.outputs.get(outpoint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    /// Unspent outputs `address` can spend, in no particular order.
+    pub fn unspent_for(&self, address: &Address) -> Vec<(OutPoint, TxOut)> {
+        self.outputs
+            .iter()
+            .filter(|(_, output)| output.lock.unlocked_by(address))
+            .map(|(outpoint, output)| (outpoint.clone(), output.clone()))
+            .collect()
+    }
+
+    /// Spends the inputs of every transaction in `block` and adds their
+    /// outputs, failing without changes if any spend is invalid.
+    pub fn apply_block(&mut self, block: &Block) -> Result<()> {
+        let mut view = UtxoView::new(self);
+        for transaction in block.transactions() {
+            view.spend(transaction)?;
+        }
+        let UtxoView { created, spent, .. } = view;
+        let spent: Vec<(OutPoint, TxOut)> = spent
+            .into_iter()
+            .filter_map(|outpoint| {
+                let output = self.outputs.remove(&outpoint)?;
+                Some((outpoint, output))
+            })
+            .collect();
+        for (outpoint, output) in created {
+            self.outputs.insert(outpoint, output);
+        }
+        self.undo.insert(block.hash(), spent);
+        Ok(())
+    }
+
+    /// Undoes [`UtxoSet::apply_block`] for the current tip.
+    pub fn revert_block(&mut self, block: &Block) {
+        for transaction in block.transactions() {
+            let txid = transaction.id();
+            for index in 0..2 {
+                self.outputs.remove(&OutPoint {
+                    txid: txid.clone(),
+                    index,
+                });
+            }
+        }
+        for (outpoint, output) in self.undo.remove(&block.hash()).unwrap_or_default() {
+            self.outputs.insert(outpoint, output);
+        }
+    }
+}
+
+/// The UTXO set as it would be after a sequence of transactions, without
+/// touching the set itself. Used to check pending transactions and blocks,
+/// and to choose inputs for new payments.
+pub struct UtxoView<'a> {
+    base: &'a UtxoSet,
+    created: HashMap<OutPoint, TxOut>,
+    spent: HashSet<OutPoint>,
+}
+
+impl<'a> UtxoView<'a> {
+    pub fn new(base: &'a UtxoSet) -> Self {
+        UtxoView {
+            base,
+            created: HashMap::new(),
+            spent: HashSet::new(),
+        }
+    }
+
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOut> {
+        if self.spent.contains(outpoint) {
+            return None;
+        }
+        self.created
+            .get(outpoint)
+            .or_else(|| self.base.get(outpoint))
+    }
+
+    /// Checks that `transaction` only spends available outputs locked to its
+    /// sender that cover its amount and fee, then records the spend.
+    pub fn spend(&mut self, transaction: &Transaction) -> Result<()> {
+        let txid = transaction.id();
+        let Some(sender) = transaction.sender() else {
+            self.created.insert(
+                OutPoint { txid, index: 0 },
+                TxOut {
+                    value: transaction.amount,
+                    lock: Lock::Address(transaction.recipient),
+                },
+            );
+            return Ok(());
+        };
+        let mut available = 0.0;
+        let mut inputs = HashSet::new();
+        for input in &transaction.inputs {
+            if !inputs.insert(input) || self.spent.contains(input) {
+                return Err(Error::DoubleSpend {
+                    output: input.clone(),
+                });
+            }
+            let output = self.get(input).ok_or_else(|| Error::UnknownOutput {
+                output: input.clone(),
+            })?;
+            if !output.lock.unlocked_by(sender) {
+                return Err(Error::ForeignOutput {
+                    output: input.clone(),
+                    sender: *sender,
+                });
+            }
+            available += output.value;
+        }
+        let required = transaction.amount + transaction.fee;
+        if available < required {
+            return Err(Error::InsufficientFunds {
+                available,
+                required,
+            });
+        }
+        for input in &transaction.inputs {
+            if self.created.remove(input).is_none() {
+                self.spent.insert(input.clone());
+            }
+        }
+        self.created.insert(
+            OutPoint {
+                txid: txid.clone(),
+                index: 0,
+            },
+            TxOut {
+                value: transaction.amount,
+                lock: Lock::Address(transaction.recipient),
+            },
+        );
+        let change = available - required;
+        if change > 0.0 {
+            self.created.insert(
+                OutPoint { txid, index: 1 },
+                TxOut {
+                    value: change,
+                    lock: Lock::Address(*sender),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Outputs `address` can spend, in no particular order.
+    pub fn unspent_for(&self, address: &Address) -> Vec<(OutPoint, TxOut)> {
+        self.base
+            .outputs
+            .iter()
+            .chain(self.created.iter())
+            .filter(|(outpoint, output)| {
+                output.lock.unlocked_by(address) && !self.spent.contains(*outpoint)
+            })
+            .map(|(outpoint, output)| (outpoint.clone(), output.clone()))
+            .collect()
+    }
+
+    /// Picks outputs spendable by `address` worth at least `amount`, largest
+    /// first, out of those `eligible` accepts.
+    pub fn select<F: Fn(&OutPoint) -> bool>(
+        &self,
+        address: &Address,
+        amount: f64,
+        eligible: F,
+    ) -> Result<Vec<OutPoint>> {
+        let mut candidates = self.unspent_for(address);
+        candidates.retain(|(outpoint, _)| eligible(outpoint));
+        candidates.sort_by(|a, b| b.1.value.total_cmp(&a.1.value));
+        let mut selected = vec![];
+        let mut total = 0.0;
+        for (outpoint, output) in candidates {
+            let value = output.value;
+            if total >= amount {
+                break;
+            }
+            total += value;
+            selected.push(outpoint);
+        }
+        if total < amount {
+            return Err(Error::InsufficientFunds {
+                available: total,
+                required: amount,
+            });
+        }
+        Ok(selected)
+    }
+}
@@ -0,0 +1,244 @@
+//! Blocks and payments that are well formed and properly signed but break
+//! the rules of the ledger, which the pool and block validation must both
+//! refuse.
+
+use super::{
+    Block, BlockBody, CancelToken, DoubleSign, Engine, Error, Mined, StakeLedger, StakeOp,
+    Transaction,
+};
+use crate::{
+    codec::{Decode, Encode},
+    testing::{self, TestChain},
+    wallet::{UnsignedTransaction, Wallet},
+};
+
+const SEED: u64 = 23;
+
+/// A block on the tip paying `miner` the reward and fees, with
+/// `transactions` after the coinbase, sealed and signed but not checked.
+fn forge(chain: &mut TestChain, miner: &Wallet, transactions: Vec<Transaction>) -> Block {
+    chain.clock.advance(testing::BLOCK_INTERVAL);
+    let blockchain = &chain.blockchain;
+    let height = blockchain.chain_height().unwrap() + 1;
+    let fees: f64 = transactions.iter().map(|t| t.fee).sum();
+    let reward = blockchain.consensus.emission().reward_at(height);
+    let coinbase = Transaction::coinbase(*miner.address(), reward + fees, height);
+    let transactions = std::iter::once(coinbase).chain(transactions).collect();
+    let block = blockchain.new_block(miner.address(), transactions).unwrap();
+    Mined::new(
+        block,
+        blockchain.consensus.as_ref(),
+        miner,
+        &CancelToken::new(),
+    )
+    .unwrap()
+    .block
+}
+
+/// Payments from an unfunded wallet to a funded one, taking its coins with
+/// a negative amount, or with one that isn't a number.
+fn thefts(chain: &mut TestChain) -> Vec<Transaction> {
+    let victim = testing::wallet(2);
+    chain.fund(victim.address(), 50.0, &testing::wallet(1));
+    let mut thief = testing::wallet(3);
+    [-50.0, f64::NAN]
+        .into_iter()
+        .map(|amount| {
+            thief
+                .sign_transaction(victim.address(), amount, 0.0, 0, vec![], vec![])
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn pool_refuses_negative_and_nan_amounts() {
+    let mut chain = TestChain::new(SEED);
+    for theft in thefts(&mut chain) {
+        assert!(matches!(
+            chain.blockchain.add_transation_to_pool(theft),
+            Err(Error::InvalidAmount { .. })
+        ));
+    }
+    assert_eq!(chain.blockchain.pending_transaction_count(), 0);
+}
+
+#[test]
+fn blocks_with_negative_and_nan_amounts_are_refused() {
+    let mut chain = TestChain::new(SEED);
+    let miner = testing::wallet(1);
+    for theft in thefts(&mut chain) {
+        let block = forge(&mut chain, &miner, vec![theft]);
+        assert!(matches!(
+            chain.blockchain.connect_block(block),
+            Err(Error::InvalidAmount { .. })
+        ));
+    }
+    assert!(chain.blockchain.verify_state_against_chain().is_ok());
+}
+
+/// A payment from a wallet that never received anything.
+fn overdraft() -> Transaction {
+    testing::wallet(3)
+        .sign_transaction(testing::wallet(2).address(), 10.0, 0.0, 0, vec![], vec![])
+        .unwrap()
+}
+
+#[test]
+fn blocks_spending_more_than_the_sender_holds_are_refused() {
+    let mut chain = TestChain::new(SEED);
+    let block = forge(&mut chain, &testing::wallet(1), vec![overdraft()]);
+    assert!(matches!(
+        chain.blockchain.connect_block(block),
+        Err(Error::Overdrawn { .. })
+    ));
+    assert!(chain.blockchain.verify_state_against_chain().is_ok());
+}
+
+#[test]
+fn reorg_onto_an_invalid_branch_keeps_the_old_tip() {
+    let mut chain = TestChain::new(SEED);
+    let miner = testing::wallet(1);
+    chain.mine(&miner);
+    let tip = forge(&mut chain, &miner, vec![]);
+    let valid = forge(&mut chain, &miner, vec![]);
+    chain.blockchain.connect_block(valid).unwrap();
+    let invalid = forge(&mut chain, &miner, vec![overdraft()]);
+    chain.blockchain.disconnect_tip().unwrap();
+    chain.blockchain.connect_block(tip.clone()).unwrap();
+    let height = chain.blockchain.chain_height().unwrap();
+
+    assert!(matches!(
+        chain.blockchain.receive_block(invalid),
+        Err(Error::Overdrawn { .. })
+    ));
+    assert_eq!(chain.blockchain.chain_height().unwrap(), height);
+    assert_eq!(chain.blockchain.last_block().unwrap().hash(), tip.hash());
+    assert!(chain.blockchain.verify_state_against_chain().is_ok());
+}
+
+#[test]
+fn bodies_repeating_their_last_transaction_are_refused() {
+    let mut chain = TestChain::new(SEED);
+    let miner = testing::wallet(1);
+    let mut payer = testing::wallet(2);
+    chain.fund(payer.address(), 50.0, &miner);
+    for _ in 0..2 {
+        chain
+            .pay(&mut payer, testing::wallet(3).address(), 1.0, 0.1)
+            .unwrap();
+    }
+    let block = chain.mine(&miner);
+    let mut transactions = block.transactions().clone();
+    assert_eq!(transactions.len(), 3);
+    transactions.push(transactions[2].clone());
+    let body = BlockBody::new(transactions);
+    assert_eq!(body.merkle_root(), block.merkle_root());
+
+    assert!(Block::from_parts(block.header().clone(), body.clone()).is_none());
+    let mut bytes = block.header().encode();
+    Some(body).encode_to(&mut bytes);
+    assert!(Block::decode(&bytes).is_err());
+}
+
+/// `sender` unstaking `amount` it never staked, signed.
+fn unstake(sender: &Wallet, amount: f64) -> Transaction {
+    let unstake = Transaction::new(*sender.address(), *sender.address(), 0.0, 0.0, 0)
+        .with_stake(Some(StakeOp::Unstake { amount }));
+    sender
+        .sign_unsigned(&UnsignedTransaction::new(unstake))
+        .unwrap()
+        .transaction()
+        .clone()
+}
+
+#[test]
+fn unstaking_nan_is_refused() {
+    let mut chain = TestChain::new(SEED);
+    let thief = testing::wallet(3);
+    assert!(matches!(
+        chain
+            .blockchain
+            .add_transation_to_pool(unstake(&thief, f64::NAN)),
+        Err(Error::InvalidAmount { .. })
+    ));
+    let block = forge(
+        &mut chain,
+        &testing::wallet(1),
+        vec![unstake(&thief, f64::NAN)],
+    );
+    assert!(matches!(
+        chain.blockchain.connect_block(block),
+        Err(Error::InvalidAmount { .. })
+    ));
+    assert_eq!(chain.blockchain.pending_transaction_count(), 0);
+}
+
+#[test]
+fn stake_operations_need_proof_of_stake() {
+    let mut chain = TestChain::new(SEED);
+    let staker = testing::wallet(2);
+    chain.fund(staker.address(), 50.0, &testing::wallet(1));
+    let stake = Transaction::new(*staker.address(), *staker.address(), 10.0, 0.0, 0)
+        .with_stake(Some(StakeOp::Stake));
+    let stake = staker
+        .sign_unsigned(&UnsignedTransaction::new(stake))
+        .unwrap();
+    assert!(matches!(
+        chain
+            .blockchain
+            .add_transation_to_pool(stake.transaction().clone()),
+        Err(Error::InvalidStake { .. })
+    ));
+}
+
+#[test]
+fn evidence_slashes_only_once() {
+    let mut chain = TestChain::new(SEED);
+    let validator = testing::wallet(2);
+    let evidence = DoubleSign::new(
+        &forge(&mut chain, &validator, vec![]),
+        &forge(&mut chain, &validator, vec![]),
+    );
+    let reporter = *testing::wallet(3).address();
+    let slash =
+        Transaction::new(reporter, reporter, 0.0, 0.0, 0).with_stake(Some(StakeOp::Slash {
+            evidence: Box::new(evidence),
+            amount: 10.0,
+        }));
+    let stake = Transaction::new(*validator.address(), *validator.address(), 10.0, 0.0, 0)
+        .with_stake(Some(StakeOp::Stake));
+    let mut stakes = StakeLedger::from_parts([(*validator.address(), 10.0)], []);
+
+    stakes.check(&slash, 0.0).unwrap();
+    stakes.apply(&slash);
+    stakes.apply(&stake);
+    assert!(matches!(
+        stakes.check(&slash, 0.0),
+        Err(Error::InvalidEvidence { .. })
+    ));
+    stakes.revert(&stake);
+    stakes.revert(&slash);
+    assert!(stakes.check(&slash, 0.0).is_ok());
+}
+
+#[test]
+fn pending_stake_is_not_spendable() {
+    let mut config = testing::config();
+    config.consensus.engine = Engine::Pos;
+    let mut chain = TestChain::with_config(SEED, &config);
+    let staker = testing::wallet(2);
+    chain.fund(staker.address(), 50.0, &testing::wallet(1));
+    let stake = Transaction::new(*staker.address(), *staker.address(), 10.0, 0.0, 0)
+        .with_stake(Some(StakeOp::Stake));
+    let stake = staker
+        .sign_unsigned(&UnsignedTransaction::new(stake))
+        .unwrap();
+    chain
+        .blockchain
+        .add_transation_to_pool(stake.transaction().clone())
+        .unwrap();
+
+    let balance = chain.blockchain.balance(staker.address(), 0).unwrap();
+    assert_eq!(balance.total(), 40.0);
+}
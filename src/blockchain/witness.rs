@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+
+use super::{htlc::Htlc, Error, Result};
+use crate::{
+    address::{Address, MultisigAddress},
+    codec::{self, Decode, Encode, Reader},
+    crypto::{PublicKey, Signature},
+};
+
+/// Signature by one of the keys of a multisig policy, identified by its
+/// position in [`MultisigAddress::keys`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cosignature {
+    pub key_index: u8,
+    #[serde(with = "codec::as_hex")]
+    pub signature: Signature,
+}
+
+/// Proof that the sender of a transaction authorized it. The witness is not
+/// part of the transaction id, so collecting signatures doesn't change it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Witness {
+    /// Coinbases, and transactions that still have to be signed.
+    #[default]
+    Unsigned,
+    /// Signature by the key the sender address was derived from.
+    Single {
+        #[serde(with = "codec::as_hex")]
+        public_key: PublicKey,
+        #[serde(with = "codec::as_hex")]
+        signature: Signature,
+    },
+    /// Signatures by keys of the policy the sender address was derived from.
+    Multisig {
+        policy: MultisigAddress,
+        signatures: Vec<Cosignature>,
+    },
+    /// Signature spending out of the address of an [`Htlc`]: by its
+    /// recipient along with the preimage, or by its refund key once it
+    /// timed out, which is checked with the block's height.
+    Htlc {
+        htlc: Htlc,
+        /// Set when claiming, left out when refunding.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preimage: Option<Vec<u8>>,
+        #[serde(with = "codec::as_hex")]
+        signature: Signature,
+    },
+}
+
+impl Witness {
+    /// Checks that the witness authorizes `message` on behalf of `sender`.
+    pub fn verify(&self, sender: &Address, message: &[u8]) -> Result<()> {
+        match self {
+            Witness::Unsigned => Err(Error::MissingSignature { sender: *sender }),
+            Witness::Single {
+                public_key,
+                signature,
+            } => {
+                if Address::from_public_key(public_key, sender.version()) != *sender {
+                    return Err(Error::ForeignKey { sender: *sender });
+                }
+                Ok(public_key.verify(message, signature)?)
+            }
+            Witness::Multisig { policy, signatures } => {
+                if policy.address(sender.version()) != *sender {
+                    return Err(Error::ForeignPolicy { sender: *sender });
+                }
+                let mut signed = vec![false; policy.keys().len()];
+                for cosignature in signatures {
+                    let index = usize::from(cosignature.key_index);
+                    let Some(key) = policy.keys().get(index) else {
+                        return Err(Error::UnknownCosigner { index });
+                    };
+                    key.verify(message, &cosignature.signature)?;
+                    signed[index] = true;
+                }
+                let provided = signed.iter().filter(|s| **s).count();
+                let required = usize::from(policy.threshold());
+                if provided < required {
+                    return Err(Error::ThresholdNotMet { required, provided });
+                }
+                Ok(())
+            }
+            Witness::Htlc {
+                htlc,
+                preimage,
+                signature,
+            } => {
+                if htlc.address(sender.version()) != *sender {
+                    return Err(Error::ForeignHtlc { sender: *sender });
+                }
+                let key = match preimage {
+                    Some(preimage) if !htlc.unlocks(preimage) => {
+                        return Err(Error::WrongPreimage { sender: *sender })
+                    }
+                    Some(_) => &htlc.recipient,
+                    None => &htlc.refund,
+                };
+                Ok(key.verify(message, signature)?)
+            }
+        }
+    }
+
+    /// Fails if the witness refunds an [`Htlc`] before a block at `height`
+    /// may.
+    pub fn check_height(&self, sender: &Address, height: u64) -> Result<()> {
+        match self {
+            Witness::Htlc {
+                htlc,
+                preimage: None,
+                ..
+            } if height < htlc.timeout => Err(Error::HtlcLocked {
+                sender: *sender,
+                timeout: htlc.timeout,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Encode for Cosignature {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.key_index.encode_to(out);
+        self.signature.encode_to(out);
+    }
+}
+
+impl Decode for Cosignature {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(Cosignature {
+            key_index: u8::decode_from(reader)?,
+            signature: Signature::decode_from(reader)?,
+        })
+    }
+}
+
+impl Encode for Witness {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Witness::Unsigned => 0u8.encode_to(out),
+            Witness::Single {
+                public_key,
+                signature,
+            } => {
+                1u8.encode_to(out);
+                public_key.encode_to(out);
+                signature.encode_to(out);
+            }
+            Witness::Multisig { policy, signatures } => {
+                2u8.encode_to(out);
+                policy.encode_to(out);
+                signatures.encode_to(out);
+            }
+            Witness::Htlc {
+                htlc,
+                preimage,
+                signature,
+            } => {
+                3u8.encode_to(out);
+                htlc.encode_to(out);
+                preimage.encode_to(out);
+                signature.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for Witness {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(Witness::Unsigned),
+            1 => Ok(Witness::Single {
+                public_key: PublicKey::decode_from(reader)?,
+                signature: Signature::decode_from(reader)?,
+            }),
+            2 => Ok(Witness::Multisig {
+                policy: MultisigAddress::decode_from(reader)?,
+                signatures: Vec::decode_from(reader)?,
+            }),
+            3 => Ok(Witness::Htlc {
+                htlc: Htlc::decode_from(reader)?,
+                preimage: Option::decode_from(reader)?,
+                signature: Signature::decode_from(reader)?,
+            }),
+            tag => Err(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
@@ -0,0 +1,1712 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{IpAddr, TcpStream},
+    path::PathBuf,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use aeonia::wallet::{call_daemon, DaemonRequest, DaemonResponse, DaemonSigner, WalletDaemon};
+use aeonia::{
+    address::{Address, MultisigAddress},
+    blockchain::{
+        AuthorityOp, BalanceProof, Blockchain, Expiry, OutPoint, Receipt, Script, StakeOp, TokenId,
+        TokenOp, Transaction, TxId,
+    },
+    clock::{Clock, SystemClock},
+    config::Config,
+    crypto::{PublicKey, SignatureScheme},
+    miner::MinerConfig,
+    network::{Network, BAN_DURATION},
+    node::Node,
+    storage::FileStore,
+    stratum::{self, Reply, StratumConfig},
+    wallet::{
+        EncryptedWallet, PaymentRequest, SignedTransaction, UnsignedTransaction, Wallet,
+        WatchWallet,
+    },
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+#[cfg(unix)]
+use std::{path::Path, sync::Arc};
+
+/// Blocks between the progress lines `node import` prints.
+const IMPORT_PROGRESS_INTERVAL: u64 = 1000;
+
+#[derive(Parser)]
+#[command(name = "aeonia", about = "A fun little homemade blockchain")]
+pub struct Cli {
+    /// TOML node configuration; AEONIA_* environment variables override it
+    /// and flags override both
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Network preset to run on: mainnet, testnet or regtest. It sets the
+    /// defaults of the other settings
+    #[arg(long, global = true)]
+    network: Option<Network>,
+    /// Directory holding the chain and wallet files
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+    /// Address version byte used for new wallets and the chain
+    #[arg(long, global = true)]
+    address_version: Option<u8>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage wallets
+    #[command(subcommand)]
+    Wallet(WalletCommand),
+    /// Create and submit transactions
+    #[command(subcommand)]
+    Tx(TxCommand),
+    /// Mine a single block
+    Mine {
+        /// Address of the stored wallet receiving the reward and signing
+        /// the block
+        #[arg(long)]
+        miner: Address,
+    },
+    /// Inspect the chain
+    #[command(subcommand)]
+    Chain(ChainCommand),
+    /// Inspect the transaction pool
+    #[command(subcommand)]
+    Mempool(MempoolCommand),
+    /// Run a node
+    #[command(subcommand)]
+    Node(NodeCommand),
+    /// Lend hash power to a node serving stratum, until interrupted
+    Worker {
+        /// Stratum address of the node, e.g. 10.0.0.2:3333
+        node: String,
+        /// Name the node counts this worker's shares under
+        #[arg(long, default_value = "worker")]
+        name: String,
+    },
+    /// Browse blocks, the pool and balances in an interactive terminal UI
+    #[cfg(feature = "tui")]
+    Explore {
+        /// Extra addresses to show balances for, besides stored wallets
+        #[arg(long = "watch")]
+        watched: Vec<Address>,
+        /// Milliseconds between reloads of the chain from the data directory
+        #[arg(long, default_value_t = 1000)]
+        refresh: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum WalletCommand {
+    /// Create a new wallet and store its key in the data directory
+    New {
+        /// Derive the key from a new mnemonic, printed after the address
+        #[arg(long, conflicts_with = "scheme")]
+        hd: bool,
+        /// Signature scheme of the key: p256, secp256k1 or ed25519
+        #[arg(long, default_value_t = SignatureScheme::P256)]
+        scheme: SignatureScheme,
+    },
+    /// Restore a wallet from its backup phrase
+    Restore {
+        phrase: String,
+        /// Scheme of a 24 word private key phrase, if not p256
+        #[arg(long)]
+        scheme: Option<SignatureScheme>,
+    },
+    /// Print the backup phrase of a stored wallet
+    Backup { address: Address },
+    /// Print the M-of-N multisig address of several stored wallets
+    Multisig(MultisigArgs),
+    /// Derive another account from a stored mnemonic wallet
+    Derive {
+        address: Address,
+        #[arg(long)]
+        account: u32,
+    },
+    /// List the addresses of stored wallets, encrypted ones marked
+    List,
+    /// Encrypt a stored wallet's key under a passphrase and delete the
+    /// plaintext copy; only `wallet daemon` signs with it afterwards. The
+    /// passphrase is read from AEONIA_WALLET_PASSPHRASE or standard input
+    Encrypt { address: Address },
+    /// Hold an encrypted wallet, locked, and sign for local clients such
+    /// as `node run --signer` once unlocked
+    #[cfg(unix)]
+    Daemon {
+        address: Address,
+        /// Socket to listen on, by default wallet.sock in the data
+        /// directory
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Unlock a running wallet daemon with its wallet's passphrase, read as
+    /// for `wallet encrypt`
+    #[cfg(unix)]
+    Unlock {
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Seconds without a signature after which the daemon locks again
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+    },
+    /// Make a running wallet daemon forget its decrypted key
+    #[cfg(unix)]
+    Lock {
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Show whether a running wallet daemon is unlocked
+    #[cfg(unix)]
+    Status {
+        #[arg(long)]
+        socket: Option<PathBuf>,
+    },
+    /// Manage named addresses that `tx` commands accept in place of `--to`
+    #[command(subcommand)]
+    Contacts(ContactsCommand),
+    /// Follow addresses whose keys are kept elsewhere
+    #[command(subcommand)]
+    Watch(WatchCommand),
+    /// Print a QR code of a payment URI for a stored wallet's address
+    Qr {
+        address: Address,
+        /// Amount the payer should send
+        #[arg(long)]
+        amount: Option<f64>,
+        /// Memo the payer should attach, such as an invoice number
+        #[arg(long)]
+        memo: Option<String>,
+        /// Write the code to this PNG file instead of the terminal
+        #[arg(long)]
+        png: Option<PathBuf>,
+        /// Pixels per module of the PNG
+        #[arg(long, default_value_t = 8)]
+        scale: u32,
+    },
+    /// Sign a payment written by `tx create`, which needs no chain and so
+    /// works on a machine that is never online
+    Sign {
+        /// File written by `tx create`
+        unsigned: PathBuf,
+        /// Where to write the signed payment for `tx broadcast`
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Show the confirmed and pending balance of an address
+    Balance {
+        address: Address,
+        /// Confirmations a payment needs to count as confirmed, by default
+        /// the mempool's `min_confirmations`
+        #[arg(long)]
+        min_confirmations: Option<u64>,
+    },
+    /// List the outputs an address can spend on a UTXO chain
+    Utxos { address: Address },
+    /// List the tokens an address holds
+    Tokens { address: Address },
+    /// List the confirmed transactions of an address, newest first
+    History {
+        address: Address,
+        /// Page to show, from 0
+        #[arg(long, default_value_t = 0)]
+        page: usize,
+        #[arg(long, default_value_t = 20)]
+        page_size: usize,
+    },
+    /// Show what became of the transactions a stored wallet submitted:
+    /// pending, confirmed, dropped or reorged out
+    Sent {
+        address: Address,
+        /// Show only this transaction
+        id: Option<TxId>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ContactsCommand {
+    /// Save an address under a name
+    Add { name: String, address: Address },
+    /// Forget a saved name
+    Remove { name: String },
+    /// List saved names and their addresses
+    List,
+}
+
+#[derive(Subcommand)]
+enum WatchCommand {
+    /// Watch an address, or the address of a public key
+    Add {
+        #[arg(required_unless_present = "public_key")]
+        address: Option<Address>,
+        /// Hex of a public key to watch the address of instead
+        #[arg(long, conflicts_with = "address")]
+        public_key: Option<String>,
+        /// Scheme of `--public-key`
+        #[arg(long, default_value_t = SignatureScheme::P256)]
+        scheme: SignatureScheme,
+    },
+    /// Stop watching an address
+    Remove { address: Address },
+    /// List watched addresses with their balances
+    List,
+}
+
+#[derive(Subcommand)]
+enum TxCommand {
+    /// Sign a payment with a stored wallet and add it to the pool
+    Send(SendArgs),
+    /// Sign several payments from one stored wallet with consecutive
+    /// sequence numbers and add them to the pool, all or none
+    SendBatch {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        /// A payment as `<recipient>=<amount>`, the recipient an address
+        /// or contact name; repeat for each payment
+        #[arg(long = "pay", required = true, value_parser = parse_payment)]
+        payments: Vec<(String, f64)>,
+        /// Fee paid on each payment
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Sign a payment out of a multisig address with the stored wallets of
+    /// its first `threshold` keys and add it to the pool
+    SendMultisig {
+        #[command(flatten)]
+        policy: MultisigArgs,
+        /// Recipient address or contact name
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+        /// Text for the recipient, such as an invoice number
+        #[arg(long, default_value = "")]
+        memo: String,
+    },
+    /// Replace a pending payment with one paying a higher fee
+    Replace {
+        #[command(flatten)]
+        payment: SendArgs,
+        /// Sequence number of the pending payment to replace
+        #[arg(long)]
+        sequence: u64,
+    },
+    /// Cancel a pending payment by replacing it with an empty one to the
+    /// sender paying a higher fee
+    Cancel {
+        #[arg(long)]
+        from: Address,
+        /// Sequence number of the pending payment to cancel
+        #[arg(long)]
+        sequence: u64,
+        #[arg(long)]
+        fee: f64,
+    },
+    /// Write a payment to a file without signing it, for `wallet sign` on
+    /// the machine holding the sender's key
+    Create {
+        #[command(flatten)]
+        payment: SendArgs,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Add a payment signed by `wallet sign` to the pool
+    Broadcast { signed: PathBuf },
+    /// Check whether the pool would accept a payment signed by `wallet
+    /// sign`, without adding it
+    TestAccept { signed: PathBuf },
+    /// Pay an `aeonia:` payment URI, such as one scanned from a QR code
+    Pay {
+        uri: PaymentRequest,
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        /// Amount to send when the URI doesn't name one
+        #[arg(long)]
+        amount: Option<f64>,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Request coins for an address from the faucet funded in genesis
+    Faucet {
+        /// Recipient address or contact name
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: f64,
+    },
+    /// Issue a new token, crediting its whole supply to the issuer. Its id
+    /// is the id printed
+    IssueToken {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        #[arg(long)]
+        name: String,
+        /// Units of the token in existence, for good
+        #[arg(long)]
+        supply: u64,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Send units of a token held by a stored wallet
+    SendToken {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        /// Recipient address or contact name
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        token: TokenId,
+        #[arg(long)]
+        amount: u64,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Lock coins of a stored wallet as stake, making it a validator under
+    /// proof of stake
+    Stake {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Release stake of a stored wallet back into its balance
+    Unstake {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Burn the stake of a validator that signed two blocks on the same
+    /// parent, reporting it from a stored wallet
+    Slash {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        /// Hash of one of the blocks
+        #[arg(long)]
+        first: String,
+        /// Hash of the other block
+        #[arg(long)]
+        second: String,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Make an address an authority under proof of authority, from the
+    /// stored wallet of a current one
+    AddAuthority {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        #[arg(long)]
+        address: Address,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Stop an address being an authority, from the stored wallet of a
+    /// current one
+    RemoveAuthority {
+        /// Address of a wallet stored in the data directory
+        #[arg(long)]
+        from: Address,
+        #[arg(long)]
+        address: Address,
+        #[arg(long, default_value_t = 0.0)]
+        fee: f64,
+    },
+    /// Look up a confirmed transaction by its id
+    Get { id: TxId },
+    /// Print, as JSON, proof that a transaction is in its block, for the
+    /// recipient to check with `tx verify-receipt`
+    Receipt {
+        id: TxId,
+        /// Write the receipt to this file instead
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Check a receipt written by `tx receipt` against this node's chain
+    VerifyReceipt { receipt: PathBuf },
+}
+
+#[derive(Args)]
+struct SendArgs {
+    /// Address of a wallet stored in the data directory
+    #[arg(long)]
+    from: Address,
+    /// Recipient address or contact name
+    #[arg(long)]
+    to: String,
+    #[arg(long)]
+    amount: f64,
+    /// Fee paid to the miner including the transaction
+    #[arg(long, default_value_t = 0.0)]
+    fee: f64,
+    /// Text for the recipient, such as an invoice number
+    #[arg(long, default_value = "")]
+    memo: String,
+    /// Last block height that may confirm the payment
+    #[arg(long, conflicts_with = "expires_in")]
+    expires_at_height: Option<u64>,
+    /// Seconds from now after which the payment can no longer be confirmed
+    #[arg(long)]
+    expires_in: Option<u64>,
+    /// Height only blocks above which may confirm the payment, for a
+    /// payment scheduled for later
+    #[arg(long, conflicts_with = "valid_in")]
+    valid_after_height: Option<u64>,
+    /// Seconds from now before which the payment can't be confirmed
+    #[arg(long)]
+    valid_in: Option<u64>,
+    /// Condition a block must meet to confirm the payment, such as
+    /// "HEIGHT 1000 GE"
+    #[arg(long)]
+    script: Option<Script>,
+    /// Hex encoded argument the script starts with on its stack, first
+    /// argument at the bottom
+    #[arg(long = "script-arg", value_parser = parse_hex)]
+    script_args: Vec<Vec<u8>>,
+}
+
+impl SendArgs {
+    fn expiry(&self) -> Option<Expiry> {
+        match (self.expires_at_height, self.expires_in) {
+            (Some(height), _) => Some(Expiry::Height(height)),
+            (None, Some(secs)) => {
+                let nanos = i64::try_from(Duration::from_secs(secs).as_nanos()).unwrap_or(i64::MAX);
+                Some(Expiry::Time(SystemClock.now().saturating_add(nanos)))
+            }
+            (None, None) => None,
+        }
+    }
+
+    fn valid_after_time(&self) -> Option<i64> {
+        self.valid_in.map(|secs| {
+            let nanos = i64::try_from(Duration::from_secs(secs).as_nanos()).unwrap_or(i64::MAX);
+            SystemClock.now().saturating_add(nanos)
+        })
+    }
+
+    /// The payment these arguments describe, unsigned.
+    fn transaction(&self, to: Address, sequence: u64, inputs: Vec<OutPoint>) -> Transaction {
+        Transaction::new(self.from, to, self.amount, self.fee, sequence)
+            .with_inputs(inputs)
+            .with_memo(self.memo.clone().into_bytes())
+            .with_expiry(self.expiry())
+            .with_valid_after_height(self.valid_after_height)
+            .with_valid_after_time(self.valid_after_time())
+            .with_script(self.script.clone())
+            .with_script_args(self.script_args.clone())
+    }
+}
+
+#[derive(Args)]
+struct MultisigArgs {
+    /// Number of signatures required to spend
+    #[arg(long)]
+    threshold: u8,
+    /// Addresses of the stored wallets whose keys make up the policy
+    #[arg(long = "key", required = true)]
+    keys: Vec<Address>,
+}
+
+impl MultisigArgs {
+    fn load(&self, store: &FileStore) -> io::Result<(MultisigAddress, Vec<Wallet>)> {
+        let wallets = self
+            .keys
+            .iter()
+            .map(|address| store.load_wallet(address))
+            .collect::<io::Result<Vec<_>>>()?;
+        let keys = wallets.iter().map(|w| *w.public_key()).collect();
+        Ok((MultisigAddress::new(self.threshold, keys)?, wallets))
+    }
+}
+
+#[derive(Subcommand)]
+enum ChainCommand {
+    /// Print every block and the transaction pool
+    Show,
+    /// Check the cached balances against a full replay of the chain
+    Verify,
+    /// Print the coins in existence and the reward of the next block
+    Supply,
+    /// Print, as JSON, proof of what an address received and paid out up
+    /// to a height for an auditor to check with `chain verify-balance-proof`
+    BalanceProof {
+        address: Address,
+        /// Height to prove up to; the tip by default
+        #[arg(long)]
+        height: Option<u64>,
+        /// Write the proof to this file instead
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Check a proof written by `chain balance-proof`, trusting only this
+    /// node's genesis block
+    VerifyBalanceProof { proof: PathBuf },
+    /// Print how the coins are spread, the largest holders and block and
+    /// fee averages
+    Stats {
+        /// Number of largest holders to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Suggest a fee for a payment to be confirmed soon
+    EstimateFee {
+        /// Number of blocks the payment should be confirmed within
+        #[arg(long, default_value_t = 1)]
+        blocks: u64,
+    },
+    /// List the validators and their stakes, for proof of stake
+    Validators,
+    /// List the authorities taking turns producing blocks, for proof of
+    /// authority
+    Authorities,
+    /// Write every block and pending transaction to stdout
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Put the faucet's private key in a block stream, for cloning one's
+        /// own node; never publish such a stream
+        #[arg(long)]
+        with_faucet_key: bool,
+    },
+    /// Move the whole chain state between nodes as a compressed,
+    /// checksummed archive
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// Write the stored chain and its latest checkpoint to an archive
+    Export { file: PathBuf },
+    /// Bootstrap an empty data directory from an archive, checking it first
+    Import { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum MempoolCommand {
+    /// List pending transactions in the order they would be mined
+    List,
+    /// Print the size of the pool, its limits and a fee rate histogram
+    Info,
+    /// Show where a pending transaction stands in the pool
+    Get { id: TxId },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// Pretty printed JSON document of blocks and pending transactions
+    Json,
+    /// One row per transaction
+    Csv,
+    /// One line per block
+    Table,
+    /// Binary stream of every block, signatures included, for `node import`
+    Blocks,
+}
+
+#[derive(Subcommand)]
+enum NodeCommand {
+    /// Run a background miner until interrupted, saving the chain on exit
+    Run {
+        /// Address of the stored wallet receiving the rewards and signing
+        /// blocks; enables mining even if the config doesn't
+        #[arg(long)]
+        miner: Option<Address>,
+        /// Seconds after which an empty block is mined if no transactions
+        /// arrive
+        #[arg(long)]
+        interval: Option<u64>,
+        /// Address to accept peers on, e.g. 0.0.0.0:7878
+        #[arg(long)]
+        listen: Option<String>,
+        /// Address to serve Prometheus metrics on, e.g. 127.0.0.1:9878
+        #[arg(long)]
+        metrics: Option<String>,
+        /// Address to serve stratum workers on, e.g. 0.0.0.0:3333
+        #[arg(long)]
+        stratum: Option<String>,
+        /// Address to serve the gRPC API on, e.g. 127.0.0.1:50051
+        #[cfg(feature = "grpc")]
+        #[arg(long)]
+        grpc: Option<String>,
+        /// Socket of a `wallet daemon` holding the miner's key, so the node
+        /// never loads it; the daemon has to be unlocked to mine
+        #[cfg(unix)]
+        #[arg(long)]
+        signer: Option<PathBuf>,
+    },
+    /// Rebuild balances and indexes from the stored blocks, checking every
+    /// block again
+    Reindex,
+    /// Build the chain of an empty data directory from a block stream
+    /// written by `chain export --format blocks`, checking every block
+    Import {
+        /// File or http:// URL to read the stream from
+        #[arg(long)]
+        from: String,
+        /// Stop after the block at this height
+        #[arg(long)]
+        to_height: Option<u64>,
+        /// Take over the faucet's key if the stream carries it; only from a
+        /// file, never over plain http
+        #[arg(long)]
+        with_faucet_key: bool,
+    },
+    /// Inspect the known peers and the addresses the node refuses
+    #[command(subcommand)]
+    Peers(PeersCommand),
+}
+
+#[derive(Subcommand)]
+enum PeersCommand {
+    /// List the known peers and the bans still in force
+    List,
+    /// Refuse an IP address, in both directions, for a while
+    Ban {
+        ip: IpAddr,
+        /// How long the ban lasts; a day by default
+        #[arg(long)]
+        secs: Option<u64>,
+        #[arg(long, default_value = "banned by hand")]
+        reason: String,
+    },
+    /// Lift the ban on an IP address and clear its ban score
+    Unban { ip: IpAddr },
+}
+
+impl Cli {
+    fn config(&self) -> io::Result<Config> {
+        let mut config = Config::load(self.config.as_deref(), self.network)?;
+        if let Some(data_dir) = &self.data_dir {
+            config.data_dir = data_dir.clone();
+        }
+        if let Some(address_version) = self.address_version {
+            config.address_version = address_version;
+        }
+        Ok(config)
+    }
+
+    pub fn run(self) -> io::Result<()> {
+        let config = self.config()?;
+        let store = FileStore::open_with(&config.data_dir, config.storage)?;
+        match self.command {
+            Command::Wallet(command) => run_wallet(command, &store, &config),
+            Command::Tx(command) => run_tx(command, &store, &config),
+            Command::Mine { miner } => {
+                let miner = store.load_wallet(&miner)?;
+                let mut blockchain = store.load_blockchain(&config)?;
+                let mined = blockchain.mining(&miner);
+                // Saved either way, so a new chain keeps the genesis block
+                // the next one may have to wait on.
+                store.save_blockchain(&blockchain)?;
+                if !mined {
+                    return Err(io::Error::other("mining failed"));
+                }
+                if let Some(block) = blockchain.last_block() {
+                    println!("mined block {}", block.hash());
+                }
+                Ok(())
+            }
+            Command::Mempool(command) => run_mempool(command, &store, &config),
+            Command::Chain(ChainCommand::Show) => {
+                let blockchain = store.load_blockchain(&config)?;
+                println!("{}", blockchain);
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Export {
+                format,
+                with_faucet_key,
+            }) => {
+                let blockchain = || store.load_blockchain(&config);
+                match format {
+                    ExportFormat::Json => println!("{}", blockchain()?.export_pretty()?),
+                    ExportFormat::Csv => print!("{}", blockchain()?.export()?.to_csv()),
+                    ExportFormat::Table => print!("{}", blockchain()?.export()?.to_table()),
+                    ExportFormat::Blocks => {
+                        store.export_blocks(&mut io::stdout().lock(), with_faucet_key)?;
+                    }
+                }
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Snapshot(SnapshotCommand::Export { file })) => {
+                let blocks = store.export_archive(&file)?;
+                println!("exported {} blocks to {}", blocks, file.display());
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Snapshot(SnapshotCommand::Import { file })) => {
+                let blockchain = store.import_archive(&file, &config)?;
+                println!(
+                    "imported {} blocks from {}",
+                    blockchain.chain_height()? + 1,
+                    file.display()
+                );
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Verify) => {
+                let blockchain = store.load_blockchain(&config)?;
+                blockchain.verify_state_against_chain()?;
+                println!("state matches chain");
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Supply) => {
+                let blockchain = store.load_blockchain(&config)?;
+                println!("height        {}", blockchain.chain_height()?);
+                println!("total supply  {}", blockchain.total_supply()?);
+                println!("circulating   {}", blockchain.circulating_supply()?);
+                if let Some(max) = blockchain.max_supply() {
+                    println!("max supply    {}", max);
+                }
+                println!("next reward   {}", blockchain.current_reward()?);
+                Ok(())
+            }
+            Command::Chain(ChainCommand::BalanceProof {
+                address,
+                height,
+                out,
+            }) => {
+                let blockchain = store.load_blockchain(&config)?;
+                let height = match height {
+                    Some(height) => height,
+                    None => blockchain.chain_height()?,
+                };
+                let proof = blockchain.balance_proof(&address, height)?;
+                let json = serde_json::to_string_pretty(&proof)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                match out {
+                    Some(out) => std::fs::write(out, json + "\n")?,
+                    None => println!("{}", json),
+                }
+                Ok(())
+            }
+            Command::Chain(ChainCommand::VerifyBalanceProof { proof }) => {
+                let proof: BalanceProof = serde_json::from_slice(&std::fs::read(proof)?)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let blockchain = store.load_blockchain(&config)?;
+                let genesis = blockchain.block_by_height(0).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "the chain has no genesis block")
+                })?;
+                proof.verify(&genesis, &config)?;
+                println!(
+                    "{} received at least {} and paid out at least {} in {} payments by height {}",
+                    proof.address, proof.received, proof.spent, proof.next_sequence, proof.height
+                );
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Stats { top }) => {
+                let stats = store.load_blockchain(&config)?.stats(top)?;
+                println!("height            {}", stats.height);
+                println!("total supply      {}", stats.total_supply);
+                println!("circulating       {}", stats.circulating_supply);
+                println!("active addresses  {}", stats.active_addresses);
+                println!("block interval    {:.1}s", stats.average_block_interval);
+                println!("transactions      {}", stats.transactions);
+                println!("average fee       {}", stats.average_fee);
+                for (rank, (address, balance)) in stats.top_holders.iter().enumerate() {
+                    println!("{:>4}  {}  {}", rank + 1, address, balance);
+                }
+                Ok(())
+            }
+            Command::Chain(ChainCommand::EstimateFee { blocks }) => {
+                let blockchain = store.load_blockchain(&config)?;
+                println!("{}", blockchain.estimate_fee(blocks));
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Validators) => {
+                let blockchain = store.load_blockchain(&config)?;
+                for (address, stake) in blockchain.stakes() {
+                    println!("{}  {}", address, stake);
+                }
+                Ok(())
+            }
+            Command::Chain(ChainCommand::Authorities) => {
+                let blockchain = store.load_blockchain(&config)?;
+                for address in blockchain.authorities() {
+                    println!("{}", address);
+                }
+                Ok(())
+            }
+            Command::Node(NodeCommand::Reindex) => {
+                let blockchain = store.reindex(&config)?;
+                println!("reindexed {} blocks", blockchain.chain_height()? + 1);
+                Ok(())
+            }
+            Command::Node(NodeCommand::Import {
+                from,
+                to_height,
+                with_faucet_key,
+            }) => {
+                if with_faucet_key && from.starts_with("http://") {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the faucet's key can't be imported over plain http",
+                    ));
+                }
+                let source = open_source(&from)?;
+                let blockchain =
+                    store.import_blocks(source, &config, to_height, with_faucet_key, |height| {
+                        if height > 0 && height % IMPORT_PROGRESS_INTERVAL == 0 {
+                            println!("connected block {}", height);
+                        }
+                    })?;
+                println!(
+                    "imported {} blocks from {}",
+                    blockchain.chain_height()? + 1,
+                    from
+                );
+                Ok(())
+            }
+            Command::Node(NodeCommand::Peers(command)) => run_peers(command, &store),
+            Command::Node(NodeCommand::Run {
+                miner,
+                interval,
+                listen,
+                metrics,
+                stratum,
+                #[cfg(feature = "grpc")]
+                grpc,
+                #[cfg(unix)]
+                signer,
+            }) => {
+                let miner = match miner {
+                    Some(miner) => miner,
+                    None if config.mining.enabled => config.mining.miner.ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "mining is enabled but no miner address is configured",
+                        )
+                    })?,
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "mining is disabled; pass --miner or enable it in the config",
+                        ))
+                    }
+                };
+                let interval = interval.unwrap_or(config.mining.idle_interval_secs);
+                #[cfg(unix)]
+                let miner = match signer {
+                    Some(socket) => daemon_wallet(&socket, &miner, config.address_version)?,
+                    None => store.load_wallet(&miner)?,
+                };
+                #[cfg(not(unix))]
+                let miner = store.load_wallet(&miner)?;
+                // The admin `Mine` call mines with the same wallet.
+                #[cfg(feature = "grpc")]
+                let grpc_miner = grpc.is_some().then(|| miner.clone());
+                let mut node = Node::open(store, &config)?;
+                let mut network = config.network.clone();
+                network.listen_address = listen.or(network.listen_address);
+                if let Some(address) = node.start_network(&network)? {
+                    println!("accepting peers on {}", address);
+                }
+                node.start_rebroadcast(&network)?;
+                if let Some(address) = metrics.or(config.network.metrics_address.clone()) {
+                    println!(
+                        "serving metrics on http://{}/metrics",
+                        node.start_metrics(&address)?
+                    );
+                }
+                if let Some(address) = stratum.or(config.mining.stratum_address.clone()) {
+                    let stratum = StratumConfig {
+                        share_difficulty: config.mining.share_difficulty,
+                        ..Default::default()
+                    };
+                    println!(
+                        "serving stratum on {}",
+                        node.start_stratum(&address, miner.clone(), stratum)?
+                    );
+                }
+                #[cfg(feature = "grpc")]
+                if let Some(address) = grpc {
+                    println!(
+                        "serving gRPC on {}",
+                        node.start_grpc(&address, &config.rpc, grpc_miner)?
+                    );
+                }
+                let shutdown = node.shutdown_handle();
+                ctrlc::set_handler(move || shutdown.request())
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                node.start_mining(
+                    miner,
+                    MinerConfig {
+                        idle_interval: Some(Duration::from_secs(interval)),
+                        ..Default::default()
+                    },
+                );
+                let result = node.run(|block| println!("mined block {}", block.hash()));
+                node.shutdown()?;
+                result
+            }
+            Command::Worker { node, name } => stratum::work(&node, &name, |reply| match reply {
+                Reply::Notify(job) => println!("job {} from {}", job.job_id, node),
+                Reply::Result {
+                    job_id,
+                    nonce,
+                    block: Some(block),
+                } => println!("share {} for job {} found block {}", nonce, job_id, block),
+                Reply::Result { .. } => {}
+                Reply::Error { code, message } => eprintln!("{}: {}", code, message),
+            }),
+            #[cfg(feature = "tui")]
+            Command::Explore { watched, refresh } => {
+                crate::tui::Explorer::new(&store, &config, watched, Duration::from_millis(refresh))
+                    .run()
+            }
+        }
+    }
+}
+
+/// Opens `from`, a file or an `http://` URL, for reading. URLs are
+/// fetched with a bare HTTP/1.0 request, so the body is never chunked.
+fn open_source(from: &str) -> io::Result<Box<dyn Read>> {
+    let Some(rest) = from.strip_prefix("http://") else {
+        if from.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "https isn't supported; download the stream and import the file",
+            ));
+        }
+        return Ok(Box::new(BufReader::new(File::open(from)?)));
+    };
+    let (host, path) = match rest.split_once('/') {
+        Some((host, path)) => (host, format!("/{}", path)),
+        None => (rest, "/".to_string()),
+    };
+    let address = match host.rsplit_once(':') {
+        Some((_, port)) if !port.contains(']') => host.to_string(),
+        _ => format!("{}:80", host),
+    };
+    let mut stream = TcpStream::connect(address)?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, host
+    )?;
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(io::Error::other(format!(
+            "{} answered {}",
+            from,
+            status.trim()
+        )));
+    }
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+    }
+    Ok(Box::new(reader))
+}
+
+fn run_peers(command: PeersCommand, store: &FileStore) -> io::Result<()> {
+    let mut bans = store.load_bans()?;
+    let now = SystemClock.now();
+    match command {
+        PeersCommand::List => {
+            for (addr, info) in store.load_peers()?.iter() {
+                match info.last_seen {
+                    Some(seen) => println!("{}  seen {}  {} failures", addr, seen, info.failures),
+                    None => println!("{}  never seen  {} failures", addr, info.failures),
+                }
+            }
+            for (ip, ban) in bans.iter(now) {
+                println!("{}  banned until {}: {}", ip, ban.until, ban.reason);
+            }
+        }
+        PeersCommand::Ban { ip, secs, reason } => {
+            let duration = secs.map_or(BAN_DURATION, Duration::from_secs);
+            bans.ban(ip, now, duration, &reason);
+            bans.expire(now);
+            store.save_bans(&bans)?;
+            println!("banned {}", ip);
+        }
+        PeersCommand::Unban { ip } => {
+            if !bans.unban(&ip) {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} is not banned", ip),
+                ));
+            }
+            store.save_bans(&bans)?;
+            println!("unbanned {}", ip);
+        }
+    }
+    Ok(())
+}
+
+fn run_mempool(command: MempoolCommand, store: &FileStore, config: &Config) -> io::Result<()> {
+    let blockchain = store.load_blockchain(config)?;
+    let mempool = blockchain.mempool();
+    match command {
+        MempoolCommand::List => {
+            for entry in mempool.entries() {
+                println!(
+                    "{:>5}  {}  ({} bytes, {:.3e} per byte, {} ancestors)",
+                    entry.position, entry.transaction, entry.size, entry.fee_rate, entry.ancestors
+                );
+            }
+        }
+        MempoolCommand::Info => {
+            let info = mempool.info();
+            println!("transactions  {} of {}", info.size, info.max_size);
+            println!("bytes         {}", info.bytes);
+            println!("min fee rate  {:.3e}", info.min_fee_rate);
+            for bucket in info.fee_histogram {
+                println!(
+                    ">= {:<5} per byte  {:>5} transactions  {:>8} bytes",
+                    format!("{:.0e}", bucket.min_fee_rate),
+                    bucket.count,
+                    bucket.bytes
+                );
+            }
+        }
+        MempoolCommand::Get { id } => {
+            let entry = mempool.entry(&id).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("transaction {} is not pending", id),
+                )
+            })?;
+            println!("{}", entry.transaction);
+            println!("position   {}", entry.position);
+            println!("size       {} bytes", entry.size);
+            println!("fee rate   {:.3e} per byte", entry.fee_rate);
+            println!("ancestors  {}", entry.ancestors);
+        }
+    }
+    Ok(())
+}
+
+fn run_wallet(command: WalletCommand, store: &FileStore, config: &Config) -> io::Result<()> {
+    match command {
+        WalletCommand::New { hd, scheme } => {
+            let wallet = if hd {
+                Wallet::new_hd(config.address_version)?
+            } else {
+                Wallet::with_scheme(scheme, config.address_version)?
+            };
+            store.save_wallet(&wallet)?;
+            println!("{}", wallet.address());
+            if let Some(mnemonic) = wallet.mnemonic() {
+                println!("{}", mnemonic);
+            }
+        }
+        WalletCommand::Restore { phrase, scheme } => {
+            let wallet = match scheme {
+                Some(scheme) => Wallet::from_key_mnemonic(&phrase, scheme, config.address_version)?,
+                None => Wallet::from_mnemonic(&phrase, config.address_version)?,
+            };
+            store.save_wallet(&wallet)?;
+            println!("{}", wallet.address());
+        }
+        WalletCommand::Backup { address } => {
+            println!("{}", store.load_wallet(&address)?.to_mnemonic()?.as_str());
+        }
+        WalletCommand::Multisig(args) => {
+            let (policy, _) = args.load(store)?;
+            println!("{}", policy.address(config.address_version));
+        }
+        WalletCommand::Derive { address, account } => {
+            let wallet = store.load_wallet(&address)?.derive_account(account)?;
+            store.save_wallet(&wallet)?;
+            println!("{}", wallet.address());
+        }
+        WalletCommand::List => {
+            for address in store.wallet_addresses()? {
+                println!("{}", address);
+            }
+            for address in store.keystore_addresses()? {
+                println!("{}  (encrypted)", address);
+            }
+            for watched in store.load_watch_list()?.iter() {
+                println!("{}  (watch-only)", watched.address());
+            }
+        }
+        WalletCommand::Encrypt { address } => {
+            let wallet = store.load_wallet(&address)?;
+            let passphrase = read_passphrase()?;
+            let path = store.save_keystore(&EncryptedWallet::encrypt(&wallet, &passphrase)?)?;
+            store.remove_wallet(&address)?;
+            println!("encrypted {} to {}", address, path.display());
+        }
+        #[cfg(unix)]
+        WalletCommand::Daemon { address, socket } => {
+            let daemon = Arc::new(WalletDaemon::new(store.load_keystore(&address)?));
+            let socket = socket.unwrap_or_else(|| default_socket(config));
+            println!(
+                "wallet daemon for {} locked, listening on {}",
+                address,
+                socket.display()
+            );
+            daemon.serve(&socket)?;
+        }
+        #[cfg(unix)]
+        WalletCommand::Unlock { socket, timeout } => {
+            let request = DaemonRequest::Unlock {
+                passphrase: read_passphrase()?,
+                timeout_secs: timeout,
+            };
+            print_daemon_status(call_daemon(
+                &socket.unwrap_or_else(|| default_socket(config)),
+                &request,
+            )?);
+        }
+        #[cfg(unix)]
+        WalletCommand::Lock { socket } => {
+            let socket = socket.unwrap_or_else(|| default_socket(config));
+            print_daemon_status(call_daemon(&socket, &DaemonRequest::Lock)?);
+        }
+        #[cfg(unix)]
+        WalletCommand::Status { socket } => {
+            let socket = socket.unwrap_or_else(|| default_socket(config));
+            print_daemon_status(call_daemon(&socket, &DaemonRequest::Status)?);
+        }
+        WalletCommand::Contacts(ContactsCommand::Add { name, address }) => {
+            let mut contacts = store.load_contacts()?;
+            contacts.add(&name, address)?;
+            store.save_contacts(&contacts)?;
+        }
+        WalletCommand::Contacts(ContactsCommand::Remove { name }) => {
+            let mut contacts = store.load_contacts()?;
+            contacts.remove(&name)?;
+            store.save_contacts(&contacts)?;
+        }
+        WalletCommand::Contacts(ContactsCommand::List) => {
+            for (name, address) in store.load_contacts()?.iter() {
+                println!("{}  {}", name, address);
+            }
+        }
+        WalletCommand::Watch(WatchCommand::Add {
+            address,
+            public_key,
+            scheme,
+        }) => {
+            let watched = match (address, public_key) {
+                (_, Some(key)) => {
+                    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+                    let key = parse_hex(&key).map_err(invalid)?;
+                    let key =
+                        PublicKey::from_bytes(scheme, &key).map_err(|e| invalid(e.to_string()))?;
+                    WatchWallet::from_public_key(&key, config.address_version)
+                }
+                (Some(address), None) => WatchWallet::new(address),
+                (None, None) => unreachable!("clap requires an address or a public key"),
+            };
+            let mut watch_list = store.load_watch_list()?;
+            watch_list.add(watched.clone())?;
+            store.save_watch_list(&watch_list)?;
+            println!("watching {}", watched.address());
+        }
+        WalletCommand::Watch(WatchCommand::Remove { address }) => {
+            let mut watch_list = store.load_watch_list()?;
+            watch_list.remove(&address)?;
+            store.save_watch_list(&watch_list)?;
+        }
+        WalletCommand::Watch(WatchCommand::List) => {
+            let blockchain = store.load_blockchain(config)?;
+            for watched in store.load_watch_list()?.iter() {
+                let balance = watched.balance(&blockchain, config.mempool.min_confirmations)?;
+                println!("{}  {}", watched.address(), balance);
+            }
+        }
+        WalletCommand::Qr {
+            address,
+            amount,
+            memo,
+            png,
+            scale,
+        } => {
+            let wallet = store.load_wallet(&address)?;
+            let mut request = PaymentRequest::new(*wallet.address());
+            request.amount = amount;
+            request.memo = memo;
+            let qr = if request.amount.is_none() && request.memo.is_none() {
+                wallet.address_qr()?
+            } else {
+                request.qr()?
+            };
+            match png {
+                Some(path) => std::fs::write(path, qr.to_png(scale)?)?,
+                None => print!("{}", qr.to_terminal()),
+            }
+            println!("{}", request);
+        }
+        WalletCommand::Sign { unsigned, out } => {
+            let sender = *UnsignedTransaction::read_from(&unsigned)?
+                .transaction()
+                .sender()
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "a coinbase can't be signed")
+                })?;
+            let signed = store
+                .load_wallet(&sender)?
+                .sign_transaction_file(&unsigned, &out)?;
+            println!("{}", signed.transaction());
+        }
+        WalletCommand::Balance {
+            address,
+            min_confirmations,
+        } => {
+            let blockchain = store.load_blockchain(config)?;
+            let min_confirmations = min_confirmations.unwrap_or(config.mempool.min_confirmations);
+            println!("{}", blockchain.balance(&address, min_confirmations)?);
+        }
+        WalletCommand::Utxos { address } => {
+            let blockchain = store.load_blockchain(config)?;
+            for (outpoint, output) in blockchain.unspent_outputs(&address)? {
+                println!("{}  {}", outpoint, output.value);
+            }
+        }
+        WalletCommand::Tokens { address } => {
+            let blockchain = store.load_blockchain(config)?;
+            for (id, amount) in blockchain.token_balances(&address) {
+                let name = blockchain.token(&id).map(|token| token.name);
+                println!("{}  {}  {}", id, name.unwrap_or_default(), amount);
+            }
+        }
+        WalletCommand::History {
+            address,
+            page,
+            page_size,
+        } => {
+            let blockchain = store.load_blockchain(config)?;
+            let history = blockchain.history(&address, page, page_size)?;
+            for entry in &history.entries {
+                println!("{}", entry);
+            }
+            println!(
+                "page {} of {}, {} transactions",
+                page + 1,
+                history.total.div_ceil(page_size.max(1)).max(1),
+                history.total
+            );
+        }
+        WalletCommand::Sent { address, id } => {
+            let blockchain = store.load_blockchain(config)?;
+            let wallet = store.load_wallet(&address)?;
+            wallet.refresh_transactions(&blockchain);
+            store.save_sent(&wallet)?;
+            if let Some(id) = id {
+                let status = wallet.tx_status(&id).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("{} did not submit transaction {}", address, id),
+                    )
+                })?;
+                println!("{}", status);
+                return Ok(());
+            }
+            for (id, tracked) in wallet.sent_transactions().iter() {
+                match &tracked.block_hash {
+                    Some(hash) => println!("{}  {}  {}", id, tracked.status, hash),
+                    None => println!("{}  {}", id, tracked.status),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_tx(command: TxCommand, store: &FileStore, config: &Config) -> io::Result<()> {
+    let mut blockchain = store.load_blockchain(config)?;
+    let resolve = |to: &str| -> io::Result<Address> { Ok(store.load_contacts()?.resolve(to)?) };
+    match command {
+        TxCommand::Send(args) => {
+            let to = resolve(&args.to)?;
+            println!("{}", send(&mut blockchain, store, &args, &to)?);
+        }
+        TxCommand::SendBatch {
+            from,
+            payments,
+            fee,
+        } => {
+            let payments = payments
+                .iter()
+                .map(|(to, amount)| Ok((resolve(to)?, *amount)))
+                .collect::<io::Result<Vec<_>>>()?;
+            let mut wallet = store.load_wallet(&from)?;
+            let sequence = blockchain.next_sequence(&from)?;
+            let batch = wallet.sign_batch(&payments, fee, sequence)?;
+            for transaction in blockchain.add_batch_to_pool(batch)? {
+                println!("{}", track(store, &wallet, &transaction)?);
+            }
+        }
+        TxCommand::Create { payment, out } => {
+            let to = resolve(&payment.to)?;
+            let sequence = blockchain.next_sequence(&payment.from)?;
+            let inputs = blockchain.select_inputs(&payment.from, payment.amount + payment.fee)?;
+            UnsignedTransaction::new(payment.transaction(to, sequence, inputs)).write_to(&out)?;
+        }
+        TxCommand::Broadcast { signed } => {
+            let transaction = SignedTransaction::read_from(&signed)?.into_transaction();
+            println!("{}", blockchain.add_transation_to_pool(transaction)?.id());
+        }
+        TxCommand::TestAccept { signed } => {
+            let transaction = SignedTransaction::read_from(&signed)?.into_transaction();
+            match blockchain.test_accept(&transaction) {
+                Ok(()) => println!("{} accepted", transaction.id()),
+                Err(e) => println!("{} rejected ({}): {}", transaction.id(), e.code(), e),
+            }
+        }
+        TxCommand::Pay {
+            uri,
+            from,
+            amount,
+            fee,
+        } => {
+            let amount = uri.amount.or(amount).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the URI names no amount, pass --amount",
+                )
+            })?;
+            let args = SendArgs {
+                from,
+                to: uri.address.to_string(),
+                amount,
+                fee,
+                memo: uri.memo.unwrap_or_default(),
+                expires_at_height: None,
+                expires_in: None,
+                valid_after_height: None,
+                valid_in: None,
+                script: None,
+                script_args: vec![],
+            };
+            println!("{}", send(&mut blockchain, store, &args, &uri.address)?);
+        }
+        TxCommand::IssueToken {
+            from,
+            name,
+            supply,
+            fee,
+        } => {
+            let transaction = Transaction::new(from, from, 0.0, fee, 0)
+                .with_token(Some(TokenOp::Issue { name, supply }));
+            println!("{}", submit(&mut blockchain, store, transaction)?);
+        }
+        TxCommand::SendToken {
+            from,
+            to,
+            token,
+            amount,
+            fee,
+        } => {
+            let to = resolve(&to)?;
+            let transaction = Transaction::new(from, to, 0.0, fee, 0)
+                .with_token(Some(TokenOp::Transfer { id: token, amount }));
+            println!("{}", submit(&mut blockchain, store, transaction)?);
+        }
+        TxCommand::Stake { from, amount, fee } => {
+            let transaction =
+                Transaction::new(from, from, amount, fee, 0).with_stake(Some(StakeOp::Stake));
+            println!("{}", submit(&mut blockchain, store, transaction)?);
+        }
+        TxCommand::Unstake { from, amount, fee } => {
+            let transaction = Transaction::new(from, from, 0.0, fee, 0)
+                .with_stake(Some(StakeOp::Unstake { amount }));
+            println!("{}", submit(&mut blockchain, store, transaction)?);
+        }
+        TxCommand::Slash {
+            from,
+            first,
+            second,
+            fee,
+        } => {
+            let evidence = blockchain.double_sign(&first, &second)?;
+            let offender = evidence.offender().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the blocks aren't signed by the same producer on the same parent",
+                )
+            })?;
+            let amount = blockchain.stake(&offender);
+            let transaction =
+                Transaction::new(from, from, 0.0, fee, 0).with_stake(Some(StakeOp::Slash {
+                    evidence: Box::new(evidence),
+                    amount,
+                }));
+            println!("{}", submit(&mut blockchain, store, transaction)?);
+        }
+        TxCommand::AddAuthority { from, address, fee } => {
+            let transaction = Transaction::new(from, from, 0.0, fee, 0)
+                .with_authority(Some(AuthorityOp::Add { address }));
+            println!("{}", submit(&mut blockchain, store, transaction)?);
+        }
+        TxCommand::RemoveAuthority { from, address, fee } => {
+            let transaction = Transaction::new(from, from, 0.0, fee, 0)
+                .with_authority(Some(AuthorityOp::Remove { address }));
+            println!("{}", submit(&mut blockchain, store, transaction)?);
+        }
+        TxCommand::Replace { payment, sequence } => {
+            let wallet = store.load_wallet(&payment.from)?;
+            let to = resolve(&payment.to)?;
+            let inputs = pending_inputs(&blockchain, &payment.from, sequence);
+            let transaction = wallet
+                .sign_unsigned(&UnsignedTransaction::new(
+                    payment.transaction(to, sequence, inputs),
+                ))?
+                .into_transaction();
+            let id = transaction.id();
+            let replaced = blockchain.replace_transaction(transaction.clone())?;
+            track(store, &wallet, &transaction)?;
+            println!("{} replaces {}", id, replaced.id());
+        }
+        TxCommand::Cancel {
+            from,
+            sequence,
+            fee,
+        } => {
+            let mut wallet = store.load_wallet(&from)?;
+            let inputs = pending_inputs(&blockchain, &from, sequence);
+            let transaction = wallet.sign_transaction(&from, 0.0, fee, sequence, inputs, vec![])?;
+            let id = transaction.id();
+            let replaced = blockchain.replace_transaction(transaction.clone())?;
+            track(store, &wallet, &transaction)?;
+            println!("{} cancels {}", id, replaced.id());
+        }
+        TxCommand::SendMultisig {
+            policy,
+            to,
+            amount,
+            fee,
+            memo,
+        } => {
+            let to = resolve(&to)?;
+            let threshold = usize::from(policy.threshold);
+            let (policy, wallets) = policy.load(store)?;
+            let sender = policy.address(config.address_version);
+            let sequence = blockchain.next_sequence(&sender)?;
+            let inputs = blockchain.select_inputs(&sender, amount + fee)?;
+            let mut transaction = Transaction::new_multisig(
+                policy,
+                config.address_version,
+                to,
+                amount,
+                fee,
+                sequence,
+            )
+            .with_inputs(inputs)
+            .with_memo(memo.into_bytes());
+            for wallet in wallets.iter().take(threshold) {
+                wallet.cosign(&mut transaction)?;
+            }
+            let transaction = blockchain.add_transation_to_pool(transaction)?;
+            println!("{}", transaction.id());
+        }
+        TxCommand::Faucet { to, amount } => {
+            let transaction = blockchain.request_from_faucet(&resolve(&to)?, amount)?;
+            println!("{}", transaction.id());
+        }
+        TxCommand::Get { id } => {
+            return match blockchain.transaction_by_id(&id)? {
+                Some((transaction, location)) => {
+                    println!(
+                        "{:?} (block {}, index {})",
+                        transaction, location.height, location.index
+                    );
+                    Ok(())
+                }
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("transaction {} not found", id),
+                )),
+            };
+        }
+        TxCommand::Receipt { id, out } => {
+            let receipt = blockchain.receipt(&id)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("transaction {} is not confirmed", id),
+                )
+            })?;
+            let json = serde_json::to_string_pretty(&receipt)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            match out {
+                Some(out) => std::fs::write(out, json + "\n")?,
+                None => println!("{}", json),
+            }
+            return Ok(());
+        }
+        TxCommand::VerifyReceipt { receipt } => {
+            let receipt: Receipt = serde_json::from_slice(&std::fs::read(receipt)?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let block = blockchain.block_by_height(receipt.height).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no block at height {} yet", receipt.height),
+                )
+            })?;
+            receipt.verify(block.header())?;
+            println!(
+                "{} confirmed in block {} at height {} ({} confirmations)",
+                receipt.txid,
+                receipt.block_hash,
+                receipt.height,
+                blockchain.chain_height()? + 1 - receipt.height
+            );
+            return Ok(());
+        }
+    }
+    store.save_blockchain(&blockchain)
+}
+
+/// Signs the payment described by `args` to `to` and adds it to the pool.
+fn send(
+    blockchain: &mut Blockchain,
+    store: &FileStore,
+    args: &SendArgs,
+    to: &Address,
+) -> io::Result<TxId> {
+    let wallet = store.load_wallet(&args.from)?;
+    let sequence = blockchain.next_sequence(&args.from)?;
+    let inputs = blockchain.select_inputs(&args.from, args.amount + args.fee)?;
+    let transaction = wallet
+        .sign_unsigned(&UnsignedTransaction::new(
+            args.transaction(*to, sequence, inputs),
+        ))?
+        .into_transaction();
+    track(
+        store,
+        &wallet,
+        &blockchain.add_transation_to_pool(transaction)?,
+    )
+}
+
+/// Fills in the sequence number and inputs of `transaction`, signs it with
+/// its sender's stored wallet and adds it to the pool.
+fn submit(
+    blockchain: &mut Blockchain,
+    store: &FileStore,
+    mut transaction: Transaction,
+) -> io::Result<TxId> {
+    let Some(from) = transaction.sender().copied() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a coinbase can't be submitted",
+        ));
+    };
+    let wallet = store.load_wallet(&from)?;
+    transaction.sequence = blockchain.next_sequence(&from)?;
+    transaction.inputs = blockchain.select_inputs(&from, transaction.amount + transaction.fee)?;
+    let transaction = wallet
+        .sign_unsigned(&UnsignedTransaction::new(transaction))?
+        .into_transaction();
+    track(
+        store,
+        &wallet,
+        &blockchain.add_transation_to_pool(transaction)?,
+    )
+}
+
+/// Has `wallet` follow `transaction`, which it just submitted, for
+/// `wallet sent` to report on.
+fn track(store: &FileStore, wallet: &Wallet, transaction: &Transaction) -> io::Result<TxId> {
+    wallet.track_transaction(transaction);
+    store.save_sent(wallet)?;
+    Ok(transaction.id())
+}
+
+fn pending_inputs(blockchain: &Blockchain, sender: &Address, sequence: u64) -> Vec<OutPoint> {
+    blockchain
+        .pending_transactions()
+        .into_iter()
+        .find(|t| t.sender() == Some(sender) && t.sequence == sequence)
+        .map(|t| t.inputs)
+        .unwrap_or_default()
+}
+
+/// Passphrase of an encrypted wallet, from AEONIA_WALLET_PASSPHRASE or
+/// else the first line of standard input.
+fn read_passphrase() -> io::Result<String> {
+    if let Ok(passphrase) = std::env::var("AEONIA_WALLET_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    let passphrase = line.trim_end_matches(['\r', '\n']);
+    if passphrase.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no passphrase given on standard input or in AEONIA_WALLET_PASSPHRASE",
+        ));
+    }
+    Ok(passphrase.to_string())
+}
+
+#[cfg(unix)]
+fn default_socket(config: &Config) -> PathBuf {
+    config.data_dir.join("wallet.sock")
+}
+
+/// Wallet signing through the daemon at `socket`, which must hold the key
+/// of `address`.
+#[cfg(unix)]
+fn daemon_wallet(socket: &Path, address: &Address, version: u8) -> io::Result<Wallet> {
+    let signer = DaemonSigner::connect(socket)?;
+    let wallet = Wallet::with_signer(Arc::new(signer), version);
+    if wallet.address() != address {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the wallet daemon at {} holds {}, not {}",
+                socket.display(),
+                wallet.address(),
+                address
+            ),
+        ));
+    }
+    Ok(wallet)
+}
+
+#[cfg(unix)]
+fn print_daemon_status(response: DaemonResponse) {
+    if let DaemonResponse::Status {
+        address,
+        locks_in_secs,
+        ..
+    } = response
+    {
+        match locks_in_secs {
+            Some(secs) => println!("{} unlocked, locks after {}s idle", address, secs),
+            None => println!("{} locked", address),
+        }
+    }
+}
+
+fn parse_payment(payment: &str) -> Result<(String, f64), String> {
+    let (to, amount) = payment
+        .split_once('=')
+        .ok_or("expected <recipient>=<amount>")?;
+    let amount = amount.parse().map_err(|e| format!("amount: {}", e))?;
+    Ok((to.to_string(), amount))
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err("hex string has an odd length".into());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
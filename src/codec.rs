@@ -0,0 +1,285 @@
+//! Canonical binary encoding used for hashing, signing and persistence.
+//!
+//! Integers are little-endian and fixed width, floats are encoded as their
+//! IEEE-754 bit pattern, and strings, byte arrays and sequences are prefixed
+//! with their length as a `u32`. The encoding of a value never depends on the
+//! platform or on formatting, so hashes and signatures over it are stable.
+
+use std::net::SocketAddr;
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("string is not valid utf-8")]
+    InvalidUtf8,
+    #[error("invalid tag {0}")]
+    InvalidTag(u8),
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+    #[error("{0} trailing bytes after value")]
+    TrailingBytes(usize),
+}
+
+impl Error {
+    /// Identifier of the kind of error, stable across releases.
+    pub fn code(&self) -> &'static str {
+        "malformed_data"
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(value: Error) -> Self {
+        Self::new(std::io::ErrorKind::InvalidData, value)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub trait Encode {
+    fn encode_to(&self, out: &mut Vec<u8>);
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.encode_to(&mut out);
+        out
+    }
+}
+
+pub trait Decode: Sized {
+    fn decode_from(reader: &mut Reader) -> Result<Self>;
+
+    /// Decodes a value that must span the whole of `bytes`.
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let value = Self::decode_from(&mut reader)?;
+        match reader.remaining() {
+            0 => Ok(value),
+            n => Err(Error::TrailingBytes(n)),
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.bytes.len() {
+            return Err(Error::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    /// Reads a length prefix, rejecting lengths that could not possibly fit
+    /// in the remaining input so corrupt data can't trigger huge allocations.
+    pub fn take_len(&mut self) -> Result<usize> {
+        let len = u32::decode_from(self)? as usize;
+        if len > self.remaining() {
+            return Err(Error::UnexpectedEof);
+        }
+        Ok(len)
+    }
+}
+
+macro_rules! impl_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode_to(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode_from(reader: &mut Reader) -> Result<Self> {
+                    Ok(<$ty>::from_le_bytes(reader.take_array()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_int!(u8, u16, u32, i32, u64, i64);
+
+impl Encode for f64 {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.to_bits().encode_to(out);
+    }
+}
+
+impl Decode for f64 {
+    fn decode_from(reader: &mut Reader) -> Result<Self> {
+        Ok(f64::from_bits(u64::decode_from(reader)?))
+    }
+}
+
+impl Encode for [u8] {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode_to(out);
+        out.extend_from_slice(self);
+    }
+}
+
+impl Encode for str {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode_to(out);
+    }
+}
+
+impl Encode for String {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.as_str().encode_to(out);
+    }
+}
+
+impl Decode for String {
+    fn decode_from(reader: &mut Reader) -> Result<Self> {
+        let len = reader.take_len()?;
+        let bytes = reader.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidUtf8)
+    }
+}
+
+/// Socket addresses are written in their textual form, which covers both
+/// IPv4 and IPv6.
+impl Encode for SocketAddr {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.to_string().encode_to(out);
+    }
+}
+
+impl Decode for SocketAddr {
+    fn decode_from(reader: &mut Reader) -> Result<Self> {
+        String::decode_from(reader)?
+            .parse()
+            .map_err(|e: std::net::AddrParseError| Error::InvalidValue(e.to_string()))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode_to(out);
+        for item in self {
+            item.encode_to(out);
+        }
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode_from(reader: &mut Reader) -> Result<Self> {
+        let len = reader.take_len()?;
+        decode_items(reader, len)
+    }
+}
+
+/// Most bytes reserved up front for the items of a vector. A length prefix
+/// only bounds the count by the bytes left, and items can take far more
+/// room in memory than on the wire, so past this the vector grows as items
+/// actually decode.
+const MAX_PREALLOCATION: usize = 64 * 1024;
+
+/// Decodes `len` items following a length prefix already read, for callers
+/// that check the count before decoding the items.
+pub fn decode_items<T: Decode>(reader: &mut Reader, len: usize) -> Result<Vec<T>> {
+    let capacity = len.min(MAX_PREALLOCATION / std::mem::size_of::<T>().max(1));
+    let mut items = Vec::with_capacity(capacity);
+    for _ in 0..len {
+        items.push(T::decode_from(reader)?);
+    }
+    Ok(items)
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            None => 0u8.encode_to(out),
+            Some(value) => {
+                1u8.encode_to(out);
+                value.encode_to(out);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode_from(reader: &mut Reader) -> Result<Self> {
+        match u8::decode_from(reader)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode_from(reader)?)),
+            tag => Err(Error::InvalidTag(tag)),
+        }
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+        self.1.encode_to(out);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode_from(reader: &mut Reader) -> Result<Self> {
+        Ok((A::decode_from(reader)?, B::decode_from(reader)?))
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (**self).encode_to(out);
+    }
+}
+
+/// Serde adapter writing a value as the hex string of its canonical encoding,
+/// for types such as keys and signatures that have no serde support of their
+/// own. Use with `#[serde(with = "crate::codec::as_hex")]`.
+pub(crate) mod as_hex {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use super::{Decode, Encode};
+
+    pub fn serialize<T: Encode, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let hex: String = value
+            .encode()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, T: Decode, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(de::Error::custom("hex string has an odd length"));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or_default(), 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(de::Error::custom)?;
+        T::decode(&bytes).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,769 @@
+//! Node settings read from a TOML file, with environment variables taking
+//! precedence over the file.
+//!
+//! ```toml
+//! data_dir = ".aeonia"
+//! storage = "file"
+//! address_version = 0
+//! checkpoint_interval = 100
+//! prune_depth = 0
+//!
+//! [network]
+//! chain = "mainnet"
+//! listen_address = "0.0.0.0:7878"
+//! peers = ["10.0.0.2:7878"]
+//! dns_seeds = ["seed.example.org"]
+//! metrics_address = "127.0.0.1:9878"
+//! encryption = "none"
+//! rebroadcast_interval_secs = 600
+//! rebroadcast_max_attempts = 10
+//!
+//! [mining]
+//! enabled = true
+//! miner = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"
+//! idle_interval_secs = 10
+//! stratum_address = "0.0.0.0:3333"
+//! share_difficulty = 2
+//!
+//! [consensus]
+//! engine = "pow"
+//! difficulty = 3
+//! # bits = 0x1e0fffff
+//! pow_hash = "sha256"
+//! slot_secs = 5
+//! block_interval_secs = 10
+//! authorities = ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"]
+//! median_window = 11
+//! max_future_drift_secs = 7200
+//! ledger = "account"
+//! block_reward = 1.0
+//! halving_interval = 210000
+//! tail_emission = 0.0
+//! max_supply = 0.0
+//!
+//! [[consensus.genesis_allocations]]
+//! address = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"
+//! amount = 50.0
+//!
+//! [mempool]
+//! max_size = 5000
+//! max_per_sender = 25
+//! min_confirmations = 1
+//! min_fee_rate = 0.0
+//! max_tx_size = 100000
+//! accept_zero_fee = true
+//!
+//! [rpc]
+//! api_keys = ["reader-key"]
+//! admin_key = "admin-key"
+//! requests_per_minute = 600
+//! # tls_cert = "node.crt"
+//! # tls_key = "node.key"
+//! ```
+//!
+//! Every key is optional. Those left out take the defaults of the network
+//! preset `network.chain` names, see [`Network`]; `AEONIA_NETWORK` or the
+//! `--network` flag pick the preset instead of the file. The environment
+//! overrides are `AEONIA_DATA_DIR`, `AEONIA_STORAGE`,
+//! `AEONIA_ADDRESS_VERSION`, `AEONIA_CHECKPOINT_INTERVAL`, `AEONIA_PRUNE_DEPTH`,
+//! `AEONIA_LISTEN_ADDRESS`, `AEONIA_PEERS` and `AEONIA_DNS_SEEDS` (comma
+//! separated),
+//! `AEONIA_METRICS_ADDRESS`, `AEONIA_ENCRYPTION`, `AEONIA_REBROADCAST_INTERVAL_SECS`,
+//! `AEONIA_REBROADCAST_MAX_ATTEMPTS`, `AEONIA_MINING_ENABLED`, `AEONIA_MINER`,
+//! `AEONIA_STRATUM_ADDRESS`, `AEONIA_SHARE_DIFFICULTY`,
+//! `AEONIA_ENGINE`, `AEONIA_DIFFICULTY`, `AEONIA_BITS`, `AEONIA_POW_HASH`,
+//! `AEONIA_SLOT_SECS`,
+//! `AEONIA_BLOCK_INTERVAL_SECS`, `AEONIA_AUTHORITIES` (comma separated),
+//! `AEONIA_LEDGER`, `AEONIA_BLOCK_REWARD`,
+//! `AEONIA_HALVING_INTERVAL`, `AEONIA_TAIL_EMISSION`, `AEONIA_MAX_SUPPLY`,
+//! `AEONIA_MEMPOOL_MAX_SIZE`, `AEONIA_MEMPOOL_MAX_PER_SENDER`,
+//! `AEONIA_MEMPOOL_MIN_CONFIRMATIONS`, `AEONIA_MEMPOOL_MIN_FEE_RATE`,
+//! `AEONIA_MEMPOOL_MAX_TX_SIZE`, `AEONIA_MEMPOOL_ACCEPT_ZERO_FEE`,
+//! `AEONIA_RPC_API_KEYS` (comma separated), `AEONIA_RPC_ADMIN_KEY`,
+//! `AEONIA_RPC_REQUESTS_PER_MINUTE`, `AEONIA_RPC_TLS_CERT` and
+//! `AEONIA_RPC_TLS_KEY`.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::Address,
+    blockchain::{
+        ConsensusEngine, EmissionSchedule, Engine, FixedInterval, HashAlgorithm, Ledger,
+        MempoolConfig, ProofOfAuthority, ProofOfStake, ProofOfWork, Target, TimestampConfig,
+    },
+    network::{Encryption, Network},
+    storage::StorageBackend,
+};
+
+const DEFAULT_IDLE_INTERVAL_SECS: u64 = 10;
+const DEFAULT_SHARE_DIFFICULTY: u8 = 2;
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 100;
+const DEFAULT_SLOT_SECS: u64 = 5;
+const DEFAULT_BLOCK_INTERVAL_SECS: u64 = 10;
+const DEFAULT_REBROADCAST_INTERVAL_SECS: u64 = 600;
+const DEFAULT_REBROADCAST_MAX_ATTEMPTS: u32 = 10;
+const ENV_PREFIX: &str = "AEONIA_";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read config: {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid config: {0}")]
+    Toml(String),
+    #[error("invalid value {value:?} for {var}")]
+    InvalidEnv { var: String, value: String },
+    #[error("difficulty on {network} must be at least {minimum}")]
+    DifficultyTooLow { network: Network, minimum: u8 },
+    #[error("proof of stake needs the account ledger")]
+    StakeWithoutAccounts,
+    #[error("proof of authority needs at least one authority")]
+    NoAuthorities,
+    #[error("bits {bits:#010x} don't encode a target")]
+    InvalidTarget { bits: u32 },
+    #[error("rpc.tls_cert and rpc.tls_key must be set together")]
+    IncompleteTls,
+    #[error("proof of work hash {algorithm} needs the {algorithm} feature")]
+    UnsupportedPowHash { algorithm: HashAlgorithm },
+    #[error("genesis allocation to {address}: {reason}")]
+    InvalidAllocation {
+        address: Address,
+        reason: &'static str,
+    },
+}
+
+impl Error {
+    /// Identifier of the kind of error, stable across releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io { .. } => "config_unreadable",
+            Error::Toml(_)
+            | Error::InvalidEnv { .. }
+            | Error::DifficultyTooLow { .. }
+            | Error::StakeWithoutAccounts
+            | Error::NoAuthorities
+            | Error::InvalidTarget { .. }
+            | Error::IncompleteTls
+            | Error::UnsupportedPowHash { .. }
+            | Error::InvalidAllocation { .. } => "invalid_config",
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(value: Error) -> Self {
+        let kind = match &value {
+            Error::Io { source, .. } => source.kind(),
+            Error::Toml(_)
+            | Error::InvalidEnv { .. }
+            | Error::DifficultyTooLow { .. }
+            | Error::StakeWithoutAccounts
+            | Error::NoAuthorities
+            | Error::InvalidTarget { .. }
+            | Error::IncompleteTls
+            | Error::InvalidAllocation { .. } => std::io::ErrorKind::InvalidData,
+            Error::UnsupportedPowHash { .. } => std::io::ErrorKind::Unsupported,
+        };
+        Self::new(kind, value)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub data_dir: PathBuf,
+    /// `"file"` or, with the `sled` feature, `"sled"`.
+    pub storage: StorageBackend,
+    pub address_version: u8,
+    /// Blocks between balance checkpoints saved alongside the chain; 0
+    /// disables them.
+    pub checkpoint_interval: u64,
+    /// Blocks this deep or deeper lose their transactions once a checkpoint
+    /// covers them; 0 keeps every block whole.
+    pub prune_depth: u64,
+    pub network: NetworkConfig,
+    pub mining: MiningConfig,
+    pub consensus: ConsensusConfig,
+    pub mempool: MempoolLimits,
+    pub rpc: RpcConfig,
+}
+
+/// Where the node listens and which peers it dials.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Preset the node runs on; nodes on different presets refuse each
+    /// other.
+    pub chain: Network,
+    pub listen_address: Option<String>,
+    pub peers: Vec<String>,
+    /// Host names resolving to nodes to ask for peers, used alongside
+    /// `peers` to find the network.
+    pub dns_seeds: Vec<String>,
+    /// Where Prometheus metrics are served; off when unset.
+    pub metrics_address: Option<String>,
+    /// `"none"` or, with the `noise` feature, `"noise"`. Peers must agree
+    /// on it.
+    pub encryption: Encryption,
+    /// Seconds between rebroadcasts of the unconfirmed payments the stored
+    /// wallets sent; 0 disables them.
+    pub rebroadcast_interval_secs: u64,
+    /// Times a payment is rebroadcast before the node gives up on it.
+    pub rebroadcast_max_attempts: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MiningConfig {
+    pub enabled: bool,
+    /// Address receiving block rewards.
+    pub miner: Option<Address>,
+    /// Seconds after which an empty block is mined if no transactions arrive.
+    pub idle_interval_secs: u64,
+    /// Where stratum workers connect to mine for this node; off when unset.
+    pub stratum_address: Option<String>,
+    /// Leading zero hex digits a stratum worker's share needs.
+    pub share_difficulty: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConsensusConfig {
+    /// `"pow"`, `"pos"`, `"poa"` or `"dev"`. Every node on a network must
+    /// agree on it.
+    pub engine: Engine,
+    /// Number of leading zero hex digits a block hash needs under proof of
+    /// work.
+    pub difficulty: u8,
+    /// Compact target a block hash must not exceed under proof of work,
+    /// replacing `difficulty` for finer steps than a hex digit.
+    pub bits: Option<u32>,
+    /// `"sha256"`, `"sha256d"` or `"blake3"`, the hash proof of work is
+    /// computed with.
+    /// It is recorded in the genesis block, and a chain refuses to load
+    /// under another.
+    pub pow_hash: HashAlgorithm,
+    /// Length of the slots proof of stake and proof of authority hand out
+    /// to block producers.
+    pub slot_secs: u64,
+    /// Shortest time between blocks under the dev engine.
+    pub block_interval_secs: u64,
+    /// Authorities taking turns producing blocks under proof of authority,
+    /// as of genesis.
+    pub authorities: Vec<Address>,
+    pub median_window: usize,
+    pub max_future_drift_secs: u64,
+    /// `"account"` or `"utxo"`. Every node on a network must agree on it.
+    pub ledger: Ledger,
+    /// Reward of the blocks before the first halving.
+    pub block_reward: f64,
+    /// Blocks between halvings of the reward; 0 never halves it.
+    pub halving_interval: u64,
+    /// Smallest reward the halvings leave; 0 lets it run out.
+    pub tail_emission: f64,
+    /// Most coins that may ever exist, genesis allocation included; 0 for
+    /// no limit.
+    pub max_supply: f64,
+    /// Balances the genesis block gives addresses besides the faucet's, so
+    /// test networks and demos start with funded wallets. Like the rest of
+    /// genesis, changing them only affects chains created afterwards.
+    pub genesis_allocations: Vec<GenesisAllocation>,
+}
+
+/// Coins the genesis block pays `address`, see
+/// [`ConsensusConfig::genesis_allocations`].
+///
+/// ```
+/// use aeonia::{
+///     blockchain::Blockchain,
+///     config::{Config, GenesisAllocation},
+///     network::Network,
+///     wallet::Wallet,
+/// };
+///
+/// let mut config = Config::for_network(Network::Regtest);
+/// let wallet = Wallet::new(Network::Regtest).unwrap();
+/// let allocation = GenesisAllocation {
+///     address: *wallet.address(),
+///     amount: 50.0,
+/// };
+/// config.consensus.genesis_allocations = vec![allocation.clone()];
+/// config.validate().unwrap();
+///
+/// let blockchain = Blockchain::with_config(&config).unwrap();
+/// let balance = blockchain.balance(wallet.address(), 1).unwrap();
+/// assert_eq!(balance.confirmed, 50.0);
+///
+/// config.consensus.genesis_allocations.push(allocation);
+/// assert!(config.validate().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisAllocation {
+    pub address: Address,
+    pub amount: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MempoolLimits {
+    pub max_size: usize,
+    pub max_per_sender: usize,
+    /// Confirmations a payment needs before its recipient can spend it.
+    pub min_confirmations: u64,
+    /// Lowest fee per byte the pool admits.
+    pub min_fee_rate: f64,
+    /// Largest transaction the pool admits, in bytes.
+    pub max_tx_size: usize,
+    pub accept_zero_fee: bool,
+}
+
+/// Who may call the node's gRPC API and how often.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    /// Keys clients present as `authorization: Bearer <key>`. Once any is
+    /// set, calls without one of them or the admin key are refused.
+    pub api_keys: Vec<String>,
+    /// Key the admin calls (mining, the faucet and shutdown) need; they are
+    /// refused while it is unset.
+    pub admin_key: Option<String>,
+    /// Calls a client may make a minute, counted per key, or per IP address
+    /// for clients without one; 0 for no limit.
+    pub requests_per_minute: u32,
+    /// PEM certificate chain the API is served over TLS with, alongside
+    /// `tls_key`; plaintext when unset.
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of `tls_cert`.
+    pub tls_key: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::for_network(Network::default())
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            chain: Network::default(),
+            listen_address: None,
+            peers: vec![],
+            dns_seeds: vec![],
+            metrics_address: None,
+            encryption: Encryption::default(),
+            rebroadcast_interval_secs: DEFAULT_REBROADCAST_INTERVAL_SECS,
+            rebroadcast_max_attempts: DEFAULT_REBROADCAST_MAX_ATTEMPTS,
+        }
+    }
+}
+
+impl Default for MiningConfig {
+    fn default() -> Self {
+        MiningConfig {
+            enabled: false,
+            miner: None,
+            idle_interval_secs: DEFAULT_IDLE_INTERVAL_SECS,
+            stratum_address: None,
+            share_difficulty: DEFAULT_SHARE_DIFFICULTY,
+        }
+    }
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        let timestamps = TimestampConfig::default();
+        let emission = EmissionSchedule::default();
+        ConsensusConfig {
+            engine: Engine::default(),
+            difficulty: Network::default().default_difficulty(),
+            bits: None,
+            pow_hash: HashAlgorithm::default(),
+            slot_secs: DEFAULT_SLOT_SECS,
+            block_interval_secs: DEFAULT_BLOCK_INTERVAL_SECS,
+            authorities: vec![],
+            median_window: timestamps.median_window,
+            max_future_drift_secs: timestamps.max_future_drift.as_secs(),
+            ledger: Ledger::default(),
+            block_reward: emission.initial_reward,
+            halving_interval: emission.halving_interval,
+            tail_emission: emission.tail_emission,
+            max_supply: emission.max_supply,
+            genesis_allocations: vec![],
+        }
+    }
+}
+
+impl Default for MempoolLimits {
+    fn default() -> Self {
+        let mempool = MempoolConfig::default();
+        MempoolLimits {
+            max_size: mempool.max_size,
+            max_per_sender: mempool.max_per_sender,
+            min_confirmations: mempool.min_confirmations,
+            min_fee_rate: mempool.min_fee_rate,
+            max_tx_size: mempool.max_tx_size,
+            accept_zero_fee: mempool.accept_zero_fee,
+        }
+    }
+}
+
+impl Config {
+    /// Default settings of the `network` preset.
+    pub fn for_network(network: Network) -> Self {
+        Config {
+            data_dir: network.data_dir(),
+            storage: StorageBackend::default(),
+            address_version: network.address_version(),
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            prune_depth: 0,
+            network: NetworkConfig {
+                chain: network,
+                ..NetworkConfig::default()
+            },
+            mining: MiningConfig::default(),
+            consensus: ConsensusConfig {
+                difficulty: network.default_difficulty(),
+                ..ConsensusConfig::default()
+            },
+            mempool: MempoolLimits::default(),
+            rpc: RpcConfig::default(),
+        }
+    }
+
+    /// Reads `path` if given, falling back to the defaults, then applies the
+    /// `AEONIA_*` environment variables. `network`, or else `AEONIA_NETWORK`,
+    /// overrides the preset the file names.
+    pub fn load(path: Option<&Path>, network: Option<Network>) -> Result<Self> {
+        let text = match path {
+            Some(path) => fs::read_to_string(path).map_err(|source| Error::Io {
+                path: path.to_path_buf(),
+                source,
+            })?,
+            None => String::new(),
+        };
+        let network = match network {
+            Some(network) => Some(network),
+            None => std::env::var(format!("{}NETWORK", ENV_PREFIX))
+                .ok()
+                .map(|value| parse_env("NETWORK", value))
+                .transpose()?,
+        };
+        let mut config = Self::from_toml_on(&text, network)?;
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Self::from_toml_on(text, None)
+    }
+
+    /// Parses `text` over the defaults of `network`, or of the preset the
+    /// text names if `None`.
+    pub fn from_toml_on(text: &str, network: Option<Network>) -> Result<Self> {
+        let toml_error = |e: &dyn std::fmt::Display| Error::Toml(e.to_string());
+        let table: toml::Table = text.parse().map_err(|e| toml_error(&e))?;
+        let network = match network {
+            Some(network) => network,
+            None => match table
+                .get("network")
+                .and_then(|network| network.get("chain"))
+            {
+                Some(chain) => chain.clone().try_into().map_err(|e| toml_error(&e))?,
+                None => Network::default(),
+            },
+        };
+        let mut merged =
+            toml::Table::try_from(Config::for_network(network)).map_err(|e| toml_error(&e))?;
+        merge(&mut merged, table);
+        let mut config: Config = toml::Value::Table(merged)
+            .try_into()
+            .map_err(|e| toml_error(&e))?;
+        config.network.chain = network;
+        Ok(config)
+    }
+
+    /// Checks the settings against the limits of the network preset.
+    pub fn validate(&self) -> Result<()> {
+        let network = self.network.chain;
+        let minimum = Target::from_zero_digits(network.min_difficulty());
+        let easiest = match self.consensus.bits {
+            Some(bits) => Target::from_compact(bits).ok_or(Error::InvalidTarget { bits })?,
+            None => Target::from_zero_digits(self.consensus.difficulty),
+        };
+        if easiest > minimum {
+            return Err(Error::DifficultyTooLow {
+                network,
+                minimum: network.min_difficulty(),
+            });
+        }
+        if self.consensus.engine == Engine::Pos && self.consensus.ledger != Ledger::Account {
+            return Err(Error::StakeWithoutAccounts);
+        }
+        if self.consensus.engine == Engine::Poa && self.consensus.authorities.is_empty() {
+            return Err(Error::NoAuthorities);
+        }
+        if self.rpc.tls_cert.is_some() != self.rpc.tls_key.is_some() {
+            return Err(Error::IncompleteTls);
+        }
+        if self.consensus.pow_hash.hasher().is_none() {
+            return Err(Error::UnsupportedPowHash {
+                algorithm: self.consensus.pow_hash,
+            });
+        }
+        let mut allocated = HashSet::new();
+        for GenesisAllocation { address, amount } in &self.consensus.genesis_allocations {
+            let reason = if !amount.is_finite() || *amount <= 0.0 {
+                "amount must be positive"
+            } else if address.version() != self.address_version {
+                "address is for another network"
+            } else if !allocated.insert(address) {
+                "address is allocated twice"
+            } else {
+                continue;
+            };
+            return Err(Error::InvalidAllocation {
+                address: *address,
+                reason,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| Error::Toml(e.to_string()))
+    }
+
+    /// Overrides settings with the variables `lookup` finds, given their name
+    /// without the `AEONIA_` prefix.
+    pub fn apply_env<F: Fn(&str) -> Option<String>>(&mut self, lookup: F) -> Result<()> {
+        let var = |name: &str| lookup(&format!("{}{}", ENV_PREFIX, name));
+        if let Some(value) = var("DATA_DIR") {
+            self.data_dir = value.into();
+        }
+        if let Some(value) = var("STORAGE") {
+            self.storage = parse_env("STORAGE", value)?;
+        }
+        if let Some(value) = var("ADDRESS_VERSION") {
+            self.address_version = parse_env("ADDRESS_VERSION", value)?;
+        }
+        if let Some(value) = var("CHECKPOINT_INTERVAL") {
+            self.checkpoint_interval = parse_env("CHECKPOINT_INTERVAL", value)?;
+        }
+        if let Some(value) = var("PRUNE_DEPTH") {
+            self.prune_depth = parse_env("PRUNE_DEPTH", value)?;
+        }
+        if let Some(value) = var("LISTEN_ADDRESS") {
+            self.network.listen_address = Some(value);
+        }
+        if let Some(value) = var("PEERS") {
+            self.network.peers = value
+                .split(',')
+                .map(str::trim)
+                .filter(|peer| !peer.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Some(value) = var("DNS_SEEDS") {
+            self.network.dns_seeds = value
+                .split(',')
+                .map(str::trim)
+                .filter(|seed| !seed.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Some(value) = var("METRICS_ADDRESS") {
+            self.network.metrics_address = Some(value);
+        }
+        if let Some(value) = var("ENCRYPTION") {
+            self.network.encryption = parse_env("ENCRYPTION", value)?;
+        }
+        if let Some(value) = var("REBROADCAST_INTERVAL_SECS") {
+            self.network.rebroadcast_interval_secs = parse_env("REBROADCAST_INTERVAL_SECS", value)?;
+        }
+        if let Some(value) = var("REBROADCAST_MAX_ATTEMPTS") {
+            self.network.rebroadcast_max_attempts = parse_env("REBROADCAST_MAX_ATTEMPTS", value)?;
+        }
+        if let Some(value) = var("MINING_ENABLED") {
+            self.mining.enabled = parse_env("MINING_ENABLED", value)?;
+        }
+        if let Some(value) = var("MINER") {
+            self.mining.miner = Some(parse_env("MINER", value)?);
+        }
+        if let Some(value) = var("STRATUM_ADDRESS") {
+            self.mining.stratum_address = Some(value);
+        }
+        if let Some(value) = var("SHARE_DIFFICULTY") {
+            self.mining.share_difficulty = parse_env("SHARE_DIFFICULTY", value)?;
+        }
+        if let Some(value) = var("ENGINE") {
+            self.consensus.engine = parse_env("ENGINE", value)?;
+        }
+        if let Some(value) = var("DIFFICULTY") {
+            self.consensus.difficulty = parse_env("DIFFICULTY", value)?;
+        }
+        if let Some(value) = var("BITS") {
+            self.consensus.bits = Some(parse_env("BITS", value)?);
+        }
+        if let Some(value) = var("POW_HASH") {
+            self.consensus.pow_hash = parse_env("POW_HASH", value)?;
+        }
+        if let Some(value) = var("SLOT_SECS") {
+            self.consensus.slot_secs = parse_env("SLOT_SECS", value)?;
+        }
+        if let Some(value) = var("BLOCK_INTERVAL_SECS") {
+            self.consensus.block_interval_secs = parse_env("BLOCK_INTERVAL_SECS", value)?;
+        }
+        if let Some(value) = var("AUTHORITIES") {
+            self.consensus.authorities = value
+                .split(',')
+                .map(str::trim)
+                .filter(|authority| !authority.is_empty())
+                .map(|authority| parse_env("AUTHORITIES", authority.to_string()))
+                .collect::<Result<_>>()?;
+        }
+        if let Some(value) = var("LEDGER") {
+            self.consensus.ledger = parse_env("LEDGER", value)?;
+        }
+        if let Some(value) = var("BLOCK_REWARD") {
+            self.consensus.block_reward = parse_env("BLOCK_REWARD", value)?;
+        }
+        if let Some(value) = var("HALVING_INTERVAL") {
+            self.consensus.halving_interval = parse_env("HALVING_INTERVAL", value)?;
+        }
+        if let Some(value) = var("TAIL_EMISSION") {
+            self.consensus.tail_emission = parse_env("TAIL_EMISSION", value)?;
+        }
+        if let Some(value) = var("MAX_SUPPLY") {
+            self.consensus.max_supply = parse_env("MAX_SUPPLY", value)?;
+        }
+        if let Some(value) = var("MEMPOOL_MAX_SIZE") {
+            self.mempool.max_size = parse_env("MEMPOOL_MAX_SIZE", value)?;
+        }
+        if let Some(value) = var("MEMPOOL_MAX_PER_SENDER") {
+            self.mempool.max_per_sender = parse_env("MEMPOOL_MAX_PER_SENDER", value)?;
+        }
+        if let Some(value) = var("MEMPOOL_MIN_CONFIRMATIONS") {
+            self.mempool.min_confirmations = parse_env("MEMPOOL_MIN_CONFIRMATIONS", value)?;
+        }
+        if let Some(value) = var("MEMPOOL_MIN_FEE_RATE") {
+            self.mempool.min_fee_rate = parse_env("MEMPOOL_MIN_FEE_RATE", value)?;
+        }
+        if let Some(value) = var("MEMPOOL_MAX_TX_SIZE") {
+            self.mempool.max_tx_size = parse_env("MEMPOOL_MAX_TX_SIZE", value)?;
+        }
+        if let Some(value) = var("MEMPOOL_ACCEPT_ZERO_FEE") {
+            self.mempool.accept_zero_fee = parse_env("MEMPOOL_ACCEPT_ZERO_FEE", value)?;
+        }
+        if let Some(value) = var("RPC_API_KEYS") {
+            self.rpc.api_keys = value
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Some(value) = var("RPC_ADMIN_KEY") {
+            self.rpc.admin_key = Some(value);
+        }
+        if let Some(value) = var("RPC_REQUESTS_PER_MINUTE") {
+            self.rpc.requests_per_minute = parse_env("RPC_REQUESTS_PER_MINUTE", value)?;
+        }
+        if let Some(value) = var("RPC_TLS_CERT") {
+            self.rpc.tls_cert = Some(value.into());
+        }
+        if let Some(value) = var("RPC_TLS_KEY") {
+            self.rpc.tls_key = Some(value.into());
+        }
+        Ok(())
+    }
+
+    pub fn mempool_config(&self) -> MempoolConfig {
+        MempoolConfig {
+            max_size: self.mempool.max_size,
+            max_per_sender: self.mempool.max_per_sender,
+            min_confirmations: self.mempool.min_confirmations,
+            min_fee_rate: self.mempool.min_fee_rate,
+            max_tx_size: self.mempool.max_tx_size,
+            accept_zero_fee: self.mempool.accept_zero_fee,
+        }
+    }
+
+    pub fn emission_schedule(&self) -> EmissionSchedule {
+        EmissionSchedule {
+            initial_reward: self.consensus.block_reward,
+            halving_interval: self.consensus.halving_interval,
+            tail_emission: self.consensus.tail_emission,
+            max_supply: self.consensus.max_supply,
+        }
+    }
+
+    /// The consensus rules `consensus.engine` names, paying the emission
+    /// schedule of the config.
+    pub fn consensus_engine(&self) -> Arc<dyn ConsensusEngine> {
+        let emission = self.emission_schedule();
+        match self.consensus.engine {
+            Engine::Pow => Arc::new(ProofOfWork {
+                bits: self.consensus.bits.unwrap_or_else(|| {
+                    Target::from_zero_digits(self.consensus.difficulty).to_compact()
+                }),
+                hash: self.consensus.pow_hash,
+                emission,
+            }),
+            Engine::Pos => Arc::new(ProofOfStake {
+                slot: Duration::from_secs(self.consensus.slot_secs),
+                emission,
+            }),
+            Engine::Dev => Arc::new(FixedInterval {
+                interval: Duration::from_secs(self.consensus.block_interval_secs),
+                emission,
+            }),
+            Engine::Poa => Arc::new(ProofOfAuthority {
+                slot: Duration::from_secs(self.consensus.slot_secs),
+                authorities: self.consensus.authorities.clone(),
+                emission,
+            }),
+        }
+    }
+
+    pub fn timestamp_config(&self) -> TimestampConfig {
+        TimestampConfig {
+            median_window: self.consensus.median_window,
+            max_future_drift: Duration::from_secs(self.consensus.max_future_drift_secs),
+        }
+    }
+}
+
+/// Copies `overrides` into `base`, descending into tables both have.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => merge(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn parse_env<T: FromStr>(name: &str, value: String) -> Result<T> {
+    value.parse().map_err(|_| Error::InvalidEnv {
+        var: format!("{}{}", ENV_PREFIX, name),
+        value,
+    })
+}
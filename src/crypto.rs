@@ -0,0 +1,370 @@
+//! Keys and signatures of the signature schemes wallets can use. P-256 is the
+//! original scheme and the default; secp256k1 and Ed25519 let keys be shared
+//! with other chains. Every encoded key and signature starts with its
+//! scheme's tag, so validators know which verifier to use.
+
+use std::{fmt, str::FromStr};
+
+use ed25519_dalek::{
+    pkcs8::{DecodePrivateKey as _, EncodePrivateKey as _},
+    Signer as _,
+};
+use p256::{
+    ecdsa::signature::{Signer as _, Verifier as _},
+    elliptic_curve::{sec1::ToEncodedPoint, zeroize::Zeroizing},
+    pkcs8::{DecodePrivateKey as _, EncodePrivateKey as _, LineEnding},
+};
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{self, Decode, Encode, Reader};
+
+/// Size of a signature in every scheme: `r || s` for ECDSA, `R || s` for
+/// Ed25519.
+const SIGNATURE_LEN: usize = 64;
+/// Size of a private key in every scheme.
+const SECRET_KEY_LEN: usize = 32;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+    #[error("invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("unknown signature scheme {0}, expected p256, secp256k1 or ed25519")]
+    UnknownScheme(String),
+}
+
+impl Error {
+    /// Identifier of the kind of error, stable across releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InvalidKey(_) => "invalid_key",
+            Error::InvalidSignature(_) => "invalid_signature",
+            Error::UnknownScheme(_) => "unknown_scheme",
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureScheme {
+    /// ECDSA over NIST P-256.
+    #[default]
+    P256,
+    /// ECDSA over secp256k1, as used by Bitcoin and Ethereum.
+    Secp256k1,
+    Ed25519,
+}
+
+impl SignatureScheme {
+    fn tag(self) -> u8 {
+        match self {
+            SignatureScheme::P256 => 0,
+            SignatureScheme::Secp256k1 => 1,
+            SignatureScheme::Ed25519 => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> codec::Result<Self> {
+        match tag {
+            0 => Ok(SignatureScheme::P256),
+            1 => Ok(SignatureScheme::Secp256k1),
+            2 => Ok(SignatureScheme::Ed25519),
+            tag => Err(codec::Error::InvalidTag(tag)),
+        }
+    }
+}
+
+impl fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SignatureScheme::P256 => "p256",
+            SignatureScheme::Secp256k1 => "secp256k1",
+            SignatureScheme::Ed25519 => "ed25519",
+        })
+    }
+}
+
+impl FromStr for SignatureScheme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "p256" | "p-256" | "secp256r1" => Ok(SignatureScheme::P256),
+            "secp256k1" => Ok(SignatureScheme::Secp256k1),
+            "ed25519" => Ok(SignatureScheme::Ed25519),
+            _ => Err(Error::UnknownScheme(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublicKey {
+    P256(p256::PublicKey),
+    Secp256k1(k256::PublicKey),
+    /// The encoded point, checked to be valid when the key was built. Kept
+    /// compressed since the decompressed form is several times larger.
+    Ed25519([u8; 32]),
+}
+
+impl PublicKey {
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            PublicKey::P256(_) => SignatureScheme::P256,
+            PublicKey::Secp256k1(_) => SignatureScheme::Secp256k1,
+            PublicKey::Ed25519(_) => SignatureScheme::Ed25519,
+        }
+    }
+
+    /// The key without its scheme: SEC1 compressed for the ECDSA curves, the
+    /// 32 byte point for Ed25519. Addresses are the HASH160 of these bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            PublicKey::P256(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+            PublicKey::Secp256k1(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+            PublicKey::Ed25519(key) => key.to_vec(),
+        }
+    }
+
+    pub fn from_bytes(scheme: SignatureScheme, bytes: &[u8]) -> Result<Self> {
+        let invalid = |e: &dyn fmt::Display| Error::InvalidKey(format!("{} {}", scheme, e));
+        match scheme {
+            SignatureScheme::P256 => p256::PublicKey::from_sec1_bytes(bytes)
+                .map(PublicKey::P256)
+                .map_err(|e| invalid(&e)),
+            SignatureScheme::Secp256k1 => k256::PublicKey::from_sec1_bytes(bytes)
+                .map(PublicKey::Secp256k1)
+                .map_err(|e| invalid(&e)),
+            SignatureScheme::Ed25519 => <&[u8; 32]>::try_from(bytes)
+                .map_err(|e| invalid(&e))
+                .and_then(|bytes| {
+                    ed25519_dalek::VerifyingKey::from_bytes(bytes).map_err(|e| invalid(&e))
+                })
+                .map(|key| PublicKey::Ed25519(key.to_bytes())),
+        }
+    }
+
+    /// Checks that `signature` is this key's signature of `message`.
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> Result<()> {
+        let result = match (self, signature) {
+            (PublicKey::P256(key), Signature::P256(signature)) => {
+                p256::ecdsa::VerifyingKey::from(key)
+                    .verify(message, signature)
+                    .map_err(|e| e.to_string())
+            }
+            (PublicKey::Secp256k1(key), Signature::Secp256k1(signature)) => {
+                k256::ecdsa::VerifyingKey::from(key)
+                    .verify(message, signature)
+                    .map_err(|e| e.to_string())
+            }
+            (PublicKey::Ed25519(key), Signature::Ed25519(signature)) => {
+                ed25519_dalek::VerifyingKey::from_bytes(key)
+                    .and_then(|key| key.verify_strict(message, signature))
+                    .map_err(|e| e.to_string())
+            }
+            _ => Err(format!(
+                "{} signature for a {} key",
+                signature.scheme(),
+                self.scheme()
+            )),
+        };
+        result.map_err(Error::InvalidSignature)
+    }
+}
+
+impl From<p256::PublicKey> for PublicKey {
+    fn from(key: p256::PublicKey) -> Self {
+        PublicKey::P256(key)
+    }
+}
+
+impl Encode for PublicKey {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.scheme().tag().encode_to(out);
+        self.to_bytes().encode_to(out);
+    }
+}
+
+impl Decode for PublicKey {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let scheme = SignatureScheme::from_tag(u8::decode_from(reader)?)?;
+        let len = reader.take_len()?;
+        PublicKey::from_bytes(scheme, reader.take(len)?)
+            .map_err(|e| codec::Error::InvalidValue(e.to_string()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signature {
+    P256(p256::ecdsa::Signature),
+    Secp256k1(k256::ecdsa::Signature),
+    Ed25519(ed25519_dalek::Signature),
+}
+
+impl Signature {
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            Signature::P256(_) => SignatureScheme::P256,
+            Signature::Secp256k1(_) => SignatureScheme::Secp256k1,
+            Signature::Ed25519(_) => SignatureScheme::Ed25519,
+        }
+    }
+
+    /// The 64 signature bytes, without the scheme.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Signature::P256(signature) => signature.as_ref().to_vec(),
+            Signature::Secp256k1(signature) => signature.as_ref().to_vec(),
+            Signature::Ed25519(signature) => signature.to_bytes().to_vec(),
+        }
+    }
+
+    pub fn from_bytes(scheme: SignatureScheme, bytes: &[u8]) -> Result<Self> {
+        let invalid = |e: &dyn fmt::Display| Error::InvalidSignature(format!("{} {}", scheme, e));
+        match scheme {
+            SignatureScheme::P256 => p256::ecdsa::Signature::try_from(bytes)
+                .map(Signature::P256)
+                .map_err(|e| invalid(&e)),
+            SignatureScheme::Secp256k1 => k256::ecdsa::Signature::try_from(bytes)
+                .map(Signature::Secp256k1)
+                .map_err(|e| invalid(&e)),
+            SignatureScheme::Ed25519 => ed25519_dalek::Signature::from_slice(bytes)
+                .map(Signature::Ed25519)
+                .map_err(|e| invalid(&e)),
+        }
+    }
+}
+
+impl Encode for Signature {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.scheme().tag().encode_to(out);
+        out.extend_from_slice(&self.to_bytes());
+    }
+}
+
+impl Decode for Signature {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let scheme = SignatureScheme::from_tag(u8::decode_from(reader)?)?;
+        Signature::from_bytes(scheme, reader.take(SIGNATURE_LEN)?)
+            .map_err(|e| codec::Error::InvalidValue(e.to_string()))
+    }
+}
+
+/// A private key of any scheme, which signs with it.
+///
+/// ```
+/// use aeonia::crypto::{KeyPair, SignatureScheme};
+///
+/// for scheme in [
+///     SignatureScheme::P256,
+///     SignatureScheme::Secp256k1,
+///     SignatureScheme::Ed25519,
+/// ] {
+///     let key = KeyPair::generate(scheme, &mut rand_core::OsRng);
+///     let signature = key.sign(b"hello");
+///     assert!(key.public_key().verify(b"hello", &signature).is_ok());
+///     assert!(key.public_key().verify(b"goodbye", &signature).is_err());
+///
+///     let restored = KeyPair::from_pkcs8_pem(&key.to_pkcs8_pem().unwrap()).unwrap();
+///     assert_eq!(restored.public_key(), key.public_key());
+/// }
+/// ```
+#[derive(Clone)]
+pub enum KeyPair {
+    P256(p256::SecretKey),
+    Secp256k1(k256::SecretKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+impl KeyPair {
+    pub fn generate<R: CryptoRngCore>(scheme: SignatureScheme, rng: &mut R) -> Self {
+        match scheme {
+            SignatureScheme::P256 => KeyPair::P256(p256::SecretKey::random(rng)),
+            SignatureScheme::Secp256k1 => KeyPair::Secp256k1(k256::SecretKey::random(rng)),
+            SignatureScheme::Ed25519 => KeyPair::Ed25519(ed25519_dalek::SigningKey::generate(rng)),
+        }
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            KeyPair::P256(_) => SignatureScheme::P256,
+            KeyPair::Secp256k1(_) => SignatureScheme::Secp256k1,
+            KeyPair::Ed25519(_) => SignatureScheme::Ed25519,
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            KeyPair::P256(key) => PublicKey::P256(key.public_key()),
+            KeyPair::Secp256k1(key) => PublicKey::Secp256k1(key.public_key()),
+            KeyPair::Ed25519(key) => PublicKey::Ed25519(key.verifying_key().to_bytes()),
+        }
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        match self {
+            KeyPair::P256(key) => Signature::P256(p256::ecdsa::SigningKey::from(key).sign(message)),
+            KeyPair::Secp256k1(key) => {
+                Signature::Secp256k1(k256::ecdsa::SigningKey::from(key).sign(message))
+            }
+            KeyPair::Ed25519(key) => Signature::Ed25519(key.sign(message)),
+        }
+    }
+
+    /// The 32 byte private key, without the scheme.
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(match self {
+            KeyPair::P256(key) => key.to_be_bytes().to_vec(),
+            KeyPair::Secp256k1(key) => key.to_be_bytes().to_vec(),
+            KeyPair::Ed25519(key) => key.to_bytes().to_vec(),
+        })
+    }
+
+    pub fn from_bytes(scheme: SignatureScheme, bytes: &[u8]) -> Result<Self> {
+        let invalid = || Error::InvalidKey(format!("not a {} private key", scheme));
+        match scheme {
+            SignatureScheme::P256 => p256::SecretKey::from_be_bytes(bytes)
+                .map(KeyPair::P256)
+                .map_err(|_| invalid()),
+            SignatureScheme::Secp256k1 => k256::SecretKey::from_be_bytes(bytes)
+                .map(KeyPair::Secp256k1)
+                .map_err(|_| invalid()),
+            SignatureScheme::Ed25519 => <&[u8; SECRET_KEY_LEN]>::try_from(bytes)
+                .map(|bytes| KeyPair::Ed25519(ed25519_dalek::SigningKey::from_bytes(bytes)))
+                .map_err(|_| invalid()),
+        }
+    }
+
+    /// PKCS#8 PEM of the key, which records its scheme.
+    pub fn to_pkcs8_pem(&self) -> Result<Zeroizing<String>> {
+        let invalid = |e: &dyn fmt::Display| Error::InvalidKey(e.to_string());
+        match self {
+            KeyPair::P256(key) => key.to_pkcs8_pem(LineEnding::LF).map_err(|e| invalid(&e)),
+            KeyPair::Secp256k1(key) => key.to_pkcs8_pem(LineEnding::LF).map_err(|e| invalid(&e)),
+            KeyPair::Ed25519(key) => key
+                .to_pkcs8_pem(ed25519_dalek::pkcs8::spki::der::pem::LineEnding::LF)
+                .map_err(|e| invalid(&e)),
+        }
+    }
+
+    pub fn from_pkcs8_pem(pem: &str) -> Result<Self> {
+        if let Ok(key) = p256::SecretKey::from_pkcs8_pem(pem) {
+            return Ok(KeyPair::P256(key));
+        }
+        if let Ok(key) = k256::SecretKey::from_pkcs8_pem(pem) {
+            return Ok(KeyPair::Secp256k1(key));
+        }
+        ed25519_dalek::SigningKey::from_pkcs8_pem(pem)
+            .map(KeyPair::Ed25519)
+            .map_err(|e| Error::InvalidKey(e.to_string()))
+    }
+}
+
+impl From<p256::SecretKey> for KeyPair {
+    fn from(key: p256::SecretKey) -> Self {
+        KeyPair::P256(key)
+    }
+}
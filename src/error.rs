@@ -0,0 +1,67 @@
+//! One error type for the whole crate, wrapping the error of each module so
+//! callers that go through several of them, such as the RPC server, can
+//! return a single type. Each error keeps its module's error as its source
+//! and reports that error's [`Error::code`].
+
+use std::io;
+
+use crate::{address, blockchain, codec, config, crypto, wallet};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Address(#[from] address::Error),
+    #[error(transparent)]
+    Blockchain(#[from] blockchain::Error),
+    #[error(transparent)]
+    Codec(#[from] codec::Error),
+    #[error(transparent)]
+    Config(#[from] config::Error),
+    #[error(transparent)]
+    Crypto(#[from] crypto::Error),
+    #[error(transparent)]
+    Wallet(#[from] wallet::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    /// Identifier of the kind of error, such as `insufficient_funds` or
+    /// `unknown_parent`. Codes never change meaning between releases, so
+    /// clients can match on them rather than on the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Address(e) => e.code(),
+            Error::Blockchain(e) => e.code(),
+            Error::Codec(e) => e.code(),
+            Error::Config(e) => e.code(),
+            Error::Crypto(e) => e.code(),
+            Error::Wallet(e) => e.code(),
+            Error::Io(_) => "io",
+        }
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Address(_) => io::ErrorKind::InvalidInput,
+            Error::Blockchain(e) => e.kind(),
+            Error::Codec(_) => io::ErrorKind::InvalidData,
+            Error::Config(config::Error::Io { source, .. }) => source.kind(),
+            Error::Config(_) => io::ErrorKind::InvalidData,
+            Error::Crypto(_) => io::ErrorKind::Other,
+            Error::Wallet(e) => e.kind(),
+            Error::Io(e) => e.kind(),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Io(e) => e,
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,1250 @@
+//! gRPC interface to a node, generated from `proto/aeonia.proto`, for
+//! clients in other languages and for following new blocks as they arrive.
+//!
+//! [`GrpcServer`] serves the same [`SharedBlockchain`] the miner and
+//! [`MetricsServer`](crate::metrics::MetricsServer) use, on a tokio runtime
+//! of its own.
+//!
+//! Failed calls carry the [`Error::code`] of what went wrong in the
+//! `aeonia-error-code` metadata of their status, alongside the message.
+//!
+//! Access follows the [`RpcConfig`]: once API keys are configured, calls
+//! must present one, or the admin key, as `authorization: Bearer <key>`;
+//! the admin calls (`Mine`, `Faucet`, `Shutdown` and the peer calls) always
+//! need the admin key; and each client is held to its share of calls a
+//! minute, refused with `RESOURCE_EXHAUSTED` beyond it. With `tls_cert` and
+//! `tls_key` set, the service is only served over TLS.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{
+    transport::{Identity, Server, ServerTlsConfig},
+    Request, Response, Status,
+};
+
+use crate::{
+    address::{Address, MultisigAddress},
+    blockchain::{
+        self, AuthorityOp, Block, BlockHeader, Blockchain, ChainEvent, Cosignature, DoubleSign,
+        Expiry, Htlc, ListFilter, MempoolEntry, Origin, OutPoint, SharedBlockchain, StakeOp,
+        TokenOp, Transaction, TxId, TxLocation, Witness,
+    },
+    clock::{Clock, SystemClock},
+    codec::{Decode, Encode},
+    config::RpcConfig,
+    crypto::{PublicKey, Signature, SignatureScheme},
+    error::Error,
+    network::{Ban, BanList, PeerBook, BAN_DURATION},
+    node::ShutdownHandle,
+    wallet::Wallet,
+};
+
+/// Metadata key of the status of a failed call holding its error code.
+pub const ERROR_CODE_KEY: &str = "aeonia-error-code";
+/// Most clients whose rate of calls is tracked at once; clients idle long
+/// enough to have their full allowance back are forgotten past it.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Messages and service definitions generated from the protobuf file.
+pub mod proto {
+    tonic::include_proto!("aeonia");
+}
+
+use proto::node_server::{Node, NodeServer};
+
+/// Who may call the service, and what the admin calls act with.
+#[derive(Clone, Default)]
+pub struct GrpcOptions {
+    pub rpc: RpcConfig,
+    /// Wallet `Mine` pays rewards to and signs blocks with; the call fails
+    /// without one.
+    pub miner: Option<Arc<Wallet>>,
+    /// Flag `Shutdown` raises; the call fails without one.
+    pub shutdown: Option<ShutdownHandle>,
+    /// Peers `ListPeers` reports; the peer calls fail without them and
+    /// the ban list.
+    pub peers: Option<Arc<Mutex<PeerBook>>>,
+    /// Ban list `BanPeer` and `UnbanPeer` change.
+    pub bans: Option<Arc<Mutex<BanList>>>,
+}
+
+/// Serves the `aeonia.Node` service on a background thread until stopped.
+pub struct GrpcServer;
+
+impl GrpcServer {
+    /// Binds `address` and serves on it, over TLS when `options.rpc`
+    /// names a certificate and key. Fails before serving if they can't be
+    /// read or don't make a valid identity.
+    pub fn start(
+        address: &str,
+        blockchain: SharedBlockchain,
+        options: GrpcOptions,
+    ) -> io::Result<GrpcHandle> {
+        let listener = std::net::TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let mut server = Server::builder();
+        if let (Some(cert), Some(key)) = (&options.rpc.tls_cert, &options.rpc.tls_key) {
+            let identity = Identity::from_pem(fs::read(cert)?, fs::read(key)?);
+            server = server
+                .tls_config(ServerTlsConfig::new().identity(identity))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+        let (stop, stopped) = watch::channel(false);
+        let service = NodeServer::new(NodeService {
+            blockchain,
+            stopped: stopped.clone(),
+            gate: Gate::new(options.rpc),
+            miner: options.miner,
+            shutdown: options.shutdown,
+            peers: options.peers,
+            bans: options.bans,
+        });
+        let thread = thread::spawn(move || {
+            let _ = runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener)?;
+                server
+                    .add_service(service)
+                    .serve_with_incoming_shutdown(
+                        TcpListenerStream::new(listener),
+                        wait_for_stop(stopped),
+                    )
+                    .await
+                    .map_err(io::Error::other)
+            });
+        });
+        Ok(GrpcHandle {
+            local_addr,
+            stop,
+            thread,
+        })
+    }
+}
+
+pub struct GrpcHandle {
+    local_addr: SocketAddr,
+    stop: watch::Sender<bool>,
+    thread: JoinHandle<()>,
+}
+
+impl GrpcHandle {
+    /// Address the server is bound to, useful when listening on port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting calls, lets calls in progress finish and waits for
+    /// the server to exit. Open block streams are closed.
+    pub fn stop(self) {
+        let _ = self.stop.send(true);
+        let _ = self.thread.join();
+    }
+}
+
+struct NodeService {
+    blockchain: SharedBlockchain,
+    stopped: watch::Receiver<bool>,
+    gate: Gate,
+    miner: Option<Arc<Wallet>>,
+    shutdown: Option<ShutdownHandle>,
+    peers: Option<Arc<Mutex<PeerBook>>>,
+    bans: Option<Arc<Mutex<BanList>>>,
+}
+
+/// What a call needs to be let through.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    /// An API key, if any are configured.
+    Public,
+    /// The admin key.
+    Admin,
+}
+
+/// Checks the key every call presents and counts the call against its
+/// client's allowance.
+struct Gate {
+    config: RpcConfig,
+    clients: Mutex<HashMap<String, Allowance>>,
+}
+
+/// Calls a client has left, refilled continuously up to a minute's worth.
+struct Allowance {
+    calls: f64,
+    updated: Instant,
+}
+
+impl Gate {
+    fn new(config: RpcConfig) -> Self {
+        Gate {
+            config,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Lets a call needing `access` through, or says why not. Clients are
+    /// told apart by their key when they present a valid one, and by their
+    /// IP address otherwise.
+    fn admit<T>(&self, request: &Request<T>, access: Access) -> Result<(), Refusal> {
+        let key = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let admin = key.is_some_and(|key| {
+            self.config
+                .admin_key
+                .as_deref()
+                .is_some_and(|admin| keys_match(admin, key))
+        });
+        let known = admin
+            || key.is_some_and(|key| {
+                self.config
+                    .api_keys
+                    .iter()
+                    .any(|known| keys_match(known, key))
+            });
+        let client = match (key, known) {
+            (Some(key), true) => format!("key:{}", key),
+            _ => request
+                .remote_addr()
+                .map_or_else(String::new, |addr| addr.ip().to_string()),
+        };
+        self.count(client)?;
+        match access {
+            Access::Admin if self.config.admin_key.is_none() => Err(Refusal::AdminDisabled),
+            Access::Admin if !admin => Err(Refusal::NotAdmin),
+            Access::Public if !self.config.api_keys.is_empty() && !known => {
+                Err(Refusal::Unauthenticated)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn count(&self, client: String) -> Result<(), Refusal> {
+        let limit = f64::from(self.config.requests_per_minute);
+        if limit == 0.0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        let refill = |allowance: &Allowance| {
+            let elapsed = now.duration_since(allowance.updated).as_secs_f64();
+            (allowance.calls + elapsed * limit / 60.0).min(limit)
+        };
+        if clients.len() >= MAX_TRACKED_CLIENTS {
+            clients.retain(|_, allowance| refill(allowance) < limit);
+        }
+        let allowance = clients.entry(client).or_insert(Allowance {
+            calls: limit,
+            updated: now,
+        });
+        allowance.calls = refill(allowance);
+        allowance.updated = now;
+        if allowance.calls < 1.0 {
+            return Err(Refusal::RateLimited);
+        }
+        allowance.calls -= 1.0;
+        Ok(())
+    }
+}
+
+/// Why [`Gate::admit`] turned a call away.
+#[derive(Clone, Copy, Debug)]
+enum Refusal {
+    Unauthenticated,
+    NotAdmin,
+    AdminDisabled,
+    RateLimited,
+}
+
+impl From<Refusal> for Status {
+    fn from(refusal: Refusal) -> Self {
+        let (mut status, code) = match refusal {
+            Refusal::Unauthenticated => (
+                Status::unauthenticated("missing or unknown API key"),
+                "unauthenticated",
+            ),
+            Refusal::NotAdmin => (
+                Status::permission_denied("this call needs the admin key"),
+                "permission_denied",
+            ),
+            Refusal::AdminDisabled => (
+                Status::permission_denied("admin calls are disabled on this node"),
+                "admin_disabled",
+            ),
+            Refusal::RateLimited => (
+                Status::resource_exhausted("too many calls, try again later"),
+                "rate_limited",
+            ),
+        };
+        if let Ok(code) = code.parse() {
+            status.metadata_mut().insert(ERROR_CODE_KEY, code);
+        }
+        status
+    }
+}
+
+/// Compares keys in time independent of where they differ.
+fn keys_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Resolves once the server is asked to stop.
+async fn wait_for_stop(mut stopped: watch::Receiver<bool>) {
+    let _ = stopped.wait_for(|stopped| *stopped).await;
+}
+
+impl NodeService {
+    /// Runs `f` with read access to the chain, off the async worker threads
+    /// since the miner may hold the lock for a while.
+    async fn with_chain<T, F>(&self, f: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Blockchain) -> blockchain::Result<T> + Send + 'static,
+    {
+        let blockchain = self.blockchain.clone();
+        tokio::task::spawn_blocking(move || f(&*blockchain.read()?))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(status)
+    }
+
+    /// Like [`NodeService::with_chain`], for calls that change the chain.
+    async fn with_chain_mut<T, F>(&self, f: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Blockchain) -> blockchain::Result<T> + Send + 'static,
+    {
+        let blockchain = self.blockchain.clone();
+        tokio::task::spawn_blocking(move || f(&mut *blockchain.write()?))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(status)
+    }
+}
+
+#[tonic::async_trait]
+impl Node for NodeService {
+    async fn submit_transaction(
+        &self,
+        request: Request<proto::Transaction>,
+    ) -> Result<Response<proto::SubmitTransactionResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let transaction =
+            Transaction::try_from(request.into_inner()).map_err(Status::invalid_argument)?;
+        let transaction = self
+            .with_chain_mut(move |blockchain| blockchain.add_transation_to_pool(transaction))
+            .await?;
+        Ok(Response::new(proto::SubmitTransactionResponse {
+            id: transaction.id().to_string(),
+        }))
+    }
+
+    async fn test_mempool_accept(
+        &self,
+        request: Request<proto::Transaction>,
+    ) -> Result<Response<proto::TestMempoolAcceptResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let transaction =
+            Transaction::try_from(request.into_inner()).map_err(Status::invalid_argument)?;
+        let id = transaction.id().to_string();
+        let result = self
+            .with_chain(move |blockchain| Ok(blockchain.test_accept(&transaction)))
+            .await?;
+        Ok(Response::new(match result {
+            Ok(()) => proto::TestMempoolAcceptResponse {
+                id,
+                accepted: true,
+                reject_code: String::new(),
+                reject_reason: String::new(),
+            },
+            Err(e) => proto::TestMempoolAcceptResponse {
+                id,
+                accepted: false,
+                reject_code: e.code().to_string(),
+                reject_reason: e.to_string(),
+            },
+        }))
+    }
+
+    async fn submit_batch(
+        &self,
+        request: Request<proto::SubmitBatchRequest>,
+    ) -> Result<Response<proto::SubmitBatchResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let transactions = request
+            .into_inner()
+            .transactions
+            .into_iter()
+            .map(Transaction::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Status::invalid_argument)?;
+        let transactions = self
+            .with_chain_mut(move |blockchain| blockchain.add_batch_to_pool(transactions))
+            .await?;
+        Ok(Response::new(proto::SubmitBatchResponse {
+            ids: transactions.iter().map(|t| t.id().to_string()).collect(),
+        }))
+    }
+
+    async fn get_block(
+        &self,
+        request: Request<proto::GetBlockRequest>,
+    ) -> Result<Response<proto::Block>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let found = match request.into_inner().block {
+            Some(proto::get_block_request::Block::Height(height)) => {
+                self.with_chain(move |blockchain| {
+                    Ok(blockchain.block_by_height(height).map(|b| (b, height)))
+                })
+                .await?
+            }
+            Some(proto::get_block_request::Block::Hash(hash)) => {
+                self.with_chain(move |blockchain| {
+                    Ok(blockchain
+                        .block_by_hash(&hash)
+                        .zip(blockchain.height_of(&hash)))
+                })
+                .await?
+            }
+            None => return Err(Status::invalid_argument("no height or hash given")),
+        };
+        let (block, height) = found.ok_or_else(|| Status::not_found("no such block"))?;
+        if block.is_pruned() {
+            return Err(status(blockchain::Error::Pruned { height }));
+        }
+        Ok(Response::new(block_message(&block, height)))
+    }
+
+    async fn list_blocks(
+        &self,
+        request: Request<proto::ListBlocksRequest>,
+    ) -> Result<Response<proto::ListBlocksResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let request = request.into_inner();
+        let filter = ListFilter::try_from(request.filter.unwrap_or_default())?;
+        let page = self
+            .with_chain(move |blockchain| {
+                blockchain.list_blocks(request.from_height, request.limit as usize, &filter)
+            })
+            .await?;
+        Ok(Response::new(proto::ListBlocksResponse {
+            blocks: page
+                .blocks
+                .iter()
+                .map(|(height, block)| block_message(block, *height))
+                .collect(),
+            next_height: page.next_height,
+        }))
+    }
+
+    async fn list_transactions(
+        &self,
+        request: Request<proto::ListTransactionsRequest>,
+    ) -> Result<Response<proto::ListTransactionsResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let request = request.into_inner();
+        let filter = ListFilter::try_from(request.filter.unwrap_or_default())?;
+        let from = request.from.map_or(TxLocation::default(), Into::into);
+        let page = self
+            .with_chain(move |blockchain| {
+                blockchain.list_transactions(from, request.limit as usize, &filter)
+            })
+            .await?;
+        Ok(Response::new(proto::ListTransactionsResponse {
+            transactions: page
+                .transactions
+                .iter()
+                .map(|(transaction, location)| proto::ConfirmedTransaction {
+                    id: transaction.id().to_string(),
+                    location: Some((*location).into()),
+                    transaction: Some(transaction.into()),
+                })
+                .collect(),
+            next: page.next.map(Into::into),
+        }))
+    }
+
+    async fn get_balance(
+        &self,
+        request: Request<proto::GetBalanceRequest>,
+    ) -> Result<Response<proto::GetBalanceResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let address: Address = request.into_inner().address.parse().map_err(status)?;
+        let balance = self
+            .with_chain(move |blockchain| blockchain.calculate_transactions_total(&address))
+            .await?;
+        Ok(Response::new(proto::GetBalanceResponse { balance }))
+    }
+
+    async fn get_supply(
+        &self,
+        request: Request<proto::GetSupplyRequest>,
+    ) -> Result<Response<proto::GetSupplyResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let supply = self
+            .with_chain(|blockchain| {
+                Ok(proto::GetSupplyResponse {
+                    total: blockchain.total_supply()?,
+                    circulating: blockchain.circulating_supply()?,
+                    max: blockchain.max_supply(),
+                    next_reward: blockchain.current_reward()?,
+                })
+            })
+            .await?;
+        Ok(Response::new(supply))
+    }
+
+    async fn get_chain_stats(
+        &self,
+        request: Request<proto::GetChainStatsRequest>,
+    ) -> Result<Response<proto::GetChainStatsResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let top = request.into_inner().top as usize;
+        let stats = self
+            .with_chain(move |blockchain| blockchain.stats(top))
+            .await?;
+        Ok(Response::new(proto::GetChainStatsResponse {
+            height: stats.height,
+            total_supply: stats.total_supply,
+            circulating_supply: stats.circulating_supply,
+            active_addresses: stats.active_addresses,
+            top_holders: stats
+                .top_holders
+                .into_iter()
+                .map(|(address, balance)| proto::Holder {
+                    address: address.to_string(),
+                    balance,
+                })
+                .collect(),
+            average_block_interval: stats.average_block_interval,
+            transactions: stats.transactions,
+            average_fee: stats.average_fee,
+        }))
+    }
+
+    async fn estimate_fee(
+        &self,
+        request: Request<proto::EstimateFeeRequest>,
+    ) -> Result<Response<proto::EstimateFeeResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let target_blocks = request.into_inner().target_blocks;
+        let fee = self
+            .with_chain(move |blockchain| Ok(blockchain.estimate_fee(target_blocks)))
+            .await?;
+        Ok(Response::new(proto::EstimateFeeResponse { fee }))
+    }
+
+    async fn get_token_balances(
+        &self,
+        request: Request<proto::GetTokenBalancesRequest>,
+    ) -> Result<Response<proto::GetTokenBalancesResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let address: Address = request.into_inner().address.parse().map_err(status)?;
+        let balances = self
+            .with_chain(move |blockchain| {
+                Ok(blockchain
+                    .token_balances(&address)
+                    .into_iter()
+                    .map(|(id, amount)| proto::TokenBalance {
+                        name: blockchain
+                            .token(&id)
+                            .map(|token| token.name)
+                            .unwrap_or_default(),
+                        token_id: id.to_string(),
+                        amount,
+                    })
+                    .collect())
+            })
+            .await?;
+        Ok(Response::new(proto::GetTokenBalancesResponse { balances }))
+    }
+
+    async fn list_mempool(
+        &self,
+        request: Request<proto::ListMempoolRequest>,
+    ) -> Result<Response<proto::ListMempoolResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let entries = self
+            .with_chain(|blockchain| Ok(blockchain.mempool().entries()))
+            .await?;
+        Ok(Response::new(proto::ListMempoolResponse {
+            entries: entries.iter().map(proto::MempoolEntry::from).collect(),
+        }))
+    }
+
+    async fn get_mempool_info(
+        &self,
+        request: Request<proto::GetMempoolInfoRequest>,
+    ) -> Result<Response<proto::GetMempoolInfoResponse>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let info = self
+            .with_chain(|blockchain| Ok(blockchain.mempool().info()))
+            .await?;
+        Ok(Response::new(proto::GetMempoolInfoResponse {
+            size: info.size as u64,
+            bytes: info.bytes as u64,
+            max_size: info.max_size as u64,
+            min_fee_rate: info.min_fee_rate,
+            fee_histogram: info
+                .fee_histogram
+                .iter()
+                .map(|bucket| proto::FeeBucket {
+                    min_fee_rate: bucket.min_fee_rate,
+                    count: bucket.count as u64,
+                    bytes: bucket.bytes as u64,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_mempool_entry(
+        &self,
+        request: Request<proto::GetMempoolEntryRequest>,
+    ) -> Result<Response<proto::MempoolEntry>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let id: TxId = request
+            .into_inner()
+            .id
+            .parse()
+            .map_err(Status::invalid_argument)?;
+        let entry = self
+            .with_chain(move |blockchain| Ok(blockchain.mempool().entry(&id)))
+            .await?
+            .ok_or_else(|| Status::not_found("no such pending transaction"))?;
+        Ok(Response::new(proto::MempoolEntry::from(&entry)))
+    }
+
+    type StreamBlocksStream = ReceiverStream<Result<proto::Block, Status>>;
+
+    async fn stream_blocks(
+        &self,
+        request: Request<proto::StreamBlocksRequest>,
+    ) -> Result<Response<Self::StreamBlocksStream>, Status> {
+        self.gate.admit(&request, Access::Public)?;
+        let events = self
+            .with_chain_mut(|blockchain| Ok(blockchain.subscribe()))
+            .await?;
+        let (sender, mut blocks) = mpsc::channel(16);
+        let blockchain = self.blockchain.clone();
+        // Event receivers block, so the stream is fed from a plain thread
+        // that exits once the client goes away or the node shuts down.
+        thread::spawn(move || {
+            for event in events {
+                let hashes = match event {
+                    ChainEvent::BlockMined { hash, .. }
+                    | ChainEvent::BlockConnected { hash, .. } => {
+                        vec![hash]
+                    }
+                    ChainEvent::Reorg { connected, .. } => connected,
+                    ChainEvent::Shutdown => return,
+                    _ => continue,
+                };
+                for hash in hashes {
+                    let found = match blockchain.read() {
+                        Ok(blockchain) => blockchain
+                            .block_by_hash(&hash)
+                            .zip(blockchain.height_of(&hash)),
+                        Err(_) => return,
+                    };
+                    // Blocks reorganized away since the event are skipped.
+                    let Some((block, height)) = found else {
+                        continue;
+                    };
+                    if sender
+                        .blocking_send(Ok(block_message(&block, height)))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        });
+        // Ending the stream on stop lets the server's graceful shutdown
+        // finish.
+        let (forward, receiver) = mpsc::channel(16);
+        let stop = wait_for_stop(self.stopped.clone());
+        tokio::spawn(async move {
+            tokio::pin!(stop);
+            loop {
+                tokio::select! {
+                    _ = &mut stop => break,
+                    block = blocks.recv() => {
+                        let Some(block) = block else { break };
+                        if forward.send(block).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn mine(
+        &self,
+        request: Request<proto::MineRequest>,
+    ) -> Result<Response<proto::Block>, Status> {
+        self.gate.admit(&request, Access::Admin)?;
+        let miner = self
+            .miner
+            .clone()
+            .ok_or_else(|| Status::failed_precondition("the node has no miner wallet"))?;
+        let blockchain = self.blockchain.clone();
+        let mined = tokio::task::spawn_blocking(move || {
+            if !blockchain.mine(&miner) {
+                return None;
+            }
+            // The background miner may have added blocks since, so the
+            // block is looked for back from the tip.
+            let blockchain = blockchain.read().ok()?;
+            (0..=blockchain.chain_height().ok()?)
+                .rev()
+                .find_map(|height| {
+                    let block = blockchain.block_by_height(height)?;
+                    (block.miner() == miner.address()).then_some((block, height))
+                })
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+        let (block, height) = mined.ok_or_else(|| Status::aborted("no block was mined"))?;
+        Ok(Response::new(block_message(&block, height)))
+    }
+
+    async fn faucet(
+        &self,
+        request: Request<proto::FaucetRequest>,
+    ) -> Result<Response<proto::SubmitTransactionResponse>, Status> {
+        self.gate.admit(&request, Access::Admin)?;
+        let request = request.into_inner();
+        let address: Address = request.address.parse().map_err(status)?;
+        let transaction = self
+            .with_chain_mut(move |blockchain| {
+                blockchain.request_from_faucet(&address, request.amount)
+            })
+            .await?;
+        Ok(Response::new(proto::SubmitTransactionResponse {
+            id: transaction.id().to_string(),
+        }))
+    }
+
+    async fn shutdown(
+        &self,
+        request: Request<proto::ShutdownRequest>,
+    ) -> Result<Response<proto::ShutdownResponse>, Status> {
+        self.gate.admit(&request, Access::Admin)?;
+        let shutdown = self
+            .shutdown
+            .as_ref()
+            .ok_or_else(|| Status::failed_precondition("the node can't be shut down remotely"))?;
+        shutdown.request();
+        Ok(Response::new(proto::ShutdownResponse {}))
+    }
+
+    async fn list_peers(
+        &self,
+        request: Request<proto::ListPeersRequest>,
+    ) -> Result<Response<proto::ListPeersResponse>, Status> {
+        self.gate.admit(&request, Access::Admin)?;
+        let (book, bans) = self
+            .peers
+            .as_ref()
+            .zip(self.bans.as_ref())
+            .ok_or_else(no_network)?;
+        let peers = book
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(addr, info)| proto::Peer {
+                address: addr.to_string(),
+                last_seen: info.last_seen.unwrap_or_default(),
+                failures: info.failures,
+            })
+            .collect();
+        let banned = bans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter(SystemClock.now())
+            .map(|(ip, ban)| banned_address(ip, ban))
+            .collect();
+        Ok(Response::new(proto::ListPeersResponse { peers, banned }))
+    }
+
+    async fn ban_peer(
+        &self,
+        request: Request<proto::BanPeerRequest>,
+    ) -> Result<Response<proto::BanPeerResponse>, Status> {
+        self.gate.admit(&request, Access::Admin)?;
+        let request = request.into_inner();
+        let ip: IpAddr = request.ip.parse().map_err(|e| {
+            Status::invalid_argument(format!("invalid IP address {}: {}", request.ip, e))
+        })?;
+        let duration = match request.duration_secs {
+            0 => BAN_DURATION,
+            secs => Duration::from_secs(secs),
+        };
+        let reason = match request.reason.as_str() {
+            "" => "banned by hand",
+            reason => reason,
+        };
+        let bans = self.bans.as_ref().ok_or_else(no_network)?;
+        let mut bans = bans.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = SystemClock.now();
+        bans.ban(ip, now, duration, reason);
+        let ban = bans.iter(now).find(|(banned, _)| **banned == ip);
+        Ok(Response::new(proto::BanPeerResponse {
+            ban: ban.map(|(ip, ban)| banned_address(ip, ban)),
+        }))
+    }
+
+    async fn unban_peer(
+        &self,
+        request: Request<proto::UnbanPeerRequest>,
+    ) -> Result<Response<proto::UnbanPeerResponse>, Status> {
+        self.gate.admit(&request, Access::Admin)?;
+        let request = request.into_inner();
+        let ip: IpAddr = request.ip.parse().map_err(|e| {
+            Status::invalid_argument(format!("invalid IP address {}: {}", request.ip, e))
+        })?;
+        let bans = self.bans.as_ref().ok_or_else(no_network)?;
+        let was_banned = bans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .unban(&ip);
+        Ok(Response::new(proto::UnbanPeerResponse { was_banned }))
+    }
+}
+
+fn no_network() -> Status {
+    Status::failed_precondition("the node has no peer network")
+}
+
+fn banned_address(ip: &IpAddr, ban: &Ban) -> proto::BannedAddress {
+    proto::BannedAddress {
+        ip: ip.to_string(),
+        until: ban.until,
+        reason: ban.reason.clone(),
+    }
+}
+
+fn status(error: impl Into<Error>) -> Status {
+    let error = error.into();
+    let message = error.to_string();
+    let mut status = match error.kind() {
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+            Status::invalid_argument(message)
+        }
+        io::ErrorKind::NotFound => Status::not_found(message),
+        io::ErrorKind::AlreadyExists => Status::already_exists(message),
+        io::ErrorKind::WouldBlock => Status::resource_exhausted(message),
+        io::ErrorKind::Unsupported => Status::failed_precondition(message),
+        _ => Status::internal(message),
+    };
+    if let Ok(code) = error.code().parse() {
+        status.metadata_mut().insert(ERROR_CODE_KEY, code);
+    }
+    status
+}
+
+fn block_message(block: &Block, height: u64) -> proto::Block {
+    proto::Block {
+        hash: block.hash(),
+        height,
+        previous_hash: block.previous_hash().clone(),
+        timestamp: block.timestamp(),
+        nonce: block.nonce(),
+        miner: block.miner().to_string(),
+        merkle_root: block.merkle_root().to_string(),
+        transactions: block.transactions().iter().map(Into::into).collect(),
+        witness: witness_message(block.witness()),
+        bits: block.bits(),
+        pruned: block.is_pruned(),
+    }
+}
+
+impl TryFrom<proto::ListFilter> for ListFilter {
+    type Error = Status;
+
+    fn try_from(filter: proto::ListFilter) -> Result<Self, Status> {
+        Ok(ListFilter {
+            since: filter.since,
+            until: filter.until,
+            address: match filter.address.as_str() {
+                "" => None,
+                address => Some(address.parse().map_err(status)?),
+            },
+        })
+    }
+}
+
+impl From<&MempoolEntry> for proto::MempoolEntry {
+    fn from(entry: &MempoolEntry) -> Self {
+        proto::MempoolEntry {
+            id: entry.id.to_string(),
+            position: entry.position as u64,
+            size: entry.size as u64,
+            fee_rate: entry.fee_rate,
+            ancestors: entry.ancestors as u64,
+            transaction: Some(proto::Transaction::from(&entry.transaction)),
+        }
+    }
+}
+
+impl From<proto::TxCursor> for TxLocation {
+    fn from(cursor: proto::TxCursor) -> Self {
+        TxLocation {
+            height: cursor.height,
+            index: cursor.index as usize,
+        }
+    }
+}
+
+impl From<TxLocation> for proto::TxCursor {
+    fn from(location: TxLocation) -> Self {
+        proto::TxCursor {
+            height: location.height,
+            index: location.index as u32,
+        }
+    }
+}
+
+impl From<SignatureScheme> for proto::SignatureScheme {
+    fn from(scheme: SignatureScheme) -> Self {
+        match scheme {
+            SignatureScheme::P256 => proto::SignatureScheme::P256,
+            SignatureScheme::Secp256k1 => proto::SignatureScheme::Secp256k1,
+            SignatureScheme::Ed25519 => proto::SignatureScheme::Ed25519,
+        }
+    }
+}
+
+impl From<proto::SignatureScheme> for SignatureScheme {
+    fn from(scheme: proto::SignatureScheme) -> Self {
+        match scheme {
+            proto::SignatureScheme::P256 => SignatureScheme::P256,
+            proto::SignatureScheme::Secp256k1 => SignatureScheme::Secp256k1,
+            proto::SignatureScheme::Ed25519 => SignatureScheme::Ed25519,
+        }
+    }
+}
+
+fn witness_message(witness: &Witness) -> Option<proto::Witness> {
+    let kind = match witness {
+        Witness::Unsigned => return None,
+        Witness::Single {
+            public_key,
+            signature,
+        } => proto::witness::Kind::Single(proto::SingleSignature {
+            public_key: public_key.to_bytes(),
+            signature: signature.to_bytes(),
+            scheme: proto::SignatureScheme::from(public_key.scheme()).into(),
+        }),
+        Witness::Multisig { policy, signatures } => {
+            proto::witness::Kind::Multisig(proto::MultisigSignatures {
+                threshold: u32::from(policy.threshold()),
+                keys: policy.keys().iter().map(PublicKey::to_bytes).collect(),
+                signatures: signatures
+                    .iter()
+                    .map(|cosignature| proto::Cosignature {
+                        key_index: u32::from(cosignature.key_index),
+                        signature: cosignature.signature.to_bytes(),
+                    })
+                    .collect(),
+                key_schemes: policy
+                    .keys()
+                    .iter()
+                    .map(|key| proto::SignatureScheme::from(key.scheme()).into())
+                    .collect(),
+            })
+        }
+        Witness::Htlc {
+            htlc,
+            preimage,
+            signature,
+        } => proto::witness::Kind::Htlc(proto::HtlcSignature {
+            hash: htlc.hash.to_vec(),
+            recipient_key: htlc.recipient.to_bytes(),
+            recipient_scheme: proto::SignatureScheme::from(htlc.recipient.scheme()).into(),
+            refund_key: htlc.refund.to_bytes(),
+            refund_scheme: proto::SignatureScheme::from(htlc.refund.scheme()).into(),
+            timeout: htlc.timeout,
+            path: Some(match preimage {
+                Some(preimage) => proto::htlc_signature::Path::Preimage(preimage.clone()),
+                None => proto::htlc_signature::Path::Refund(true),
+            }),
+            signature: signature.to_bytes(),
+        }),
+    };
+    Some(proto::Witness { kind: Some(kind) })
+}
+
+impl From<&Transaction> for proto::Transaction {
+    fn from(transaction: &Transaction) -> Self {
+        proto::Transaction {
+            origin: Some(match transaction.origin {
+                Origin::Coinbase { height } => proto::transaction::Origin::CoinbaseHeight(height),
+                Origin::Account(sender) => proto::transaction::Origin::Sender(sender.to_string()),
+            }),
+            recipient: transaction.recipient.to_string(),
+            amount: transaction.amount,
+            fee: transaction.fee,
+            sequence: transaction.sequence,
+            inputs: transaction
+                .inputs
+                .iter()
+                .map(|input| proto::OutPoint {
+                    txid: input.txid.to_string(),
+                    index: input.index,
+                })
+                .collect(),
+            witness: witness_message(&transaction.witness),
+            id: transaction.id().to_string(),
+            memo: transaction.memo.clone(),
+            expiry: transaction.expiry.map(|expiry| match expiry {
+                Expiry::Height(height) => proto::transaction::Expiry::ExpiryHeight(height),
+                Expiry::Time(time) => proto::transaction::Expiry::ExpiryTime(time),
+            }),
+            script: transaction
+                .script
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            script_args: transaction.script_args.clone(),
+            valid_after_height: transaction.valid_after_height.unwrap_or_default(),
+            valid_after_time: transaction.valid_after_time.unwrap_or_default(),
+            token: transaction.token.as_ref().map(|token| match token {
+                TokenOp::Issue { name, supply } => {
+                    proto::transaction::Token::TokenIssue(proto::TokenIssue {
+                        name: name.clone(),
+                        supply: *supply,
+                    })
+                }
+                TokenOp::Transfer { id, amount } => {
+                    proto::transaction::Token::TokenTransfer(proto::TokenTransfer {
+                        token_id: id.to_string(),
+                        amount: *amount,
+                    })
+                }
+            }),
+            staking: transaction.stake.as_ref().map(|stake| match stake {
+                StakeOp::Stake => proto::transaction::Staking::Stake(true),
+                StakeOp::Unstake { amount } => proto::transaction::Staking::Unstake(*amount),
+                StakeOp::Slash { evidence, amount } => {
+                    proto::transaction::Staking::Slash(proto::Slash {
+                        first: evidence.first.encode(),
+                        second: evidence.second.encode(),
+                        amount: *amount,
+                    })
+                }
+            }),
+            authority: transaction
+                .authority
+                .as_ref()
+                .map(|authority| match authority {
+                    AuthorityOp::Add { address } => {
+                        proto::transaction::Authority::AddAuthority(address.to_string())
+                    }
+                    AuthorityOp::Remove { address } => {
+                        proto::transaction::Authority::RemoveAuthority(address.to_string())
+                    }
+                }),
+        }
+    }
+}
+
+impl TryFrom<proto::Transaction> for Transaction {
+    type Error = String;
+
+    fn try_from(message: proto::Transaction) -> Result<Self, Self::Error> {
+        let origin = match message.origin {
+            Some(proto::transaction::Origin::CoinbaseHeight(height)) => Origin::Coinbase { height },
+            Some(proto::transaction::Origin::Sender(sender)) => {
+                Origin::Account(sender.parse().map_err(|e| format!("sender: {}", e))?)
+            }
+            None => return Err("transaction has no origin".into()),
+        };
+        let inputs = message
+            .inputs
+            .into_iter()
+            .map(|input| {
+                Ok(OutPoint {
+                    txid: input.txid.parse()?,
+                    index: input.index,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Transaction {
+            origin,
+            recipient: message
+                .recipient
+                .parse()
+                .map_err(|e| format!("recipient: {}", e))?,
+            amount: message.amount,
+            fee: message.fee,
+            sequence: message.sequence,
+            inputs,
+            memo: message.memo,
+            expiry: message.expiry.map(|expiry| match expiry {
+                proto::transaction::Expiry::ExpiryHeight(height) => Expiry::Height(height),
+                proto::transaction::Expiry::ExpiryTime(time) => Expiry::Time(time),
+            }),
+            valid_after_height: Some(message.valid_after_height).filter(|h| *h != 0),
+            valid_after_time: Some(message.valid_after_time).filter(|t| *t != 0),
+            token: match message.token {
+                Some(proto::transaction::Token::TokenIssue(issue)) => Some(TokenOp::Issue {
+                    name: issue.name,
+                    supply: issue.supply,
+                }),
+                Some(proto::transaction::Token::TokenTransfer(transfer)) => {
+                    Some(TokenOp::Transfer {
+                        id: transfer
+                            .token_id
+                            .parse()
+                            .map_err(|e| format!("token_id: {}", e))?,
+                        amount: transfer.amount,
+                    })
+                }
+                None => None,
+            },
+            stake: match message.staking {
+                Some(proto::transaction::Staking::Stake(_)) => Some(StakeOp::Stake),
+                Some(proto::transaction::Staking::Unstake(amount)) => {
+                    Some(StakeOp::Unstake { amount })
+                }
+                Some(proto::transaction::Staking::Slash(slash)) => {
+                    let header = |bytes: &[u8]| {
+                        BlockHeader::decode(bytes).map_err(|e| format!("slash evidence: {}", e))
+                    };
+                    Some(StakeOp::Slash {
+                        evidence: Box::new(DoubleSign {
+                            first: header(&slash.first)?,
+                            second: header(&slash.second)?,
+                        }),
+                        amount: slash.amount,
+                    })
+                }
+                None => None,
+            },
+            authority: match message.authority {
+                Some(proto::transaction::Authority::AddAuthority(address)) => {
+                    Some(AuthorityOp::Add {
+                        address: address.parse().map_err(|e| format!("authority: {}", e))?,
+                    })
+                }
+                Some(proto::transaction::Authority::RemoveAuthority(address)) => {
+                    Some(AuthorityOp::Remove {
+                        address: address.parse().map_err(|e| format!("authority: {}", e))?,
+                    })
+                }
+                None => None,
+            },
+            script: match message.script.as_str() {
+                "" => None,
+                script => Some(script.parse().map_err(|e| format!("script: {}", e))?),
+            },
+            witness: witness_from_message(message.witness)?,
+            script_args: message.script_args,
+        })
+    }
+}
+
+fn witness_from_message(message: Option<proto::Witness>) -> Result<Witness, String> {
+    let scheme = |scheme: i32| {
+        proto::SignatureScheme::try_from(scheme)
+            .map(SignatureScheme::from)
+            .map_err(|_| format!("unknown signature scheme {}", scheme))
+    };
+    match message.and_then(|witness| witness.kind) {
+        None => Ok(Witness::Unsigned),
+        Some(proto::witness::Kind::Single(single)) => {
+            let scheme = scheme(single.scheme)?;
+            Ok(Witness::Single {
+                public_key: PublicKey::from_bytes(scheme, &single.public_key)
+                    .map_err(|e| e.to_string())?,
+                signature: Signature::from_bytes(scheme, &single.signature)
+                    .map_err(|e| e.to_string())?,
+            })
+        }
+        Some(proto::witness::Kind::Multisig(multisig)) => {
+            let keys = multisig
+                .keys
+                .iter()
+                .enumerate()
+                .map(|(index, key)| {
+                    let scheme = scheme(multisig.key_schemes.get(index).copied().unwrap_or(0))?;
+                    PublicKey::from_bytes(scheme, key).map_err(|e| e.to_string())
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let threshold =
+                u8::try_from(multisig.threshold).map_err(|_| "threshold out of range")?;
+            let signatures = multisig
+                .signatures
+                .iter()
+                .map(|cosignature| {
+                    Ok(Cosignature {
+                        key_index: u8::try_from(cosignature.key_index)
+                            .map_err(|_| "key index out of range")?,
+                        signature: Signature::from_bytes(
+                            keys.get(usize::try_from(cosignature.key_index).unwrap_or(usize::MAX))
+                                .ok_or("key index out of range")?
+                                .scheme(),
+                            &cosignature.signature,
+                        )
+                        .map_err(|e| e.to_string())?,
+                    })
+                })
+                .collect::<Result<_, String>>()?;
+            Ok(Witness::Multisig {
+                policy: MultisigAddress::new(threshold, keys).map_err(|e| e.to_string())?,
+                signatures,
+            })
+        }
+        Some(proto::witness::Kind::Htlc(message)) => {
+            let key = |scheme_tag, bytes: &[u8]| {
+                PublicKey::from_bytes(scheme(scheme_tag)?, bytes).map_err(|e| e.to_string())
+            };
+            let htlc = Htlc::new(
+                message
+                    .hash
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| "HTLC hash is not 32 bytes")?,
+                key(message.recipient_scheme, &message.recipient_key)?,
+                key(message.refund_scheme, &message.refund_key)?,
+                message.timeout,
+            );
+            let preimage = match message.path {
+                Some(proto::htlc_signature::Path::Preimage(preimage)) => Some(preimage),
+                Some(proto::htlc_signature::Path::Refund(_)) | None => None,
+            };
+            let signer = match preimage {
+                Some(_) => &htlc.recipient,
+                None => &htlc.refund,
+            };
+            let signature = Signature::from_bytes(signer.scheme(), &message.signature)
+                .map_err(|e| e.to_string())?;
+            Ok(Witness::Htlc {
+                htlc,
+                preimage,
+                signature,
+            })
+        }
+    }
+}
@@ -0,0 +1,22 @@
+pub mod address;
+#[cfg(test)]
+mod address_tests;
+pub mod blockchain;
+pub mod clock;
+pub mod codec;
+pub mod config;
+pub mod crypto;
+pub mod error;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod metrics;
+pub mod miner;
+pub mod network;
+pub mod node;
+#[cfg(feature = "tokio")]
+pub mod runtime;
+pub mod storage;
+pub mod stratum;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod wallet;
@@ -0,0 +1,257 @@
+//! Node metrics in the Prometheus text exposition format, served over HTTP
+//! at `/metrics`.
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    blockchain::{Blockchain, Counters, Result, SharedBlockchain},
+    stratum::{ShareLedger, WorkerShares},
+};
+
+/// How many recent blocks the transaction rate is averaged over.
+const RATE_WINDOW: u64 = 10;
+/// How long the server sleeps when no connection is waiting.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A point-in-time reading of the node's metrics.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    pub chain_height: u64,
+    pub mempool_size: usize,
+    pub peers: usize,
+    /// Confirmed transactions per second over the last few blocks.
+    pub transactions_per_second: f64,
+    pub counters: Counters,
+    /// Shares of each stratum worker, by name.
+    pub workers: Vec<(String, WorkerShares)>,
+}
+
+impl Metrics {
+    pub fn collect(blockchain: &Blockchain, peers: usize) -> Result<Self> {
+        let chain_height = blockchain.chain_height()?;
+        let window_start = chain_height.saturating_sub(RATE_WINDOW);
+        let transactions_per_second = match (
+            blockchain.block_by_height(window_start),
+            blockchain.block_by_height(chain_height),
+        ) {
+            (Some(first), Some(tip)) if tip.timestamp() > first.timestamp() => {
+                let transactions: usize = (window_start + 1..=chain_height)
+                    .filter_map(|height| blockchain.block_by_height(height))
+                    .map(|block| {
+                        block
+                            .transactions()
+                            .iter()
+                            .filter(|t| !t.is_coinbase())
+                            .count()
+                    })
+                    .sum();
+                let nanos = (tip.timestamp() - first.timestamp()) as f64;
+                transactions as f64 / (nanos / 1e9)
+            }
+            _ => 0.0,
+        };
+        Ok(Metrics {
+            chain_height,
+            mempool_size: blockchain.pending_transaction_count(),
+            peers,
+            transactions_per_second,
+            counters: blockchain.counters().clone(),
+            workers: vec![],
+        })
+    }
+
+    /// Renders every metric with its `HELP` and `TYPE` lines.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP aeonia_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE aeonia_{} {}", name, kind);
+            let _ = writeln!(out, "aeonia_{} {}", name, value);
+        };
+        metric(
+            "chain_height",
+            "gauge",
+            "Height of the main chain tip.",
+            self.chain_height.to_string(),
+        );
+        metric(
+            "mempool_transactions",
+            "gauge",
+            "Transactions waiting in the pool.",
+            self.mempool_size.to_string(),
+        );
+        metric("peers", "gauge", "Connected peers.", self.peers.to_string());
+        metric(
+            "hash_rate",
+            "gauge",
+            "Hashes per second achieved mining the last block.",
+            self.counters.hash_rate.to_string(),
+        );
+        metric(
+            "transactions_per_second",
+            "gauge",
+            "Confirmed transactions per second over recent blocks.",
+            self.transactions_per_second.to_string(),
+        );
+        metric(
+            "hashes_total",
+            "counter",
+            "Header hashes computed while mining.",
+            self.counters.hashes.to_string(),
+        );
+        metric(
+            "blocks_mined_total",
+            "counter",
+            "Blocks mined by this node.",
+            self.counters.blocks_mined.to_string(),
+        );
+        metric(
+            "transactions_confirmed_total",
+            "counter",
+            "Transactions connected to the main chain, excluding coinbases.",
+            self.counters.transactions_confirmed.to_string(),
+        );
+        metric(
+            "reorgs_total",
+            "counter",
+            "Chain reorganizations.",
+            self.counters.reorgs.to_string(),
+        );
+        if !self.workers.is_empty() {
+            let _ = writeln!(
+                out,
+                "# HELP aeonia_stratum_shares_total Shares submitted by stratum workers."
+            );
+            let _ = writeln!(out, "# TYPE aeonia_stratum_shares_total counter");
+            for (worker, shares) in &self.workers {
+                for (result, count) in
+                    [("accepted", shares.accepted), ("rejected", shares.rejected)]
+                {
+                    let _ = writeln!(
+                        out,
+                        "aeonia_stratum_shares_total{{worker=\"{}\",result=\"{}\"}} {}",
+                        worker, result, count
+                    );
+                }
+            }
+            let _ = writeln!(
+                out,
+                "# HELP aeonia_stratum_blocks_total Blocks found by stratum workers."
+            );
+            let _ = writeln!(out, "# TYPE aeonia_stratum_blocks_total counter");
+            for (worker, shares) in &self.workers {
+                let _ = writeln!(
+                    out,
+                    "aeonia_stratum_blocks_total{{worker=\"{}\"}} {}",
+                    worker, shares.blocks
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Serves [`Metrics`] on a background thread until stopped.
+pub struct MetricsServer;
+
+impl MetricsServer {
+    /// Listens on `address`, reading the chain, `peers` and `shares` afresh
+    /// for every scrape.
+    pub fn start(
+        address: &str,
+        blockchain: SharedBlockchain,
+        peers: Arc<AtomicUsize>,
+        shares: Arc<ShareLedger>,
+    ) -> io::Result<MetricsHandle> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Acquire) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            // A client that hangs up early only loses its
+                            // own response.
+                            let _ = serve(stream, &blockchain, &peers, &shares);
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::park_timeout(ACCEPT_INTERVAL);
+                        }
+                        Err(_) => break,
+                    }
+                }
+            })
+        };
+        Ok(MetricsHandle {
+            local_addr,
+            stop,
+            thread,
+        })
+    }
+}
+
+fn serve(
+    mut stream: TcpStream,
+    blockchain: &SharedBlockchain,
+    peers: &AtomicUsize,
+    shares: &ShareLedger,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let blockchain = blockchain.read()?;
+            let mut metrics = Metrics::collect(&blockchain, peers.load(Ordering::Acquire))?;
+            metrics.workers = shares.snapshot();
+            ("200 OK", metrics.render())
+        }
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+pub struct MetricsHandle {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl MetricsHandle {
+    /// Address the server is bound to, useful when listening on port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Release);
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    blockchain::{CancelToken, ChainEvent, SharedBlockchain},
+    wallet::Wallet,
+};
+
+#[derive(Clone, Debug)]
+pub struct MinerConfig {
+    /// How often the miner checks the pool for new transactions.
+    pub poll_interval: Duration,
+    /// Mine a block even when the pool is empty once this much time has
+    /// passed since the last block. `None` only mines when there are
+    /// transactions waiting.
+    pub idle_interval: Option<Duration>,
+}
+
+impl Default for MinerConfig {
+    fn default() -> Self {
+        MinerConfig {
+            poll_interval: Duration::from_millis(250),
+            idle_interval: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+/// Mines blocks on a background thread until stopped, signing them with the
+/// miner's wallet. When a block from elsewhere changes the tip, the search
+/// in progress is abandoned and starts over on the new tip, with the pool's
+/// transactions picked afresh.
+pub struct Miner;
+
+impl Miner {
+    pub fn start(blockchain: SharedBlockchain, wallet: Wallet, config: MinerConfig) -> MinerHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let cancel = CancelToken::new();
+        let blocks_mined = Arc::new(AtomicU64::new(0));
+        let watcher = blockchain.subscribe().ok().map(|events| {
+            let stop = stop.clone();
+            let cancel = cancel.clone();
+            let poll_interval = config.poll_interval;
+            thread::spawn(move || watch_tip(events, &stop, &cancel, poll_interval))
+        });
+        let thread = {
+            let stop = stop.clone();
+            let cancel = cancel.clone();
+            let blocks_mined = blocks_mined.clone();
+            thread::spawn(move || {
+                let mut last_block = Instant::now();
+                let mut restart = false;
+                while !stop.load(Ordering::Acquire) {
+                    let idle_elapsed = config
+                        .idle_interval
+                        .is_some_and(|idle| last_block.elapsed() >= idle);
+                    if blockchain.read().is_err() {
+                        break;
+                    }
+                    if restart || blockchain.pending_transaction_count() > 0 || idle_elapsed {
+                        // Cleared before the candidate is built, so a tip
+                        // change while it is searched always trips it.
+                        cancel.reset();
+                        if blockchain.mine_until(&wallet, &cancel) {
+                            blocks_mined.fetch_add(1, Ordering::AcqRel);
+                        }
+                        restart = cancel.is_cancelled() && !stop.load(Ordering::Acquire);
+                        if restart {
+                            continue;
+                        }
+                        last_block = Instant::now();
+                    }
+                    thread::park_timeout(config.poll_interval);
+                }
+            })
+        };
+        MinerHandle {
+            stop,
+            cancel,
+            blocks_mined,
+            thread,
+            watcher,
+        }
+    }
+}
+
+/// Trips `cancel` whenever a block the miner didn't produce changes the
+/// tip, until the miner stops or the chain shuts down.
+pub(crate) fn watch_tip(
+    events: Receiver<ChainEvent>,
+    stop: &AtomicBool,
+    cancel: &CancelToken,
+    poll_interval: Duration,
+) {
+    while !stop.load(Ordering::Acquire) {
+        match events.recv_timeout(poll_interval) {
+            Ok(
+                ChainEvent::BlockConnected { .. }
+                | ChainEvent::BlockDisconnected { .. }
+                | ChainEvent::Reorg { .. },
+            ) => cancel.cancel(),
+            Ok(ChainEvent::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+pub struct MinerHandle {
+    stop: Arc<AtomicBool>,
+    cancel: CancelToken,
+    blocks_mined: Arc<AtomicU64>,
+    thread: JoinHandle<()>,
+    watcher: Option<JoinHandle<()>>,
+}
+
+impl MinerHandle {
+    pub fn blocks_mined(&self) -> u64 {
+        self.blocks_mined.load(Ordering::Acquire)
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.thread.is_finished()
+    }
+
+    /// Signals the miner to stop, abandoning the block in progress, if any,
+    /// and waits for it to exit.
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Release);
+        self.cancel.cancel();
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+        if let Some(watcher) = self.watcher {
+            let _ = watcher.join();
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    fmt, io,
+    ops::{BitAnd, BitOr},
+};
+
+use crate::codec::{self, Decode, Encode, Reader};
+
+/// Version of the peer-to-peer protocol this node speaks.
+pub const PROTOCOL_VERSION: u32 = 4;
+/// Oldest protocol version this node still talks to, the first to send
+/// messages in versioned envelopes.
+pub const MIN_PROTOCOL_VERSION: u32 = 4;
+
+/// Optional parts of the protocol a node supports, as bit flags. Bits this
+/// node doesn't know are kept, so a peer's later additions read as
+/// capabilities it simply doesn't share.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities(u64);
+
+impl Capabilities {
+    pub const NONE: Capabilities = Capabilities(0);
+    /// Takes in blocks announced as compact blocks.
+    pub const COMPACT_BLOCKS: Capabilities = Capabilities(1);
+    /// Serves headers and blocks for initial sync.
+    pub const HEADERS_SYNC: Capabilities = Capabilities(1 << 1);
+    /// Takes in transactions for its pool.
+    pub const TRANSACTION_RELAY: Capabilities = Capabilities(1 << 2);
+
+    pub fn from_bits(bits: u64) -> Self {
+        Capabilities(bits)
+    }
+
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    pub fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitAnd for Capabilities {
+    type Output = Capabilities;
+
+    fn bitand(self, rhs: Capabilities) -> Capabilities {
+        Capabilities(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Capabilities;
+
+    fn bitor(self, rhs: Capabilities) -> Capabilities {
+        Capabilities(self.0 | rhs.0)
+    }
+}
+
+/// First message each side of a connection sends, saying which network and
+/// chain it follows and what it can do. Nodes whose network or genesis
+/// block differ, or whose protocol versions are too far apart, hang up.
+///
+/// ```
+/// use aeonia::network::{Capabilities, Handshake, Network};
+///
+/// let ours = Handshake::new(Network::Mainnet.magic(), "00ab".into());
+/// assert!(ours.check(&ours).is_ok());
+/// let testnet = Handshake::new(Network::Testnet.magic(), "00ab".into());
+/// assert!(ours.check(&testnet).is_err());
+/// assert!(ours.capabilities.contains(Capabilities::COMPACT_BLOCKS));
+/// let mut relay_only = ours.clone();
+/// relay_only.capabilities = Capabilities::TRANSACTION_RELAY | Capabilities::from_bits(1 << 40);
+/// assert_eq!(ours.negotiate(&relay_only), Capabilities::TRANSACTION_RELAY);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Handshake {
+    /// Bytes identifying the network, see [`Network::magic`](super::Network::magic).
+    pub magic: [u8; 4],
+    /// Hash of the genesis block.
+    pub genesis: String,
+    pub version: u32,
+    pub capabilities: Capabilities,
+}
+
+impl Handshake {
+    /// Handshake of this node, speaking [`PROTOCOL_VERSION`] with every
+    /// capability it has.
+    pub fn new(magic: [u8; 4], genesis: String) -> Self {
+        Handshake {
+            magic,
+            genesis,
+            version: PROTOCOL_VERSION,
+            capabilities: Capabilities::COMPACT_BLOCKS
+                | Capabilities::HEADERS_SYNC
+                | Capabilities::TRANSACTION_RELAY,
+        }
+    }
+
+    /// Capabilities both this node and a peer sending `theirs` have, those
+    /// the messages between them can rely on.
+    pub fn negotiate(&self, theirs: &Handshake) -> Capabilities {
+        self.capabilities & theirs.capabilities
+    }
+
+    /// Checks that a peer sending `theirs` can be talked to, failing with
+    /// [`io::ErrorKind::Unsupported`] if not.
+    pub fn check(&self, theirs: &Handshake) -> io::Result<()> {
+        let reason = if theirs.magic != self.magic {
+            format!("peer is on network {}", Magic(theirs.magic))
+        } else if theirs.genesis != self.genesis {
+            format!("peer follows the chain with genesis {}", theirs.genesis)
+        } else if theirs.version < MIN_PROTOCOL_VERSION {
+            format!(
+                "peer speaks protocol {}, older than {}",
+                theirs.version, MIN_PROTOCOL_VERSION
+            )
+        } else {
+            return Ok(());
+        };
+        Err(io::Error::new(io::ErrorKind::Unsupported, reason))
+    }
+}
+
+impl Encode for Handshake {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.magic);
+        self.genesis.encode_to(out);
+        self.version.encode_to(out);
+        self.capabilities.0.encode_to(out);
+    }
+}
+
+impl Decode for Handshake {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let mut magic = [0; 4];
+        magic.copy_from_slice(reader.take(4)?);
+        Ok(Handshake {
+            magic,
+            genesis: String::decode_from(reader)?,
+            version: u32::decode_from(reader)?,
+            capabilities: Capabilities(u64::decode_from(reader)?),
+        })
+    }
+}
+
+/// Magic bytes shown as text when printable, as hex otherwise.
+struct Magic([u8; 4]);
+
+impl fmt::Display for Magic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(&self.0) {
+            Ok(s) if s.chars().all(|c| c.is_ascii_graphic()) => write!(f, "{}", s),
+            _ => self.0.iter().try_for_each(|b| write!(f, "{:02x}", b)),
+        }
+    }
+}
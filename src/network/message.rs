@@ -0,0 +1,281 @@
+use std::{
+    io::{self, Read, Write},
+    net::SocketAddr,
+};
+
+use super::{Capabilities, Handshake};
+use crate::{
+    blockchain::{BlockBody, BlockHeader, CompactBlock, Transaction, TxId},
+    codec::{self, Decode, Encode, Reader},
+};
+
+/// Largest message accepted from a peer, so a corrupt or hostile length
+/// prefix can't make the node allocate without bound.
+pub const MAX_MESSAGE_LEN: usize = 4 * 1024 * 1024;
+/// Most addresses a single [`Message::Addr`] may carry.
+pub const MAX_ADDRS: usize = 1000;
+/// Most headers a single [`Message::Headers`] may carry.
+pub const MAX_HEADERS: usize = 2000;
+/// Most block bodies asked for or sent in one [`Message::GetBodies`] or
+/// [`Message::Bodies`].
+pub const MAX_BLOCKS: usize = 16;
+/// Most transactions a single [`Message::Transactions`] may carry.
+pub const MAX_TRANSACTIONS: usize = 1000;
+/// Most transactions of an announced block asked for in one
+/// [`Message::GetBlockTxn`] or sent in one [`Message::BlockTxn`].
+pub const MAX_BLOCK_TXN: usize = 10_000;
+/// Most hashes in the locator of a [`Message::GetHeaders`], well above the
+/// few dozen [`Blockchain::locator`] gives for any chain. Like the other
+/// limits, it is checked against the count before any item is decoded.
+///
+/// ```
+/// use aeonia::network::{Message, MAX_LOCATOR};
+///
+/// let mut wire = vec![];
+/// Message::GetHeaders { locator: vec![String::new(); MAX_LOCATOR + 1] }
+///     .write_to(&mut wire)
+///     .unwrap();
+/// assert!(Message::read_from(&mut wire.as_slice()).is_err());
+/// ```
+///
+/// [`Blockchain::locator`]: crate::blockchain::Blockchain::locator
+pub const MAX_LOCATOR: usize = 101;
+/// Layout of the envelope messages travel in.
+pub const ENVELOPE_VERSION: u8 = 1;
+/// Unknown messages skipped in a row before a peer is taken to be sending
+/// garbage.
+const MAX_SKIPPED: usize = 16;
+
+/// Message exchanged between nodes. Each travels in an envelope: its length
+/// as a `u32`, then [`ENVELOPE_VERSION`], the command byte and the command's
+/// payload in canonical encoding. Envelopes of a later version or carrying a
+/// command this node doesn't know are skipped, so newer nodes can add
+/// messages without breaking older ones; they only send them to peers whose
+/// handshake advertises the [`Message::capability`] they need.
+///
+/// ```
+/// use aeonia::network::Message;
+///
+/// let mut wire = vec![3, 0, 0, 0, 1, 200, 7];
+/// Message::GetAddr { listen_port: Some(7878) }
+///     .write_to(&mut wire)
+///     .unwrap();
+/// let message = Message::read_from(&mut wire.as_slice()).unwrap();
+/// assert!(matches!(message, Message::GetAddr { listen_port: Some(7878) }));
+/// ```
+#[derive(Clone, Debug)]
+pub enum Message {
+    /// Asks for the addresses of other nodes. `listen_port` is the port the
+    /// sender accepts connections on, if it does, so the receiver can pass
+    /// it on to others.
+    GetAddr { listen_port: Option<u16> },
+    /// Addresses of nodes the sender knows, in reply to
+    /// [`Message::GetAddr`].
+    Addr(Vec<SocketAddr>),
+    /// A new block, announced by its header and transaction ids.
+    CompactBlock(Box<CompactBlock>),
+    /// Asks for the transactions of an announced block the receiver's pool
+    /// doesn't hold.
+    GetBlockTxn { hash: String, ids: Vec<TxId> },
+    /// The transactions asked for with [`Message::GetBlockTxn`], in the
+    /// order they were asked for.
+    BlockTxn {
+        hash: String,
+        transactions: Vec<Transaction>,
+    },
+    /// Asks for the headers following the first block of `locator` on the
+    /// receiver's main chain, see [`Blockchain::locator`].
+    ///
+    /// [`Blockchain::locator`]: crate::blockchain::Blockchain::locator
+    GetHeaders { locator: Vec<String> },
+    /// Headers in chain order.
+    Headers(Vec<BlockHeader>),
+    /// Asks for the bodies of blocks by hash, once their headers are
+    /// known.
+    GetBodies(Vec<String>),
+    /// The bodies asked for with [`Message::GetBodies`], in the order they
+    /// were asked for, up to the first block the sender doesn't have in
+    /// full.
+    Bodies(Vec<BlockBody>),
+    /// Opens every connection, see [`Handshake`].
+    Version(Handshake),
+    /// Refuses a peer whose handshake doesn't match, saying why.
+    Reject(String),
+    /// Transactions for the receiver's pool, sent to peers with
+    /// [`Capabilities::TRANSACTION_RELAY`].
+    ///
+    /// [`Capabilities::TRANSACTION_RELAY`]: super::Capabilities::TRANSACTION_RELAY
+    Transactions(Vec<Transaction>),
+}
+
+impl Message {
+    /// Capability a peer has to advertise to be sent this message. Replies
+    /// and the handshake need none.
+    pub fn capability(&self) -> Capabilities {
+        match self {
+            Message::CompactBlock(_) => Capabilities::COMPACT_BLOCKS,
+            Message::GetHeaders { .. } | Message::GetBodies(_) => Capabilities::HEADERS_SYNC,
+            Message::Transactions(_) => Capabilities::TRANSACTION_RELAY,
+            _ => Capabilities::NONE,
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut envelope = vec![ENVELOPE_VERSION];
+        self.encode_to(&mut envelope);
+        writer.write_all(&(envelope.len() as u32).to_le_bytes())?;
+        writer.write_all(&envelope)?;
+        writer.flush()
+    }
+
+    /// Reads the next message this node understands, skipping up to a few
+    /// it doesn't.
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        for _ in 0..=MAX_SKIPPED {
+            let envelope = read_envelope(reader)?;
+            let [version, command, payload @ ..] = envelope.as_slice() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "message envelope too short",
+                ));
+            };
+            if *version > ENVELOPE_VERSION {
+                continue;
+            }
+            let mut reader = Reader::new(payload);
+            if let Some(message) = decode_command(*command, &mut reader)? {
+                if reader.remaining() > 0 {
+                    return Err(codec::Error::TrailingBytes(reader.remaining()).into());
+                }
+                return Ok(message);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("more than {} unknown messages in a row", MAX_SKIPPED),
+        ))
+    }
+}
+
+fn read_envelope<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes exceeds {}", len, MAX_MESSAGE_LEN),
+        ));
+    }
+    let mut envelope = vec![0; len];
+    reader.read_exact(&mut envelope)?;
+    Ok(envelope)
+}
+
+impl Encode for Message {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Message::GetAddr { listen_port } => {
+                0u8.encode_to(out);
+                listen_port.encode_to(out);
+            }
+            Message::Addr(addrs) => {
+                1u8.encode_to(out);
+                addrs.encode_to(out);
+            }
+            Message::CompactBlock(compact) => {
+                2u8.encode_to(out);
+                compact.encode_to(out);
+            }
+            Message::GetBlockTxn { hash, ids } => {
+                3u8.encode_to(out);
+                hash.encode_to(out);
+                ids.encode_to(out);
+            }
+            Message::BlockTxn { hash, transactions } => {
+                4u8.encode_to(out);
+                hash.encode_to(out);
+                transactions.encode_to(out);
+            }
+            Message::GetHeaders { locator } => {
+                5u8.encode_to(out);
+                locator.encode_to(out);
+            }
+            Message::Headers(headers) => {
+                6u8.encode_to(out);
+                headers.encode_to(out);
+            }
+            Message::GetBodies(hashes) => {
+                7u8.encode_to(out);
+                hashes.encode_to(out);
+            }
+            Message::Bodies(bodies) => {
+                8u8.encode_to(out);
+                bodies.encode_to(out);
+            }
+            Message::Version(handshake) => {
+                9u8.encode_to(out);
+                handshake.encode_to(out);
+            }
+            Message::Reject(reason) => {
+                10u8.encode_to(out);
+                reason.encode_to(out);
+            }
+            Message::Transactions(transactions) => {
+                11u8.encode_to(out);
+                transactions.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for Message {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let command = u8::decode_from(reader)?;
+        decode_command(command, reader)?.ok_or(codec::Error::InvalidTag(command))
+    }
+}
+
+/// Decodes the payload of `command`, or returns `None` for a command this
+/// node doesn't know.
+fn decode_command(command: u8, reader: &mut Reader) -> codec::Result<Option<Message>> {
+    let message = match command {
+        0 => Message::GetAddr {
+            listen_port: Option::decode_from(reader)?,
+        },
+        1 => Message::Addr(decode_at_most(reader, MAX_ADDRS)?),
+        2 => Message::CompactBlock(Box::new(CompactBlock::decode_from(reader)?)),
+        3 => Message::GetBlockTxn {
+            hash: String::decode_from(reader)?,
+            ids: decode_at_most(reader, MAX_BLOCK_TXN)?,
+        },
+        4 => Message::BlockTxn {
+            hash: String::decode_from(reader)?,
+            transactions: decode_at_most(reader, MAX_BLOCK_TXN)?,
+        },
+        5 => Message::GetHeaders {
+            locator: decode_at_most(reader, MAX_LOCATOR)?,
+        },
+        6 => Message::Headers(decode_at_most(reader, MAX_HEADERS)?),
+        7 => Message::GetBodies(decode_at_most(reader, MAX_BLOCKS)?),
+        8 => Message::Bodies(decode_at_most(reader, MAX_BLOCKS)?),
+        9 => Message::Version(Handshake::decode_from(reader)?),
+        10 => Message::Reject(String::decode_from(reader)?),
+        11 => Message::Transactions(decode_at_most(reader, MAX_TRANSACTIONS)?),
+        _ => return Ok(None),
+    };
+    Ok(Some(message))
+}
+
+/// Decodes a vector of at most `max` items, refusing a longer one from its
+/// length prefix before decoding any of it.
+fn decode_at_most<T: Decode>(reader: &mut Reader, max: usize) -> codec::Result<Vec<T>> {
+    let len = reader.take_len()?;
+    if len > max {
+        return Err(codec::Error::InvalidValue(format!(
+            "{} items exceed {}",
+            len, max
+        )));
+    }
+    codec::decode_items(reader, len)
+}
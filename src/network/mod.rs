@@ -0,0 +1,508 @@
+//! Peer-to-peer networking. A node bootstraps from DNS seeds and the peers in
+//! its config, then finds the rest of the network by asking every node it
+//! reaches for the addresses that node knows (peer exchange). What it learns
+//! is kept in a [`PeerBook`] so the seeds are only needed the first time.
+//!
+//! New blocks are relayed as [`CompactBlock`]s: peers rebuild them from
+//! their own pool and fetch only the transactions they are missing. A node
+//! that is behind, or hears of a block whose parent it lacks, catches up
+//! headers first: it checks the header chain a peer offers before
+//! downloading the blocks, in batches from several peers at once.
+//! Transactions are only sent with [`NetworkHandle::broadcast`], which a
+//! node uses to rebroadcast its own wallets' payments until they confirm.
+//!
+//! Every connection opens with a [`Handshake`] naming the network, the
+//! genesis block and the protocol version, so nodes of different networks or
+//! chains, or of incompatible versions, refuse each other; the capabilities
+//! both sides advertise decide which messages they send each other.
+//! Messages travel in versioned envelopes, and ones a node doesn't know are
+//! skipped, see [`Message`]. With
+//! [`Encryption::Noise`] configured, a Noise handshake comes first and
+//! everything after it is encrypted.
+
+mod handshake;
+mod message;
+mod peers;
+mod preset;
+mod relay;
+mod sync;
+mod transport;
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::RecvTimeoutError,
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle, Thread},
+    time::Duration,
+};
+
+pub use handshake::{Capabilities, Handshake, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+pub use message::{
+    Message, ENVELOPE_VERSION, MAX_ADDRS, MAX_BLOCKS, MAX_BLOCK_TXN, MAX_HEADERS, MAX_LOCATOR,
+    MAX_MESSAGE_LEN, MAX_TRANSACTIONS,
+};
+pub use peers::{Ban, BanList, PeerBook, PeerInfo, BAN_DURATION, BAN_THRESHOLD};
+pub use preset::Network;
+pub use transport::Encryption;
+
+use transport::Connection;
+
+use crate::{
+    blockchain::{ChainEvent, CompactBlock, SharedBlockchain, Transaction},
+    clock::{Clock, SystemClock},
+    config::NetworkConfig,
+};
+
+/// How long the listener sleeps when no connection is waiting.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// How often known peers are asked for more addresses and checked for a
+/// longer chain.
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(60);
+/// Most peers dialed in one discovery round, and most a block is relayed
+/// to.
+const PEERS_PER_ROUND: usize = 8;
+/// Limit on connecting to, reading from and writing to a peer.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Ban score added for a message that can't be decoded or isn't expected.
+const INVALID_MESSAGE_SCORE: u32 = 20;
+/// Ban score added for a block that fails validation, enough for a ban.
+const INVALID_BLOCK_SCORE: u32 = BAN_THRESHOLD;
+/// Ban score added for each invalid transaction sent for the pool.
+const INVALID_TRANSACTION_SCORE: u32 = 10;
+
+/// Resolves `seeds`, host names or addresses with or without a port, to the
+/// addresses they stand for, assuming `default_port` where none is given.
+/// Seeds that fail to resolve are skipped, since the others may still lead
+/// to the network.
+pub fn resolve_seeds(seeds: &[String], default_port: u16) -> Vec<SocketAddr> {
+    let mut addrs = vec![];
+    for seed in seeds {
+        let resolved = seed
+            .to_socket_addrs()
+            .or_else(|_| (seed.as_str(), default_port).to_socket_addrs());
+        if let Ok(resolved) = resolved {
+            addrs.extend(resolved);
+        }
+    }
+    addrs.sort();
+    addrs.dedup();
+    addrs
+}
+
+/// Accepts peers and runs discovery on background threads until stopped.
+pub struct PeerNetwork;
+
+impl PeerNetwork {
+    /// Listens on `config.listen_address`, if set, answering address,
+    /// header and block requests and taking in announced blocks, and every
+    /// [`DISCOVERY_INTERVAL`] asks the configured peers, the DNS seeds and
+    /// the best known peers in `book` for more addresses, then syncs
+    /// `blockchain` with those that answered. `peers` is kept at the number
+    /// that answered last round. Blocks that become the tip are announced to
+    /// the best known peers. Only peers on the same network and chain are
+    /// talked to, and none whose IP address `bans` holds; peers that
+    /// misbehave are scored there.
+    pub fn start(
+        config: &NetworkConfig,
+        blockchain: SharedBlockchain,
+        book: Arc<Mutex<PeerBook>>,
+        bans: Arc<Mutex<BanList>>,
+        peers: Arc<AtomicUsize>,
+    ) -> io::Result<NetworkHandle> {
+        transport::check_supported(config.encryption)?;
+        let genesis = blockchain
+            .read()?
+            .block_by_height(0)
+            .ok_or_else(|| io::Error::other("chain has no genesis block"))?
+            .hash();
+        let events = blockchain.subscribe()?;
+        let listener = config
+            .listen_address
+            .as_deref()
+            .map(TcpListener::bind)
+            .transpose()?;
+        let shared = Arc::new(Shared {
+            blockchain,
+            book,
+            bans,
+            handshake: Handshake::new(config.chain.magic(), genesis),
+            encryption: config.encryption,
+            local_addr: listener.as_ref().map(TcpListener::local_addr).transpose()?,
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut threads = vec![];
+        let bootstrap: Vec<String> = config
+            .peers
+            .iter()
+            .chain(&config.dns_seeds)
+            .cloned()
+            .collect();
+        let default_port = config.chain.default_port();
+        threads.push({
+            let stop = stop.clone();
+            let shared = shared.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Acquire) {
+                    let reached = discover(&bootstrap, default_port, &shared);
+                    peers.store(reached.len(), Ordering::Release);
+                    // Peers that fail to serve the chain are scored by the
+                    // sync itself.
+                    let _ = sync::sync(&reached, &shared);
+                    thread::park_timeout(DISCOVERY_INTERVAL);
+                }
+            })
+        });
+        if let Some(listener) = listener {
+            listener.set_nonblocking(true)?;
+            let stop = stop.clone();
+            let shared = shared.clone();
+            // Hearing of a block that doesn't connect means the node is
+            // behind, so the discovery thread is woken to sync.
+            let syncer = threads[0].thread().clone();
+            threads.push(thread::spawn(move || {
+                while !stop.load(Ordering::Acquire) {
+                    match listener.accept() {
+                        Ok((_, remote)) if shared.is_banned(remote.ip()) => {}
+                        Ok((stream, remote)) => {
+                            // A peer that misbehaves loses its answer and
+                            // is scored.
+                            if let Err(e) = serve(stream, remote, &shared, &syncer) {
+                                shared.record_inbound_failure(remote.ip(), &e);
+                            }
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::park_timeout(ACCEPT_INTERVAL);
+                        }
+                        Err(_) => break,
+                    }
+                }
+            }));
+        }
+        threads.push({
+            let stop = stop.clone();
+            let shared = shared.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Acquire) {
+                    let hashes = match events.recv_timeout(ACCEPT_INTERVAL) {
+                        Ok(ChainEvent::BlockMined { hash, .. })
+                        | Ok(ChainEvent::BlockConnected { hash, .. }) => vec![hash],
+                        Ok(ChainEvent::Reorg { connected, .. }) => connected,
+                        Ok(ChainEvent::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+                        Ok(_) | Err(RecvTimeoutError::Timeout) => continue,
+                    };
+                    for hash in hashes {
+                        relay(&hash, &shared);
+                    }
+                }
+            })
+        });
+        Ok(NetworkHandle {
+            local_addr: shared.local_addr,
+            shared,
+            stop,
+            threads,
+        })
+    }
+}
+
+/// What the network's threads share. No thread holds more than one of the
+/// chain, the peer book and the ban list at a time, so their locks can't
+/// deadlock.
+struct Shared {
+    blockchain: SharedBlockchain,
+    book: Arc<Mutex<PeerBook>>,
+    bans: Arc<Mutex<BanList>>,
+    handshake: Handshake,
+    encryption: Encryption,
+    local_addr: Option<SocketAddr>,
+}
+
+impl Shared {
+    /// Connects to `addr` and exchanges handshakes, returning the peer's.
+    fn connect(&self, addr: SocketAddr) -> io::Result<(Connection, Handshake)> {
+        let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let mut stream = Connection::initiate(stream, self.encryption)?;
+        Message::Version(self.handshake.clone()).write_to(&mut stream)?;
+        match Message::read_from(&mut stream)? {
+            Message::Version(theirs) => {
+                self.handshake.check(&theirs)?;
+                Ok((stream, theirs))
+            }
+            Message::Reject(reason) => Err(io::Error::new(io::ErrorKind::Unsupported, reason)),
+            message => Err(unexpected(message)),
+        }
+    }
+
+    /// Whether `addr` is likely this node's own listen address, which it
+    /// may hear about from peers.
+    fn is_self(&self, addr: &SocketAddr) -> bool {
+        self.local_addr.is_some_and(|local| {
+            addr.port() == local.port()
+                && (addr.ip() == local.ip()
+                    || addr.ip().is_loopback()
+                    || local.ip().is_unspecified())
+        })
+    }
+
+    fn is_banned(&self, ip: IpAddr) -> bool {
+        self.bans
+            .lock()
+            .is_ok_and(|bans| bans.is_banned(&ip, SystemClock.now()))
+    }
+
+    /// Adds `score` to the ban score of `ip`.
+    fn misbehaved(&self, ip: IpAddr, score: u32, reason: &str) {
+        if let Ok(mut bans) = self.bans.lock() {
+            bans.misbehaved(ip, score, reason, SystemClock.now());
+        }
+    }
+
+    /// Peers worth dialing, see [`PeerBook::candidates`], leaving out this
+    /// node and banned addresses.
+    fn candidates(&self) -> Vec<SocketAddr> {
+        let Ok(candidates) = self
+            .book
+            .lock()
+            .map(|book| book.candidates(SystemClock.now()))
+        else {
+            return vec![];
+        };
+        candidates
+            .into_iter()
+            .filter(|addr| !self.is_self(addr) && !self.is_banned(addr.ip()))
+            .collect()
+    }
+
+    /// Records the outcome of talking to `addr`: peers that sent garbage
+    /// are scored and peers on another network or chain banned.
+    fn record_failure(&self, addr: SocketAddr, error: &io::Error) {
+        match error.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::Unsupported => {
+                self.record_inbound_failure(addr.ip(), error);
+            }
+            _ => {
+                if let Ok(mut book) = self.book.lock() {
+                    book.mark_failed(&addr, SystemClock.now());
+                }
+            }
+        }
+    }
+
+    /// Scores a peer at `ip` that connected to this node and sent garbage,
+    /// and bans it if it is on another network or chain.
+    fn record_inbound_failure(&self, ip: IpAddr, error: &io::Error) {
+        match error.kind() {
+            io::ErrorKind::InvalidData => {
+                self.misbehaved(ip, INVALID_MESSAGE_SCORE, "malformed message")
+            }
+            io::ErrorKind::Unsupported => {
+                if let Ok(mut bans) = self.bans.lock() {
+                    bans.ban(ip, SystemClock.now(), BAN_DURATION, &error.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Answers one request from an inbound peer, after the handshake.
+fn serve(
+    stream: TcpStream,
+    remote: SocketAddr,
+    shared: &Shared,
+    syncer: &Thread,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut stream = Connection::respond(stream, shared.encryption)?;
+    match Message::read_from(&mut stream)? {
+        Message::Version(theirs) => {
+            if let Err(e) = shared.handshake.check(&theirs) {
+                Message::Reject(e.to_string()).write_to(&mut stream)?;
+                return Err(e);
+            }
+        }
+        message => return Err(unexpected(message)),
+    }
+    Message::Version(shared.handshake.clone()).write_to(&mut stream)?;
+    let blockchain = &shared.blockchain;
+    match Message::read_from(&mut stream)? {
+        Message::GetAddr { listen_port } => {
+            let now = SystemClock.now();
+            let mut book = shared
+                .book
+                .lock()
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            if let Some(port) = listen_port {
+                let addr = SocketAddr::new(remote.ip(), port);
+                if book.is_banned(&addr, now) {
+                    return Ok(());
+                }
+                book.add(addr);
+            }
+            let reply = Message::Addr(book.shareable(now, MAX_ADDRS));
+            drop(book);
+            reply.write_to(&mut stream)
+        }
+        Message::CompactBlock(compact) => match relay::receive(&mut stream, compact, blockchain) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                syncer.unpark();
+                Err(e)
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                shared.misbehaved(remote.ip(), INVALID_BLOCK_SCORE, "invalid block");
+                Ok(())
+            }
+            result => result,
+        },
+        Message::GetHeaders { locator } => {
+            let headers = blockchain.read()?.headers_after(&locator, MAX_HEADERS)?;
+            Message::Headers(headers).write_to(&mut stream)
+        }
+        Message::GetBodies(hashes) => {
+            let blockchain = blockchain.read()?;
+            let bodies = hashes
+                .iter()
+                .map_while(|hash| blockchain.block_by_hash(hash)?.body().cloned())
+                .collect();
+            drop(blockchain);
+            Message::Bodies(bodies).write_to(&mut stream)
+        }
+        Message::Transactions(transactions) => {
+            for transaction in transactions {
+                // Ones already pending or confirmed, or that don't fit
+                // the pool, are simply not taken; only those that could
+                // never be valid count against the peer.
+                if let Err(e) = blockchain.add_transaction(transaction) {
+                    if e.kind() == io::ErrorKind::InvalidData {
+                        shared.misbehaved(
+                            remote.ip(),
+                            INVALID_TRANSACTION_SCORE,
+                            "invalid transaction",
+                        );
+                    }
+                }
+            }
+            Ok(())
+        }
+        message => Err(unexpected(message)),
+    }
+}
+
+/// Announces the block `hash` to up to [`PEERS_PER_ROUND`] known peers.
+fn relay(hash: &str, shared: &Shared) {
+    // Blocks connected while catching up are old news to peers.
+    let Some(block) = shared
+        .blockchain
+        .read()
+        .ok()
+        .filter(|blockchain| blockchain.sync_status().is_ok_and(|sync| !sync.syncing))
+        .and_then(|blockchain| blockchain.block_by_hash(hash))
+    else {
+        return;
+    };
+    let compact = CompactBlock::new(&block);
+    for addr in shared.candidates().into_iter().take(PEERS_PER_ROUND) {
+        if let Err(e) = relay::announce(addr, &compact, shared) {
+            shared.record_failure(addr, &e);
+        }
+    }
+}
+
+/// Asks up to [`PEERS_PER_ROUND`] peers for addresses, returning those that
+/// answered. Bootstrap entries without a port use `default_port`.
+fn discover(bootstrap: &[String], default_port: u16, shared: &Shared) -> Vec<SocketAddr> {
+    match shared.book.lock() {
+        Ok(mut book) => {
+            for addr in resolve_seeds(bootstrap, default_port) {
+                book.add(addr);
+            }
+        }
+        Err(_) => return vec![],
+    }
+    let mut reached = vec![];
+    for addr in shared.candidates().into_iter().take(PEERS_PER_ROUND) {
+        match request_addrs(addr, shared) {
+            Ok(addrs) => {
+                reached.push(addr);
+                let Ok(mut book) = shared.book.lock() else {
+                    break;
+                };
+                book.mark_seen(addr, SystemClock.now());
+                for addr in addrs.into_iter().filter(|addr| !shared.is_self(addr)) {
+                    book.add(addr);
+                }
+            }
+            Err(e) => shared.record_failure(addr, &e),
+        }
+    }
+    reached
+}
+
+fn request_addrs(addr: SocketAddr, shared: &Shared) -> io::Result<Vec<SocketAddr>> {
+    let (mut stream, _) = shared.connect(addr)?;
+    let listen_port = shared.local_addr.map(|local| local.port());
+    Message::GetAddr { listen_port }.write_to(&mut stream)?;
+    match Message::read_from(&mut stream)? {
+        Message::Addr(addrs) => Ok(addrs),
+        message => Err(unexpected(message)),
+    }
+}
+
+fn unexpected(message: Message) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected {:?}", message),
+    )
+}
+
+pub struct NetworkHandle {
+    local_addr: Option<SocketAddr>,
+    shared: Arc<Shared>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl NetworkHandle {
+    /// Address peers are accepted on, if listening, useful when listening
+    /// on port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Sends `transactions` to up to [`PEERS_PER_ROUND`] known peers that
+    /// take them, on a thread of its own so slow peers hold nobody up.
+    pub fn broadcast(&self, transactions: Vec<Transaction>) {
+        if transactions.is_empty() {
+            return;
+        }
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            for addr in shared.candidates().into_iter().take(PEERS_PER_ROUND) {
+                for batch in transactions.chunks(MAX_TRANSACTIONS) {
+                    if let Err(e) = relay::send_transactions(addr, batch, &shared) {
+                        shared.record_failure(addr, &e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Release);
+        for thread in &self.threads {
+            thread.thread().unpark();
+        }
+        for thread in self.threads {
+            let _ = thread.join();
+        }
+    }
+}
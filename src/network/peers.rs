@@ -0,0 +1,247 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Ban score at which a peer is banned.
+pub const BAN_THRESHOLD: u32 = 100;
+/// How long a peer stays banned once its score reaches [`BAN_THRESHOLD`].
+pub const BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+/// Failed connection attempts in a row after which an address is forgotten.
+const MAX_FAILURES: u32 = 3;
+
+/// What the node knows about one peer.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerInfo {
+    /// When the peer last answered, in nanoseconds since the Unix epoch.
+    pub last_seen: Option<i64>,
+    /// Connection attempts that failed since it last answered.
+    pub failures: u32,
+    /// Misbehavior accumulated towards a ban.
+    pub ban_score: u32,
+    pub banned_until: Option<i64>,
+}
+
+impl PeerInfo {
+    pub fn is_banned(&self, now: i64) -> bool {
+        self.banned_until.is_some_and(|until| until > now)
+    }
+}
+
+/// Addresses of the nodes this one has heard of, with how reliable and how
+/// well behaved each has been. Kept in the data directory so a restarted
+/// node doesn't need the seeds to find the network again.
+///
+/// ```
+/// use aeonia::network::{PeerBook, BAN_THRESHOLD};
+///
+/// let peer = "10.0.0.2:7878".parse().unwrap();
+/// let mut book = PeerBook::default();
+/// assert!(book.add(peer));
+/// assert!(!book.misbehaved(peer, BAN_THRESHOLD / 2, 0));
+/// assert!(book.misbehaved(peer, BAN_THRESHOLD / 2, 0));
+/// assert!(book.is_banned(&peer, 1));
+/// assert!(book.candidates(1).is_empty());
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PeerBook {
+    peers: BTreeMap<SocketAddr, PeerInfo>,
+}
+
+impl PeerBook {
+    /// Records an address, returning whether it was new.
+    pub fn add(&mut self, addr: SocketAddr) -> bool {
+        if self.peers.contains_key(&addr) {
+            return false;
+        }
+        self.peers.insert(addr, PeerInfo::default());
+        true
+    }
+
+    pub fn get(&self, addr: &SocketAddr) -> Option<&PeerInfo> {
+        self.peers.get(addr)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Peers in address order.
+    pub fn iter(&self) -> impl Iterator<Item = (&SocketAddr, &PeerInfo)> {
+        self.peers.iter()
+    }
+
+    /// Records that `addr` answered at `now`.
+    pub fn mark_seen(&mut self, addr: SocketAddr, now: i64) {
+        let info = self.peers.entry(addr).or_default();
+        info.last_seen = Some(now);
+        info.failures = 0;
+    }
+
+    /// Records a failed connection to `addr`, forgetting it once it has
+    /// failed too often in a row. Banned peers are kept so the ban holds.
+    pub fn mark_failed(&mut self, addr: &SocketAddr, now: i64) {
+        let Some(info) = self.peers.get_mut(addr) else {
+            return;
+        };
+        info.failures += 1;
+        if info.failures >= MAX_FAILURES && !info.is_banned(now) {
+            self.peers.remove(addr);
+        }
+    }
+
+    /// Adds `score` to the ban score of `addr`, banning it for
+    /// [`BAN_DURATION`] once the score reaches [`BAN_THRESHOLD`]. Returns
+    /// whether the peer is now banned.
+    pub fn misbehaved(&mut self, addr: SocketAddr, score: u32, now: i64) -> bool {
+        let info = self.peers.entry(addr).or_default();
+        info.ban_score = info.ban_score.saturating_add(score);
+        if info.ban_score >= BAN_THRESHOLD {
+            self.ban(addr, now, BAN_DURATION);
+            return true;
+        }
+        info.is_banned(now)
+    }
+
+    /// Refuses `addr` until `duration` from `now`.
+    pub fn ban(&mut self, addr: SocketAddr, now: i64, duration: Duration) {
+        let nanos = i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX);
+        let info = self.peers.entry(addr).or_default();
+        info.ban_score = 0;
+        info.banned_until = Some(now.saturating_add(nanos));
+    }
+
+    /// Lifts the ban on `addr`, returning whether it was banned.
+    pub fn unban(&mut self, addr: &SocketAddr) -> bool {
+        self.peers
+            .get_mut(addr)
+            .and_then(|info| {
+                info.ban_score = 0;
+                info.banned_until.take()
+            })
+            .is_some()
+    }
+
+    pub fn is_banned(&self, addr: &SocketAddr, now: i64) -> bool {
+        self.peers.get(addr).is_some_and(|info| info.is_banned(now))
+    }
+
+    /// Peers worth dialing, those that answered most recently first and
+    /// those never reached last. Banned peers are left out.
+    pub fn candidates(&self, now: i64) -> Vec<SocketAddr> {
+        let mut candidates: Vec<_> = self
+            .peers
+            .iter()
+            .filter(|(_, info)| !info.is_banned(now))
+            .collect();
+        candidates.sort_by_key(|(_, info)| (std::cmp::Reverse(info.last_seen), info.failures));
+        candidates.into_iter().map(|(addr, _)| *addr).collect()
+    }
+
+    /// Up to `limit` addresses to pass on to other nodes, in the order of
+    /// [`PeerBook::candidates`].
+    pub fn shareable(&self, now: i64, limit: usize) -> Vec<SocketAddr> {
+        let mut shareable = self.candidates(now);
+        shareable.truncate(limit);
+        shareable
+    }
+}
+
+/// Why and until when an address is refused.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    /// Nanoseconds since the Unix epoch.
+    pub until: i64,
+    pub reason: String,
+}
+
+/// IP addresses the node refuses to talk to, in either direction, whether
+/// banned by hand or for misbehaving: sending malformed messages, invalid
+/// blocks or invalid transactions adds to an address's ban score, and it is
+/// banned for [`BAN_DURATION`] once that reaches [`BAN_THRESHOLD`]. Bans are
+/// kept in the data directory; scores only last as long as the node runs.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use aeonia::network::{BanList, BAN_THRESHOLD};
+///
+/// let ip = "10.0.0.2".parse().unwrap();
+/// let mut bans = BanList::default();
+/// assert!(!bans.misbehaved(ip, BAN_THRESHOLD / 2, "invalid block", 0));
+/// assert!(bans.misbehaved(ip, BAN_THRESHOLD / 2, "invalid block", 0));
+/// assert!(bans.is_banned(&ip, 1));
+/// assert!(bans.unban(&ip));
+/// bans.ban(ip, 0, Duration::from_secs(1), "manual");
+/// assert!(!bans.is_banned(&ip, 1_000_000_000));
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BanList {
+    banned: BTreeMap<IpAddr, Ban>,
+    #[serde(skip)]
+    scores: HashMap<IpAddr, u32>,
+}
+
+impl BanList {
+    /// Adds `score` to the ban score of `ip`, banning it for
+    /// [`BAN_DURATION`] with `reason` once the score reaches
+    /// [`BAN_THRESHOLD`]. Returns whether it is now banned.
+    pub fn misbehaved(&mut self, ip: IpAddr, score: u32, reason: &str, now: i64) -> bool {
+        let total = self.scores.entry(ip).or_default();
+        *total = total.saturating_add(score);
+        if *total >= BAN_THRESHOLD {
+            self.ban(ip, now, BAN_DURATION, reason);
+            return true;
+        }
+        self.is_banned(&ip, now)
+    }
+
+    /// Refuses `ip` until `duration` from `now`, replacing any ban it
+    /// already has.
+    pub fn ban(&mut self, ip: IpAddr, now: i64, duration: Duration, reason: &str) {
+        let nanos = i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX);
+        self.scores.remove(&ip);
+        self.banned.insert(
+            ip,
+            Ban {
+                until: now.saturating_add(nanos),
+                reason: reason.to_string(),
+            },
+        );
+    }
+
+    /// Lifts the ban on `ip` and clears its score, returning whether it
+    /// was banned.
+    pub fn unban(&mut self, ip: &IpAddr) -> bool {
+        self.scores.remove(ip);
+        self.banned.remove(ip).is_some()
+    }
+
+    pub fn is_banned(&self, ip: &IpAddr, now: i64) -> bool {
+        self.banned.get(ip).is_some_and(|ban| ban.until > now)
+    }
+
+    /// Current ban score of `ip`.
+    pub fn score(&self, ip: &IpAddr) -> u32 {
+        self.scores.get(ip).copied().unwrap_or_default()
+    }
+
+    /// Bans still in force at `now`, in address order.
+    pub fn iter(&self, now: i64) -> impl Iterator<Item = (&IpAddr, &Ban)> {
+        self.banned.iter().filter(move |(_, ban)| ban.until > now)
+    }
+
+    /// Forgets the bans that have run out by `now`.
+    pub fn expire(&mut self, now: i64) {
+        self.banned.retain(|_, ban| ban.until > now);
+    }
+}
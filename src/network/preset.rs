@@ -0,0 +1,119 @@
+use std::{fmt, path::PathBuf, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::FaucetConfig;
+
+/// Which network a node belongs to. Each has its own address version,
+/// handshake magic, default port and proof of work floor, so coins and
+/// peers of one never mix with another.
+///
+/// ```
+/// use aeonia::network::Network;
+///
+/// let regtest: Network = "regtest".parse().unwrap();
+/// assert_eq!(regtest.min_difficulty(), 0);
+/// assert_ne!(regtest.magic(), Network::Mainnet.magic());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    /// Public network for trying things out, with coins of no value.
+    Testnet,
+    /// Local network for tests, where blocks need no work and the faucet
+    /// pays out without limits.
+    Regtest,
+}
+
+impl Network {
+    pub const ALL: [Network; 3] = [Network::Mainnet, Network::Testnet, Network::Regtest];
+
+    /// Version byte of addresses on this network.
+    pub fn address_version(self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet | Network::Regtest => 0x6f,
+        }
+    }
+
+    /// Bytes opening every handshake, see
+    /// [`Handshake`](super::Handshake).
+    pub fn magic(self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => *b"AEON",
+            Network::Testnet => *b"AETS",
+            Network::Regtest => *b"AERG",
+        }
+    }
+
+    /// Port nodes listen on unless configured otherwise.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Network::Mainnet => 7878,
+            Network::Testnet => 17878,
+            Network::Regtest => 27878,
+        }
+    }
+
+    /// Difficulty new chains start with.
+    pub fn default_difficulty(self) -> u8 {
+        match self {
+            Network::Mainnet => 3,
+            Network::Testnet => 2,
+            Network::Regtest => 0,
+        }
+    }
+
+    /// Lowest difficulty a node on this network may be configured with.
+    pub fn min_difficulty(self) -> u8 {
+        match self {
+            Network::Mainnet => 3,
+            Network::Testnet => 1,
+            Network::Regtest => 0,
+        }
+    }
+
+    /// Genesis allocation and payout limits of the faucet.
+    pub fn faucet_config(self) -> FaucetConfig {
+        match self {
+            Network::Mainnet | Network::Testnet => FaucetConfig::default(),
+            Network::Regtest => FaucetConfig {
+                max_per_request: f64::MAX,
+                cooldown: Duration::ZERO,
+                ..FaucetConfig::default()
+            },
+        }
+    }
+
+    /// Default data directory, kept apart per network so switching
+    /// networks never mixes their chains.
+    pub fn data_dir(self) -> PathBuf {
+        match self {
+            Network::Mainnet => PathBuf::from(".aeonia"),
+            network => PathBuf::from(".aeonia").join(network.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Regtest => "regtest",
+        })
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Network::ALL
+            .into_iter()
+            .find(|network| network.to_string() == s)
+            .ok_or_else(|| format!("unknown network {}", s))
+    }
+}
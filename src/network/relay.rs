@@ -0,0 +1,131 @@
+use std::{io, net::SocketAddr};
+
+use super::{transport::Connection, Message, Shared};
+use crate::blockchain::{CompactBlock, Reconstruction, SharedBlockchain, Transaction};
+
+/// Announces `compact` to the peer at `addr`, sending the transactions it
+/// asks for if its pool lacks some. Peers that don't take compact blocks
+/// are left to pick the block up when they sync.
+pub(super) fn announce(
+    addr: SocketAddr,
+    compact: &CompactBlock,
+    shared: &Shared,
+) -> io::Result<()> {
+    let (mut stream, theirs) = shared.connect(addr)?;
+    let message = Message::CompactBlock(Box::new(compact.clone()));
+    if !shared
+        .handshake
+        .negotiate(&theirs)
+        .contains(message.capability())
+    {
+        return Ok(());
+    }
+    message.write_to(&mut stream)?;
+    let ids = match Message::read_from(&mut stream) {
+        Ok(Message::GetBlockTxn { hash, ids }) if hash == compact.hash() => ids,
+        Ok(message) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected a transaction request, got {:?}", message),
+            ))
+        }
+        // The peer hangs up once it has the block.
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let block = shared
+        .blockchain
+        .read()?
+        .block_by_hash(&compact.hash())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "announced block is gone"))?;
+    let transactions = ids
+        .iter()
+        .map(|id| {
+            block
+                .transactions()
+                .iter()
+                .find(|transaction| &transaction.id() == id)
+                .cloned()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("transaction {} is not in the block", id),
+                    )
+                })
+        })
+        .collect::<io::Result<_>>()?;
+    Message::BlockTxn {
+        hash: compact.hash(),
+        transactions,
+    }
+    .write_to(&mut stream)
+}
+
+/// Sends `transactions` to the peer at `addr` for its pool. Peers that
+/// don't take transactions are skipped.
+pub(super) fn send_transactions(
+    addr: SocketAddr,
+    transactions: &[Transaction],
+    shared: &Shared,
+) -> io::Result<()> {
+    let (mut stream, theirs) = shared.connect(addr)?;
+    let message = Message::Transactions(transactions.to_vec());
+    if !shared
+        .handshake
+        .negotiate(&theirs)
+        .contains(message.capability())
+    {
+        return Ok(());
+    }
+    message.write_to(&mut stream)
+}
+
+/// Rebuilds a block announced on `stream`, fetching the transactions the
+/// pool doesn't hold, and adds it to the chain.
+pub(super) fn receive(
+    stream: &mut Connection,
+    compact: Box<CompactBlock>,
+    blockchain: &SharedBlockchain,
+) -> io::Result<()> {
+    let hash = compact.hash();
+    if blockchain.read()?.block_by_hash(&hash).is_some() {
+        return Ok(());
+    }
+    let reconstruction = blockchain.read()?.reconstruct_block(&compact, &[])?;
+    let block = match reconstruction {
+        Reconstruction::Complete(block) => block,
+        Reconstruction::Missing(ids) => {
+            Message::GetBlockTxn {
+                hash: hash.clone(),
+                ids,
+            }
+            .write_to(stream)?;
+            let transactions = match Message::read_from(stream)? {
+                Message::BlockTxn {
+                    hash: replied,
+                    transactions,
+                } if replied == hash => transactions,
+                message => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("expected block transactions, got {:?}", message),
+                    ))
+                }
+            };
+            match blockchain
+                .read()?
+                .reconstruct_block(&compact, &transactions)?
+            {
+                Reconstruction::Complete(block) => block,
+                Reconstruction::Missing(ids) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} transactions still missing from {}", ids.len(), hash),
+                    ))
+                }
+            }
+        }
+    };
+    blockchain.receive_block(*block)?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{io, net::SocketAddr, thread};
+
+use super::{unexpected, Message, Shared, MAX_BLOCKS, MAX_HEADERS};
+use crate::blockchain::{Block, BlockHeader};
+
+/// Catches up with the longest chain among `candidates`, headers first:
+/// fetches headers from a peer, checks that they form a valid chain, then
+/// downloads the blocks in batches spread over the peers and connects
+/// them in order. Peers that send invalid data are scored. Returns the
+/// number of blocks connected.
+pub(super) fn sync(candidates: &[SocketAddr], shared: &Shared) -> io::Result<u64> {
+    let mut connected = 0;
+    for &source in candidates {
+        match sync_from(source, candidates, shared) {
+            Ok(blocks) => connected += blocks,
+            Err(e) => shared.record_failure(source, &e),
+        }
+    }
+    shared.blockchain.write()?.set_sync_target(None)?;
+    Ok(connected)
+}
+
+fn sync_from(source: SocketAddr, peers: &[SocketAddr], shared: &Shared) -> io::Result<u64> {
+    let blockchain = &shared.blockchain;
+    let mut connected = 0;
+    loop {
+        let locator = blockchain.read()?.locator()?;
+        let headers = match request(source, Message::GetHeaders { locator }, shared)? {
+            Message::Headers(headers) => headers,
+            message => return Err(unexpected(message)),
+        };
+        let target = blockchain
+            .read()?
+            .check_headers(&headers)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        if target <= blockchain.chain_height()? {
+            return Ok(connected);
+        }
+        blockchain.write()?.set_sync_target(Some(target))?;
+        // Batches are fetched a round at a time, one per peer, with the
+        // source standing in for peers that don't have their batch.
+        let batches: Vec<&[BlockHeader]> = headers.chunks(MAX_BLOCKS).collect();
+        for round in batches.chunks(peers.len().max(1)) {
+            let downloaded: Vec<io::Result<Vec<Block>>> = thread::scope(|scope| {
+                let downloads: Vec<_> = round
+                    .iter()
+                    .zip(peers.iter().cycle())
+                    .map(|(batch, &peer)| {
+                        scope.spawn(move || {
+                            download(peer, batch, shared)
+                                .or_else(|_| download(source, batch, shared))
+                        })
+                    })
+                    .collect();
+                downloads
+                    .into_iter()
+                    .map(|download| {
+                        download
+                            .join()
+                            .unwrap_or_else(|_| Err(io::Error::other("download panicked")))
+                    })
+                    .collect()
+            });
+            let mut blockchain = blockchain.write()?;
+            for blocks in downloaded {
+                for block in blocks? {
+                    blockchain
+                        .receive_block(block)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                    connected += 1;
+                }
+            }
+            blockchain.set_sync_target(Some(target))?;
+        }
+        if headers.len() < MAX_HEADERS {
+            return Ok(connected);
+        }
+    }
+}
+
+/// Fetches the bodies of the blocks behind `headers` from `peer` and puts
+/// each together with its header, checking it against the header's Merkle
+/// root.
+fn download(peer: SocketAddr, headers: &[BlockHeader], shared: &Shared) -> io::Result<Vec<Block>> {
+    let hashes: Vec<String> = headers.iter().map(BlockHeader::hash).collect();
+    let bodies = match request(peer, Message::GetBodies(hashes.clone()), shared)? {
+        Message::Bodies(bodies) => bodies,
+        message => return Err(unexpected(message)),
+    };
+    if bodies.len() != hashes.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("peer sent {} of {} blocks", bodies.len(), hashes.len()),
+        ));
+    }
+    headers
+        .iter()
+        .zip(bodies)
+        .map(|(header, body)| {
+            Block::from_parts(header.clone(), body).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("block {} does not match its header", header.hash()),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Sends `message` to a peer that serves the chain and returns its answer.
+fn request(peer: SocketAddr, message: Message, shared: &Shared) -> io::Result<Message> {
+    let (mut stream, theirs) = shared.connect(peer)?;
+    if !shared
+        .handshake
+        .negotiate(&theirs)
+        .contains(message.capability())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "peer does not serve the chain",
+        ));
+    }
+    message.write_to(&mut stream)?;
+    Message::read_from(&mut stream)
+}
@@ -0,0 +1,243 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How connections between peers are protected. Both ends must agree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encryption {
+    /// Messages go over the wire as they are.
+    #[default]
+    None,
+    /// Every connection opens with a Noise `NN` handshake, and messages are
+    /// then sent encrypted and authenticated with ChaCha20-Poly1305 under
+    /// keys fresh to the connection. Peers aren't identified by key, so
+    /// this stops eavesdropping and tampering, not an active man in the
+    /// middle. Needs the `noise` feature.
+    Noise,
+}
+
+impl FromStr for Encryption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Encryption::None),
+            "noise" => Ok(Encryption::Noise),
+            _ => Err(format!("unknown encryption {}", s)),
+        }
+    }
+}
+
+/// A connection to a peer, encrypted as its [`Encryption`] says.
+pub(super) enum Connection {
+    Plain(TcpStream),
+    #[cfg(feature = "noise")]
+    Noise(Box<noise::NoiseStream>),
+}
+
+impl Connection {
+    /// Sets up the dialing end of `stream`.
+    pub(super) fn initiate(stream: TcpStream, encryption: Encryption) -> io::Result<Self> {
+        match encryption {
+            Encryption::None => Ok(Connection::Plain(stream)),
+            #[cfg(feature = "noise")]
+            Encryption::Noise => Ok(Connection::Noise(Box::new(noise::NoiseStream::initiate(
+                stream,
+            )?))),
+            #[cfg(not(feature = "noise"))]
+            Encryption::Noise => Err(noise_unsupported()),
+        }
+    }
+
+    /// Sets up the accepting end of `stream`.
+    pub(super) fn respond(stream: TcpStream, encryption: Encryption) -> io::Result<Self> {
+        match encryption {
+            Encryption::None => Ok(Connection::Plain(stream)),
+            #[cfg(feature = "noise")]
+            Encryption::Noise => Ok(Connection::Noise(Box::new(noise::NoiseStream::respond(
+                stream,
+            )?))),
+            #[cfg(not(feature = "noise"))]
+            Encryption::Noise => Err(noise_unsupported()),
+        }
+    }
+}
+
+/// Fails if this build can't set up connections with `encryption`.
+pub(super) fn check_supported(encryption: Encryption) -> io::Result<()> {
+    match encryption {
+        #[cfg(not(feature = "noise"))]
+        Encryption::Noise => Err(noise_unsupported()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "noise"))]
+fn noise_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "noise encryption needs the noise feature",
+    )
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "noise")]
+            Connection::Noise(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "noise")]
+            Connection::Noise(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.flush(),
+            #[cfg(feature = "noise")]
+            Connection::Noise(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "noise")]
+mod noise {
+    use std::{
+        io::{self, Read, Write},
+        net::TcpStream,
+    };
+
+    use snow::{Builder, TransportState};
+
+    const PATTERN: &str = "Noise_NN_25519_ChaChaPoly_BLAKE2s";
+    /// Largest Noise message, ciphertext and tag included.
+    const MAX_FRAME: usize = 65535;
+    const TAG_LEN: usize = 16;
+
+    /// Messages as a sequence of encrypted frames, each its length as a
+    /// big-endian `u16` and then the ciphertext. Writes are buffered until
+    /// flushed.
+    pub(in crate::network) struct NoiseStream {
+        stream: TcpStream,
+        transport: TransportState,
+        read_buf: Vec<u8>,
+        read_pos: usize,
+        write_buf: Vec<u8>,
+    }
+
+    impl NoiseStream {
+        pub(super) fn initiate(mut stream: TcpStream) -> io::Result<Self> {
+            let mut handshake = Builder::new(pattern()?).build_initiator().map_err(failed)?;
+            let mut frame = vec![0; MAX_FRAME];
+            let len = handshake.write_message(&[], &mut frame).map_err(failed)?;
+            write_frame(&mut stream, &frame[..len])?;
+            let reply = read_frame(&mut stream)?;
+            handshake.read_message(&reply, &mut frame).map_err(failed)?;
+            Ok(Self::new(
+                stream,
+                handshake.into_transport_mode().map_err(failed)?,
+            ))
+        }
+
+        pub(super) fn respond(mut stream: TcpStream) -> io::Result<Self> {
+            let mut handshake = Builder::new(pattern()?).build_responder().map_err(failed)?;
+            let mut frame = vec![0; MAX_FRAME];
+            let opening = read_frame(&mut stream)?;
+            handshake
+                .read_message(&opening, &mut frame)
+                .map_err(failed)?;
+            let len = handshake.write_message(&[], &mut frame).map_err(failed)?;
+            write_frame(&mut stream, &frame[..len])?;
+            Ok(Self::new(
+                stream,
+                handshake.into_transport_mode().map_err(failed)?,
+            ))
+        }
+
+        fn new(stream: TcpStream, transport: TransportState) -> Self {
+            NoiseStream {
+                stream,
+                transport,
+                read_buf: vec![],
+                read_pos: 0,
+                write_buf: vec![],
+            }
+        }
+    }
+
+    impl Read for NoiseStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.read_pos == self.read_buf.len() {
+                let frame = read_frame(&mut self.stream)?;
+                self.read_buf.resize(frame.len(), 0);
+                let len = self
+                    .transport
+                    .read_message(&frame, &mut self.read_buf)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                self.read_buf.truncate(len);
+                self.read_pos = 0;
+            }
+            let available = &self.read_buf[self.read_pos..];
+            let len = available.len().min(buf.len());
+            buf[..len].copy_from_slice(&available[..len]);
+            self.read_pos += len;
+            Ok(len)
+        }
+    }
+
+    impl Write for NoiseStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            let mut frame = vec![0; MAX_FRAME];
+            for chunk in self.write_buf.chunks(MAX_FRAME - TAG_LEN) {
+                let len = self
+                    .transport
+                    .write_message(chunk, &mut frame)
+                    .map_err(io::Error::other)?;
+                write_frame(&mut self.stream, &frame[..len])?;
+            }
+            self.write_buf.clear();
+            self.stream.flush()
+        }
+    }
+
+    fn pattern() -> io::Result<snow::params::NoiseParams> {
+        PATTERN.parse().map_err(failed)
+    }
+
+    /// A handshake that fails is taken as the peer not speaking Noise,
+    /// rather than as misbehaviour.
+    fn failed(error: snow::Error) -> io::Error {
+        io::Error::other(format!("noise handshake failed: {}", error))
+    }
+
+    fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> io::Result<()> {
+        stream.write_all(&(frame.len() as u16).to_be_bytes())?;
+        stream.write_all(frame)
+    }
+
+    fn read_frame(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+        let mut len = [0; 2];
+        stream.read_exact(&mut len)?;
+        let mut frame = vec![0; usize::from(u16::from_be_bytes(len))];
+        stream.read_exact(&mut frame)?;
+        Ok(frame)
+    }
+}
@@ -0,0 +1,398 @@
+//! A running node: the shared chain, its background tasks and the store it
+//! is persisted to. Transactions the pool accepts are also logged to a
+//! [`MempoolWal`] as they arrive, so a crash between saves doesn't lose
+//! them, and the payments the stored wallets sent are rebroadcast until
+//! they confirm.
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::Receiver,
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    blockchain::{Block, ChainEvent, SharedBlockchain},
+    clock::{Clock, SystemClock},
+    config::{Config, NetworkConfig},
+    metrics::{MetricsHandle, MetricsServer},
+    miner::{Miner, MinerConfig, MinerHandle},
+    network::{BanList, NetworkHandle, PeerBook, PeerNetwork},
+    storage::{FileStore, MempoolWal},
+    stratum::{ShareLedger, StratumConfig, StratumHandle, StratumServer},
+    wallet::Wallet,
+};
+#[cfg(feature = "grpc")]
+use crate::{
+    config::RpcConfig,
+    grpc::{GrpcHandle, GrpcOptions, GrpcServer},
+};
+
+/// How often [`Node::run`] checks for new blocks and shutdown requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// When the stored wallets' unconfirmed payments are next rebroadcast, see
+/// [`Node::start_rebroadcast`].
+struct Rebroadcast {
+    events: Receiver<ChainEvent>,
+    interval: Duration,
+    max_attempts: u32,
+    next: Instant,
+}
+
+/// Cloneable flag asking a [`Node`] to stop, safe to set from a signal
+/// handler.
+#[derive(Clone, Default)]
+pub struct ShutdownHandle(Arc<AtomicBool>);
+
+impl ShutdownHandle {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+pub struct Node {
+    store: FileStore,
+    blockchain: SharedBlockchain,
+    miner: Option<MinerHandle>,
+    metrics: Option<MetricsHandle>,
+    stratum: Option<StratumHandle>,
+    shares: Arc<ShareLedger>,
+    #[cfg(feature = "grpc")]
+    grpc: Option<GrpcHandle>,
+    network: Option<NetworkHandle>,
+    peers: Arc<AtomicUsize>,
+    peer_book: Arc<Mutex<PeerBook>>,
+    bans: Arc<Mutex<BanList>>,
+    journal: Option<JoinHandle<io::Result<()>>>,
+    rebroadcast: Option<Rebroadcast>,
+    saved_blocks: u64,
+    shutdown: ShutdownHandle,
+}
+
+impl Node {
+    /// Loads the chain kept in `store`, or creates it if there is none yet,
+    /// and puts back into the pool the logged transactions it is missing.
+    pub fn open(store: FileStore, config: &Config) -> io::Result<Self> {
+        let mut blockchain = store.load_blockchain(config)?;
+        let peer_book = store.load_peers()?;
+        let bans = store.load_bans()?;
+        let mut wal = store.open_mempool_wal()?;
+        for transaction in wal.replay()? {
+            // Transactions since confirmed or expired, or already restored
+            // with the chain, are turned away.
+            let _ = blockchain.add_transation_to_pool(transaction);
+        }
+        wal.compact(&blockchain.pending_transactions())?;
+        let events = blockchain.subscribe();
+        let blockchain = SharedBlockchain::new(blockchain);
+        let journal = {
+            let blockchain = blockchain.clone();
+            thread::spawn(move || journal(wal, events, &blockchain))
+        };
+        Ok(Node {
+            store,
+            blockchain,
+            miner: None,
+            metrics: None,
+            stratum: None,
+            shares: Arc::default(),
+            #[cfg(feature = "grpc")]
+            grpc: None,
+            network: None,
+            peers: Arc::new(AtomicUsize::new(0)),
+            peer_book: Arc::new(Mutex::new(peer_book)),
+            bans: Arc::new(Mutex::new(bans)),
+            journal: Some(journal),
+            rebroadcast: None,
+            saved_blocks: 0,
+            shutdown: ShutdownHandle::default(),
+        })
+    }
+
+    pub fn blockchain(&self) -> SharedBlockchain {
+        self.blockchain.clone()
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Number of connected peers reported in metrics, kept up to date by
+    /// whatever manages connections.
+    pub fn peers(&self) -> Arc<AtomicUsize> {
+        self.peers.clone()
+    }
+
+    /// Peers the node has learned of, saved with the chain on shutdown.
+    pub fn peer_book(&self) -> Arc<Mutex<PeerBook>> {
+        self.peer_book.clone()
+    }
+
+    /// Addresses the node refuses to talk to, saved with the chain on
+    /// shutdown.
+    pub fn bans(&self) -> Arc<Mutex<BanList>> {
+        self.bans.clone()
+    }
+
+    /// Accepts peers on the configured listen address, if any, and starts
+    /// discovering peers from the configured ones and the DNS seeds,
+    /// returning the address actually bound.
+    pub fn start_network(&mut self, config: &NetworkConfig) -> io::Result<Option<SocketAddr>> {
+        if let Some(network) = self.network.take() {
+            network.stop();
+        }
+        let network = PeerNetwork::start(
+            config,
+            self.blockchain.clone(),
+            self.peer_book.clone(),
+            self.bans.clone(),
+            self.peers.clone(),
+        )?;
+        let local_addr = network.local_addr();
+        self.network = Some(network);
+        Ok(local_addr)
+    }
+
+    /// Every `config.rebroadcast_interval_secs`, and whenever a reorg
+    /// disconnects blocks, puts the unconfirmed payments the stored wallets
+    /// sent back into the pool if it lost them and sends them to peers
+    /// again, up to `config.rebroadcast_max_attempts` times each, see
+    /// [`Wallet::resubmit_transactions`]. The first round runs on the next
+    /// [`Node::run`] poll. An interval of 0 turns it off.
+    pub fn start_rebroadcast(&mut self, config: &NetworkConfig) -> io::Result<()> {
+        self.rebroadcast = None;
+        if config.rebroadcast_interval_secs == 0 {
+            return Ok(());
+        }
+        self.rebroadcast = Some(Rebroadcast {
+            events: self.blockchain.subscribe()?,
+            interval: Duration::from_secs(config.rebroadcast_interval_secs),
+            max_attempts: config.rebroadcast_max_attempts,
+            next: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Serves Prometheus metrics at `http://{address}/metrics`, returning
+    /// the address actually bound.
+    pub fn start_metrics(&mut self, address: &str) -> io::Result<SocketAddr> {
+        if let Some(metrics) = self.metrics.take() {
+            metrics.stop();
+        }
+        let metrics = MetricsServer::start(
+            address,
+            self.blockchain.clone(),
+            self.peers.clone(),
+            self.shares.clone(),
+        )?;
+        let local_addr = metrics.local_addr();
+        self.metrics = Some(metrics);
+        Ok(local_addr)
+    }
+
+    /// Serves mining jobs to stratum workers on `address`, paying the blocks
+    /// they find to `miner`, and returns the address actually bound. Their
+    /// shares show up in metrics.
+    pub fn start_stratum(
+        &mut self,
+        address: &str,
+        miner: Wallet,
+        config: StratumConfig,
+    ) -> io::Result<SocketAddr> {
+        if let Some(stratum) = self.stratum.take() {
+            stratum.stop();
+        }
+        let stratum = StratumServer::start(
+            address,
+            self.blockchain.clone(),
+            miner,
+            config,
+            self.shares.clone(),
+        )?;
+        let local_addr = stratum.local_addr();
+        self.stratum = Some(stratum);
+        Ok(local_addr)
+    }
+
+    /// Serves the gRPC API on `address` to the clients `rpc` lets in,
+    /// returning the address actually bound. The admin `Mine` call mines
+    /// with `miner`, and `Shutdown` stops this node.
+    #[cfg(feature = "grpc")]
+    pub fn start_grpc(
+        &mut self,
+        address: &str,
+        rpc: &RpcConfig,
+        miner: Option<Wallet>,
+    ) -> io::Result<SocketAddr> {
+        if let Some(grpc) = self.grpc.take() {
+            grpc.stop();
+        }
+        let options = GrpcOptions {
+            rpc: rpc.clone(),
+            miner: miner.map(Arc::new),
+            shutdown: Some(self.shutdown.clone()),
+            peers: Some(self.peer_book.clone()),
+            bans: Some(self.bans.clone()),
+        };
+        let grpc = GrpcServer::start(address, self.blockchain.clone(), options)?;
+        let local_addr = grpc.local_addr();
+        self.grpc = Some(grpc);
+        Ok(local_addr)
+    }
+
+    /// Starts a background miner paying rewards to `wallet`, replacing any
+    /// miner already running.
+    pub fn start_mining(&mut self, wallet: Wallet, config: MinerConfig) {
+        if let Some(miner) = self.miner.take() {
+            miner.stop();
+        }
+        self.saved_blocks = 0;
+        self.miner = Some(Miner::start(self.blockchain.clone(), wallet, config));
+    }
+
+    /// Saves the chain whenever the miner or a stratum worker finds a
+    /// block, calling `on_block` with each new tip, until shutdown is
+    /// requested. Fails if the miner stops on its own.
+    pub fn run(&mut self, mut on_block: impl FnMut(&Block)) -> io::Result<()> {
+        while !self.shutdown.is_requested() {
+            thread::sleep(POLL_INTERVAL);
+            let mut mined = 0;
+            if let Some(miner) = &self.miner {
+                if !miner.is_running() {
+                    return Err(io::Error::other("miner stopped unexpectedly"));
+                }
+                mined += miner.blocks_mined();
+            }
+            if let Some(stratum) = &self.stratum {
+                mined += stratum.blocks_found();
+            }
+            if mined != self.saved_blocks {
+                let blockchain = self.blockchain.read()?;
+                self.store.save_blockchain(&blockchain)?;
+                if let Some(block) = blockchain.last_block() {
+                    on_block(&block);
+                }
+                drop(blockchain);
+                self.saved_blocks = mined;
+            }
+            self.rebroadcast()?;
+        }
+        Ok(())
+    }
+
+    /// Runs a round of rebroadcasting if one is due.
+    fn rebroadcast(&mut self) -> io::Result<()> {
+        let Some(rebroadcast) = &mut self.rebroadcast else {
+            return Ok(());
+        };
+        let reorgs = rebroadcast
+            .events
+            .try_iter()
+            .filter(|event| {
+                matches!(
+                    event,
+                    ChainEvent::Reorg { .. } | ChainEvent::BlockDisconnected { .. }
+                )
+            })
+            .count();
+        if reorgs == 0 && Instant::now() < rebroadcast.next {
+            return Ok(());
+        }
+        rebroadcast.next = Instant::now() + rebroadcast.interval;
+        let mut transactions = vec![];
+        for address in self.store.wallet_addresses()? {
+            let wallet = self.store.load_wallet(&address)?;
+            let mut blockchain = self.blockchain.write()?;
+            transactions
+                .extend(wallet.resubmit_transactions(&mut blockchain, rebroadcast.max_attempts));
+            drop(blockchain);
+            self.store.save_sent(&wallet)?;
+        }
+        if let Some(network) = &self.network {
+            network.broadcast(transactions);
+        }
+        Ok(())
+    }
+
+    /// Stops the miner, letting the block in progress finish, the network
+    /// and the stratum, metrics and gRPC servers, then flushes the chain, pool and
+    /// peer book to disk.
+    pub fn shutdown(mut self) -> io::Result<()> {
+        if let Some(miner) = self.miner.take() {
+            miner.stop();
+        }
+        if let Some(network) = self.network.take() {
+            network.stop();
+        }
+        if let Some(stratum) = self.stratum.take() {
+            stratum.stop();
+        }
+        if let Some(metrics) = self.metrics.take() {
+            metrics.stop();
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = self.grpc.take() {
+            grpc.stop();
+        }
+        let snapshot = self.blockchain.shutdown()?;
+        self.store.save_snapshot(&snapshot)?;
+        if let Some(journal) = self.journal.take() {
+            journal
+                .join()
+                .map_err(|_| io::Error::other("mempool log thread panicked"))??;
+        }
+        let peer_book = self
+            .peer_book
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        self.store.save_peers(&peer_book)?;
+        drop(peer_book);
+        let mut bans = self
+            .bans
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        bans.expire(SystemClock.now());
+        self.store.save_bans(&bans)
+    }
+}
+
+/// Logs each transaction the pool accepts to `wal`, and compacts it to the
+/// pool whenever blocks or replacements change what is pending, until the
+/// chain shuts down.
+fn journal(
+    mut wal: MempoolWal,
+    events: Receiver<ChainEvent>,
+    blockchain: &SharedBlockchain,
+) -> io::Result<()> {
+    for event in events {
+        match event {
+            ChainEvent::TransactionAccepted { id } => {
+                if let Some(transaction) = blockchain.pending_transaction(&id) {
+                    wal.append(&transaction)?;
+                }
+            }
+            // A replacement can't be replayed after the transaction it
+            // replaced, so the log is rewritten instead.
+            ChainEvent::TransactionReplaced { .. }
+            | ChainEvent::BlockMined { .. }
+            | ChainEvent::BlockConnected { .. }
+            | ChainEvent::BlockDisconnected { .. }
+            | ChainEvent::Reorg { .. } => {
+                wal.compact(&blockchain.pending_transactions())?;
+            }
+            ChainEvent::Shutdown => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
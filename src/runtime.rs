@@ -0,0 +1,229 @@
+//! Async API for running the node on tokio.
+//!
+//! [`AsyncBlockchain`] shares a [`Blockchain`] behind a `tokio::sync::RwLock`
+//! so RPC handlers, the miner and networking tasks can use it concurrently
+//! without tying up runtime threads, the way a
+//! [`SharedBlockchain`](crate::blockchain::SharedBlockchain) does for
+//! threads. Proof of work runs on the blocking pool.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use tokio::{
+    sync::{mpsc, watch, RwLock, RwLockWriteGuard},
+    task::JoinHandle,
+    time,
+};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    address::Address,
+    blockchain::{
+        Block, BlockStatus, Blockchain, CancelToken, ChainEvent, Mined, Result, Snapshot,
+        Transaction,
+    },
+    miner::{self, MinerConfig},
+    wallet::Wallet,
+};
+
+/// Blocks [`AsyncBlockchain::stream_blocks`] reads ahead of its consumer.
+const STREAM_BUFFER: usize = 16;
+
+#[derive(Clone)]
+pub struct AsyncBlockchain {
+    inner: Arc<RwLock<Blockchain>>,
+}
+
+impl AsyncBlockchain {
+    pub fn new(blockchain: Blockchain) -> Self {
+        AsyncBlockchain {
+            inner: Arc::new(RwLock::new(blockchain)),
+        }
+    }
+
+    /// Exclusive access to the chain, for changes the methods here don't
+    /// cover.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, Blockchain> {
+        self.inner.write().await
+    }
+
+    pub async fn add_transaction(&self, transaction: Transaction) -> Result<Transaction> {
+        self.inner.write().await.add_transation_to_pool(transaction)
+    }
+
+    /// Swaps a pending transaction for `transaction`, which has the same
+    /// sender and sequence number and pays a higher fee.
+    pub async fn replace_transaction(&self, transaction: Transaction) -> Result<Transaction> {
+        self.inner.write().await.replace_transaction(transaction)
+    }
+
+    pub async fn next_sequence(&self, sender: Address) -> Result<u64> {
+        self.inner.read().await.next_sequence(&sender)
+    }
+
+    pub async fn receive_block(&self, block: Block) -> Result<BlockStatus> {
+        self.inner.write().await.receive_block(block)
+    }
+
+    pub async fn balance(&self, address: Address) -> Result<f64> {
+        self.inner
+            .read()
+            .await
+            .calculate_transactions_total(&address)
+    }
+
+    pub async fn last_block(&self) -> Option<Arc<Block>> {
+        self.inner.read().await.last_block()
+    }
+
+    /// Main chain blocks from height `from` on, streamed by a task that
+    /// takes the lock for one block at a time, like
+    /// [`SharedBlockchain::iter_blocks_from`](crate::blockchain::SharedBlockchain::iter_blocks_from).
+    /// The task stops once the stream is dropped.
+    pub fn stream_blocks(&self, from: u64) -> ReceiverStream<Arc<Block>> {
+        let (sender, blocks) = mpsc::channel(STREAM_BUFFER);
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            for height in from.. {
+                let Some(block) = inner.read().await.block_by_height(height) else {
+                    break;
+                };
+                if sender.send(block).await.is_err() {
+                    break;
+                }
+            }
+        });
+        ReceiverStream::new(blocks)
+    }
+
+    pub async fn pending_transaction_count(&self) -> usize {
+        self.inner.read().await.pending_transaction_count()
+    }
+
+    pub async fn subscribe(&self) -> std::sync::mpsc::Receiver<ChainEvent> {
+        self.inner.write().await.subscribe()
+    }
+
+    pub async fn snapshot(&self) -> Result<Snapshot> {
+        self.inner.read().await.snapshot()
+    }
+
+    /// Mines one block, searching for its proof of work on the blocking
+    /// thread pool without holding the lock. Returns `false` if another
+    /// block extended the tip first.
+    pub async fn mine(&self, miner: Arc<Wallet>) -> bool {
+        self.mine_until(miner, CancelToken::new()).await
+    }
+
+    /// Like [`AsyncBlockchain::mine`], but gives up the search as soon as
+    /// `cancel` is tripped, so a miner can start over on a new tip.
+    pub async fn mine_until(&self, miner: Arc<Wallet>, cancel: CancelToken) -> bool {
+        let candidate = {
+            let blockchain = self.inner.read().await;
+            blockchain
+                .block_candidate(miner.address())
+                .map(|block| (block, blockchain.consensus()))
+        };
+        let Ok((block, consensus)) = candidate else {
+            return false;
+        };
+        let mined = tokio::task::spawn_blocking(move || {
+            Mined::new(block, consensus.as_ref(), &miner, &cancel)
+        })
+        .await;
+        let Ok(Ok(mined)) = mined else {
+            return false;
+        };
+        self.inner.write().await.connect_mined(mined).is_ok()
+    }
+
+    /// Spawns a task that mines blocks whenever transactions are waiting or
+    /// the idle interval elapses, like [`crate::miner::Miner`]. A block
+    /// from elsewhere changing the tip abandons the search in progress,
+    /// which starts over on the new tip.
+    pub fn spawn_miner(&self, wallet: Wallet, config: MinerConfig) -> AsyncMinerHandle {
+        let wallet = Arc::new(wallet);
+        let (stop, mut stopped) = watch::channel(false);
+        let cancel = CancelToken::new();
+        let blocks_mined = Arc::new(AtomicU64::new(0));
+        let blockchain = self.clone();
+        let counter = blocks_mined.clone();
+        let token = cancel.clone();
+        let task = tokio::spawn(async move {
+            let events = blockchain.subscribe().await;
+            let done = Arc::new(AtomicBool::new(false));
+            let watcher = {
+                let (done, token) = (done.clone(), token.clone());
+                let poll_interval = config.poll_interval;
+                tokio::task::spawn_blocking(move || {
+                    miner::watch_tip(events, &done, &token, poll_interval)
+                })
+            };
+            let mut poll = time::interval(config.poll_interval);
+            let mut last_block = Instant::now();
+            loop {
+                tokio::select! {
+                    _ = stopped.changed() => break,
+                    _ = poll.tick() => {
+                        let idle_elapsed = config
+                            .idle_interval
+                            .is_some_and(|idle| last_block.elapsed() >= idle);
+                        if blockchain.pending_transaction_count().await > 0 || idle_elapsed {
+                            loop {
+                                // Cleared before the candidate is built, so a
+                                // tip change while it is searched always trips
+                                // it, and before the stop flag is read, so a
+                                // stop can't be missed.
+                                token.reset();
+                                if *stopped.borrow() {
+                                    break;
+                                }
+                                if blockchain.mine_until(wallet.clone(), token.clone()).await {
+                                    counter.fetch_add(1, Ordering::AcqRel);
+                                }
+                                if !token.is_cancelled() {
+                                    break;
+                                }
+                            }
+                            last_block = Instant::now();
+                        }
+                    }
+                }
+            }
+            done.store(true, Ordering::Release);
+            let _ = watcher.await;
+        });
+        AsyncMinerHandle {
+            stop,
+            cancel,
+            blocks_mined,
+            task,
+        }
+    }
+}
+
+pub struct AsyncMinerHandle {
+    stop: watch::Sender<bool>,
+    cancel: CancelToken,
+    blocks_mined: Arc<AtomicU64>,
+    task: JoinHandle<()>,
+}
+
+impl AsyncMinerHandle {
+    pub fn blocks_mined(&self) -> u64 {
+        self.blocks_mined.load(Ordering::Acquire)
+    }
+
+    /// Signals the miner task to stop, abandoning the block in progress, if
+    /// any, and waits for it to finish.
+    pub async fn stop(self) {
+        let _ = self.stop.send(true);
+        self.cancel.cancel();
+        let _ = self.task.await;
+    }
+}
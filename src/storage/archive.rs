@@ -0,0 +1,74 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Read, Result, Write},
+    path::Path,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use sha2::{Digest, Sha256};
+
+use super::SCHEMA_VERSION;
+use crate::{
+    blockchain::{Checkpoint, Snapshot},
+    codec::{Decode, Encode},
+};
+
+const MAGIC: &[u8; 8] = b"AEONSNAP";
+const HEADER_LEN: usize = MAGIC.len() + 4 + 32;
+
+/// Writes `snapshot` and `checkpoint` to an archive at `path` another node
+/// can bootstrap from, see [`read_archive`]. The archive is the magic
+/// bytes, the schema version as a little-endian `u32` and the SHA-256 of
+/// the payload, followed by the payload deflated: the encoded snapshot and
+/// then the checkpoint, if any.
+pub fn write_archive(
+    path: &Path,
+    snapshot: &Snapshot,
+    checkpoint: Option<&Checkpoint>,
+) -> Result<()> {
+    let payload = (snapshot, checkpoint).encode();
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len() / 2);
+    out.extend(MAGIC);
+    out.extend(SCHEMA_VERSION.to_le_bytes());
+    out.extend(Sha256::digest(&payload));
+    let mut encoder = DeflateEncoder::new(out, Compression::default());
+    encoder.write_all(&payload)?;
+    super::write_atomic(path, &encoder.finish()?)
+}
+
+/// Reads an archive written by [`write_archive`], failing if it isn't one,
+/// was written with another schema or doesn't match its checksum.
+pub fn read_archive(path: &Path) -> Result<(Snapshot, Option<Checkpoint>)> {
+    let bytes = fs::read(path)?;
+    let invalid = |reason: &str| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), reason),
+        )
+    };
+    if bytes.len() < HEADER_LEN || !bytes.starts_with(MAGIC) {
+        return Err(invalid("not a snapshot archive"));
+    }
+    let (version, rest) = bytes[MAGIC.len()..].split_at(4);
+    let version = u32::from_le_bytes(version.try_into().unwrap_or_default());
+    if version != SCHEMA_VERSION {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "{} has schema {}, this build reads {}",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            ),
+        ));
+    }
+    let (checksum, compressed) = rest.split_at(32);
+    let mut payload = vec![];
+    DeflateDecoder::new(compressed)
+        .read_to_end(&mut payload)
+        .map_err(|_| invalid("corrupt payload"))?;
+    if Sha256::digest(&payload).as_slice() != checksum {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(Decode::decode(&payload)?)
+}
@@ -0,0 +1,248 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use sled::{
+    transaction::{ConflictableTransactionResult, TransactionError},
+    Transactional, Tree,
+};
+
+use crate::{
+    blockchain::{Block, BlockBody, BlockHeader, Checkpoint, Snapshot, TxId, TxLocation},
+    codec::{Decode, Encode},
+    wallet::StoredWallet,
+};
+
+const DB_DIR: &str = "chain.sled";
+const HEADERS_TREE: &str = "headers";
+const BODIES_TREE: &str = "bodies";
+const TX_INDEX_TREE: &str = "tx_index";
+const STATE_TREE: &str = "state";
+const WALLET_KEY: &[u8] = b"wallet";
+const POOL_KEY: &[u8] = b"pool";
+const CHECKPOINT_KEY: &[u8] = b"checkpoint";
+/// Number of blocks at the start of the chain whose bodies were dropped.
+const PRUNED_KEY: &[u8] = b"pruned";
+
+/// Chain kept in a sled database, with a tree each for headers by height,
+/// block bodies by hash, the transaction index and the rest of the state:
+/// the faucet wallet, the pool and the latest checkpoint. Saving writes only
+/// the blocks connected or disconnected since the last save, all in one
+/// transaction, so a crash leaves either the old chain or the new one.
+pub struct KvStore {
+    db: sled::Db,
+    headers: Tree,
+    bodies: Tree,
+    tx_index: Tree,
+    state: Tree,
+}
+
+impl KvStore {
+    pub fn open(dir: &Path) -> Result<Self> {
+        let db = sled::open(dir.join(DB_DIR))?;
+        Ok(KvStore {
+            headers: db.open_tree(HEADERS_TREE)?,
+            bodies: db.open_tree(BODIES_TREE)?,
+            tx_index: db.open_tree(TX_INDEX_TREE)?,
+            state: db.open_tree(STATE_TREE)?,
+            db,
+        })
+    }
+
+    /// The stored chain, or `None` if nothing has been saved yet.
+    pub fn load_snapshot(&self) -> Result<Option<Snapshot>> {
+        let Some(wallet) = self.state.get(WALLET_KEY)? else {
+            return Ok(None);
+        };
+        let mut chain = Vec::with_capacity(self.headers.len());
+        for entry in self.headers.iter() {
+            let (_, header) = entry?;
+            chain.push(self.with_body(BlockHeader::decode(&header)?)?);
+        }
+        let transaction_pool = match self.state.get(POOL_KEY)? {
+            Some(pool) => Vec::decode(&pool)?,
+            None => vec![],
+        };
+        Ok(Some(Snapshot::new(
+            StoredWallet::decode(&wallet)?,
+            chain,
+            transaction_pool,
+        )))
+    }
+
+    pub fn load_checkpoint(&self) -> Result<Option<Checkpoint>> {
+        match self.state.get(CHECKPOINT_KEY)? {
+            Some(checkpoint) => Ok(Some(Checkpoint::decode(&checkpoint)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Where the transaction `id` was confirmed, as of the last save.
+    pub fn locate(&self, id: &TxId) -> Result<Option<TxLocation>> {
+        match self.tx_index.get(id.to_bytes())? {
+            Some(location) => {
+                let (height, index) = <(u64, u32)>::decode(&location)?;
+                Ok(Some(TxLocation {
+                    height,
+                    index: index as usize,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Brings the database in line with `snapshot`, and stores `checkpoint`
+    /// if given. Blocks past the last one both share are disconnected and
+    /// those of `snapshot` connected in their place, and blocks pruned since
+    /// the last save lose their bodies.
+    pub fn save(&self, snapshot: &Snapshot, checkpoint: Option<&Checkpoint>) -> Result<()> {
+        let chain = snapshot.chain();
+        let stored = self.headers.len() as u64;
+        let mut common = stored.min(chain.len() as u64);
+        while common > 0 && self.hash_at(common - 1)? != Some(chain[common as usize - 1].hash()) {
+            common -= 1;
+        }
+        let disconnected = (common..stored)
+            .map(|height| Ok((height, self.block_at(height)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let pruned = chain.iter().take_while(|block| block.is_pruned()).count() as u64;
+        let newly_pruned = (self.pruned()?..pruned.min(common))
+            .map(|height| self.block_at(height))
+            .collect::<Result<Vec<_>>>()?;
+        let connected: Vec<(u64, &Block)> = (common..).zip(&chain[common as usize..]).collect();
+        let wallet = snapshot.wallet().encode();
+        let pool = snapshot.transaction_pool().encode();
+        let checkpoint = checkpoint.map(Checkpoint::encode);
+        (&self.headers, &self.bodies, &self.tx_index, &self.state)
+            .transaction(
+                |(headers, bodies, tx_index, state)| -> ConflictableTransactionResult<()> {
+                    for (height, block) in &disconnected {
+                        headers.remove(&height.to_be_bytes())?;
+                        bodies.remove(block.hash().as_bytes())?;
+                        for transaction in block.transactions() {
+                            tx_index.remove(&transaction.id().to_bytes())?;
+                        }
+                    }
+                    for block in &newly_pruned {
+                        bodies.remove(block.hash().as_bytes())?;
+                        for transaction in block.transactions() {
+                            tx_index.remove(&transaction.id().to_bytes())?;
+                        }
+                    }
+                    for (height, block) in &connected {
+                        headers.insert(&height.to_be_bytes(), block.header().encode())?;
+                        let Some(body) = block.body() else {
+                            continue;
+                        };
+                        bodies.insert(block.hash().as_bytes(), body.encode())?;
+                        for (index, transaction) in block.transactions().iter().enumerate() {
+                            tx_index.insert(
+                                &transaction.id().to_bytes(),
+                                (*height, index as u32).encode(),
+                            )?;
+                        }
+                    }
+                    state.insert(WALLET_KEY, wallet.as_slice())?;
+                    state.insert(POOL_KEY, pool.as_slice())?;
+                    state.insert(PRUNED_KEY, &pruned.to_be_bytes())?;
+                    if let Some(checkpoint) = &checkpoint {
+                        state.insert(CHECKPOINT_KEY, checkpoint.as_slice())?;
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(|e: TransactionError<()>| match e {
+                TransactionError::Storage(e) => e.into(),
+                TransactionError::Abort(()) => Error::other("chain write aborted"),
+            })?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Replaces the transaction index and checkpoint with ones built from
+    /// `snapshot`, whose blocks must already be stored, then saves it.
+    pub fn reindex(&self, snapshot: &Snapshot, checkpoint: Option<&Checkpoint>) -> Result<()> {
+        self.tx_index.clear()?;
+        self.state.remove(CHECKPOINT_KEY)?;
+        for (height, block) in snapshot.chain().iter().enumerate() {
+            for (index, transaction) in block.transactions().iter().enumerate() {
+                self.tx_index.insert(
+                    transaction.id().to_bytes(),
+                    (height as u64, index as u32).encode(),
+                )?;
+            }
+        }
+        self.save(snapshot, checkpoint)
+    }
+
+    fn pruned(&self) -> Result<u64> {
+        Ok(match self.state.get(PRUNED_KEY)? {
+            Some(bytes) => {
+                u64::from_be_bytes(bytes.as_ref().try_into().map_err(|_| {
+                    Error::new(ErrorKind::InvalidData, "malformed pruned block count")
+                })?)
+            }
+            None => 0,
+        })
+    }
+
+    fn hash_at(&self, height: u64) -> Result<Option<String>> {
+        match self.headers.get(height.to_be_bytes())? {
+            Some(header) => Ok(Some(BlockHeader::decode(&header)?.hash())),
+            None => Ok(None),
+        }
+    }
+
+    fn block_at(&self, height: u64) -> Result<Block> {
+        let header = self.headers.get(height.to_be_bytes())?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("no header stored at height {}", height),
+            )
+        })?;
+        self.with_body(BlockHeader::decode(&header)?)
+    }
+
+    /// The block of `header` with its body, or pruned if its body has been
+    /// dropped.
+    fn with_body(&self, header: BlockHeader) -> Result<Block> {
+        let hash = header.hash();
+        let Some(body) = self.bodies.get(hash.as_bytes())? else {
+            return Ok(Block::from_header(header));
+        };
+        Block::from_parts(header, BlockBody::decode(&body)?).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("stored body of block {} doesn't match its header", hash),
+            )
+        })
+    }
+}
+
+/// The stored header at height 0 under `dir`, if there is a database
+/// holding one.
+pub(super) fn first_header(dir: &Path) -> Result<Option<Vec<u8>>> {
+    if !dir.join(DB_DIR).exists() {
+        return Ok(None);
+    }
+    let db = sled::open(dir.join(DB_DIR))?;
+    let header = db.open_tree(HEADERS_TREE)?.get(0u64.to_be_bytes())?;
+    Ok(header.map(|header| header.to_vec()))
+}
+
+/// Rewrites every stored header under `dir` with `f`, for schema
+/// migrations.
+pub(super) fn map_headers(dir: &Path, f: impl Fn(&[u8]) -> Result<Vec<u8>>) -> Result<()> {
+    if !dir.join(DB_DIR).exists() {
+        return Ok(());
+    }
+    let db = sled::open(dir.join(DB_DIR))?;
+    let headers = db.open_tree(HEADERS_TREE)?;
+    for entry in headers.iter() {
+        let (height, header) = entry?;
+        headers.insert(height, f(&header)?)?;
+    }
+    db.flush()?;
+    Ok(())
+}
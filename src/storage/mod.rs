@@ -0,0 +1,493 @@
+mod archive;
+#[cfg(feature = "sled")]
+mod kv;
+mod schema;
+mod stream;
+mod wal;
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Read, Result, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+
+pub use archive::{read_archive, write_archive};
+#[cfg(feature = "sled")]
+pub use kv::KvStore;
+pub use schema::{migrate, MIN_SCHEMA_VERSION, SCHEMA_VERSION};
+pub use stream::{write_blocks, BlockReader};
+pub use wal::MempoolWal;
+
+use crate::{
+    address::Address,
+    blockchain::{Block, Blockchain, Checkpoint, Snapshot},
+    codec::{Decode, Encode},
+    config::Config,
+    network::{BanList, PeerBook},
+    wallet::{AddressBook, EncryptedWallet, StoredWallet, TxTracker, Wallet, WatchList},
+};
+
+const CHAIN_FILE: &str = "chain.dat";
+const CHECKPOINT_FILE: &str = "checkpoint.dat";
+const WALLETS_DIR: &str = "wallets";
+const KEYSTORE_DIR: &str = "keystore";
+const SENT_DIR: &str = "sent";
+const CONTACTS_FILE: &str = "contacts.json";
+const WATCH_FILE: &str = "watch.json";
+const PEERS_FILE: &str = "peers.json";
+const BANS_FILE: &str = "banned.json";
+const MEMPOOL_WAL_FILE: &str = "mempool.wal";
+
+/// Where the chain is kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// The whole chain in one file, rewritten on every save.
+    #[default]
+    File,
+    /// A sled database written block by block. Needs the `sled` feature.
+    Sled,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(StorageBackend::File),
+            "sled" => Ok(StorageBackend::Sled),
+            _ => Err(format!("unknown storage backend {}", s)),
+        }
+    }
+}
+
+/// Store keeping the chain, its latest balance checkpoint and wallet keys
+/// under a single data directory. The chain goes to flat files or to a
+/// database depending on the [`StorageBackend`]; everything else is always
+/// in flat files.
+pub struct FileStore {
+    dir: PathBuf,
+    #[cfg(feature = "sled")]
+    kv: Option<KvStore>,
+}
+
+impl FileStore {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        Self::open_with(dir, StorageBackend::File)
+    }
+
+    /// Opens `dir`, keeping the chain with `backend`. A database that is
+    /// still empty picks up the chain from the flat files, so switching to
+    /// it keeps the chain. Data written by an older build is upgraded
+    /// first, and data from a newer one refused, see [`migrate`].
+    pub fn open_with<P: AsRef<Path>>(dir: P, backend: StorageBackend) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join(WALLETS_DIR))?;
+        fs::create_dir_all(dir.join(KEYSTORE_DIR))?;
+        fs::create_dir_all(dir.join(SENT_DIR))?;
+        migrate(&dir)?;
+        match backend {
+            StorageBackend::File => Ok(FileStore {
+                dir,
+                #[cfg(feature = "sled")]
+                kv: None,
+            }),
+            #[cfg(feature = "sled")]
+            StorageBackend::Sled => Ok(FileStore {
+                kv: Some(KvStore::open(&dir)?),
+                dir,
+            }),
+            #[cfg(not(feature = "sled"))]
+            StorageBackend::Sled => Err(Error::new(
+                ErrorKind::Unsupported,
+                "the sled storage backend needs the sled feature",
+            )),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Loads the stored chain, or creates a new one if there is none yet.
+    /// Balances are restored from the latest checkpoint when it still
+    /// matches the chain.
+    pub fn load_blockchain(&self, config: &Config) -> Result<Blockchain> {
+        let Some(snapshot) = self.load_snapshot()? else {
+            return Ok(Blockchain::with_config(config)?);
+        };
+        let checkpoint = self.load_checkpoint()?;
+        Ok(Blockchain::from_checkpoint(
+            snapshot,
+            checkpoint.as_ref(),
+            config,
+        )?)
+    }
+
+    /// Rebuilds the stored chain's balances and indexes from its blocks,
+    /// checking every one, see [`Blockchain::reindex`], and stores the
+    /// result in place of the old ones, checkpoint included.
+    pub fn reindex(&self, config: &Config) -> Result<Blockchain> {
+        let snapshot = self
+            .load_snapshot()?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no chain stored to reindex"))?;
+        let blockchain = Blockchain::reindex(snapshot, config)?;
+        #[cfg(feature = "sled")]
+        if let Some(kv) = &self.kv {
+            kv.reindex(&blockchain.snapshot()?, blockchain.checkpoint()?.as_ref())?;
+            return Ok(blockchain);
+        }
+        let path = self.dir.join(CHECKPOINT_FILE);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        self.save_blockchain(&blockchain)?;
+        Ok(blockchain)
+    }
+
+    /// Writes the stored chain and its latest checkpoint to an archive at
+    /// `path`, see [`write_archive`]. Returns the number of blocks written.
+    pub fn export_archive(&self, path: &Path) -> Result<usize> {
+        let snapshot = self
+            .load_snapshot()?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no chain stored to export"))?;
+        write_archive(path, &snapshot, self.load_checkpoint()?.as_ref())?;
+        Ok(snapshot.chain().len())
+    }
+
+    /// Bootstraps this store from an archive written by
+    /// [`FileStore::export_archive`], refusing to overwrite a stored chain.
+    /// Beyond the archive's checksum, every block is checked again as in
+    /// [`Blockchain::reindex`]; a pruned chain can't be, and is restored
+    /// from the archive's checkpoint instead, which must cover the pruned
+    /// blocks.
+    pub fn import_archive(&self, path: &Path, config: &Config) -> Result<Blockchain> {
+        if self.load_snapshot()?.is_some() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already holds a chain", self.dir.display()),
+            ));
+        }
+        let (snapshot, checkpoint) = read_archive(path)?;
+        let blockchain = if snapshot.chain().iter().any(Block::is_pruned) {
+            Blockchain::from_checkpoint(snapshot, checkpoint.as_ref(), config)?
+        } else {
+            Blockchain::reindex(snapshot, config)?
+        };
+        self.save_blockchain(&blockchain)?;
+        Ok(blockchain)
+    }
+
+    /// Writes the stored chain to `writer` as a block stream, see
+    /// [`write_blocks`], with the faucet's key only if `with_key` is set.
+    /// Returns the number of blocks written.
+    pub fn export_blocks<W: Write>(&self, writer: &mut W, with_key: bool) -> Result<usize> {
+        let snapshot = self
+            .load_snapshot()?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no chain stored to export"))?;
+        if let Some(height) = snapshot.chain().iter().rposition(Block::is_pruned) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("transactions up to height {} have been pruned", height),
+            ));
+        }
+        write_blocks(writer, &snapshot, with_key)
+    }
+
+    /// Builds the chain of an empty data directory from a block stream,
+    /// connecting each block with the checks of
+    /// [`Blockchain::connect_block`] as it is read, and stores it. Stops
+    /// after `to_height` if given, and calls `progress` with the height of
+    /// each block connected.
+    ///
+    /// A stream carrying the faucet's key is refused unless `accept_key` is
+    /// set, and the node then takes over the faucet. Without the key the
+    /// node gets a faucet key of its own, which holds nothing to give out.
+    pub fn import_blocks<R: Read>(
+        &self,
+        reader: R,
+        config: &Config,
+        to_height: Option<u64>,
+        accept_key: bool,
+        mut progress: impl FnMut(u64),
+    ) -> Result<Blockchain> {
+        if self.load_snapshot()?.is_some() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already holds a chain", self.dir.display()),
+            ));
+        }
+        let mut blocks = BlockReader::new(reader)?;
+        let key = blocks.wallet().cloned();
+        if key.is_some() && !accept_key {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "block stream carries the faucet's key, which wasn't asked for",
+            ));
+        }
+        let genesis = blocks
+            .next()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "block stream has no blocks"))??;
+        let wallet = match key {
+            Some(wallet) => {
+                let faucet = *Wallet::from_stored(&wallet)?.address();
+                if genesis.transactions().first().map(|t| t.recipient) != Some(faucet) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "genesis block doesn't fund the faucet of the stream",
+                    ));
+                }
+                wallet
+            }
+            None => Wallet::from_rng(&mut OsRng, config.address_version)?.to_stored()?,
+        };
+        let snapshot = Snapshot::new(wallet, vec![genesis], vec![]);
+        let mut blockchain = Blockchain::from_checkpoint(snapshot, None, config)?;
+        progress(0);
+        for height in 1..=to_height.unwrap_or(u64::MAX) {
+            let Some(block) = blocks.next() else {
+                break;
+            };
+            blockchain.connect_block(block?)?;
+            progress(height);
+        }
+        self.save_blockchain(&blockchain)?;
+        Ok(blockchain)
+    }
+
+    /// Saves the chain, and its checkpoint when a new one has been reached.
+    pub fn save_blockchain(&self, blockchain: &Blockchain) -> Result<()> {
+        #[cfg(feature = "sled")]
+        if let Some(kv) = &self.kv {
+            return kv.save(&blockchain.snapshot()?, blockchain.checkpoint()?.as_ref());
+        }
+        self.save_snapshot(&blockchain.snapshot()?)?;
+        if let Some(checkpoint) = blockchain.checkpoint()? {
+            let stored = self.load_checkpoint()?;
+            let current = stored.is_some_and(|stored| {
+                stored.height() == checkpoint.height() && stored.tip_hash() == checkpoint.tip_hash()
+            });
+            if !current {
+                write_atomic(&self.dir.join(CHECKPOINT_FILE), &checkpoint.encode())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_checkpoint(&self) -> Result<Option<Checkpoint>> {
+        #[cfg(feature = "sled")]
+        if let Some(kv) = &self.kv {
+            if let Some(checkpoint) = kv.load_checkpoint()? {
+                return Ok(Some(checkpoint));
+            }
+        }
+        let path = self.dir.join(CHECKPOINT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(Checkpoint::decode(&fs::read(path)?)?))
+    }
+
+    /// The stored chain, or `None` if there is none yet.
+    pub fn load_snapshot(&self) -> Result<Option<Snapshot>> {
+        #[cfg(feature = "sled")]
+        if let Some(kv) = &self.kv {
+            if let Some(snapshot) = kv.load_snapshot()? {
+                return Ok(Some(snapshot));
+            }
+        }
+        let path = self.dir.join(CHAIN_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(Snapshot::decode(&fs::read(path)?)?))
+    }
+
+    pub fn save_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        #[cfg(feature = "sled")]
+        if let Some(kv) = &self.kv {
+            return kv.save(snapshot, None);
+        }
+        write_atomic(&self.dir.join(CHAIN_FILE), &snapshot.encode())
+    }
+
+    pub fn save_wallet(&self, wallet: &Wallet) -> Result<PathBuf> {
+        let path = self.wallet_path(wallet.address());
+        let json = serde_json::to_vec_pretty(&wallet.to_stored()?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&path, &json)?;
+        Ok(path)
+    }
+
+    pub fn load_wallet(&self, address: &Address) -> Result<Wallet> {
+        let path = self.wallet_path(address);
+        if !path.exists() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("no wallet stored for address {}", address),
+            ));
+        }
+        let stored: StoredWallet = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let sent = self.load_sent(address)?;
+        Ok(Wallet::from_stored(&stored)?.with_sent_transactions(sent))
+    }
+
+    /// Loads the transactions the wallet of `address` submitted, none
+    /// before it first sends one.
+    fn load_sent(&self, address: &Address) -> Result<TxTracker> {
+        let path = self.sent_path(address);
+        if !path.exists() {
+            return Ok(TxTracker::default());
+        }
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Saves the transactions `wallet` follows, see
+    /// [`Wallet::track_transaction`].
+    pub fn save_sent(&self, wallet: &Wallet) -> Result<()> {
+        let json = serde_json::to_vec_pretty(&wallet.sent_transactions())
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&self.sent_path(wallet.address()), &json)
+    }
+
+    pub fn wallet_addresses(&self) -> Result<Vec<Address>> {
+        self.addresses_in(WALLETS_DIR)
+    }
+
+    /// Removes the plaintext key of `address`, once it is kept elsewhere.
+    pub fn remove_wallet(&self, address: &Address) -> Result<()> {
+        fs::remove_file(self.wallet_path(address))
+    }
+
+    /// Stores a wallet encrypted at rest, which only a
+    /// [`WalletDaemon`](crate::wallet::WalletDaemon) can sign with.
+    pub fn save_keystore(&self, keystore: &EncryptedWallet) -> Result<PathBuf> {
+        let path = self.keystore_path(&keystore.address);
+        let json = serde_json::to_vec_pretty(keystore)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&path, &json)?;
+        Ok(path)
+    }
+
+    pub fn load_keystore(&self, address: &Address) -> Result<EncryptedWallet> {
+        let path = self.keystore_path(address);
+        if !path.exists() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("no encrypted wallet stored for address {}", address),
+            ));
+        }
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Addresses of the wallets stored encrypted.
+    pub fn keystore_addresses(&self) -> Result<Vec<Address>> {
+        self.addresses_in(KEYSTORE_DIR)
+    }
+
+    fn addresses_in(&self, dir: &str) -> Result<Vec<Address>> {
+        let mut addresses = vec![];
+        for entry in fs::read_dir(self.dir.join(dir))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(Ok(address)) = path.file_stem().map(|s| s.to_string_lossy().parse()) {
+                    addresses.push(address);
+                }
+            }
+        }
+        addresses.sort();
+        Ok(addresses)
+    }
+
+    /// Loads the address book, which is empty until a contact is added.
+    pub fn load_contacts(&self) -> Result<AddressBook> {
+        let path = self.dir.join(CONTACTS_FILE);
+        if !path.exists() {
+            return Ok(AddressBook::default());
+        }
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn save_contacts(&self, contacts: &AddressBook) -> Result<()> {
+        let json = serde_json::to_vec_pretty(contacts)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&self.dir.join(CONTACTS_FILE), &json)
+    }
+
+    /// Loads the watch-only wallets, none until one is added.
+    pub fn load_watch_list(&self) -> Result<WatchList> {
+        let path = self.dir.join(WATCH_FILE);
+        if !path.exists() {
+            return Ok(WatchList::default());
+        }
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn save_watch_list(&self, watched: &WatchList) -> Result<()> {
+        let json = serde_json::to_vec_pretty(watched)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&self.dir.join(WATCH_FILE), &json)
+    }
+
+    /// Loads the peers the node has learned of, none before it first
+    /// joins the network.
+    pub fn load_peers(&self) -> Result<PeerBook> {
+        let path = self.dir.join(PEERS_FILE);
+        if !path.exists() {
+            return Ok(PeerBook::default());
+        }
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn save_peers(&self, peers: &PeerBook) -> Result<()> {
+        let json =
+            serde_json::to_vec_pretty(peers).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&self.dir.join(PEERS_FILE), &json)
+    }
+
+    /// Loads the addresses the node refuses to talk to, none until one
+    /// misbehaves or is banned by hand.
+    pub fn load_bans(&self) -> Result<BanList> {
+        let path = self.dir.join(BANS_FILE);
+        if !path.exists() {
+            return Ok(BanList::default());
+        }
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn save_bans(&self, bans: &BanList) -> Result<()> {
+        let json =
+            serde_json::to_vec_pretty(bans).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        write_atomic(&self.dir.join(BANS_FILE), &json)
+    }
+
+    /// Opens the log of accepted transactions, creating it if needed.
+    pub fn open_mempool_wal(&self) -> Result<MempoolWal> {
+        MempoolWal::open(&self.dir.join(MEMPOOL_WAL_FILE))
+    }
+
+    fn wallet_path(&self, address: &Address) -> PathBuf {
+        self.dir.join(WALLETS_DIR).join(format!("{}.json", address))
+    }
+
+    fn sent_path(&self, address: &Address) -> PathBuf {
+        self.dir.join(SENT_DIR).join(format!("{}.json", address))
+    }
+
+    fn keystore_path(&self, address: &Address) -> PathBuf {
+        self.dir
+            .join(KEYSTORE_DIR)
+            .join(format!("{}.json", address))
+    }
+}
+
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)
+}
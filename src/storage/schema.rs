@@ -0,0 +1,250 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use crate::{
+    address::Address,
+    blockchain::{Block, BlockBody, BlockHeader, Snapshot, Transaction, Witness},
+    codec::{self, Decode, Encode, Reader},
+    wallet::StoredWallet,
+};
+
+/// Version of the on-disk layout this build reads and writes.
+pub const SCHEMA_VERSION: u32 = 3;
+/// Oldest layout [`migrate`] can still upgrade.
+pub const MIN_SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA_FILE: &str = "schema";
+
+/// A step upgrading a data directory from `from` to the next version.
+struct Migration {
+    from: u32,
+    description: &'static str,
+    apply: fn(&Path) -> Result<()>,
+}
+
+/// Every upgrade, in order.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "split blocks into a header carrying the Merkle root and a body",
+        apply: split_headers,
+    },
+    Migration {
+        from: 2,
+        description: "widen block nonces to 64 bits",
+        apply: widen_nonces,
+    },
+];
+
+/// Brings the data directory `dir` up to [`SCHEMA_VERSION`], applying every
+/// migration from the version it is stamped with, and stamps it. Fails,
+/// touching nothing, if it was written by a newer build or is too old to
+/// upgrade.
+///
+/// Directories from before versioning carry no stamp: an empty one is
+/// stamped as it is, and one holding a chain is taken to be at version 1
+/// unless the chain already reads as the current layout.
+pub fn migrate(dir: &Path) -> Result<()> {
+    let version = stored_version(dir)?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "{} was written by a newer build (schema {}, this one reads up to {})",
+                dir.display(),
+                version,
+                SCHEMA_VERSION
+            ),
+        ));
+    }
+    if version < MIN_SCHEMA_VERSION {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "{} has schema {}, older than the oldest this build upgrades ({})",
+                dir.display(),
+                version,
+                MIN_SCHEMA_VERSION
+            ),
+        ));
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+        (migration.apply)(dir).map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!(
+                    "migrating {} from schema {} ({}): {}",
+                    dir.display(),
+                    migration.from,
+                    migration.description,
+                    e
+                ),
+            )
+        })?;
+        stamp(dir, migration.from + 1)?;
+    }
+    stamp(dir, SCHEMA_VERSION)
+}
+
+fn stored_version(dir: &Path) -> Result<u32> {
+    let path = dir.join(SCHEMA_FILE);
+    if path.exists() {
+        return fs::read_to_string(&path)?.trim().parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("malformed schema version in {}", path.display()),
+            )
+        });
+    }
+    let chain = dir.join(super::CHAIN_FILE);
+    if chain.exists() && Snapshot::decode(&fs::read(&chain)?).is_err() {
+        return Ok(1);
+    }
+    #[cfg(feature = "sled")]
+    if super::kv::first_header(dir)?.is_some_and(|header| BlockHeader::decode(&header).is_err()) {
+        return Ok(1);
+    }
+    #[cfg(not(feature = "sled"))]
+    sled_unsupported(dir)?;
+    Ok(SCHEMA_VERSION)
+}
+
+fn stamp(dir: &Path, version: u32) -> Result<()> {
+    super::write_atomic(&dir.join(SCHEMA_FILE), version.to_string().as_bytes())
+}
+
+/// Version 1 blocks: the header fields inline, then the transactions, or
+/// the Merkle root they were pruned down to.
+struct BlockV1(Block);
+
+impl Decode for BlockV1 {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let nonce = i32::decode_from(reader)?;
+        let previous_hash = String::decode_from(reader)?;
+        let timestamp = i64::decode_from(reader)?;
+        let bits = u32::decode_from(reader)?;
+        let body = BlockBody::new(Vec::<Transaction>::decode_from(reader)?);
+        let miner = Address::decode_from(reader)?;
+        let witness = Witness::decode_from(reader)?;
+        let pruned_root = Option::<String>::decode_from(reader)?;
+        // The header layout is the same fields with the root in place of
+        // the transactions.
+        let mut header = vec![];
+        u64::from(nonce as u32).encode_to(&mut header);
+        previous_hash.encode_to(&mut header);
+        timestamp.encode_to(&mut header);
+        bits.encode_to(&mut header);
+        pruned_root
+            .clone()
+            .unwrap_or_else(|| body.merkle_root())
+            .encode_to(&mut header);
+        miner.encode_to(&mut header);
+        witness.encode_to(&mut header);
+        let header = BlockHeader::decode(&header)?;
+        Ok(BlockV1(match pruned_root {
+            Some(_) => Block::from_header(header),
+            None => Block::from_parts(header, body).ok_or_else(|| {
+                codec::Error::InvalidValue("transactions don't match the merkle root".into())
+            })?,
+        }))
+    }
+}
+
+/// Version 2 headers, whose nonce was 32 bits.
+struct HeaderV2(BlockHeader);
+
+impl Encode for HeaderV2 {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        // Only written for headers read from version 1, whose nonces fit.
+        out.extend(self.0.encode_narrow().unwrap_or_default());
+    }
+}
+
+impl Decode for HeaderV2 {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(HeaderV2(BlockHeader::decode_narrow(reader)?))
+    }
+}
+
+/// Version 2 blocks: a [`HeaderV2`], then the body unless it was pruned.
+struct BlockV2(Block);
+
+impl Encode for BlockV2 {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        HeaderV2(self.0.header().clone()).encode_to(out);
+        self.0.body().cloned().encode_to(out);
+    }
+}
+
+impl Decode for BlockV2 {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        let header = HeaderV2::decode_from(reader)?.0;
+        Ok(BlockV2(match Option::<BlockBody>::decode_from(reader)? {
+            Some(body) => Block::from_parts(header, body).ok_or_else(|| {
+                codec::Error::InvalidValue("transactions don't match the merkle root".into())
+            })?,
+            None => Block::from_header(header),
+        }))
+    }
+}
+
+fn split_headers(dir: &Path) -> Result<()> {
+    let chain = dir.join(super::CHAIN_FILE);
+    if chain.exists() {
+        let bytes = fs::read(&chain)?;
+        let mut reader = Reader::new(&bytes);
+        let wallet = StoredWallet::decode_from(&mut reader)?;
+        let blocks = Vec::<BlockV1>::decode_from(&mut reader)?;
+        let pool = Vec::<Transaction>::decode_from(&mut reader)?;
+        // Written as version 2, for the next migration to pick up.
+        let mut out = wallet.encode();
+        blocks
+            .into_iter()
+            .map(|b| BlockV2(b.0))
+            .collect::<Vec<_>>()
+            .encode_to(&mut out);
+        pool.encode_to(&mut out);
+        super::write_atomic(&chain, &out)?;
+    }
+    #[cfg(feature = "sled")]
+    super::kv::map_headers(dir, |header| {
+        Ok(HeaderV2(BlockV1::decode(header)?.0.header().clone()).encode())
+    })?;
+    #[cfg(not(feature = "sled"))]
+    sled_unsupported(dir)?;
+    Ok(())
+}
+
+fn widen_nonces(dir: &Path) -> Result<()> {
+    let chain = dir.join(super::CHAIN_FILE);
+    if chain.exists() {
+        let bytes = fs::read(&chain)?;
+        let mut reader = Reader::new(&bytes);
+        let wallet = StoredWallet::decode_from(&mut reader)?;
+        let blocks = Vec::<BlockV2>::decode_from(&mut reader)?;
+        let pool = Vec::<Transaction>::decode_from(&mut reader)?;
+        let snapshot = Snapshot::new(wallet, blocks.into_iter().map(|b| b.0).collect(), pool);
+        super::write_atomic(&chain, &snapshot.encode())?;
+    }
+    #[cfg(feature = "sled")]
+    super::kv::map_headers(dir, |header| Ok(HeaderV2::decode(header)?.0.encode()))?;
+    #[cfg(not(feature = "sled"))]
+    sled_unsupported(dir)?;
+    Ok(())
+}
+
+/// Fails if `dir` holds a chain database, which builds without the `sled`
+/// feature can't check or upgrade.
+#[cfg(not(feature = "sled"))]
+fn sled_unsupported(dir: &Path) -> Result<()> {
+    if dir.join("chain.sled").exists() {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "the chain database needs the sled feature to check its schema",
+        ));
+    }
+    Ok(())
+}
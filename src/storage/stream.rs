@@ -0,0 +1,135 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use super::SCHEMA_VERSION;
+use crate::{
+    blockchain::{Block, Snapshot},
+    codec::{Decode, Encode},
+    wallet::StoredWallet,
+};
+
+const MAGIC: &[u8; 8] = b"AEONBLKS";
+/// Largest encoded block or wallet accepted, so a corrupt length prefix
+/// can't make the reader allocate without bound.
+const MAX_ITEM_LEN: usize = 64 * 1024 * 1024;
+
+/// Writes the blocks of `snapshot` as a stream another node can import one
+/// block at a time, see [`BlockReader`]. The stream is the magic bytes and
+/// the schema version as a little-endian `u32`, then the faucet's key if
+/// `with_key` is set, as an encoded `Option`, and every block from genesis
+/// on, each encoded and prefixed with its length as a `u32`. Only a stream
+/// without the key is safe to publish; one with it is for cloning one's own
+/// nodes, like an archive. Returns the number of blocks written.
+///
+/// ```
+/// use aeonia::{
+///     blockchain::Blockchain,
+///     config::Config,
+///     storage::{write_blocks, BlockReader},
+/// };
+///
+/// let blockchain = Blockchain::with_config(&Config::default()).unwrap();
+/// let mut stream = vec![];
+/// write_blocks(&mut stream, &blockchain.snapshot().unwrap(), false).unwrap();
+/// let blocks = BlockReader::new(stream.as_slice()).unwrap();
+/// assert!(blocks.wallet().is_none());
+/// assert_eq!(blocks.count(), 1);
+/// ```
+pub fn write_blocks<W: Write>(
+    writer: &mut W,
+    snapshot: &Snapshot,
+    with_key: bool,
+) -> Result<usize> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&SCHEMA_VERSION.to_le_bytes())?;
+    let key = with_key.then(|| snapshot.wallet().clone());
+    write_item(writer, &key.encode())?;
+    for block in snapshot.chain() {
+        write_item(writer, &block.encode())?;
+    }
+    writer.flush()?;
+    Ok(snapshot.chain().len())
+}
+
+fn write_item<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+/// Reads a stream written by [`write_blocks`], yielding its blocks in order
+/// without holding more than one at a time.
+pub struct BlockReader<R> {
+    reader: R,
+    wallet: Option<StoredWallet>,
+}
+
+impl<R: Read> BlockReader<R> {
+    /// Reads the start of the stream, failing if it isn't a block stream or
+    /// was written with another schema.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| invalid("not a block stream"))?;
+        if &magic != MAGIC {
+            return Err(invalid("not a block stream"));
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != SCHEMA_VERSION {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "block stream has schema {}, this build reads {}",
+                    version, SCHEMA_VERSION
+                ),
+            ));
+        }
+        let wallet = match read_item(&mut reader)? {
+            Some(bytes) => Option::decode(&bytes)?,
+            None => return Err(invalid("block stream ends before the faucet's key")),
+        };
+        Ok(BlockReader { reader, wallet })
+    }
+
+    /// Key of the faucet the genesis block funds, if the stream was written
+    /// with it.
+    pub fn wallet(&self) -> Option<&StoredWallet> {
+        self.wallet.as_ref()
+    }
+}
+
+impl<R: Read> Iterator for BlockReader<R> {
+    type Item = Result<Block>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match read_item(&mut self.reader) {
+            Ok(Some(bytes)) => Some(Block::decode(&bytes).map_err(Error::from)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Reads one length-prefixed item, or `None` at the end of the stream.
+fn read_item<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_ITEM_LEN {
+        return Err(invalid("block stream item too large"));
+    }
+    let mut bytes = vec![0; len];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(|_| invalid("block stream cut short"))?;
+    Ok(Some(bytes))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, reason)
+}
@@ -0,0 +1,81 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Result, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    blockchain::Transaction,
+    codec::{Decode, Encode},
+};
+
+/// Append-only log of the transactions accepted into the pool, so those a
+/// node hadn't saved with its chain yet survive a crash. Each record is an
+/// encoded transaction after its length as a little-endian `u32`.
+/// [`MempoolWal::compact`] rewrites the log with just what is still pending.
+pub struct MempoolWal {
+    path: PathBuf,
+    file: File,
+}
+
+impl MempoolWal {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(MempoolWal {
+            path: path.to_path_buf(),
+            file: open_append(path)?,
+        })
+    }
+
+    /// Transactions in the log, oldest first. A record cut short by a crash
+    /// ends the log; compacting drops it.
+    pub fn replay(&self) -> Result<Vec<Transaction>> {
+        let mut bytes = vec![];
+        File::open(&self.path)?.read_to_end(&mut bytes)?;
+        let mut transactions = vec![];
+        let mut rest = bytes.as_slice();
+        while let Some((len, tail)) = rest.split_first_chunk::<4>() {
+            let len = u32::from_le_bytes(*len) as usize;
+            let Some(record) = tail.get(..len) else {
+                break;
+            };
+            transactions.push(Transaction::decode(record)?);
+            rest = &tail[len..];
+        }
+        Ok(transactions)
+    }
+
+    /// Adds `transaction` to the log, returning once it is on disk.
+    pub fn append(&mut self, transaction: &Transaction) -> Result<()> {
+        self.file.write_all(&record(transaction)?)?;
+        self.file.sync_data()
+    }
+
+    /// Replaces the log with `pending`, once blocks have confirmed or
+    /// expired some of what it holds.
+    pub fn compact(&mut self, pending: &[Transaction]) -> Result<()> {
+        let mut contents = vec![];
+        for transaction in pending {
+            contents.extend(record(transaction)?);
+        }
+        let tmp = self.path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&contents)?;
+        file.sync_data()?;
+        fs::rename(tmp, &self.path)?;
+        self.file = open_append(&self.path)?;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn record(transaction: &Transaction) -> Result<Vec<u8>> {
+    let body = transaction.encode();
+    let len = u32::try_from(body.len())
+        .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "transaction too large"))?;
+    let mut out = len.to_le_bytes().to_vec();
+    out.extend(body);
+    Ok(out)
+}
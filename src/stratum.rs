@@ -0,0 +1,616 @@
+//! A mining protocol after Stratum, letting worker processes on other
+//! machines lend their hash power to one node.
+//!
+//! Workers connect over TCP and exchange JSON objects, one per line, each a
+//! `method` with its `params`. A worker sends `subscribe` naming itself,
+//! and the node answers with a `notify` carrying a [`Job`], then a new one
+//! whenever the tip moves or the pool has had time to change. Each worker
+//! is handed a [`NonceRange`] of its own, searches it for the job's header
+//! and sends each nonce that meets the share target back in a `submit`,
+//! answered by a `result` or an `error`.
+//! Shares prove work without having to find blocks, so they are counted
+//! per worker and exported as metrics; the ones meeting the block target
+//! are signed with the node's miner wallet and connected.
+//!
+//! ```text
+//! → {"method":"subscribe","params":{"worker":"rig-1"}}
+//! ← {"method":"notify","params":{"job_id":7,"header":"00000000…","pow_hash":"sha256","bits":504365055,"share_bits":520159231,"nonce_range":{"start":0,"end":281474976710654},"clean":true}}
+//! → {"method":"submit","params":{"job_id":7,"nonce":1184}}
+//! ← {"method":"result","params":{"job_id":7,"nonce":1184,"block":null}}
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blockchain::{BlockHeader, HashAlgorithm, NonceRange, SharedBlockchain, Target},
+    codec,
+    wallet::Wallet,
+};
+
+/// How often the server checks for a new tip and for connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Jobs on the current tip kept open for late shares, oldest dropped first.
+const MAX_JOBS: usize = 8;
+/// Longest worker name accepted, which ends up as a metrics label.
+const MAX_WORKER_NAME: usize = 64;
+/// How long writing to a worker may block before it is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Nonces a worker tries between checks for a new job.
+const BATCH: u32 = 10_000;
+/// Parts the nonce space is cut into, one per worker, handed out again
+/// from the first once all are taken.
+const WORKER_RANGES: u64 = 1 << 16;
+
+#[derive(Clone, Debug)]
+pub struct StratumConfig {
+    /// Leading zero hex digits a share's hash needs. Shares never need more
+    /// than blocks do.
+    pub share_difficulty: u8,
+    /// Age after which a job is replaced even if the tip hasn't moved, so
+    /// new transactions make it into the work.
+    pub job_refresh: Duration,
+}
+
+impl Default for StratumConfig {
+    fn default() -> Self {
+        StratumConfig {
+            share_difficulty: 2,
+            job_refresh: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Work handed to workers: the header hashed for the proof of work, with
+/// its nonce left for the worker to fill in.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub job_id: u64,
+    /// [`BlockHeader::signing_bytes`] with a zero nonce. The low half of
+    /// the nonce goes in the first four bytes and the high half, unless
+    /// zero, after the last, both little-endian.
+    #[serde(with = "codec::as_hex")]
+    pub header: Vec<u8>,
+    pub pow_hash: HashAlgorithm,
+    /// Compact target a hash must meet to find a block.
+    pub bits: u32,
+    /// Compact target a hash must meet to count as a share.
+    pub share_bits: u32,
+    /// The nonces this worker searches; shares outside them are refused.
+    pub nonce_range: NonceRange,
+    /// Whether earlier jobs were for an old tip, so their shares no longer
+    /// count.
+    pub clean: bool,
+}
+
+impl Job {
+    /// The proof of work hash of the header with `nonce`, `None` if this
+    /// build lacks the job's hash.
+    pub fn hash(&self, nonce: u64) -> Option<[u8; 32]> {
+        let mut header = self.header.clone();
+        header
+            .get_mut(..4)?
+            .copy_from_slice(&(nonce as u32).to_le_bytes());
+        let high = (nonce >> 32) as u32;
+        if high != 0 {
+            header.extend(high.to_le_bytes());
+        }
+        Some(self.pow_hash.hasher()?.hash(&header))
+    }
+}
+
+/// What a worker sends.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Request {
+    Subscribe { worker: String },
+    Submit { job_id: u64, nonce: u64 },
+}
+
+/// What the node sends a worker.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Reply {
+    Notify(Job),
+    /// The share was accepted; `block` is the hash of the block it found,
+    /// if it met the block target and the block was connected.
+    Result {
+        job_id: u64,
+        nonce: u64,
+        block: Option<String>,
+    },
+    /// The request was refused, a submitted share with one of `stale_job`,
+    /// `out_of_range`, `duplicate_share` or `low_difficulty`.
+    Error {
+        code: String,
+        message: String,
+    },
+}
+
+/// Shares one worker has submitted since the node started.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkerShares {
+    pub accepted: u64,
+    pub rejected: u64,
+    /// Accepted shares that also found a block.
+    pub blocks: u64,
+}
+
+/// Share counts of every worker that has subscribed, by name.
+#[derive(Default)]
+pub struct ShareLedger {
+    workers: Mutex<BTreeMap<String, WorkerShares>>,
+}
+
+impl ShareLedger {
+    /// Counts per worker, in name order.
+    pub fn snapshot(&self) -> Vec<(String, WorkerShares)> {
+        self.lock()
+            .iter()
+            .map(|(worker, shares)| (worker.clone(), *shares))
+            .collect()
+    }
+
+    fn record(&self, worker: &str, update: impl FnOnce(&mut WorkerShares)) {
+        update(self.lock().entry(worker.to_string()).or_default());
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, WorkerShares>> {
+        self.workers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A job along with what the server needs to check shares for it.
+struct OpenJob {
+    job: Job,
+    header: BlockHeader,
+    submitted: HashSet<u64>,
+}
+
+struct Jobs {
+    next_id: u64,
+    tip: String,
+    refreshed: Instant,
+    open: VecDeque<OpenJob>,
+}
+
+struct State {
+    blockchain: SharedBlockchain,
+    miner: Wallet,
+    config: StratumConfig,
+    shares: Arc<ShareLedger>,
+    jobs: Mutex<Jobs>,
+    /// Every subscribed worker's connection, with the nonces it searches.
+    subscribers: Mutex<Vec<(TcpStream, NonceRange)>>,
+    next_range: AtomicU64,
+    blocks_found: AtomicU64,
+    stop: AtomicBool,
+}
+
+/// Serves [`Job`]s to workers on background threads until stopped, paying
+/// the blocks they find to `miner`.
+pub struct StratumServer;
+
+impl StratumServer {
+    /// Listens on `address`, counting shares in `shares`. Fails if the
+    /// chain's engine takes no proof of work.
+    pub fn start(
+        address: &str,
+        blockchain: SharedBlockchain,
+        miner: Wallet,
+        config: StratumConfig,
+        shares: Arc<ShareLedger>,
+    ) -> io::Result<StratumHandle> {
+        let state = Arc::new(State {
+            blockchain,
+            miner,
+            config,
+            shares,
+            jobs: Mutex::new(Jobs {
+                next_id: 0,
+                tip: String::new(),
+                refreshed: Instant::now(),
+                open: VecDeque::new(),
+            }),
+            subscribers: Mutex::new(vec![]),
+            next_range: AtomicU64::new(0),
+            blocks_found: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+        });
+        state.refresh()?;
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let thread = {
+            let state = state.clone();
+            thread::spawn(move || {
+                let mut last_poll = Instant::now();
+                while !state.stop.load(Ordering::Acquire) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let state = state.clone();
+                            thread::spawn(move || state.serve(stream));
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::park_timeout(POLL_INTERVAL);
+                        }
+                        Err(_) => break,
+                    }
+                    if last_poll.elapsed() >= POLL_INTERVAL {
+                        // A failed template is retried on the next poll.
+                        let _ = state.refresh_if_stale();
+                        last_poll = Instant::now();
+                    }
+                }
+            })
+        };
+        Ok(StratumHandle {
+            local_addr,
+            state,
+            thread,
+        })
+    }
+}
+
+impl State {
+    fn jobs(&self) -> MutexGuard<'_, Jobs> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn refresh_if_stale(&self) -> io::Result<()> {
+        let tip = self
+            .blockchain
+            .last_block()
+            .map(|block| block.hash())
+            .unwrap_or_default();
+        let jobs = self.jobs();
+        if jobs.tip == tip && jobs.refreshed.elapsed() < self.config.job_refresh {
+            return Ok(());
+        }
+        drop(jobs);
+        self.refresh()
+    }
+
+    /// Opens a job on a fresh template and sends it to every subscriber.
+    fn refresh(&self) -> io::Result<()> {
+        let template = self.blockchain.get_block_template(self.miner.address())?;
+        let (Some(pow_hash), Some(target)) = (template.pow_hash, template.target()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "stratum needs a proof of work engine",
+            ));
+        };
+        let share_target = Target::from_zero_digits(self.config.share_difficulty).max(target);
+        let header = template.header;
+        let mut jobs = self.jobs();
+        let clean = jobs.tip != *header.previous_hash();
+        if clean {
+            jobs.open.clear();
+            jobs.tip = header.previous_hash().clone();
+        }
+        if jobs.open.len() >= MAX_JOBS {
+            jobs.open.pop_front();
+        }
+        jobs.next_id += 1;
+        jobs.refreshed = Instant::now();
+        let job = Job {
+            job_id: jobs.next_id,
+            header: header.signing_bytes(),
+            pow_hash,
+            bits: header.bits(),
+            share_bits: share_target.to_compact(),
+            nonce_range: NonceRange::FULL,
+            clean,
+        };
+        jobs.open.push_back(OpenJob {
+            job: job.clone(),
+            header,
+            submitted: HashSet::new(),
+        });
+        drop(jobs);
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut notified = vec![];
+        for (mut subscriber, nonce_range) in subscribers.drain(..) {
+            let line = reply_line(&Reply::Notify(Job {
+                nonce_range,
+                ..job.clone()
+            }))?;
+            if subscriber.write_all(&line).is_ok() {
+                notified.push((subscriber, nonce_range));
+            }
+        }
+        *subscribers = notified;
+        Ok(())
+    }
+
+    /// Answers one worker until it disconnects or the server stops.
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut worker: Option<(String, NonceRange)> = None;
+        let mut line = vec![];
+        while !self.stop.load(Ordering::Acquire) {
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e),
+            }
+            let reply = match serde_json::from_slice(&line) {
+                Ok(Request::Subscribe { worker: name }) => {
+                    self.subscribe(name, &writer, &mut worker)
+                }
+                Ok(Request::Submit { job_id, nonce }) => match &worker {
+                    Some((worker, range)) => self.submit(worker, *range, job_id, nonce),
+                    None => error("not_subscribed", "subscribe before submitting"),
+                },
+                Err(e) => error("invalid_request", &e.to_string()),
+            };
+            line.clear();
+            writer.write_all(&reply_line(&reply)?)?;
+        }
+        Ok(())
+    }
+
+    fn subscribe(
+        &self,
+        name: String,
+        writer: &TcpStream,
+        worker: &mut Option<(String, NonceRange)>,
+    ) -> Reply {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_WORKER_NAME
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+        if !valid {
+            return error(
+                "invalid_worker",
+                "worker names are 1 to 64 letters, digits, '.', '-' or '_'",
+            );
+        }
+        let nonce_range = match worker.take() {
+            Some((_, nonce_range)) => nonce_range,
+            None => {
+                let index = self.next_range.fetch_add(1, Ordering::AcqRel) % WORKER_RANGES;
+                let nonce_range =
+                    NonceRange::part(index, WORKER_RANGES).unwrap_or(NonceRange::FULL);
+                if let Ok(subscriber) = writer.try_clone() {
+                    self.subscribers
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push((subscriber, nonce_range));
+                }
+                nonce_range
+            }
+        };
+        self.shares.record(&name, |_| {});
+        *worker = Some((name, nonce_range));
+        let jobs = self.jobs();
+        match jobs.open.back() {
+            Some(open) => Reply::Notify(Job {
+                nonce_range,
+                clean: true,
+                ..open.job.clone()
+            }),
+            None => error("no_job", "no job is open yet"),
+        }
+    }
+
+    fn submit(&self, worker: &str, nonce_range: NonceRange, job_id: u64, nonce: u64) -> Reply {
+        let checked = match nonce_range.contains(nonce) {
+            true => self.check_share(job_id, nonce),
+            false => Err(error(
+                "out_of_range",
+                "the nonce is outside the worker's range",
+            )),
+        };
+        let header = match checked {
+            Ok(header) => header,
+            Err(reply) => {
+                self.shares.record(worker, |shares| shares.rejected += 1);
+                return reply;
+            }
+        };
+        let block = header.and_then(|mut header| {
+            self.miner.sign_header(&mut header).ok()?;
+            let hash = header.hash();
+            self.blockchain.submit_block(header, 0).ok()?;
+            Some(hash)
+        });
+        self.shares.record(worker, |shares| {
+            shares.accepted += 1;
+            shares.blocks += u64::from(block.is_some());
+        });
+        if block.is_some() {
+            self.blocks_found.fetch_add(1, Ordering::AcqRel);
+            let _ = self.refresh();
+        }
+        Reply::Result {
+            job_id,
+            nonce,
+            block,
+        }
+    }
+
+    /// Checks a share, returning the header with its nonce if it also
+    /// meets the block target.
+    fn check_share(&self, job_id: u64, nonce: u64) -> Result<Option<BlockHeader>, Reply> {
+        let mut jobs = self.jobs();
+        let Some(open) = jobs.open.iter_mut().find(|open| open.job.job_id == job_id) else {
+            return Err(error("stale_job", "the job is no longer open"));
+        };
+        if !open.submitted.insert(nonce) {
+            return Err(error("duplicate_share", "the nonce was already submitted"));
+        }
+        let hash = open.job.hash(nonce).unwrap_or([0xff; 32]);
+        let meets = |bits| Target::from_compact(bits).is_some_and(|t| t.is_met_by(&hash));
+        if !meets(open.job.share_bits) {
+            return Err(error(
+                "low_difficulty",
+                "the hash doesn't meet the share target",
+            ));
+        }
+        if !meets(open.job.bits) {
+            return Ok(None);
+        }
+        let mut header = open.header.clone();
+        header.set_nonce(nonce);
+        Ok(Some(header))
+    }
+}
+
+fn error(code: &str, message: &str) -> Reply {
+    Reply::Error {
+        code: code.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn reply_line(reply: &Reply) -> io::Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(reply).map_err(io::Error::other)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+pub struct StratumHandle {
+    local_addr: SocketAddr,
+    state: Arc<State>,
+    thread: JoinHandle<()>,
+}
+
+impl StratumHandle {
+    /// Address the server is bound to, useful when listening on port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Blocks workers have found and the node connected.
+    pub fn blocks_found(&self) -> u64 {
+        self.state.blocks_found.load(Ordering::Acquire)
+    }
+
+    pub fn stop(self) {
+        self.state.stop.store(true, Ordering::Release);
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+    }
+}
+
+/// Works for the node serving stratum at `address` as `worker`, searching
+/// the nonce range of each job and submitting every share found, until the
+/// connection drops. `on_reply` sees each reply of the node.
+pub fn work(address: &str, worker: &str, mut on_reply: impl FnMut(&Reply)) -> io::Result<()> {
+    let stream = TcpStream::connect(address)?;
+    let mut writer = stream.try_clone()?;
+    let send = |writer: &mut TcpStream, request: &Request| -> io::Result<()> {
+        let mut line = serde_json::to_vec(request).map_err(io::Error::other)?;
+        line.push(b'\n');
+        writer.write_all(&line)
+    };
+    send(
+        &mut writer,
+        &Request::Subscribe {
+            worker: worker.to_string(),
+        },
+    )?;
+    let replies = read_replies(stream);
+    // The job, its share target and the next nonce to try, `None` once
+    // the worker's range is used up.
+    let mut job: Option<(Job, Target, Option<u64>)> = None;
+    loop {
+        let reply = match &job {
+            Some((_, _, Some(_))) => replies.try_recv().map_err(|e| match e {
+                mpsc::TryRecvError::Empty => RecvTimeoutError::Timeout,
+                mpsc::TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+            }),
+            _ => replies.recv_timeout(POLL_INTERVAL),
+        };
+        match reply {
+            Ok(reply) => {
+                if let Reply::Notify(notified) = &reply {
+                    let share_target = Target::from_compact(notified.share_bits)
+                        .ok_or_else(|| io::Error::other("job has an invalid share target"))?;
+                    if notified.pow_hash.hasher().is_none() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            format!("this build can't compute {}", notified.pow_hash),
+                        ));
+                    }
+                    let start = notified.nonce_range.start;
+                    job = Some((notified.clone(), share_target, Some(start)));
+                }
+                on_reply(&reply);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        let Some((job, share_target, next)) = &mut job else {
+            continue;
+        };
+        for _ in 0..BATCH {
+            let Some(nonce) = *next else {
+                break;
+            };
+            *next = (nonce < job.nonce_range.end).then(|| nonce + 1);
+            if job
+                .hash(nonce)
+                .is_some_and(|hash| share_target.is_met_by(&hash))
+            {
+                send(
+                    &mut writer,
+                    &Request::Submit {
+                        job_id: job.job_id,
+                        nonce,
+                    },
+                )?;
+                // A share may have found a block, so see whether the job
+                // moved on before searching further.
+                break;
+            }
+        }
+    }
+}
+
+/// Replies read off `stream` on a thread of their own, so the search isn't
+/// held up waiting for them.
+fn read_replies(stream: TcpStream) -> Receiver<Reply> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let Ok(reply) = serde_json::from_str(&line) else {
+                continue;
+            };
+            if sender.send(reply).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
@@ -0,0 +1,122 @@
+//! Helpers for building chains that come out the same on every run: keys are
+//! drawn from seeded RNGs and time only moves when a block is mined.
+//!
+//! ```
+//! use aeonia::testing::{self, TestChain};
+//!
+//! let miner = testing::wallet(1);
+//! let mut a = TestChain::new(7);
+//! let mut b = TestChain::new(7);
+//! a.mine_blocks(&miner, 3);
+//! b.mine_blocks(&miner, 3);
+//! assert_eq!(
+//!     a.blockchain.last_block().unwrap().hash(),
+//!     b.blockchain.last_block().unwrap().hash(),
+//! );
+//! ```
+
+use std::{sync::Arc, time::Duration};
+
+use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+use crate::{
+    address::Address,
+    blockchain::{Block, Blockchain, FaucetConfig, Result, Transaction},
+    clock::{Clock, ManualClock},
+    config::Config,
+    wallet::Wallet,
+};
+
+/// Time of the genesis block of every test chain: 2024-01-01T00:00:00Z.
+pub const GENESIS_TIME: i64 = 1_704_067_200_000_000_000;
+/// How far the clock moves for each block [`TestChain::mine`] adds.
+pub const BLOCK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// RNG that always produces the same values for the same `seed`.
+pub fn rng(seed: u64) -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(seed)
+}
+
+/// The same wallet on every call with the same `seed`.
+pub fn wallet(seed: u64) -> Wallet {
+    Wallet::from_rng(&mut rng(seed), 0).unwrap()
+}
+
+/// Default settings with the difficulty lowered so blocks mine instantly.
+pub fn config() -> Config {
+    let mut config = Config::default();
+    config.consensus.difficulty = 1;
+    config
+}
+
+/// A chain along with the clock it runs on.
+pub struct TestChain {
+    pub blockchain: Blockchain,
+    pub clock: Arc<ManualClock>,
+}
+
+impl TestChain {
+    /// Chain with [`config`] whose keys come from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self::with_config(seed, &config())
+    }
+
+    pub fn with_config(seed: u64, config: &Config) -> Self {
+        let clock = Arc::new(ManualClock::new(GENESIS_TIME));
+        let blockchain = Blockchain::with_clock_and_rng(
+            config,
+            FaucetConfig::default(),
+            clock.clone(),
+            &mut rng(seed),
+        )
+        .unwrap();
+        TestChain { blockchain, clock }
+    }
+
+    /// Moves the clock on by [`BLOCK_INTERVAL`] and mines a block of the
+    /// pending transactions paying `miner`.
+    pub fn mine(&mut self, miner: &Wallet) -> Arc<Block> {
+        self.clock.advance(BLOCK_INTERVAL);
+        assert!(self.blockchain.mining(miner), "mining failed");
+        self.blockchain.last_block().unwrap()
+    }
+
+    pub fn mine_blocks(&mut self, miner: &Wallet, count: usize) {
+        for _ in 0..count {
+            self.mine(miner);
+        }
+    }
+
+    /// Sends `amount` from the faucet to `recipient` and mines it.
+    pub fn fund(&mut self, recipient: &Address, amount: f64, miner: &Wallet) -> Transaction {
+        let transaction = self
+            .blockchain
+            .request_from_faucet(recipient, amount)
+            .unwrap();
+        self.mine(miner);
+        transaction
+    }
+
+    /// Signs a payment from `sender` with its next sequence number and adds
+    /// it to the pool.
+    pub fn pay(
+        &mut self,
+        sender: &mut Wallet,
+        recipient: &Address,
+        amount: f64,
+        fee: f64,
+    ) -> Result<Transaction> {
+        let sequence = self.blockchain.next_sequence(sender.address())?;
+        let inputs = self
+            .blockchain
+            .select_inputs(sender.address(), amount + fee)?;
+        let transaction = sender
+            .sign_transaction(recipient, amount, fee, sequence, inputs, vec![])
+            .unwrap();
+        self.blockchain.add_transation_to_pool(transaction)
+    }
+
+    pub fn now(&self) -> i64 {
+        self.clock.now()
+    }
+}
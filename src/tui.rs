@@ -0,0 +1,226 @@
+use std::{
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use aeonia::{
+    address::Address,
+    blockchain::{Block, Blockchain, Transaction},
+    config::Config,
+    storage::FileStore,
+};
+use chrono::DateTime;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block as Panel, Borders, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+/// Characters of a hash shown in lists.
+const SHORT_HASH: usize = 12;
+
+/// What the explorer last read from the data directory.
+struct ChainView {
+    blocks: Vec<Arc<Block>>,
+    total_work: u128,
+    pending: Vec<Transaction>,
+    balances: Vec<(Address, f64)>,
+}
+
+impl ChainView {
+    fn load(store: &FileStore, config: &Config, watched: &[Address]) -> io::Result<Self> {
+        let mut blockchain = store.load_blockchain(config)?;
+        let height = blockchain.chain_height()?;
+        let blocks = (0..=height)
+            .rev()
+            .filter_map(|h| blockchain.block_by_height(h))
+            .collect();
+        let pending = blockchain.pending_transactions();
+        let mut addresses = store.wallet_addresses()?;
+        addresses.extend_from_slice(watched);
+        addresses.sort();
+        addresses.dedup();
+        let balances = addresses
+            .into_iter()
+            .map(|address| Ok((address, balance(&mut blockchain, &address)?)))
+            .collect::<io::Result<_>>()?;
+        Ok(ChainView {
+            blocks,
+            total_work: blockchain.total_work(),
+            pending,
+            balances,
+        })
+    }
+}
+
+fn balance(blockchain: &mut Blockchain, address: &Address) -> io::Result<f64> {
+    Ok(blockchain.calculate_transactions_total(address)?)
+}
+
+/// Interactive view of the chain kept in a data directory, reloaded every
+/// `refresh` so it follows a node running against the same directory.
+pub struct Explorer<'a> {
+    store: &'a FileStore,
+    config: &'a Config,
+    watched: Vec<Address>,
+    refresh: Duration,
+    selected: ListState,
+}
+
+impl<'a> Explorer<'a> {
+    pub fn new(
+        store: &'a FileStore,
+        config: &'a Config,
+        watched: Vec<Address>,
+        refresh: Duration,
+    ) -> Self {
+        Explorer {
+            store,
+            config,
+            watched,
+            refresh,
+            selected: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    pub fn run(mut self) -> io::Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.event_loop(&mut terminal);
+        ratatui::restore();
+        result
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut view = ChainView::load(self.store, self.config, &self.watched)?;
+        let mut loaded_at = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame, &view))?;
+            let timeout = self.refresh.saturating_sub(loaded_at.elapsed());
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Down | KeyCode::Char('j') => self.selected.select_next(),
+                        KeyCode::Up | KeyCode::Char('k') => self.selected.select_previous(),
+                        KeyCode::Home | KeyCode::Char('g') => self.selected.select_first(),
+                        _ => {}
+                    }
+                }
+            }
+            if loaded_at.elapsed() >= self.refresh {
+                view = ChainView::load(self.store, self.config, &self.watched)?;
+                loaded_at = Instant::now();
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, view: &ChainView) {
+        let [header, body] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(frame.area());
+        let [blocks, details] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(body);
+        let [transactions, pending, balances] = Layout::vertical([
+            Constraint::Percentage(40),
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+        ])
+        .areas(details);
+
+        let tip = view.blocks.first();
+        let summary = format!(
+            "height {}  work {}  tip {}  pending {}    q quit  j/k select block",
+            view.blocks.len().saturating_sub(1),
+            view.total_work,
+            tip.map(|b| short(&b.hash())).unwrap_or_default(),
+            view.pending.len(),
+        );
+        frame.render_widget(
+            Paragraph::new(summary).block(panel(" aeonia explorer ")),
+            header,
+        );
+
+        let height = view.blocks.len().saturating_sub(1);
+        let items: Vec<ListItem> = view
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                ListItem::new(format!(
+                    "{:>6}  {}  {:>3} tx  work {}  {}  {}",
+                    height - i,
+                    short(&block.hash()),
+                    block.transactions().len(),
+                    block.work(),
+                    timestamp(block.timestamp()),
+                    block.miner(),
+                ))
+            })
+            .collect();
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(panel(" blocks "))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            blocks,
+            &mut self.selected,
+        );
+
+        let selected = self
+            .selected
+            .selected()
+            .and_then(|i| view.blocks.get(i))
+            .map(|block| block.transactions().as_slice())
+            .unwrap_or_default();
+        render_transactions(frame, " block transactions ", selected, transactions);
+        render_transactions(frame, " mempool ", &view.pending, pending);
+
+        let lines: Vec<Line> = view
+            .balances
+            .iter()
+            .map(|(address, balance)| Line::from(format!("{}  {}", address, balance)))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(panel(" balances ")), balances);
+    }
+}
+
+fn render_transactions(frame: &mut Frame, title: &str, transactions: &[Transaction], area: Rect) {
+    let items: Vec<ListItem> = transactions
+        .iter()
+        .map(|transaction| {
+            let sender = match transaction.sender() {
+                Some(sender) => sender.to_string(),
+                None => "coinbase".to_string(),
+            };
+            ListItem::new(format!(
+                "{}  {} -> {}  {} (fee {})",
+                short(&transaction.id().to_string()),
+                sender,
+                transaction.recipient,
+                transaction.amount,
+                transaction.fee,
+            ))
+        })
+        .collect();
+    frame.render_widget(List::new(items).block(panel(title)), area);
+}
+
+fn panel(title: &str) -> Panel<'_> {
+    Panel::default().borders(Borders::ALL).title(title)
+}
+
+fn short(hash: &str) -> String {
+    hash.chars().take(SHORT_HASH).collect()
+}
+
+fn timestamp(nanos: i64) -> String {
+    DateTime::from_timestamp_nanos(nanos)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Error, Result};
+use crate::address::Address;
+
+/// Named addresses, so payments can be sent to a contact rather than a
+/// pasted Base58 string.
+///
+/// ```
+/// use aeonia::{address::Address, wallet::AddressBook};
+///
+/// let alice: Address = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".parse().unwrap();
+/// let mut book = AddressBook::default();
+/// book.add("alice", alice).unwrap();
+/// assert_eq!(book.resolve("alice").unwrap(), alice);
+/// assert_eq!(book.resolve(&alice.to_string()).unwrap(), alice);
+/// assert!(book.resolve("bob").is_err());
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AddressBook {
+    contacts: BTreeMap<String, Address>,
+}
+
+impl AddressBook {
+    /// Adds a contact. Names must be non-empty, free of whitespace and not
+    /// themselves valid addresses, so resolving is never ambiguous.
+    pub fn add(&mut self, name: &str, address: Address) -> Result<()> {
+        if name.is_empty()
+            || name.chars().any(char::is_whitespace)
+            || name.parse::<Address>().is_ok()
+        {
+            return Err(Error::InvalidContactName {
+                name: name.to_string(),
+            });
+        }
+        if self.contacts.contains_key(name) {
+            return Err(Error::DuplicateContact {
+                name: name.to_string(),
+            });
+        }
+        self.contacts.insert(name.to_string(), address);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<Address> {
+        self.contacts
+            .remove(name)
+            .ok_or_else(|| Error::UnknownContact {
+                name: name.to_string(),
+            })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Address> {
+        self.contacts.get(name)
+    }
+
+    /// Contacts in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Address)> {
+        self.contacts
+            .iter()
+            .map(|(name, address)| (name.as_str(), address))
+    }
+
+    /// Reads `name_or_address` as an address, falling back to looking it up
+    /// as a contact name.
+    pub fn resolve(&self, name_or_address: &str) -> Result<Address> {
+        if let Ok(address) = name_or_address.parse() {
+            return Ok(address);
+        }
+        self.get(name_or_address)
+            .copied()
+            .ok_or_else(|| Error::UnknownContact {
+                name: name_or_address.to_string(),
+            })
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{EncryptedWallet, Error, Result, Signer};
+use crate::{
+    address::Address,
+    codec,
+    crypto::{PublicKey, Signature},
+};
+
+/// How often an idle unlocked daemon checks whether to lock itself.
+const EXPIRY_CHECK: Duration = Duration::from_secs(1);
+
+/// Request to a [`WalletDaemon`], sent as one line of JSON.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    /// Decrypts the key, keeping it in memory until `timeout_secs` pass
+    /// without a signature being asked for.
+    Unlock {
+        passphrase: String,
+        timeout_secs: u64,
+    },
+    /// Forgets the decrypted key.
+    Lock,
+    Status,
+    /// Signs `message`, which needs the daemon unlocked.
+    Sign {
+        #[serde(with = "codec::as_hex")]
+        message: Vec<u8>,
+    },
+}
+
+/// Reply of a [`WalletDaemon`], one line of JSON per request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DaemonResponse {
+    /// Reply to everything but [`DaemonRequest::Sign`].
+    Status {
+        address: Address,
+        #[serde(with = "codec::as_hex")]
+        public_key: PublicKey,
+        /// Seconds of inactivity left before the key is locked again,
+        /// `None` while locked.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        locks_in_secs: Option<u64>,
+    },
+    Signature {
+        #[serde(with = "codec::as_hex")]
+        signature: Signature,
+    },
+    /// The request failed; `code` is one of [`Error::code`].
+    Error { code: String, message: String },
+}
+
+enum State {
+    Locked,
+    Unlocked {
+        signer: Arc<dyn Signer>,
+        timeout: Duration,
+        last_used: Instant,
+    },
+}
+
+/// Long-running holder of one wallet whose key stays encrypted until a
+/// client unlocks it with its passphrase, and is dropped again after a
+/// spell without signatures or on request. Clients reach it over a Unix
+/// socket only its owner can open, so a node signs blocks through a
+/// [`DaemonSigner`] without ever holding the key itself.
+pub struct WalletDaemon {
+    keystore: EncryptedWallet,
+    state: Mutex<State>,
+}
+
+impl WalletDaemon {
+    /// A daemon for `keystore`, locked.
+    pub fn new(keystore: EncryptedWallet) -> Self {
+        WalletDaemon {
+            keystore,
+            state: Mutex::new(State::Locked),
+        }
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.keystore.address
+    }
+
+    /// Answers `request`, locking first if the unlock timed out.
+    pub fn handle(&self, request: DaemonRequest) -> DaemonResponse {
+        let result = match request {
+            DaemonRequest::Unlock {
+                passphrase,
+                timeout_secs,
+            } => self.unlock(&passphrase, Duration::from_secs(timeout_secs)),
+            DaemonRequest::Lock => {
+                *self.state() = State::Locked;
+                Ok(self.status())
+            }
+            DaemonRequest::Status => Ok(self.status()),
+            DaemonRequest::Sign { message } => self.sign(&message),
+        };
+        result.unwrap_or_else(|e| DaemonResponse::Error {
+            code: e.code().to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    fn unlock(&self, passphrase: &str, timeout: Duration) -> Result<DaemonResponse> {
+        let signer = Arc::clone(&self.keystore.decrypt(passphrase)?.signer);
+        *self.state() = State::Unlocked {
+            signer,
+            timeout,
+            last_used: Instant::now(),
+        };
+        Ok(self.status())
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<DaemonResponse> {
+        let mut state = self.state();
+        let State::Unlocked {
+            signer, last_used, ..
+        } = &mut *state
+        else {
+            return Err(Error::Locked);
+        };
+        *last_used = Instant::now();
+        Ok(DaemonResponse::Signature {
+            signature: signer.sign(message)?,
+        })
+    }
+
+    fn status(&self) -> DaemonResponse {
+        let locks_in_secs = match &*self.state() {
+            State::Locked => None,
+            State::Unlocked {
+                timeout, last_used, ..
+            } => {
+                let left = timeout.saturating_sub(last_used.elapsed());
+                Some(left.as_secs() + u64::from(left.subsec_nanos() > 0))
+            }
+        };
+        DaemonResponse::Status {
+            address: self.keystore.address,
+            public_key: self.keystore.public_key,
+            locks_in_secs,
+        }
+    }
+
+    /// The state, locked first if the key has gone unused for longer than
+    /// it was unlocked for.
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let State::Unlocked {
+            timeout, last_used, ..
+        } = &*state
+        {
+            if last_used.elapsed() >= *timeout {
+                *state = State::Locked;
+            }
+        }
+        state
+    }
+
+    /// Listens on a Unix socket at `path` until the process exits, serving
+    /// each connection on its own thread. A stale socket left by a daemon
+    /// that died is replaced; a live one is an error.
+    pub fn serve(self: Arc<Self>, path: &Path) -> io::Result<()> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("a wallet daemon is already listening on {}", path.display()),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        let daemon = Arc::clone(&self);
+        thread::spawn(move || loop {
+            thread::sleep(EXPIRY_CHECK);
+            drop(daemon.state());
+        });
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let daemon = Arc::clone(&self);
+            thread::spawn(move || daemon.serve_client(stream));
+        }
+        Ok(())
+    }
+
+    fn serve_client(&self, stream: UnixStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let response = match serde_json::from_str(&line?) {
+                Ok(request) => self.handle(request),
+                Err(e) => DaemonResponse::Error {
+                    code: "invalid_request".to_string(),
+                    message: e.to_string(),
+                },
+            };
+            let mut json = serde_json::to_vec(&response).map_err(io::Error::other)?;
+            json.push(b'\n');
+            writer.write_all(&json)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends `request` to the daemon listening at `socket` and returns its
+/// reply, failing with the daemon's error if it refused.
+pub fn call_daemon(socket: &Path, request: &DaemonRequest) -> Result<DaemonResponse> {
+    let unreachable = |e: &dyn std::fmt::Display| {
+        Error::Signer(format!("wallet daemon at {}: {}", socket.display(), e))
+    };
+    let mut stream = UnixStream::connect(socket).map_err(|e| unreachable(&e))?;
+    let mut json = serde_json::to_vec(request).map_err(|e| unreachable(&e))?;
+    json.push(b'\n');
+    stream.write_all(&json).map_err(|e| unreachable(&e))?;
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| unreachable(&e))?;
+    match serde_json::from_str(&line).map_err(|e| unreachable(&e))? {
+        DaemonResponse::Error { code, message } => Err(match code.as_str() {
+            "wallet_locked" => Error::Locked,
+            "wrong_passphrase" => Error::WrongPassphrase,
+            _ => Error::Signer(message),
+        }),
+        response => Ok(response),
+    }
+}
+
+/// [`Signer`] asking a [`WalletDaemon`] for every signature, which fails
+/// with [`Error::Locked`] while the daemon is locked.
+pub struct DaemonSigner {
+    socket: PathBuf,
+    public_key: PublicKey,
+}
+
+impl DaemonSigner {
+    /// Signer for the wallet of the daemon listening at `socket`, which
+    /// may still be locked.
+    pub fn connect(socket: &Path) -> Result<Self> {
+        match call_daemon(socket, &DaemonRequest::Status)? {
+            DaemonResponse::Status { public_key, .. } => Ok(DaemonSigner {
+                socket: socket.to_path_buf(),
+                public_key,
+            }),
+            response => Err(unexpected(&response)),
+        }
+    }
+}
+
+impl Signer for DaemonSigner {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Signature> {
+        let request = DaemonRequest::Sign {
+            message: message.to_vec(),
+        };
+        match call_daemon(&self.socket, &request)? {
+            DaemonResponse::Signature { signature } => Ok(signature),
+            response => Err(unexpected(&response)),
+        }
+    }
+}
+
+fn unexpected(response: &DaemonResponse) -> Error {
+    Error::Signer(format!(
+        "unexpected reply from wallet daemon: {:?}",
+        response
+    ))
+}
@@ -0,0 +1,105 @@
+use hmac::{Hmac, Mac};
+use p256::{
+    elliptic_curve::{sec1::ToEncodedPoint, PrimeField},
+    FieldBytes, NonZeroScalar, Scalar, SecretKey,
+};
+use sha2::Sha512;
+
+use super::Result;
+use crate::crypto;
+
+/// Offset added to an index to request hardened derivation.
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// SLIP-44 coin type shared by every test network.
+const COIN_TYPE: u32 = 1;
+
+const CURVE_SEED_KEY: &[u8] = b"Nist256p1 seed";
+
+/// Extended private key: a P-256 secret and the chain code needed to derive
+/// its children.
+///
+/// Derivation follows SLIP-10, which carries BIP-32 over to NIST P-256.
+pub struct ExtendedKey {
+    secret: SecretKey,
+    chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    pub fn from_seed(seed: &[u8]) -> Result<Self> {
+        let mut output = hmac_sha512(CURVE_SEED_KEY, seed)?;
+        loop {
+            let (key, chain_code) = output.split_at(32);
+            if let Ok(secret) = SecretKey::from_be_bytes(key) {
+                return Ok(ExtendedKey {
+                    secret,
+                    chain_code: chain_code.try_into().expect("32 byte chain code"),
+                });
+            }
+            output = hmac_sha512(CURVE_SEED_KEY, &output)?;
+        }
+    }
+
+    /// Derives the child at `index`; indices at or above [`HARDENED`] use
+    /// hardened derivation.
+    pub fn derive_child(&self, index: u32) -> Result<Self> {
+        let mut data = if index >= HARDENED {
+            [&[0u8][..], &self.secret.to_be_bytes()].concat()
+        } else {
+            self.secret
+                .public_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec()
+        };
+        data.extend_from_slice(&index.to_be_bytes());
+        let parent = *self.secret.to_nonzero_scalar();
+        loop {
+            let output = hmac_sha512(&self.chain_code, &data)?;
+            let (tweak, chain_code) = output.split_at(32);
+            let tweak = Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(tweak)));
+            let child =
+                tweak.and_then(|t| Option::<NonZeroScalar>::from(NonZeroScalar::new(t + parent)));
+            if let Some(child) = child {
+                return Ok(ExtendedKey {
+                    secret: SecretKey::from(child),
+                    chain_code: chain_code.try_into().expect("32 byte chain code"),
+                });
+            }
+            data = [&[1u8][..], chain_code, &index.to_be_bytes()].concat();
+        }
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self> {
+        path.iter().try_fold(
+            ExtendedKey {
+                secret: self.secret.clone(),
+                chain_code: self.chain_code,
+            },
+            |key, index| key.derive_child(*index),
+        )
+    }
+
+    pub fn secret(&self) -> &SecretKey {
+        &self.secret
+    }
+}
+
+/// BIP-44 path of the first receiving key of `account`:
+/// `m/44'/1'/account'/0/0`.
+pub fn account_path(account: u32) -> [u32; 5] {
+    [
+        44 + HARDENED,
+        COIN_TYPE + HARDENED,
+        account + HARDENED,
+        0,
+        0,
+    ]
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> Result<[u8; 64]> {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key)
+        .map_err(|e| crypto::Error::InvalidKey(e.to_string()))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().into())
+}
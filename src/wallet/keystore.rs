@@ -0,0 +1,143 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use p256::elliptic_curve::zeroize::Zeroizing;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::{Error, Result, StoredWallet, Wallet};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode},
+    crypto::PublicKey,
+};
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Cost of stretching a passphrase into the key a wallet is encrypted
+/// with, kept next to the ciphertext so new wallets can be made costlier
+/// without breaking old ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// A wallet with its key encrypted at rest: the passphrase is stretched
+/// with Argon2id and the stored wallet sealed with XChaCha20-Poly1305,
+/// which also authenticates the address. The public key is left in the
+/// clear, so the wallet can be named and checked before it is unlocked.
+///
+/// ```
+/// use aeonia::{network::Network, wallet::{EncryptedWallet, KdfParams, Wallet}};
+///
+/// let wallet = Wallet::new(Network::Regtest).unwrap();
+/// let cheap = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+/// let encrypted = EncryptedWallet::encrypt_with(&wallet, "hunter2", cheap).unwrap();
+/// assert_eq!(encrypted.decrypt("hunter2").unwrap().address(), wallet.address());
+/// assert!(encrypted.decrypt("hunter3").is_err());
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EncryptedWallet {
+    pub address: Address,
+    #[serde(with = "codec::as_hex")]
+    pub public_key: PublicKey,
+    pub kdf: KdfParams,
+    #[serde(with = "codec::as_hex")]
+    salt: Vec<u8>,
+    #[serde(with = "codec::as_hex")]
+    nonce: Vec<u8>,
+    #[serde(with = "codec::as_hex")]
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedWallet {
+    /// Encrypts `wallet` under `passphrase` at the default cost. Fails for
+    /// wallets whose key is held by an external signer.
+    pub fn encrypt(wallet: &Wallet, passphrase: &str) -> Result<Self> {
+        Self::encrypt_with(wallet, passphrase, KdfParams::default())
+    }
+
+    pub fn encrypt_with(wallet: &Wallet, passphrase: &str, kdf: KdfParams) -> Result<Self> {
+        let plaintext = Zeroizing::new(wallet.to_stored()?.encode());
+        let mut salt = vec![0; SALT_LEN];
+        let mut nonce = vec![0; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        let key = derive_key(passphrase, &salt, &kdf)?;
+        let address = *wallet.address();
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: address.to_string().as_bytes(),
+                },
+            )
+            .map_err(|_| invalid("encryption failed"))?;
+        Ok(EncryptedWallet {
+            address,
+            public_key: *wallet.public_key(),
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// The wallet, with its key, if `passphrase` is the one it was
+    /// encrypted under.
+    pub fn decrypt(&self, passphrase: &str) -> Result<Wallet> {
+        if self.nonce.len() != NONCE_LEN {
+            return Err(invalid("nonce has the wrong length"));
+        }
+        let key = derive_key(passphrase, &self.salt, &self.kdf)?;
+        let plaintext = Zeroizing::new(
+            XChaCha20Poly1305::new(key.as_ref().into())
+                .decrypt(
+                    XNonce::from_slice(&self.nonce),
+                    Payload {
+                        msg: &self.ciphertext,
+                        aad: self.address.to_string().as_bytes(),
+                    },
+                )
+                .map_err(|_| Error::WrongPassphrase)?,
+        );
+        let stored = StoredWallet::decode(&plaintext).map_err(|e| invalid(&e.to_string()))?;
+        let wallet = Wallet::from_stored(&stored)?;
+        if *wallet.address() != self.address || *wallet.public_key() != self.public_key {
+            return Err(invalid("key doesn't match the address"));
+        }
+        Ok(wallet)
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| invalid(&e.to_string()))?;
+    let mut key = Zeroizing::new([0; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| invalid(&e.to_string()))?;
+    Ok(key)
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidKeystore {
+        reason: reason.to_string(),
+    }
+}
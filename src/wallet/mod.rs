@@ -1,86 +1,674 @@
-use super::blockchain::Transaction;
-
-use base58::ToBase58;
-use p256::{
-    ecdsa::{signature::Signer, Signature, SigningKey, VerifyingKey},
-    elliptic_curve::zeroize::Zeroizing,
-    pkcs8::EncodePrivateKey,
-    PublicKey, SecretKey,
+mod contacts;
+#[cfg(unix)]
+mod daemon;
+mod hd;
+mod keystore;
+mod offline;
+mod qr;
+mod signer;
+mod tracker;
+mod uri;
+mod watch;
+
+pub use contacts::AddressBook;
+#[cfg(unix)]
+pub use daemon::{call_daemon, DaemonRequest, DaemonResponse, DaemonSigner, WalletDaemon};
+pub use keystore::{EncryptedWallet, KdfParams};
+pub use offline::{SignedTransaction, UnsignedTransaction};
+pub use qr::QrImage;
+pub use signer::{LocalSigner, Signer};
+pub use tracker::{TrackedTransaction, TxStatus, TxTracker};
+pub use uri::{PaymentRequest, URI_SCHEME};
+pub use watch::{WatchList, WatchWallet};
+
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::blockchain::{
+    Block, BlockHeader, Blockchain, ChainEvent, Cosignature, Htlc, OutPoint, Transaction, TxId,
+    Witness,
+};
+use crate::{
+    address::Address,
+    codec::{self, Decode, Encode, Reader},
+    crypto::{self, KeyPair, PublicKey, Signature, SignatureScheme},
+    network::Network,
 };
-use rand_core::OsRng;
-use ripemd::Digest;
 
-#[derive(Debug)]
+use bip39::Mnemonic;
+use hd::ExtendedKey;
+use p256::elliptic_curve::zeroize::Zeroizing;
+use rand_core::{CryptoRngCore, OsRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
-    EcdsaError(String),
+    #[error(transparent)]
+    Crypto(#[from] crypto::Error),
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(#[from] bip39::Error),
+    #[error("invalid mnemonic: a private key takes {expected} words")]
+    KeyMnemonicLength { expected: usize },
+    #[error("invalid mnemonic: phrase is not a valid private key")]
+    NotAKeyMnemonic(#[source] crypto::Error),
+    #[error("account {account} is out of range")]
+    InvalidAccount { account: u32 },
+    #[error("wallet was not created from a mnemonic")]
+    NotHierarchical,
+    #[error("wallet key is not part of the transaction's multisig policy")]
+    NotACosigner,
+    #[error("block is not mined to this wallet's address")]
+    NotTheMiner,
+    #[error("transaction is not sent from this wallet's address")]
+    NotTheSender,
+    #[error("wallet key is not the HTLC's {role} key")]
+    WrongHtlcKey { role: &'static str },
+    #[error("transaction is not sent from the HTLC's address")]
+    NotFromHtlc,
+    #[error("preimage does not unlock the HTLC")]
+    WrongPreimage,
+    #[error("wallet's key is held by an external signer")]
+    ExternalKey,
+    #[error("signer failed: {0}")]
+    Signer(String),
+    #[error("wrong passphrase")]
+    WrongPassphrase,
+    #[error("invalid keystore: {reason}")]
+    InvalidKeystore { reason: String },
+    #[error("wallet is locked, unlock it with its passphrase first")]
+    Locked,
+    #[error(
+        "invalid contact name {name:?}, it must be non-empty, without spaces and not an address"
+    )]
+    InvalidContactName { name: String },
+    #[error("contact {name} already exists")]
+    DuplicateContact { name: String },
+    #[error("{name} is neither an address nor a known contact")]
+    UnknownContact { name: String },
+    #[error("{address} is already watched")]
+    AlreadyWatched { address: Address },
+    #[error("{address} is not watched")]
+    NotWatched { address: Address },
+    #[error("invalid payment URI {uri}: {reason}")]
+    InvalidUri { uri: String, reason: String },
+    #[error("failed to encode QR code: {0}")]
+    Qr(#[from] qrcode::types::QrError),
+    #[error("failed to encode QR code: {0}")]
+    Png(#[from] png::EncodingError),
+    #[error("QR code of {size} pixels is too large for an image")]
+    QrTooLarge { size: usize },
 }
 
-impl ToString for Error {
-    fn to_string(&self) -> String {
+impl Error {
+    /// Identifier of the kind of error, stable across releases so RPC
+    /// clients can match on it instead of the message.
+    pub fn code(&self) -> &'static str {
         match self {
-            Error::EcdsaError(e) => e.clone(),
+            Error::Crypto(e) => e.code(),
+            Error::InvalidMnemonic(_)
+            | Error::KeyMnemonicLength { .. }
+            | Error::NotAKeyMnemonic(_) => "invalid_mnemonic",
+            Error::InvalidAccount { .. } => "invalid_account",
+            Error::NotHierarchical => "not_hierarchical",
+            Error::NotACosigner => "not_a_cosigner",
+            Error::NotTheMiner => "not_the_miner",
+            Error::NotTheSender => "not_the_sender",
+            Error::WrongHtlcKey { .. } => "wrong_htlc_key",
+            Error::NotFromHtlc => "not_from_htlc",
+            Error::WrongPreimage => "wrong_preimage",
+            Error::ExternalKey => "external_key",
+            Error::Signer(_) => "signer_failed",
+            Error::WrongPassphrase => "wrong_passphrase",
+            Error::InvalidKeystore { .. } => "invalid_keystore",
+            Error::Locked => "wallet_locked",
+            Error::InvalidContactName { .. } => "invalid_contact_name",
+            Error::DuplicateContact { .. } => "duplicate_contact",
+            Error::UnknownContact { .. } => "unknown_contact",
+            Error::AlreadyWatched { .. } => "already_watched",
+            Error::NotWatched { .. } => "not_watched",
+            Error::InvalidUri { .. } => "invalid_uri",
+            Error::Qr(_) | Error::Png(_) | Error::QrTooLarge { .. } => "qr_failed",
         }
     }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Crypto(_) | Error::Signer(_) => io::ErrorKind::Other,
+            Error::InvalidMnemonic(_)
+            | Error::KeyMnemonicLength { .. }
+            | Error::NotAKeyMnemonic(_)
+            | Error::InvalidAccount { .. }
+            | Error::NotHierarchical
+            | Error::NotACosigner
+            | Error::NotTheMiner
+            | Error::NotTheSender
+            | Error::WrongHtlcKey { .. }
+            | Error::NotFromHtlc
+            | Error::WrongPreimage
+            | Error::ExternalKey
+            | Error::InvalidContactName { .. }
+            | Error::InvalidUri { .. }
+            | Error::Qr(_)
+            | Error::Png(_)
+            | Error::QrTooLarge { .. } => io::ErrorKind::InvalidInput,
+            Error::WrongPassphrase | Error::Locked => io::ErrorKind::PermissionDenied,
+            Error::InvalidKeystore { .. } => io::ErrorKind::InvalidData,
+            Error::DuplicateContact { .. } | Error::AlreadyWatched { .. } => {
+                io::ErrorKind::AlreadyExists
+            }
+            Error::UnknownContact { .. } | Error::NotWatched { .. } => io::ErrorKind::NotFound,
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(value: Error) -> Self {
+        Self::new(value.kind(), value)
+    }
 }
 
-type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = std::result::Result<T, Error>;
 
+/// Number of words in newly generated mnemonics.
+const MNEMONIC_WORDS: usize = 12;
+/// Number of words in a phrase spelling out a raw 32 byte private key.
+const KEY_MNEMONIC_WORDS: usize = 24;
+
+/// On-disk representation of a wallet: the address version byte and the
+/// PKCS#8 PEM encoded private key. Hierarchical deterministic wallets also
+/// keep the mnemonic and the account their key was derived for.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredWallet {
+    pub version: u8,
+    pub private_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    #[serde(default)]
+    pub account: u32,
+}
+
+impl Encode for StoredWallet {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.version.encode_to(out);
+        self.private_key.encode_to(out);
+        self.mnemonic.encode_to(out);
+        self.account.encode_to(out);
+    }
+}
+
+impl Decode for StoredWallet {
+    fn decode_from(reader: &mut Reader) -> codec::Result<Self> {
+        Ok(StoredWallet {
+            version: u8::decode_from(reader)?,
+            private_key: String::decode_from(reader)?,
+            mnemonic: Option::decode_from(reader)?,
+            account: u32::decode_from(reader)?,
+        })
+    }
+}
+
+/// Seed a hierarchical deterministic wallet was derived from.
+#[derive(Clone)]
+struct HdOrigin {
+    mnemonic: Zeroizing<String>,
+    account: u32,
+}
+
+#[derive(Clone)]
 pub struct Wallet {
-    address: String,
-    private_key: Zeroizing<String>,
+    version: u8,
+    address: Address,
+    /// PKCS#8 PEM of the key, unless an external signer holds it.
+    private_key: Option<Zeroizing<String>>,
     public_key: PublicKey,
+    signer: Arc<dyn Signer>,
+    hd: Option<HdOrigin>,
+    /// Shared between clones, so a copy handed to another thread keeps
+    /// the same record of what was sent.
+    sent: Arc<Mutex<TxTracker>>,
 }
 
 impl Wallet {
-    pub fn new(version: u8) -> Result<Self> {
-        let private_key = SecretKey::random(&mut OsRng);
-        let public_key = private_key.public_key();
-        let private_key = private_key
-            .to_pkcs8_pem(Default::default())
-            .map_err(|e| Error::EcdsaError(e.to_string()))?;
-        let address = Self::derive_address(public_key, version);
+    /// Creates a wallet with a new P-256 key, with an address for `network`.
+    pub fn new(network: Network) -> Result<Self> {
+        Self::from_rng(&mut OsRng, network.address_version())
+    }
+
+    /// Creates a wallet with a new key of `scheme` rather than P-256.
+    pub fn with_scheme(scheme: SignatureScheme, version: u8) -> Result<Self> {
+        Self::from_rng_with_scheme(&mut OsRng, scheme, version)
+    }
+
+    /// Creates a wallet with a key drawn from `rng`. A seeded RNG always
+    /// gives the same wallet.
+    pub fn from_rng<R: CryptoRngCore>(rng: &mut R, version: u8) -> Result<Self> {
+        Self::from_rng_with_scheme(rng, SignatureScheme::P256, version)
+    }
+
+    pub fn from_rng_with_scheme<R: CryptoRngCore>(
+        rng: &mut R,
+        scheme: SignatureScheme,
+        version: u8,
+    ) -> Result<Self> {
+        Self::from_key_pair(KeyPair::generate(scheme, rng), version)
+    }
+
+    pub fn from_key_pair(key: KeyPair, version: u8) -> Result<Self> {
+        let public_key = key.public_key();
+        let private_key = key.to_pkcs8_pem()?;
 
         Ok(Wallet {
-            address,
-            private_key,
+            version,
+            address: Address::from_public_key(&public_key, version),
+            private_key: Some(private_key),
             public_key,
+            signer: Arc::new(LocalSigner::new(key)),
+            hd: None,
+            sent: Arc::default(),
         })
     }
 
-    pub fn derive_address(public_key: PublicKey, version: u8) -> String {
-        let mut public_key_sha256 = sha256::digest(public_key.to_string());
-        let public_key_ripemd = ripemd::Ripemd160::digest(&public_key_sha256);
-        let public_key_ripemd = public_key_ripemd.as_slice();
-        let versioned_public_key_ripemd = &[&[version], public_key_ripemd].concat();
-        public_key_sha256 = sha256::digest(versioned_public_key_ripemd.clone());
-        public_key_sha256 = sha256::digest(public_key_sha256);
-        let versioned_public_key_ripemd = &[
-            versioned_public_key_ripemd,
-            public_key_sha256.split_off(4).as_bytes(),
-        ]
-        .concat();
-        versioned_public_key_ripemd.as_slice().to_base58()
+    /// Wallet whose key is held by `signer`, which is asked for every
+    /// signature. It can't be backed up or stored, since its key never
+    /// leaves the signer.
+    pub fn with_signer(signer: Arc<dyn Signer>, version: u8) -> Self {
+        let public_key = signer.public_key();
+        Wallet {
+            version,
+            address: Address::from_public_key(&public_key, version),
+            private_key: None,
+            public_key,
+            signer,
+            hd: None,
+            sent: Arc::default(),
+        }
+    }
+
+    /// Restores a wallet from a BIP-39 backup phrase.
+    ///
+    /// A 24 word phrase is read as a P-256 private key itself, the form
+    /// [`Wallet::to_mnemonic`] produces for wallets without a mnemonic. Any
+    /// other length is a hierarchical deterministic seed, restored to its
+    /// first account.
+    pub fn from_mnemonic(phrase: &str, version: u8) -> Result<Self> {
+        let mnemonic = Mnemonic::parse(phrase)?;
+        if mnemonic.word_count() != KEY_MNEMONIC_WORDS {
+            return Self::restore_hd(phrase, version);
+        }
+        Self::from_key_mnemonic(phrase, SignatureScheme::P256, version)
+    }
+
+    /// Restores a wallet from the 24 word phrase spelling out its `scheme`
+    /// private key, which is what backs up wallets of the other schemes.
+    pub fn from_key_mnemonic(phrase: &str, scheme: SignatureScheme, version: u8) -> Result<Self> {
+        let mnemonic = Mnemonic::parse(phrase)?;
+        if mnemonic.word_count() != KEY_MNEMONIC_WORDS {
+            return Err(Error::KeyMnemonicLength {
+                expected: KEY_MNEMONIC_WORDS,
+            });
+        }
+        let entropy = Zeroizing::new(mnemonic.to_entropy());
+        let key = KeyPair::from_bytes(scheme, &entropy).map_err(Error::NotAKeyMnemonic)?;
+        Self::from_key_pair(key, version)
+    }
+
+    /// Backup phrase for this wallet: its mnemonic if it has one, otherwise
+    /// the private key written out as 24 BIP-39 words.
+    ///
+    /// For a derived account the phrase restores the first account; use
+    /// [`Wallet::derive_account`] to get back to this one.
+    pub fn to_mnemonic(&self) -> Result<Zeroizing<String>> {
+        if let Some(hd) = &self.hd {
+            return Ok(hd.mnemonic.clone());
+        }
+        let private_key = self.private_key.as_ref().ok_or(Error::ExternalKey)?;
+        let key = KeyPair::from_pkcs8_pem(private_key)?;
+        let mnemonic = Mnemonic::from_entropy(&key.to_bytes())?;
+        Ok(Zeroizing::new(mnemonic.to_string()))
+    }
+
+    /// Creates a wallet from a freshly generated mnemonic. The phrase is
+    /// available from [`Wallet::mnemonic`] and is all that is needed to
+    /// restore this wallet and every account derived from it.
+    pub fn new_hd(version: u8) -> Result<Self> {
+        Self::new_hd_from_rng(&mut OsRng, version)
+    }
+
+    /// Like [`Wallet::new_hd`], drawing the mnemonic's entropy from `rng`.
+    pub fn new_hd_from_rng<R: CryptoRngCore>(rng: &mut R, version: u8) -> Result<Self> {
+        let mut entropy = Zeroizing::new([0u8; MNEMONIC_WORDS / 3 * 4]);
+        rng.fill_bytes(entropy.as_mut());
+        let mnemonic = Mnemonic::from_entropy(entropy.as_ref())?;
+        Self::restore_hd(&mnemonic.to_string(), version)
+    }
+
+    /// Restores the first account of the wallet backed up as `phrase`.
+    pub fn restore_hd(phrase: &str, version: u8) -> Result<Self> {
+        Self::from_hd_origin(
+            HdOrigin {
+                mnemonic: Zeroizing::new(phrase.to_string()),
+                account: 0,
+            },
+            version,
+        )
+    }
+
+    /// Derives the wallet for account `account` from the same mnemonic as
+    /// this one, following the BIP-44 path `m/44'/1'/account'/0/0`.
+    pub fn derive_account(&self, account: u32) -> Result<Self> {
+        let hd = self.hd.as_ref().ok_or(Error::NotHierarchical)?;
+        Self::from_hd_origin(
+            HdOrigin {
+                mnemonic: hd.mnemonic.clone(),
+                account,
+            },
+            self.version,
+        )
+    }
+
+    fn from_hd_origin(hd: HdOrigin, version: u8) -> Result<Self> {
+        if account_out_of_range(hd.account) {
+            return Err(Error::InvalidAccount {
+                account: hd.account,
+            });
+        }
+        let mnemonic = Mnemonic::parse(hd.mnemonic.as_str())?;
+        let seed = Zeroizing::new(mnemonic.to_seed(""));
+        let key =
+            ExtendedKey::from_seed(seed.as_ref())?.derive_path(&hd::account_path(hd.account))?;
+
+        Ok(Wallet {
+            hd: Some(HdOrigin {
+                mnemonic: Zeroizing::new(mnemonic.to_string()),
+                account: hd.account,
+            }),
+            ..Self::from_key_pair(KeyPair::P256(key.secret().clone()), version)?
+        })
+    }
+
+    /// Loads a wallet of any scheme, which its PKCS#8 key records.
+    pub fn from_stored(stored: &StoredWallet) -> Result<Self> {
+        let key = KeyPair::from_pkcs8_pem(&stored.private_key)?;
+
+        Ok(Wallet {
+            hd: stored.mnemonic.as_ref().map(|mnemonic| HdOrigin {
+                mnemonic: Zeroizing::new(mnemonic.clone()),
+                account: stored.account,
+            }),
+            ..Self::from_key_pair(key, stored.version)?
+        })
+    }
+
+    /// Fails for wallets with an external signer, whose key can't be
+    /// written out.
+    pub fn to_stored(&self) -> Result<StoredWallet> {
+        let private_key = self.private_key.as_ref().ok_or(Error::ExternalKey)?;
+        Ok(StoredWallet {
+            version: self.version,
+            private_key: private_key.to_string(),
+            mnemonic: self.hd.as_ref().map(|hd| hd.mnemonic.to_string()),
+            account: self.hd.as_ref().map_or(0, |hd| hd.account),
+        })
     }
 
+    /// Creates a payment from this wallet's address, signed and ready for
+    /// the pool. `sequence` is the number of payments this address has made
+    /// before, see [`Blockchain::next_sequence`]. `inputs` are the outputs
+    /// it spends on a UTXO chain, see [`Blockchain::select_inputs`]. `memo`
+    /// is signed along with the rest and may be empty.
+    ///
+    /// [`Blockchain::next_sequence`]: crate::blockchain::Blockchain::next_sequence
+    /// [`Blockchain::select_inputs`]: crate::blockchain::Blockchain::select_inputs
     pub fn sign_transaction(
         &mut self,
-        recipient: &String,
+        recipient: &Address,
         amount: f64,
-    ) -> Result<(Transaction, Signature, VerifyingKey)> {
-        let transaction = Transaction::new(self.address.clone(), recipient.clone(), amount);
-        let private_key = self
-            .private_key
-            .parse::<SecretKey>()
-            .map_err(|e| Error::EcdsaError(e.to_string()))?;
-        let signing_key: SigningKey = private_key.into();
-        Ok((
-            transaction.clone(),
-            signing_key.sign(transaction.to_string().as_bytes()),
-            self.public_key.into(),
-        ))
-    }
-
-    pub fn address(&self) -> &String {
+        fee: f64,
+        sequence: u64,
+        inputs: Vec<OutPoint>,
+        memo: Vec<u8>,
+    ) -> Result<Transaction> {
+        let mut transaction = Transaction::new(self.address, *recipient, amount, fee, sequence)
+            .with_inputs(inputs)
+            .with_memo(memo);
+        self.sign_single(&mut transaction)?;
+        Ok(transaction)
+    }
+
+    /// Creates one signed payment per `(recipient, amount)` in `payments`,
+    /// each paying `fee`, with consecutive sequence numbers from
+    /// `sequence` on, for [`Blockchain::add_batch_to_pool`] to admit
+    /// together. Spends no inputs, so it suits an account chain.
+    ///
+    /// [`Blockchain::add_batch_to_pool`]: crate::blockchain::Blockchain::add_batch_to_pool
+    pub fn sign_batch(
+        &mut self,
+        payments: &[(Address, f64)],
+        fee: f64,
+        sequence: u64,
+    ) -> Result<Vec<Transaction>> {
+        (sequence..)
+            .zip(payments)
+            .map(|(sequence, (recipient, amount))| {
+                self.sign_transaction(recipient, *amount, fee, sequence, vec![], vec![])
+            })
+            .collect()
+    }
+
+    /// Signs a payment created on another machine, see
+    /// [`UnsignedTransaction`]. It has to be sent from this wallet's
+    /// address.
+    pub fn sign_unsigned(&self, unsigned: &UnsignedTransaction) -> Result<SignedTransaction> {
+        let mut transaction = unsigned.transaction().clone();
+        if transaction.sender() != Some(&self.address) {
+            return Err(Error::NotTheSender);
+        }
+        self.sign_single(&mut transaction)?;
+        Ok(SignedTransaction::new(transaction))
+    }
+
+    /// Reads an [`UnsignedTransaction`] from `unsigned`, signs it and writes
+    /// the [`SignedTransaction`] to `signed`.
+    pub fn sign_transaction_file(
+        &self,
+        unsigned: &Path,
+        signed: &Path,
+    ) -> io::Result<SignedTransaction> {
+        let transaction = self.sign_unsigned(&UnsignedTransaction::read_from(unsigned)?)?;
+        transaction.write_to(signed)?;
+        Ok(transaction)
+    }
+
+    fn sign_single(&self, transaction: &mut Transaction) -> Result<()> {
+        transaction.witness = Witness::Single {
+            public_key: self.public_key,
+            signature: self.sign(&transaction.signing_bytes())?,
+        };
+        Ok(())
+    }
+
+    /// Adds this wallet's signature to a multisig transaction whose policy
+    /// includes its key.
+    pub fn cosign(&self, transaction: &mut Transaction) -> Result<()> {
+        let signature = self.sign(&transaction.signing_bytes())?;
+        let Witness::Multisig { policy, signatures } = &mut transaction.witness else {
+            return Err(Error::NotACosigner);
+        };
+        let key_index = policy
+            .key_index(&self.public_key)
+            .ok_or(Error::NotACosigner)?;
+        signatures.retain(|s| s.key_index != key_index);
+        signatures.push(Cosignature {
+            key_index,
+            signature,
+        });
+        Ok(())
+    }
+
+    /// Contract paying `recipient` against the preimage of `hash`, refundable
+    /// to this wallet from height `timeout` on. Fund it by paying its
+    /// [`Htlc::address`].
+    pub fn htlc(&self, recipient: &PublicKey, hash: [u8; 32], timeout: u64) -> Htlc {
+        Htlc::new(hash, *recipient, self.public_key, timeout)
+    }
+
+    /// Signs `transaction`, a payment out of the address of `htlc`, as its
+    /// recipient claiming it with `preimage`.
+    pub fn claim_htlc(
+        &self,
+        transaction: &mut Transaction,
+        htlc: &Htlc,
+        preimage: Vec<u8>,
+    ) -> Result<()> {
+        if htlc.recipient != self.public_key {
+            return Err(Error::WrongHtlcKey { role: "recipient" });
+        }
+        if !htlc.unlocks(&preimage) {
+            return Err(Error::WrongPreimage);
+        }
+        self.sign_htlc(transaction, htlc, Some(preimage))
+    }
+
+    /// Signs `transaction`, a payment out of the address of `htlc`, as its
+    /// refund key. Blocks only confirm it from the HTLC's timeout on.
+    pub fn refund_htlc(&self, transaction: &mut Transaction, htlc: &Htlc) -> Result<()> {
+        if htlc.refund != self.public_key {
+            return Err(Error::WrongHtlcKey { role: "refund" });
+        }
+        self.sign_htlc(transaction, htlc, None)
+    }
+
+    fn sign_htlc(
+        &self,
+        transaction: &mut Transaction,
+        htlc: &Htlc,
+        preimage: Option<Vec<u8>>,
+    ) -> Result<()> {
+        if transaction.sender() != Some(&htlc.address(self.version)) {
+            return Err(Error::NotFromHtlc);
+        }
+        transaction.witness = Witness::Htlc {
+            htlc: htlc.clone(),
+            preimage,
+            signature: self.sign(&transaction.signing_bytes())?,
+        };
+        Ok(())
+    }
+
+    /// Signs the header of a block mined to this wallet's address.
+    pub fn sign_block(&self, block: &mut Block) -> Result<()> {
+        block.set_witness(self.header_witness(block.header())?);
+        Ok(())
+    }
+
+    /// Signs a header mined to this wallet's address, such as one of a
+    /// [`BlockTemplate`](crate::blockchain::BlockTemplate) once its nonce
+    /// is found.
+    pub fn sign_header(&self, header: &mut BlockHeader) -> Result<()> {
+        header.set_witness(self.header_witness(header)?);
+        Ok(())
+    }
+
+    fn header_witness(&self, header: &BlockHeader) -> Result<Witness> {
+        if *header.miner() != self.address {
+            return Err(Error::NotTheMiner);
+        }
+        Ok(Witness::Single {
+            public_key: self.public_key,
+            signature: self.sign(&header.signing_bytes())?,
+        })
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Signature> {
+        self.signer.sign(message)
+    }
+
+    pub fn address(&self) -> &Address {
         &self.address
     }
+
+    /// QR code of a payment URI for this wallet's address, to be scanned by
+    /// whoever pays it.
+    pub fn address_qr(&self) -> Result<QrImage> {
+        PaymentRequest::new(self.address).qr()
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        self.public_key.scheme()
+    }
+
+    /// Backup phrase of a hierarchical deterministic wallet.
+    pub fn mnemonic(&self) -> Option<&str> {
+        self.hd.as_ref().map(|hd| hd.mnemonic.as_str())
+    }
+
+    /// Account index this wallet's key was derived for, if it has a mnemonic.
+    pub fn account(&self) -> Option<u32> {
+        self.hd.as_ref().map(|hd| hd.account)
+    }
+
+    /// Follows `transaction`, just handed to the pool, so its fate can be
+    /// asked for with [`Wallet::tx_status`].
+    pub fn track_transaction(&self, transaction: &Transaction) {
+        self.sent().track(transaction);
+    }
+
+    /// What became of the transaction `id` this wallet submitted, `None` if
+    /// it isn't followed.
+    pub fn tx_status(&self, id: &TxId) -> Option<TxStatus> {
+        self.sent().status(id)
+    }
+
+    /// Updates the followed transactions `event` concerns, see
+    /// [`TxTracker::apply`].
+    pub fn apply_event(&self, event: &ChainEvent, blockchain: &Blockchain) -> Vec<TxId> {
+        self.sent().apply(event, blockchain)
+    }
+
+    /// Puts followed transactions back into the pool and picks those to
+    /// rebroadcast, see [`TxTracker::resubmit`].
+    pub fn resubmit_transactions(
+        &self,
+        blockchain: &mut Blockchain,
+        max_attempts: u32,
+    ) -> Vec<Transaction> {
+        self.sent().resubmit(blockchain, max_attempts)
+    }
+
+    /// Brings every followed transaction up to date, see
+    /// [`TxTracker::refresh`].
+    pub fn refresh_transactions(&self, blockchain: &Blockchain) -> Vec<TxId> {
+        self.sent().refresh(blockchain)
+    }
+
+    /// Copy of the followed transactions, to be saved with the wallet.
+    pub fn sent_transactions(&self) -> TxTracker {
+        self.sent().clone()
+    }
+
+    /// Follows the transactions in `sent`, as saved from
+    /// [`Wallet::sent_transactions`], instead of those followed so far.
+    pub fn with_sent_transactions(self, sent: TxTracker) -> Self {
+        *self.sent() = sent;
+        self
+    }
+
+    fn sent(&self) -> MutexGuard<'_, TxTracker> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn account_out_of_range(account: u32) -> bool {
+    account >= hd::HARDENED
 }
@@ -0,0 +1,81 @@
+use std::{fs, io, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::blockchain::{Transaction, TxId, Witness};
+
+/// A payment complete but for its signature, written to a file on a machine
+/// that follows the chain so it can be signed on one that holds the key but
+/// never goes online. The sequence number and inputs are filled in when it
+/// is created, since the signing machine can't look them up.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnsignedTransaction {
+    unsigned: Transaction,
+}
+
+impl UnsignedTransaction {
+    /// Wraps `transaction`, dropping any witness it has.
+    pub fn new(mut transaction: Transaction) -> Self {
+        transaction.witness = Witness::Unsigned;
+        UnsignedTransaction {
+            unsigned: transaction,
+        }
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.unsigned
+    }
+
+    pub fn read_from(path: &Path) -> io::Result<Self> {
+        read_json(path)
+    }
+
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        write_json(path, self)
+    }
+}
+
+/// A payment signed offline, to be carried back and added to the pool.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    signed: Transaction,
+}
+
+impl SignedTransaction {
+    pub(super) fn new(transaction: Transaction) -> Self {
+        SignedTransaction {
+            signed: transaction,
+        }
+    }
+
+    pub fn id(&self) -> TxId {
+        self.signed.id()
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.signed
+    }
+
+    pub fn into_transaction(self) -> Transaction {
+        self.signed
+    }
+
+    pub fn read_from(path: &Path) -> io::Result<Self> {
+        read_json(path)
+    }
+
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        write_json(path, self)
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    serde_json::from_slice(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(value)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(path, json)
+}
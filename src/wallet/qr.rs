@@ -0,0 +1,83 @@
+use qrcode::{Color, QrCode};
+
+use super::{Error, Result};
+
+/// Width of the light border scanners need around the code, in modules.
+const QUIET_ZONE: usize = 4;
+
+/// A QR code, rendered either as text for the terminal or as a PNG image.
+pub struct QrImage {
+    width: usize,
+    dark: Vec<bool>,
+}
+
+impl QrImage {
+    pub fn new(data: &str) -> Result<Self> {
+        let code = QrCode::new(data.as_bytes())?;
+        Ok(QrImage {
+            width: code.width(),
+            dark: code
+                .to_colors()
+                .into_iter()
+                .map(|color| color == Color::Dark)
+                .collect(),
+        })
+    }
+
+    /// Width of the code in modules, without the quiet zone.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        let (Some(x), Some(y)) = (x.checked_sub(QUIET_ZONE), y.checked_sub(QUIET_ZONE)) else {
+            return false;
+        };
+        x < self.width && y < self.width && self.dark[y * self.width + x]
+    }
+
+    /// Draws the code with half block characters, two rows of modules per
+    /// line of text. Dark modules are drawn as spaces so the code reads
+    /// correctly on a dark terminal background.
+    pub fn to_terminal(&self) -> String {
+        let size = self.width + 2 * QUIET_ZONE;
+        let mut out = String::new();
+        for y in (0..size).step_by(2) {
+            for x in 0..size {
+                out.push(match (self.is_dark(x, y), self.is_dark(x, y + 1)) {
+                    (false, false) => '█',
+                    (false, true) => '▀',
+                    (true, false) => '▄',
+                    (true, true) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Encodes the code as a greyscale PNG, each module `scale` pixels wide.
+    pub fn to_png(&self, scale: u32) -> Result<Vec<u8>> {
+        let scale = scale.max(1) as usize;
+        let size = (self.width + 2 * QUIET_ZONE) * scale;
+        let mut pixels = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                pixels.push(if self.is_dark(x / scale, y / scale) {
+                    0
+                } else {
+                    255
+                });
+            }
+        }
+        let side = u32::try_from(size).map_err(|_| Error::QrTooLarge { size })?;
+        let mut out = vec![];
+        let mut encoder = png::Encoder::new(&mut out, side, side);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))?;
+        Ok(out)
+    }
+}
@@ -0,0 +1,71 @@
+use super::Result;
+use crate::crypto::{KeyPair, PublicKey, Signature};
+
+/// Holder of the private key a [`Wallet`](super::Wallet) signs with. The
+/// wallet only ever asks it for signatures, so the key can live outside the
+/// process, in a hardware wallet, an HSM or a remote signing service.
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use aeonia::{
+///     address::Address,
+///     crypto::{KeyPair, PublicKey, Signature, SignatureScheme},
+///     wallet::{LocalSigner, Result, Signer, Wallet},
+/// };
+///
+/// /// Stands in for a device that has to approve each signature.
+/// struct Device(LocalSigner);
+///
+/// impl Signer for Device {
+///     fn public_key(&self) -> PublicKey {
+///         self.0.public_key()
+///     }
+///
+///     fn sign(&self, message: &[u8]) -> Result<Signature> {
+///         self.0.sign(message)
+///     }
+/// }
+///
+/// let key = KeyPair::generate(SignatureScheme::Ed25519, &mut rand_core::OsRng);
+/// let device = Device(LocalSigner::new(key));
+/// let mut wallet = Wallet::with_signer(Arc::new(device), 0);
+/// let recipient: Address = "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".parse().unwrap();
+/// let transaction = wallet
+///     .sign_transaction(&recipient, 1.0, 0.0, 0, vec![], vec![])
+///     .unwrap();
+/// assert!(transaction
+///     .witness
+///     .verify(wallet.address(), &transaction.signing_bytes())
+///     .is_ok());
+/// assert!(wallet.to_stored().is_err());
+/// ```
+pub trait Signer: Send + Sync {
+    fn public_key(&self) -> PublicKey;
+
+    /// Signature of `message` with the key of [`Signer::public_key`].
+    fn sign(&self, message: &[u8]) -> Result<Signature>;
+}
+
+/// Signer holding its key in memory, the one wallets created or loaded from
+/// disk use.
+#[derive(Clone)]
+pub struct LocalSigner {
+    key: KeyPair,
+}
+
+impl LocalSigner {
+    pub fn new(key: KeyPair) -> Self {
+        LocalSigner { key }
+    }
+}
+
+impl Signer for LocalSigner {
+    fn public_key(&self) -> PublicKey {
+        self.key.public_key()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Signature> {
+        Ok(self.key.sign(message))
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::{Blockchain, ChainEvent, Transaction, TxId};
+
+/// Where a transaction a wallet submitted stands, as last seen on the
+/// chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TxStatus {
+    /// Waiting in the pool.
+    Pending,
+    /// On the main chain, 1 confirmation in the tip.
+    Confirmed { confirmations: u64 },
+    /// Left the pool without being confirmed: replaced, expired, evicted
+    /// or turned away.
+    Dropped,
+    /// Was confirmed, but its block was disconnected and no block on the
+    /// new main chain has it yet. It may still be waiting in the pool to
+    /// be confirmed again.
+    ReorgedOut,
+}
+
+impl std::fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TxStatus::Pending => write!(f, "pending"),
+            TxStatus::Confirmed { confirmations } => write!(f, "confirmed ({})", confirmations),
+            TxStatus::Dropped => write!(f, "dropped"),
+            TxStatus::ReorgedOut => write!(f, "reorged out"),
+        }
+    }
+}
+
+/// A submitted transaction and what became of it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrackedTransaction {
+    pub status: TxStatus,
+    /// Hash of the block confirming it, while it is confirmed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<String>,
+    /// The transaction as signed, kept so it can be submitted again.
+    pub transaction: Transaction,
+    /// Times it has been rebroadcast, see [`TxTracker::resubmit`].
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl TrackedTransaction {
+    /// Whether it may still be confirmed without the wallet signing
+    /// anything again.
+    pub fn is_unconfirmed(&self) -> bool {
+        matches!(self.status, TxStatus::Pending | TxStatus::ReorgedOut)
+    }
+}
+
+/// The transactions a wallet submitted, each with its [`TxStatus`], kept
+/// up to date from [`ChainEvent`]s or by [`TxTracker::refresh`]ing against
+/// the chain. Kept in the data directory next to the wallet, so a sender
+/// can still tell whether a payment confirmed long after sending it.
+///
+/// ```
+/// use aeonia::{
+///     blockchain::{Blockchain, SharedBlockchain},
+///     config::Config,
+///     network::Network,
+///     wallet::{TxStatus, Wallet},
+/// };
+///
+/// let mut config = Config::default();
+/// config.consensus.difficulty = 1;
+/// let mut blockchain = Blockchain::with_config(&config).unwrap();
+/// let events = blockchain.subscribe();
+/// let wallet = Wallet::new(Network::Regtest).unwrap();
+/// let payment = blockchain.request_from_faucet(wallet.address(), 1.0).unwrap();
+/// wallet.track_transaction(&payment);
+/// assert_eq!(wallet.tx_status(&payment.id()), Some(TxStatus::Pending));
+///
+/// let blockchain = SharedBlockchain::new(blockchain);
+/// assert!(blockchain.mine(&wallet));
+/// for event in events.try_iter() {
+///     wallet.apply_event(&event, &blockchain.read().unwrap());
+/// }
+/// let confirmed = TxStatus::Confirmed { confirmations: 1 };
+/// assert_eq!(wallet.tx_status(&payment.id()), Some(confirmed));
+///
+/// blockchain.write().unwrap().disconnect_tip().unwrap();
+/// for event in events.try_iter() {
+///     wallet.apply_event(&event, &blockchain.read().unwrap());
+/// }
+/// assert_eq!(wallet.tx_status(&payment.id()), Some(TxStatus::ReorgedOut));
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TxTracker {
+    transactions: BTreeMap<TxId, TrackedTransaction>,
+}
+
+impl TxTracker {
+    /// Starts following `transaction`, just handed to the pool. Following
+    /// it again leaves its status alone.
+    pub fn track(&mut self, transaction: &Transaction) {
+        self.transactions
+            .entry(transaction.id())
+            .or_insert(TrackedTransaction {
+                status: TxStatus::Pending,
+                block_hash: None,
+                transaction: transaction.clone(),
+                attempts: 0,
+            });
+    }
+
+    /// Stops following `id`, returning what was known of it.
+    pub fn forget(&mut self, id: &TxId) -> Option<TrackedTransaction> {
+        self.transactions.remove(id)
+    }
+
+    pub fn get(&self, id: &TxId) -> Option<&TrackedTransaction> {
+        self.transactions.get(id)
+    }
+
+    pub fn status(&self, id: &TxId) -> Option<TxStatus> {
+        self.get(id).map(|tracked| tracked.status)
+    }
+
+    /// Followed transactions in id order.
+    pub fn iter(&self) -> impl Iterator<Item = (&TxId, &TrackedTransaction)> {
+        self.transactions.iter()
+    }
+
+    /// Updates the statuses `event` may have changed, looking them up in
+    /// `blockchain`, which the event has already been applied to. Returns
+    /// the transactions whose status changed.
+    pub fn apply(&mut self, event: &ChainEvent, blockchain: &Blockchain) -> Vec<TxId> {
+        match event {
+            ChainEvent::TransactionRejected { id, .. }
+            | ChainEvent::TransactionReplaced { replaced: id, .. } => {
+                match self.transactions.get_mut(id) {
+                    Some(tracked) if tracked.status == TxStatus::Pending => {
+                        tracked.status = TxStatus::Dropped;
+                        vec![id.clone()]
+                    }
+                    _ => vec![],
+                }
+            }
+            ChainEvent::BlockMined { .. }
+            | ChainEvent::BlockConnected { .. }
+            | ChainEvent::BlockDisconnected { .. }
+            | ChainEvent::Reorg { .. }
+            | ChainEvent::SyncProgress { .. } => self.refresh(blockchain),
+            ChainEvent::TransactionAccepted { .. } | ChainEvent::Shutdown => vec![],
+        }
+    }
+
+    /// Puts the unconfirmed transactions `blockchain` has lost track of,
+    /// such as those a reorg disconnected or another process submitted,
+    /// back into its pool, brings every status up to date and returns the
+    /// unconfirmed ones waiting in the pool that have been rebroadcast
+    /// fewer than `max_attempts` times, counting this one.
+    pub fn resubmit(&mut self, blockchain: &mut Blockchain, max_attempts: u32) -> Vec<Transaction> {
+        for (id, tracked) in &self.transactions {
+            if tracked.is_unconfirmed()
+                && blockchain.confirmations(id) == 0
+                && blockchain.pending_transaction(id).is_none()
+            {
+                // Those turned away are marked dropped just below.
+                let _ = blockchain.add_transation_to_pool(tracked.transaction.clone());
+            }
+        }
+        self.refresh(blockchain);
+        self.transactions
+            .iter_mut()
+            .filter(|(id, tracked)| {
+                tracked.is_unconfirmed()
+                    && tracked.attempts < max_attempts
+                    && blockchain.pending_transaction(id).is_some()
+            })
+            .map(|(_, tracked)| {
+                tracked.attempts += 1;
+                tracked.transaction.clone()
+            })
+            .collect()
+    }
+
+    /// Brings every status up to date with `blockchain`, for a tracker
+    /// that missed events, such as one loaded from disk. Returns the
+    /// transactions whose status changed.
+    pub fn refresh(&mut self, blockchain: &Blockchain) -> Vec<TxId> {
+        let tip = blockchain.chain_height().unwrap_or_default();
+        let mut changed = vec![];
+        for (id, tracked) in &mut self.transactions {
+            let confirmations = blockchain.confirmations(id);
+            let was_confirmed = matches!(
+                tracked.status,
+                TxStatus::Confirmed { .. } | TxStatus::ReorgedOut
+            );
+            let status = if confirmations > 0 {
+                TxStatus::Confirmed { confirmations }
+            } else if was_confirmed {
+                TxStatus::ReorgedOut
+            } else if blockchain.pending_transaction(id).is_some() {
+                TxStatus::Pending
+            } else {
+                TxStatus::Dropped
+            };
+            let block_hash = (confirmations > 0)
+                .then(|| blockchain.block_by_height(tip + 1 - confirmations))
+                .flatten()
+                .map(|block| block.hash());
+            if status != tracked.status {
+                changed.push(id.clone());
+            }
+            tracked.status = status;
+            tracked.block_hash = block_hash;
+        }
+        changed
+    }
+}
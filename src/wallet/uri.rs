@@ -0,0 +1,142 @@
+use std::{fmt, str::FromStr};
+
+use super::{qr::QrImage, Error, Result};
+use crate::address::Address;
+
+/// Scheme of payment URIs.
+pub const URI_SCHEME: &str = "aeonia";
+
+/// Request for a payment to an address, written as a URI such as
+/// `aeonia:<address>?amount=1.5&memo=INV-42` so it can be shared as a link or
+/// a QR code and turned back into a payment by whoever scans it. Unknown
+/// parameters are ignored unless they start with `req-`, which marks them as
+/// required.
+///
+/// ```
+/// use aeonia::wallet::PaymentRequest;
+///
+/// let uri = "aeonia:1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa?amount=2.5&memo=order%2042";
+/// let request: PaymentRequest = uri.parse().unwrap();
+/// assert_eq!(request.amount, Some(2.5));
+/// assert_eq!(request.memo.as_deref(), Some("order 42"));
+/// assert_eq!(request.to_string(), uri);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaymentRequest {
+    pub address: Address,
+    pub amount: Option<f64>,
+    pub memo: Option<String>,
+}
+
+impl PaymentRequest {
+    pub fn new(address: Address) -> Self {
+        PaymentRequest {
+            address,
+            amount: None,
+            memo: None,
+        }
+    }
+
+    pub fn with_amount(mut self, amount: f64) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn with_memo(mut self, memo: String) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    /// QR code of the URI.
+    pub fn qr(&self) -> Result<QrImage> {
+        QrImage::new(&self.to_string())
+    }
+}
+
+impl fmt::Display for PaymentRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", URI_SCHEME, self.address)?;
+        let mut separator = '?';
+        if let Some(amount) = self.amount {
+            write!(f, "{}amount={}", separator, amount)?;
+            separator = '&';
+        }
+        if let Some(memo) = &self.memo {
+            write!(f, "{}memo={}", separator, percent_encode(memo))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PaymentRequest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidUri {
+            uri: s.to_string(),
+            reason: reason.to_string(),
+        };
+        let rest = s
+            .split_once(':')
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case(URI_SCHEME))
+            .map(|(_, rest)| rest)
+            .ok_or_else(|| invalid("not an aeonia URI"))?;
+        let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut request = PaymentRequest::new(
+            address
+                .parse()
+                .map_err(|e: crate::address::Error| invalid(&e.to_string()))?,
+        );
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "amount" => {
+                    let amount = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|amount| amount.is_finite() && *amount > 0.0)
+                        .ok_or_else(|| invalid("amount must be a positive number"))?;
+                    request.amount = Some(amount);
+                }
+                "memo" => {
+                    request.memo =
+                        Some(percent_decode(value).ok_or_else(|| invalid("malformed memo"))?);
+                }
+                key if key.starts_with("req-") => {
+                    return Err(invalid(&format!("unsupported parameter {}", key)));
+                }
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+}
+
+/// Escapes everything but the characters RFC 3986 leaves unreserved.
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            out.push(char::from(byte));
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Error, Result, UnsignedTransaction};
+use crate::{
+    address::Address,
+    blockchain::{self, Balance, Blockchain, HistoryPage, Transaction},
+    codec,
+    crypto::PublicKey,
+};
+
+/// A wallet following an address whose key it doesn't hold: it reads the
+/// address's balance and history off the chain and writes payments out of
+/// it unsigned, for the key holder to sign offline with
+/// [`Wallet::sign_unsigned`](super::Wallet::sign_unsigned).
+///
+/// ```
+/// use aeonia::{
+///     blockchain::Blockchain,
+///     config::Config,
+///     network::Network,
+///     wallet::{Wallet, WatchWallet},
+/// };
+///
+/// let blockchain = Blockchain::with_config(&Config::default()).unwrap();
+/// let cold = Wallet::new(Network::Regtest).unwrap();
+/// let watch = WatchWallet::from_public_key(cold.public_key(), cold.version());
+/// assert_eq!(watch.address(), cold.address());
+/// assert_eq!(watch.balance(&blockchain, 0).unwrap().total(), 0.0);
+///
+/// let unsigned = watch
+///     .unsigned_payment(&blockchain, cold.address(), 0.0, 0.0, vec![])
+///     .unwrap();
+/// assert!(cold.sign_unsigned(&unsigned).is_ok());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchWallet {
+    address: Address,
+    /// Known when the wallet was made from a key rather than an address.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "codec::as_hex"
+    )]
+    public_key: Option<PublicKey>,
+}
+
+impl WatchWallet {
+    pub fn new(address: Address) -> Self {
+        WatchWallet {
+            address,
+            public_key: None,
+        }
+    }
+
+    /// Watches the address `public_key` has with address version `version`.
+    pub fn from_public_key(public_key: &PublicKey, version: u8) -> Self {
+        WatchWallet {
+            address: Address::from_public_key(public_key, version),
+            public_key: Some(*public_key),
+        }
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    pub fn public_key(&self) -> Option<&PublicKey> {
+        self.public_key.as_ref()
+    }
+
+    /// See [`Blockchain::balance`].
+    pub fn balance(
+        &self,
+        blockchain: &Blockchain,
+        min_confirmations: u64,
+    ) -> blockchain::Result<Balance> {
+        blockchain.balance(&self.address, min_confirmations)
+    }
+
+    /// See [`Blockchain::history`].
+    pub fn history(
+        &self,
+        blockchain: &Blockchain,
+        page: usize,
+        page_size: usize,
+    ) -> blockchain::Result<HistoryPage> {
+        blockchain.history(&self.address, page, page_size)
+    }
+
+    /// A payment of `amount` to `recipient` out of the watched address,
+    /// with its sequence number and, on a UTXO chain, inputs taken from
+    /// `blockchain`.
+    pub fn unsigned_payment(
+        &self,
+        blockchain: &Blockchain,
+        recipient: &Address,
+        amount: f64,
+        fee: f64,
+        memo: Vec<u8>,
+    ) -> blockchain::Result<UnsignedTransaction> {
+        let sequence = blockchain.next_sequence(&self.address)?;
+        let inputs = blockchain.select_inputs(&self.address, amount + fee)?;
+        Ok(UnsignedTransaction::new(
+            Transaction::new(self.address, *recipient, amount, fee, sequence)
+                .with_inputs(inputs)
+                .with_memo(memo),
+        ))
+    }
+}
+
+/// The watch-only wallets kept in the data directory, in address order.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WatchList {
+    wallets: Vec<WatchWallet>,
+}
+
+impl WatchList {
+    pub fn add(&mut self, wallet: WatchWallet) -> Result<()> {
+        match self
+            .wallets
+            .binary_search_by_key(&wallet.address, |w| w.address)
+        {
+            Ok(_) => Err(Error::AlreadyWatched {
+                address: wallet.address,
+            }),
+            Err(at) => {
+                self.wallets.insert(at, wallet);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn remove(&mut self, address: &Address) -> Result<WatchWallet> {
+        let at = self
+            .wallets
+            .binary_search_by_key(address, |w| w.address)
+            .map_err(|_| Error::NotWatched { address: *address })?;
+        Ok(self.wallets.remove(at))
+    }
+
+    pub fn get(&self, address: &Address) -> Option<&WatchWallet> {
+        self.wallets.iter().find(|w| w.address == *address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WatchWallet> {
+        self.wallets.iter()
+    }
+}